@@ -1,6 +1,7 @@
 fn main() {
     cc::Build::new()
         .file("src/arch/i386/cpu/cpu.S")
+        .file("src/arch/i386/sys/vdso.S")
         .compile("cpu");
 
     cc::Build::new()
@@ -9,6 +10,7 @@ fn main() {
         .compile("boot");
 
     println!("cargo:rerun-if-changed=src/arch/i386/cpu/cpu.S");
+    println!("cargo:rerun-if-changed=src/arch/i386/sys/vdso.S");
     println!("cargo:rerun-if-changed=src/arch/i386/boot/sys.S");
     println!("cargo:rerun-if-changed=src/arch/i386/boot/multiboot.S");
 }
@@ -0,0 +1,94 @@
+use prelude::*;
+
+use arch::include::cpu::cpu::*;
+
+extern "C" {
+    fn x86_cpuid_check() -> u32;
+}
+
+/* names of the CPUID.1:EDX feature bits, as linux reports them */
+static EDX_FLAGS: [&str; 32] = [
+    "fpu", "vme", "de", "pse", "tsc", "msr", "pae", "mce",
+    "cx8", "apic", "", "sep", "mtrr", "pge", "mca", "cmov",
+    "pat", "pse36", "pn", "clflush", "", "dts", "acpi", "mmx",
+    "fxsr", "sse", "sse2", "ss", "ht", "tm", "ia64", "pbe",
+];
+
+/* names of the CPUID.1:ECX feature bits */
+static ECX_FLAGS: [&str; 32] = [
+    "pni", "pclmulqdq", "dtes64", "monitor", "ds_cpl", "vmx", "smx", "est",
+    "tm2", "ssse3", "cid", "sdbg", "fma", "cx16", "xtpr", "pdcm",
+    "", "pcid", "dca", "sse4_1", "sse4_2", "x2apic", "movbe", "popcnt",
+    "tsc_deadline_timer", "aes", "xsave", "osxsave", "avx", "f16c", "rdrand", "hypervisor",
+];
+
+unsafe fn cpuid_string(regs: &[u32]) -> String {
+    let mut bytes = Vec::new();
+
+    for reg in regs {
+        bytes.extend_from_slice(&reg.to_le_bytes());
+    }
+
+    String::from_utf8_lossy(&bytes)
+        .trim_matches(|c: char| c == '\0' || c == ' ')
+        .to_owned()
+}
+
+/** /proc/cpuinfo contents of the boot processor */
+pub unsafe fn arch_cpuinfo() -> String {
+    let mut info = String::new();
+
+    info += "processor\t: 0\n";
+
+    if x86_cpuid_check() == 0 {
+        info += "vendor_id\t: unknown\n";
+        return info;
+    }
+
+    let (max, ebx, ecx, edx) = cpuid(0);
+    info += &format!("vendor_id\t: {}\n", cpuid_string(&[ebx, edx, ecx]));
+
+    if max < 1 {
+        return info;
+    }
+
+    let (eax, _, ecx, edx) = cpuid(1);
+
+    let mut family = (eax >> 8) & 0xf;
+    let mut model  = (eax >> 4) & 0xf;
+
+    if family == 0xf {
+        family += (eax >> 20) & 0xff;
+    }
+
+    if family == 0x6 || family >= 0xf {
+        model += ((eax >> 16) & 0xf) << 4;
+    }
+
+    info += &format!("cpu family\t: {}\n", family);
+    info += &format!("model\t\t: {}\n", model);
+
+    let (max_ext, _, _, _) = cpuid(0x80000000);
+
+    if max_ext >= 0x80000004 {
+        let mut regs = Vec::new();
+
+        for leaf in 0x80000002..=0x80000004 {
+            let (a, b, c, d) = cpuid(leaf);
+            regs.extend_from_slice(&[a, b, c, d]);
+        }
+
+        info += &format!("model name\t: {}\n", cpuid_string(&regs));
+    }
+
+    info += &format!("stepping\t: {}\n", eax & 0xf);
+
+    let flags: Vec<&str> = (0..32).filter(|bit| edx & (1 << bit) != 0).map(|bit| EDX_FLAGS[bit])
+        .chain((0..32).filter(|bit| ecx & (1 << bit) != 0).map(|bit| ECX_FLAGS[bit]))
+        .filter(|flag| !flag.is_empty())
+        .collect();
+
+    info += &format!("flags\t\t: {}\n", flags.join(" "));
+
+    info
+}
@@ -1,5 +1,7 @@
 use prelude::*;
 
+use arch::cpu::sysenter::x86_sysenter_stack_set;
+
 use crate::{print};
 
 extern "C" {
@@ -251,6 +253,93 @@ impl GdtEntry {
 
 static mut GDT: [GdtEntry; 256] = [GdtEntry(0); 256];
 
+/* per-thread TLS descriptors occupy GDT[6..9] */
+pub const GDT_TLS_MIN: usize = 6;
+pub const GDT_TLS_ENTRIES: usize = 3;
+
+/** segment descriptor as passed by set_thread_area(2) */
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct UserDesc {
+    pub entry_number: u32,
+    pub base_addr: u32,
+    pub limit: u32,
+
+    /* seg_32bit:1, contents:2, read_exec_only:1, limit_in_pages:1, seg_not_present:1, useable:1 */
+    pub flags: u32,
+}
+
+pub const UD_SEG_32BIT       : u32 = 0x01;
+pub const UD_CONTENTS        : u32 = 0x06;
+pub const UD_READ_EXEC_ONLY  : u32 = 0x08;
+pub const UD_LIMIT_IN_PAGES  : u32 = 0x10;
+pub const UD_SEG_NOT_PRESENT : u32 = 0x20;
+pub const UD_USEABLE         : u32 = 0x40;
+
+impl GdtEntry {
+    /** build a user data descriptor from a set_thread_area(2) request */
+    pub fn from_user_desc(desc: &UserDesc) -> Result<Self, Error> {
+        /* an all-empty request clears the slot */
+        if desc.base_addr == 0 && desc.limit == 0 && desc.flags & UD_SEG_NOT_PRESENT != 0 {
+            return Ok(GdtEntry::new());
+        }
+
+        /* only plain data segments, no code or expand-down ones */
+        if desc.flags & UD_CONTENTS != 0 {
+            return Err(Error::EINVAL);
+        }
+
+        let mut entry = GdtEntry::new()
+            .base(desc.base_addr as u64)
+            .limit(desc.limit as u64)
+            .segment_type(SegmentType::ReadWriteData)
+            .descriptor_type(DescriptorType::Code)
+            .privellage_level(PrivellageLevel::Ring3)
+            .operation_size(if desc.flags & UD_SEG_32BIT != 0 { OperationSize::Bit32 } else { OperationSize::Bit16 })
+            .granularity(if desc.flags & UD_LIMIT_IN_PAGES != 0 { Granularity::Page } else { Granularity::Byte })
+            .available(desc.flags & UD_USEABLE != 0)
+            .present(desc.flags & UD_SEG_NOT_PRESENT == 0);
+
+        if desc.flags & UD_READ_EXEC_ONLY != 0 {
+            /* read-only data */
+            entry = entry._type(0x0);
+        }
+
+        Ok(entry)
+    }
+
+    /** convert back to the set_thread_area(2) representation */
+    pub fn to_user_desc(&self, entry_number: u32) -> UserDesc {
+        let e = self.0;
+
+        let base  = ((e >> 16) & 0xFFFFFF) | ((e >> 32) & 0xFF000000);
+        let limit = (e & 0xFFFF) | ((e >> 32) & 0xF0000);
+
+        let mut flags = 0;
+
+        if e == 0 {
+            flags |= UD_READ_EXEC_ONLY | UD_SEG_NOT_PRESENT;
+        } else {
+            if (e >> 54) & 1 != 0 { flags |= UD_SEG_32BIT; }
+            if (e >> 41) & 1 == 0 { flags |= UD_READ_EXEC_ONLY; }
+            if (e >> 55) & 1 != 0 { flags |= UD_LIMIT_IN_PAGES; }
+            if (e >> 47) & 1 == 0 { flags |= UD_SEG_NOT_PRESENT; }
+            if (e >> 52) & 1 != 0 { flags |= UD_USEABLE; }
+        }
+
+        UserDesc {
+            entry_number: entry_number,
+            base_addr: base as u32,
+            limit: limit as u32,
+            flags: flags,
+        }
+    }
+
+    pub fn is_present(&self) -> bool {
+        (self.0 >> 47) & 1 != 0
+    }
+}
+
 pub unsafe fn x86_gdt_setup() {
     let base = 0;
     let limit = 0xFFFF_FFFF;
@@ -333,4 +422,12 @@ pub unsafe fn x86_tss_setup(sp: usize) {
 
 pub unsafe fn x86_kernel_stack_set(sp: usize) {
     TSS_ENTRY.sp = sp;
+    x86_sysenter_stack_set(sp);
+}
+
+/** install the TLS descriptors of the thread about to run */
+pub unsafe fn x86_tls_set(tls: &[GdtEntry; GDT_TLS_ENTRIES]) {
+    for i in 0..GDT_TLS_ENTRIES {
+        GDT[GDT_TLS_MIN + i] = tls[i];
+    }
 }
@@ -14,6 +14,8 @@ use arch::cpu::isr::x86_isr_setup;
 use arch::cpu::idt::x86_idt_setup;
 use arch::cpu::gdt::x86_tss_setup;
 use arch::cpu::gdt::x86_gdt_setup;
+use arch::cpu::sysenter::x86_sysenter_setup;
+use arch::sys::vdso::vdso_init;
 use arch::earlycon::earlycon::earlycon_init;
 
 extern "C" {
@@ -67,5 +69,8 @@ pub unsafe extern "C" fn x86_cpu_init() {
 
     platform_init();
 
+    vdso_init();
+    x86_sysenter_setup();
+
     kmain(boot);
 }
@@ -3,11 +3,14 @@ use prelude::*;
 use arch::cpu::fpu::x86_fpu_trap;
 use arch::cpu::gdt::x86_kernel_stack_set;
 use arch::cpu::idt::*;
+use arch::cpu::sysenter::*;
 use arch::include::core::arch::*;
 use arch::include::cpu::cpu::*;
 use arch::mm::i386::arch_mm_page_fault;
+use arch::mm::uaccess::x86_fixup_search;
 use arch::sys::syscall::arch_syscall;
 use sys::sched::*;
+use sys::signal::*;
 
 extern "Rust" {
     fn __x86_isr0();
@@ -99,7 +102,12 @@ pub unsafe extern "C" fn __x86_isr(regs: *mut X86Regs) {
         }
 
         let arch: *mut X86Thread = (*curthread!()).arch as *mut X86Thread;
-        //arch->regs = regs;
+
+        /* faults in user mode may end in a signal, keep the user registers at hand */
+        if (*regs).cs & 3 == 3 {
+            (*arch).regs = regs as *mut u8;
+            (*arch).orig_eax = !0;
+        }
 
         if (*regs).eip == 0x0FFF {  /* Signal return */
 
@@ -114,7 +122,19 @@ pub unsafe extern "C" fn __x86_isr(regs: *mut X86Regs) {
         }
 
         let addr = read_cr2();
-        arch_mm_page_fault(addr, __x86_isr_err_num as usize);
+
+        if arch_mm_page_fault(addr, __x86_isr_err_num as usize) != 0 {
+            /* bad user memory given to the uaccess helpers, they fail with EFAULT */
+            if (*regs).cs & 3 == 0 {
+                if let Some(fixup) = x86_fixup_search((*regs).eip) {
+                    (*regs).eip = fixup;
+                    return;
+                }
+            }
+
+            signal_proc_send(curproc!(), SIGSEGV);
+        }
+
         return;
     }
 
@@ -123,6 +143,22 @@ pub unsafe extern "C" fn __x86_isr(regs: *mut X86Regs) {
         return;
     }
     
+    if __x86_isr_int_num == 0x01 && (*regs).cs & 3 == 0 && x86_sysenter_stepped((*regs).eip) {
+        /* sysenter keeps the trap flag, hand it over to the syscall frame */
+        (*regs).eflags &= !X86_EFLAGS_TF;
+        x86_sysenter_step = 1;
+        return;
+    }
+
+    if (__x86_isr_int_num == 0x01 || __x86_isr_int_num == 0x03) && (*regs).cs & 3 == 3 {  /* Debug / Breakpoint */
+        let arch: *mut X86Thread = (*curthread!()).arch as *mut X86Thread;
+        (*arch).regs = regs as *mut u8;
+        (*arch).orig_eax = !0;
+
+        signal_proc_send(curproc!(), SIGTRAP);
+        return;
+    }
+
     if (__x86_isr_int_num == 0x80) {  /* syscall */
         let arch: *mut X86Thread = (*curthread!()).arch as *mut X86Thread;
         (*arch).regs = regs as *mut u8;
@@ -152,7 +188,7 @@ pub unsafe fn x86_isr_setup() {
     x86_idt_gate_set(0x00, __x86_isr0  as usize);
     x86_idt_gate_set(0x01, __x86_isr1  as usize);
     x86_idt_gate_set(0x02, __x86_isr2  as usize);
-    x86_idt_gate_set(0x03, __x86_isr3  as usize);
+    x86_idt_gate_user_set(0x03, __x86_isr3  as usize);
     x86_idt_gate_set(0x04, __x86_isr4  as usize);
     x86_idt_gate_set(0x05, __x86_isr5  as usize);
     x86_idt_gate_set(0x06, __x86_isr6  as usize);
@@ -4,6 +4,8 @@ pub mod idt;
 pub mod init;
 pub mod isr;
 pub mod fpu;
+pub mod cpuinfo;
+pub mod sysenter;
 pub mod trace;
 
 //pub use self::cpu::*;
@@ -0,0 +1,87 @@
+use prelude::*;
+
+use arch::cpu::gdt::TSS_ENTRY;
+use arch::include::core::arch::X86Thread;
+use arch::include::cpu::cpu::*;
+use arch::sys::syscall::arch_syscall;
+use arch::sys::vdso::*;
+use sys::sched::*;
+
+extern "C" {
+    fn x86_cpuid_check() -> u32;
+    fn __x86_sysenter();
+}
+
+/** kernel code segment, sysexit derives the user segments from it */
+const SYSENTER_CS: u64 = 0x08;
+
+/** whether the cpu takes system calls through sysenter */
+pub static mut X86_SYSENTER: bool = false;
+
+/**
+ * user address sysexit returns to, the instruction after sysenter in the
+ * vdso trampoline. system calls returning anywhere else go through iret
+ */
+#[no_mangle]
+pub static mut x86_sysenter_return: usize = 0;
+
+/** set by the debug trap when a traced thread single stepped into sysenter */
+#[no_mangle]
+pub static mut x86_sysenter_step: usize = 0;
+
+unsafe fn x86_sysenter_supported() -> bool {
+    if x86_cpuid_check() == 0 {
+        return false;
+    }
+
+    let (eax, _, _, edx) = cpuid(1);
+
+    let family   = (eax >> 8) & 0xf;
+    let model    = (eax >> 4) & 0xf;
+    let stepping = eax & 0xf;
+
+    /* early pentium pro parts report SEP without implementing it */
+    if family == 6 && model < 3 && stepping < 3 {
+        return false;
+    }
+
+    edx & CPUID_EDX_SEP != 0
+}
+
+pub unsafe fn x86_sysenter_setup() {
+    if !x86_sysenter_supported() {
+        print!("x86: sysenter not supported, system calls use int 0x80\n");
+        return;
+    }
+
+    x86_sysenter_return = vdso_sysenter_return();
+
+    write_msr(MSR_SYSENTER_CS,  SYSENTER_CS);
+    write_msr(MSR_SYSENTER_ESP, TSS_ENTRY.sp as u64);
+    write_msr(MSR_SYSENTER_EIP, __x86_sysenter as usize as u64);
+
+    X86_SYSENTER = true;
+    vdso_sysenter_enable();
+
+    print!("x86: system calls use sysenter\n");
+}
+
+/** keep the sysenter stack on the kernel stack of the running thread */
+pub unsafe fn x86_sysenter_stack_set(sp: usize) {
+    if X86_SYSENTER {
+        write_msr(MSR_SYSENTER_ESP, sp as u64);
+    }
+}
+
+/** entry point of the sysenter path, `regs` has the same layout as for int 0x80 */
+#[no_mangle]
+pub unsafe extern "C" fn __x86_sysenter_handler(regs: *mut X86Regs) {
+    let arch: *mut X86Thread = (*curthread!()).arch as *mut X86Thread;
+    (*arch).regs = regs as *mut u8;
+    arch_syscall(regs);
+}
+
+/** whether a kernel mode trap at `eip` is the single step into sysenter */
+pub unsafe fn x86_sysenter_stepped(eip: usize) -> bool {
+    X86_SYSENTER && eip == __x86_sysenter as usize
+}
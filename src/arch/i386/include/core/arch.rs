@@ -1,5 +1,6 @@
 use prelude::*;
 use mm::*;
+use arch::cpu::gdt::{GdtEntry, GDT_TLS_ENTRIES};
 
 use crate::{malloc_declare};
 
@@ -7,6 +8,9 @@ pub const X86_SS:     usize = 0x20 | 3;
 pub const X86_EFLAGS: usize = 0x200;
 pub const X86_CS:     usize = 0x18 | 3;
 
+/* trap flag, raises a debug exception after each instruction */
+pub const X86_EFLAGS_TF: usize = 0x100;
+
 #[repr(C)]
 pub struct X86Thread {
     pub kstack: usize, /* Kernel stack */
@@ -23,6 +27,16 @@ pub struct X86Thread {
 
     /* Flags */
     pub fpu_enabled: isize,
+
+    /* TLS descriptors, installed in the GDT when the thread runs */
+    pub tls: [GdtEntry; GDT_TLS_ENTRIES],
+
+    /* user %fs and %gs selectors, 0 means the default data segment */
+    pub fs: usize,
+    pub gs: usize,
+
+    /* syscall number of the last syscall entry, -1 after other traps */
+    pub orig_eax: usize,
 }
 
 //void arch_syscall(struct x86_regs *r);
@@ -73,6 +73,14 @@ pub const CR0_NE: usize = 1 << 5;
 /* CR4 */
 //#define CR4_PSE _BV(4)
 
+/* CPUID.1:EDX */
+pub const CPUID_EDX_SEP: u32 = 1 << 11;
+
+/* model specific registers */
+pub const MSR_SYSENTER_CS:  u32 = 0x174;
+pub const MSR_SYSENTER_ESP: u32 = 0x175;
+pub const MSR_SYSENTER_EIP: u32 = 0x176;
+
 /* CPU function */
 #[inline]
 pub unsafe fn read_cr0() -> usize {
@@ -134,6 +142,26 @@ pub unsafe fn write_cr4(val: usize) {
     llvm_asm!("mov $0, %cr4"::"r"(val));
 }
 
+/** cpuid `leaf`, returns (eax, ebx, ecx, edx) */
+#[inline]
+pub unsafe fn cpuid(leaf: u32) -> (u32, u32, u32, u32) {
+    let (eax, ebx, ecx, edx);
+    llvm_asm!("cpuid":"={eax}"(eax), "={ebx}"(ebx), "={ecx}"(ecx), "={edx}"(edx):"{eax}"(leaf), "{ecx}"(0));
+    (eax, ebx, ecx, edx)
+}
+
+#[inline]
+pub unsafe fn read_msr(msr: u32) -> u64 {
+    let (lo, hi): (u32, u32);
+    llvm_asm!("rdmsr":"={eax}"(lo), "={edx}"(hi):"{ecx}"(msr));
+    (hi as u64) << 32 | lo as u64
+}
+
+#[inline]
+pub unsafe fn write_msr(msr: u32, val: u64) {
+    llvm_asm!("wrmsr"::"{ecx}"(msr), "{eax}"(val as u32), "{edx}"((val >> 32) as u32));
+}
+
 /*
 /* cpu/gdt.c */
 void x86_gdt_setup(void);
@@ -383,7 +383,7 @@ pub unsafe fn arch_page_unmap(vaddr: usize) -> isize {
     return 0;
 }
 
-pub unsafe fn arch_mm_page_fault(vaddr: usize, err: usize) {
+pub unsafe fn arch_mm_page_fault(vaddr: usize, err: usize) -> isize {
     let mut flags = 0;
 
     flags |= if err & 0x01 != 0 { PF_PRESENT } else { 0 };
@@ -391,9 +391,7 @@ pub unsafe fn arch_mm_page_fault(vaddr: usize, err: usize) {
     flags |= if err & 0x04 != 0 { PF_USER    } else { 0 };
     flags |= if err & 0x10 != 0 { PF_EXEC    } else { 0 };
 
-    mm_page_fault(vaddr, flags as isize);
-
-    return;
+    return mm_page_fault(vaddr, flags as isize);
 }
 
 unsafe fn pmap_alloc() -> *mut PhysicalMap {
@@ -588,8 +586,19 @@ pub unsafe fn arch_page_get_mapping(pmap: *mut PhysicalMap, vaddr: vaddr_t) -> p
     return 0;
 }
 
+/** is `vaddr` mapped writable in `pmap`? */
+pub unsafe fn arch_page_writable(pmap: *mut PhysicalMap, vaddr: vaddr_t) -> bool {
+    let old_map = pmap_switch(pmap);
+
+    let page = __page_get_mapping(vaddr);
+
+    pmap_switch(old_map);
+
+    return page & PG_WRITE != 0;
+}
+
 pub unsafe fn pmap_page_read(paddr: paddr_t, off: usize, size: usize, buf: *mut u8) -> isize {
-    let sz = max!(PAGE_SIZE, size - off);
+    let sz = if off + size > PAGE_SIZE { PAGE_SIZE - off } else { size };
     let old = frame_mount(paddr);
     let page = MOUNT_ADDR as *const u8;
     memcpy(buf, page.offset(off as isize), sz);
@@ -598,7 +607,7 @@ pub unsafe fn pmap_page_read(paddr: paddr_t, off: usize, size: usize, buf: *mut
 }
 
 pub unsafe fn pmap_page_write(paddr: paddr_t, off: usize, size: usize, buf: *const u8) -> isize {
-    let sz = max!(PAGE_SIZE, size - off);
+    let sz = if off + size > PAGE_SIZE { PAGE_SIZE - off } else { size };
     let old = frame_mount(paddr);
     let page = MOUNT_ADDR as *mut u8;
     memcpy(page.offset(off as isize), buf, sz);
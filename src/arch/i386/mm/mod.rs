@@ -1,2 +1,3 @@
 pub mod i386;
 pub mod mm;
+pub mod uaccess;
@@ -0,0 +1,43 @@
+use prelude::*;
+
+/** entry of the exception table in cpu.S */
+#[repr(C)]
+struct X86ExTableEntry {
+    /** instruction allowed to fault on user memory */
+    insn: usize,
+
+    /** where to resume if it does */
+    fixup: usize,
+}
+
+extern "C" {
+    fn x86_copy_user(dst: *mut u8, src: *const u8, n: usize) -> usize;
+    fn x86_strncpy_user(dst: *mut u8, src: *const u8, n: usize) -> isize;
+
+    static __x86_ex_table: X86ExTableEntry;
+    static __x86_ex_table_end: X86ExTableEntry;
+}
+
+/** copy `n` bytes from or to user memory, returns the number of bytes not copied */
+pub unsafe fn arch_copy_user(dst: *mut u8, src: *const u8, n: usize) -> usize {
+    x86_copy_user(dst, src, n)
+}
+
+/**
+ * copy a null terminated string of at most `n` bytes from user memory,
+ * returns its length, `n` if it is not terminated or -1 on fault
+ */
+pub unsafe fn arch_strncpy_user(dst: *mut u8, src: *const u8, n: usize) -> isize {
+    x86_strncpy_user(dst, src, n)
+}
+
+/** fixup address of `eip` if it is allowed to fault on user memory */
+pub unsafe fn x86_fixup_search(eip: usize) -> Option<usize> {
+    let start = &__x86_ex_table as *const X86ExTableEntry;
+    let end = &__x86_ex_table_end as *const X86ExTableEntry;
+
+    let count = (end as usize - start as usize) / core::mem::size_of::<X86ExTableEntry>();
+    let table = core::slice::from_raw_parts(start, count);
+
+    table.iter().find(|entry| entry.insn == eip).map(|entry| entry.fixup)
+}
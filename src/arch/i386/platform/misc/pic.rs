@@ -143,6 +143,20 @@ extern "C" {
 
 static mut IRQ_HANDLERS: [Option<unsafe fn(_: *const X86Regs)>; 16] = [None; 16];
 
+/** interrupts received on each line */
+static mut IRQ_COUNTS: [u64; 16] = [0; 16];
+
+/** number of interrupts received on each irq line, and if it has a handler */
+pub unsafe fn arch_irq_stats() -> [(u64, bool); 16] {
+    let mut stats = [(0, false); 16];
+
+    for irq in 0..16 {
+        stats[irq] = (IRQ_COUNTS[irq], IRQ_HANDLERS[irq].is_some());
+    }
+
+    stats
+}
+
 pub unsafe fn x86_irq_handler_install(irq: usize, handler: unsafe fn(_: *const X86Regs)) {
     if (irq < 16) {
         x86_irq_unmask(irq);
@@ -177,6 +191,7 @@ pub unsafe extern "C" fn __x86_irq_handler(r: *const X86Regs) {
         handler = None;
     } else {
         handler = IRQ_HANDLERS[(__x86_isr_int_num - 32) as usize];
+        IRQ_COUNTS[(__x86_isr_int_num - 32) as usize] += 1;
     }
 
     x86_irq_ack((__x86_isr_int_num - 32) as usize);
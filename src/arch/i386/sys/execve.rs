@@ -7,6 +7,7 @@ use mm::*;
 
 use crate::arch::i386::include::core::arch::X86_EFLAGS;
 use crate::arch::i386::include::core::arch::X86Thread;
+use crate::arch::i386::cpu::gdt::*;
 use crate::arch::i386::include::cpu::cpu::{read_cr3, write_cr3};
 use crate::{malloc_declare};
 
@@ -14,6 +15,38 @@ pub unsafe fn tlb_flush() {
     write_cr3(read_cr3());
 }
 
+/**
+ * carve the TLS block of the main thread from the top of the user stack,
+ * using the i386 layout where the thread pointer follows the block and
+ * points to itself. returns the new top of the stack
+ */
+unsafe fn x86_tls_setup(proc: *mut Process, arch: *mut X86Thread, stack: usize) -> usize {
+    let tls = &(*proc).tls;
+
+    let align = max!(tls.align, core::mem::size_of::<usize>());
+    let size  = (tls.memsz + align - 1) & !(align - 1);
+
+    let tp    = (stack - core::mem::size_of::<usize>()) & !(align - 1);
+    let block = tp - size;
+
+    memcpy(block as *mut u8, tls.image as *const u8, tls.filesz);
+    core::ptr::write_bytes((block + tls.filesz) as *mut u8, 0, size - tls.filesz);
+    *(tp as *mut usize) = tp;
+
+    let desc = UserDesc {
+        entry_number: GDT_TLS_MIN as u32,
+        base_addr: tp as u32,
+        limit: 0xFFFFF,
+        flags: UD_SEG_32BIT | UD_LIMIT_IN_PAGES | UD_USEABLE,
+    };
+
+    /* a plain data segment, always accepted */
+    (*arch).tls[0] = GdtEntry::from_user_desc(&desc).unwrap();
+    (*arch).gs = (GDT_TLS_MIN << 3) | 3;
+
+    block
+}
+
 pub unsafe fn arch_sys_execve(proc: *mut Process, argc: usize, _argp: *const *const u8, envc: usize, _envp: *const *const u8) {
     let thread = (*proc).threads.head().unwrap().value;
     let arch = (*thread).arch as *mut X86Thread;
@@ -31,7 +64,28 @@ pub unsafe fn arch_sys_execve(proc: *mut Process, argc: usize, _argp: *const *co
     let mut stack: usize = USER_STACK;
     tlb_flush();
 
+    /* the new image starts without any thread-local storage */
+    (*arch).tls = [GdtEntry::new(); GDT_TLS_ENTRIES];
+    (*arch).fs  = 0;
+    (*arch).gs  = 0;
+
+    if (*proc).tls.memsz != 0 {
+        stack = x86_tls_setup(proc, arch, stack);
+    }
+
+    /* bytes for AT_RANDOM */
+    stack -= 16;
+    binfmt_random(stack as *mut u8, 16);
+    let random = stack;
+
+    /* path for AT_EXECFN */
+    stack -= strlen((*proc).name) + 1;
+    strcpy(stack as *mut u8, (*proc).name);
+    let execfn = stack;
+
     /* push envp strings */
+    (*proc).args.env_end = stack;
+
     let mut tmp_envc = envc - 1;
     *u_envp.offset(tmp_envc as isize) = core::ptr::null_mut();
 
@@ -43,6 +97,9 @@ pub unsafe fn arch_sys_execve(proc: *mut Process, argc: usize, _argp: *const *co
     }
 
     /* push argp strings */
+    (*proc).args.env_start = stack;
+    (*proc).args.arg_end = stack;
+
     let mut tmp_argc = argc - 1;
     *u_argp.offset(tmp_argc as isize) = core::ptr::null_mut();
 
@@ -53,6 +110,15 @@ pub unsafe fn arch_sys_execve(proc: *mut Process, argc: usize, _argp: *const *co
         *u_argp.offset(tmp_argc as isize) = stack as *mut u8;
     }
 
+    (*proc).args.arg_start = stack;
+
+    /* auxiliary vector, follows the envp array */
+    let auxv = binfmt_auxv(proc, random, execfn);
+
+    stack &= !(core::mem::size_of::<usize>() - 1);
+    stack -= auxv.len() * core::mem::size_of::<usize>();
+    memcpy(stack as *mut u8, auxv.as_ptr() as *const u8, auxv.len() * core::mem::size_of::<usize>());
+
     stack -= envc * core::mem::size_of::<usize>();
     memcpy(stack as *mut u8, u_envp as *const u8, envc * core::mem::size_of::<usize>());
 
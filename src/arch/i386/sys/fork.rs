@@ -6,6 +6,7 @@ use sys::thread::*;
 
 use crate::arch::i386::include::core::*;
 use crate::arch::i386::include::cpu::*;
+use crate::arch::i386::sys::thread::x86_tls_save;
 
 use crate::{malloc_declare};
 
@@ -52,6 +53,15 @@ pub unsafe fn arch_proc_fork(thread: *mut Thread, fork: *mut Process) -> isize {
     (*ftarch).fpu_enabled = 0;
     (*ftarch).fpu_context = core::ptr::null_mut();
 
+    /* child inherits the TLS setup of the forking thread */
+    x86_tls_save(ptarch);
+
+    (*ftarch).tls = (*ptarch).tls;
+    (*ftarch).fs  = (*ptarch).fs;
+    (*ftarch).gs  = (*ptarch).gs;
+
+    (*ftarch).orig_eax = (*ptarch).orig_eax;
+
     return 0;
 
     /*
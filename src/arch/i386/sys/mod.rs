@@ -5,6 +5,7 @@ pub mod proc;
 pub mod thread;
 pub mod sched;
 pub mod execve;
+pub mod vdso;
 
 pub use self::fork::*;
 pub use self::syscall::*;
@@ -13,3 +14,4 @@ pub use self::proc::*;
 pub use self::thread::*;
 pub use self::sched::*;
 pub use self::execve::*;
+pub use self::vdso::*;
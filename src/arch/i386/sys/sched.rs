@@ -8,6 +8,9 @@ use arch::include::cpu::cpu::X86Regs;
 use arch::cpu::gdt::x86_kernel_stack_set;
 use arch::cpu::init::virtual_address;
 use arch::include::core::arch::X86Thread;
+use arch::sys::thread::{x86_tls_save, arch_thread_signals};
+use arch::sys::vdso::vdso_update;
+use sys::ptrace::*;
 
 extern "C" {
     fn x86_read_ip() -> usize;
@@ -61,10 +64,21 @@ unsafe fn x86_sched_handler(r: *const X86Regs) {
     */
 
     TIMER_TICKS += 1;
+    vdso_update(arch_rtime_ns());
 
     if kidle == 0 {
         let arch = (*curthread!()).arch as *mut X86Thread;
 
+        if (*r).cs & 3 == 3 {
+            /* interrupted in user mode, keep the user registers at hand */
+            (*arch).regs = r as *mut u8;
+            (*arch).orig_eax = !0;
+
+            /* traced threads stop for their tracer on their own stack */
+            if ptrace_traced(curproc!()) {
+                arch_thread_signals(curthread!());
+            }
+        }
 
         let (ip, sp, bp);    
 
@@ -80,6 +94,8 @@ unsafe fn x86_sched_handler(r: *const X86Regs) {
         (*arch).eip = ip;
         (*arch).esp = sp;
         (*arch).ebp = bp;
+
+        x86_tls_save(arch);
     }
 
     schedule();
@@ -123,6 +139,21 @@ pub unsafe fn arch_cur_thread_kill() {
     x86_goto(__arch_cur_thread_kill as usize, stack, stack);
 }
 
+unsafe fn __arch_cur_thread_exit() {
+    /* will set the stack to vma(0x100000) */
+    (*curthread!()).kill();
+
+    /* the zombie is freed by whoever joins it */
+    curthread!() = core::ptr::null_mut();
+    kernel_idle();
+}
+
+/* like arch_cur_thread_kill, but keep the thread structure around */
+pub unsafe fn arch_cur_thread_exit() {
+    let stack = &__IDLE_STACK as *const _ as usize + 8192;
+    x86_goto(__arch_cur_thread_exit as usize, stack, stack);
+}
+
 pub unsafe fn arch_sleep() {
     x86_sleep();
 }
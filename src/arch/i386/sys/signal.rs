@@ -6,7 +6,11 @@ use arch::include::core::arch::X86_SS;
 use arch::include::core::arch::X86Thread;
 use arch::include::cpu::cpu::X86Regs;
 use arch::sys::sched::arch_sleep;
+use arch::sys::thread::x86_tls_restore;
+use sys::coredump::*;
+use sys::linux::signal::linux_proc_signal;
 use sys::process::*;
+use sys::ptrace::*;
 use sys::signal::*;
 use sys::sched::*;
 
@@ -15,6 +19,27 @@ extern "C" {
 }
 
 pub unsafe fn arch_handle_signal(sig: usize) {
+    let mut sig = sig;
+
+    /* can't signal a zmobie */
+    if (*curproc!()).running == 0 {
+        return;
+    }
+
+    /* traced processes stop on every signal but SIGKILL, the tracer decides what gets delivered */
+    if ptrace_traced(curproc!()) && sig != SIGKILL as usize {
+        sig = ptrace_stop(sig as isize) as usize;
+
+        if sig == 0 {
+            return;
+        }
+    }
+
+    arch_signal_deliver(sig);
+}
+
+/** act on `sig` for the current thread, without stopping for a tracer */
+pub unsafe fn arch_signal_deliver(sig: usize) {
     let mut handler = (*curproc!()).sigaction[sig].sa_handler;
 
     /* can't signal a zmobie */
@@ -30,7 +55,17 @@ pub unsafe fn arch_handle_signal(sig: usize) {
 
     match (handler) {
         /* SIGACT_IGNORE */ 3 => return,
-        /* SIGACT_ABORT */ 1 |
+        /* SIGACT_ABORT */ 1 => {
+            let core = match proc_coredump(curproc!(), sig) {
+                Ok(_) => WCOREFLAG as usize,
+                Err(_) => 0,
+            };
+
+            (*curproc!()).exit = proc_exit!(sig, sig | core) as isize;
+            proc_kill(curproc!());
+            arch_sleep();
+            /* unreachable */
+        },
         /* SIGACT_TERMINATE */ 2 => {
             (*curproc!()).exit = proc_exit!(sig, sig) as isize;
             proc_kill(curproc!());
@@ -45,14 +80,16 @@ pub unsafe fn arch_handle_signal(sig: usize) {
 
     let mut sig_sp = (*((*arch).regs as *mut X86Regs)).esp;
 
-    /* push signal number */
+    /* push signal number, as numbered by the process personality */
     sig_sp -= core::mem::size_of::<isize>();
-    *(sig_sp as *mut isize) = sig as isize;
+    *(sig_sp as *mut isize) = linux_proc_signal(curproc!(), sig as isize);
 
     /* push return address */
     sig_sp -= core::mem::size_of::<usize>();
     *(sig_sp as *mut usize) = 0x0FFF;
 
+    /* the handler runs with the thread's own TLS, the segments may belong to the last thread run */
+    x86_tls_restore(arch);
     x86_jump_user(0, handler, X86_CS, (*arch).eflags, sig_sp, X86_SS);
 }
 
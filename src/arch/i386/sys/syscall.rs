@@ -4,19 +4,60 @@ use arch::include::core::arch::X86Thread;
 use arch::include::cpu::cpu::X86Regs;
 use kern::print::cstr;
 use sys::syscall::*;
+use sys::syscall::table::*;
+use sys::syscall::trace::*;
 use sys::thread::*;
 use sys::sched::*;
+use sys::ptrace::*;
+use sys::signal::*;
+use arch::sys::signal::arch_signal_deliver;
 
 #[no_mangle]
 pub unsafe fn arch_syscall(r: *mut X86Regs) {
-    if (*r).eax >= SYSCALL_CNT {
-        print!("[{}:{}] {}: undefined syscall {}\n", (*curproc!()).pid, (*curthread!()).tid, cstr((*curproc!()).name), (*r).eax);
-        arch_syscall_return(curthread!(), -ENOSYS as usize);
-        return;
+    let arch = (*curthread!()).arch as *mut X86Thread;
+    let mut sig = 0;
+
+    (*arch).orig_eax = (*r).eax;
+
+    if ptrace_syscall_traced(curproc!()) {
+        /* syscall entry stop, the tracer may change the syscall through orig_eax */
+        (*r).eax = -ENOSYS as usize;
+        sig = ptrace_stop(SIGTRAP);
+    }
+
+    let nr = (*arch).orig_eax;
+
+    match proc_syscall_lookup(curproc!(), nr) {
+        None => {
+            print!("[{}:{}] {}: undefined syscall {}\n", (*curproc!()).pid, (*curthread!()).tid, cstr((*curproc!()).name), nr as isize);
+            arch_syscall_return(curthread!(), -ENOSYS as usize);
+        },
+        Some(def) => {
+            let args = [(*r).ebx, (*r).ecx, (*r).edx, (*r).esi, (*r).edi, (*r).ebp];
+            let trace = syscall_trace_enter(nr, def, &args);
+
+            let ret = syscall_dispatch(def, &args);
+            arch_syscall_return(curthread!(), ret);
+
+            if let Some(trace) = trace {
+                syscall_trace_exit(trace, ret as isize);
+            }
+        },
+    }
+
+    if ptrace_syscall_traced(curproc!()) {
+        /* syscall exit stop */
+        let exit_sig = ptrace_stop(SIGTRAP);
+
+        if exit_sig != 0 {
+            sig = exit_sig;
+        }
+    }
+
+    /* signals the tracer resumed us with */
+    if sig != 0 {
+        arch_signal_deliver(sig as usize);
     }
-	
-    let syscall = &SYSCALL_TABLE[(*r).eax].0 as *const _ as *const fn(usize, usize, usize);
-    (*syscall)((*r).ebx, (*r).ecx, (*r).edx);
 }
 
 pub unsafe fn arch_syscall_return(thread: *mut Thread, val: usize) {
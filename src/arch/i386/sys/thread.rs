@@ -1,13 +1,16 @@
 use prelude::*;
 
 use arch::cpu::fpu::*;
-use arch::cpu::gdt::x86_kernel_stack_set;
+use arch::cpu::gdt::*;
 use arch::cpu::init::virtual_address;
 use arch::include::core::arch::*;
+use arch::include::cpu::cpu::X86Regs;
 use arch::mm::i386::*;
 use arch::sys::signal::arch_handle_signal;
 use mm::*;
+use sys::ptrace::*;
 use sys::sched::*;
+use sys::signal::*;
 use sys::thread::*;
 
 extern "C" {
@@ -26,12 +29,50 @@ macro push {
     }
 }
 
+/** save the user %fs and %gs selectors of the running thread */
+pub unsafe fn x86_tls_save(arch: *mut X86Thread) {
+    let (fs, gs): (usize, usize);
+
+    llvm_asm!("mov %fs, $0":"=r"(fs));
+    llvm_asm!("mov %gs, $0":"=r"(gs));
+
+    (*arch).fs = fs & 0xFFFF;
+    (*arch).gs = gs & 0xFFFF;
+}
+
+/* loading a selector of a cleared TLS slot would fault, use the user data segment instead */
+unsafe fn x86_tls_selector(arch: *mut X86Thread, sel: usize) -> usize {
+    let idx = sel >> 3;
+
+    if sel == 0 {
+        return X86_SS;
+    }
+
+    if idx >= GDT_TLS_MIN && idx < GDT_TLS_MIN + GDT_TLS_ENTRIES && !(*arch).tls[idx - GDT_TLS_MIN].is_present() {
+        return X86_SS;
+    }
+
+    sel
+}
+
+/** install the TLS descriptors and the %fs and %gs selectors of a thread */
+pub unsafe fn x86_tls_restore(arch: *mut X86Thread) {
+    x86_tls_set(&(*arch).tls);
+
+    let fs = x86_tls_selector(arch, (*arch).fs);
+    let gs = x86_tls_selector(arch, (*arch).gs);
+
+    llvm_asm!("mov $0, %fs"::"r"(fs));
+    llvm_asm!("mov $0, %gs"::"r"(gs));
+}
+
 pub unsafe fn arch_thread_spawn(thread: *mut Thread) {
     let arch = (*thread).arch as *mut X86Thread;
     let pmap = (*(*thread).owner).vm_space.pmap;
 
     pmap_switch(pmap);
     x86_kernel_stack_set((*arch).kstack);
+    x86_tls_restore(arch);
     x86_jump_user((*arch).eax, (*arch).eip, X86_CS, (*arch).eflags, (*arch).esp, X86_SS);
 }
 
@@ -42,15 +83,36 @@ pub unsafe fn arch_thread_switch(thread: *mut Thread) {
     pmap_switch(pmap);
 
     x86_kernel_stack_set((*arch).kstack);
+    x86_tls_restore(arch);
     x86_fpu_disable();
 
-    if (*(*thread).owner).sig_queue.as_ref().unwrap().count() != 0 {
-        let sig = (*(*thread).owner).sig_queue.as_mut().unwrap().dequeue().unwrap();
+    /* we are not on the stack of the thread yet, traced threads can't stop
+     * here for their tracer and take their signals from the timer instead */
+    if !ptrace_traced((*thread).owner) {
+        arch_thread_signals(thread);
+    }
+
+    x86_goto((*arch).eip, (*arch).ebp, (*arch).esp);
+}
+
+/** handle a pending signal of `thread`, which is the current one */
+pub unsafe fn arch_thread_signals(thread: *mut Thread) {
+    /* sig_pending has bit `sig` set, the mask bit `sig - 1` */
+    let pending = (*thread).sig_pending & !(((*thread).sig_mask as u32) << 1);
+
+    if pending != 0 {
+        /* signals directed at this thread go first */
+        let sig = pending.trailing_zeros();
+        (*thread).sig_pending &= !(1 << sig);
         arch_handle_signal(sig as usize);
         /* if we get back here, the signal was ignored */
     }
 
-    x86_goto((*arch).eip, (*arch).ebp, (*arch).esp);
+    /* blocked signals stay queued */
+    if let Some(sig) = signal_dequeue((*thread).owner, !(*thread).sig_mask) {
+        arch_handle_signal(sig as usize);
+        /* if we get back here, the signal was ignored */
+    }
 }
 
 pub unsafe fn arch_thread_create(thread: *mut Thread, stack: usize, entry: usize, uentry: usize, arg: usize) {
@@ -82,9 +144,133 @@ pub unsafe fn arch_thread_create(thread: *mut Thread, stack: usize, entry: usize
     push!(stack, usize, 0);
 
     (*arch).esp = stack;
+
+    /* threads start with the TLS setup of their creator */
+    if !curthread!().is_null() && !(*curthread!()).arch.is_null() {
+        let carch = (*curthread!()).arch as *mut X86Thread;
+
+        x86_tls_save(carch);
+
+        (*arch).tls = (*carch).tls;
+        (*arch).fs  = (*carch).fs;
+        (*arch).gs  = (*carch).gs;
+    }
+
     (*thread).arch = arch as *mut u8;
 }
 
+pub unsafe fn arch_set_thread_area(thread: *mut Thread, desc: &mut UserDesc) -> Result<(), Error> {
+    let arch = (*thread).arch as *mut X86Thread;
+
+    if desc.entry_number == u32::MAX {
+        /* pick a free slot and report it back */
+        let free = (0..GDT_TLS_ENTRIES).find(|&i| (*arch).tls[i].0 == 0).ok_or(Error::ESRCH)?;
+        desc.entry_number = (GDT_TLS_MIN + free) as u32;
+    }
+
+    let idx = desc.entry_number as usize;
+
+    if idx < GDT_TLS_MIN || idx >= GDT_TLS_MIN + GDT_TLS_ENTRIES {
+        return Err(Error::EINVAL);
+    }
+
+    (*arch).tls[idx - GDT_TLS_MIN] = GdtEntry::from_user_desc(desc)?;
+
+    if thread == curthread!() {
+        x86_tls_set(&(*arch).tls);
+    }
+
+    Ok(())
+}
+
+pub unsafe fn arch_get_thread_area(thread: *mut Thread, desc: &mut UserDesc) -> Result<(), Error> {
+    let arch = (*thread).arch as *mut X86Thread;
+    let idx = desc.entry_number as usize;
+
+    if idx < GDT_TLS_MIN || idx >= GDT_TLS_MIN + GDT_TLS_ENTRIES {
+        return Err(Error::EINVAL);
+    }
+
+    *desc = (*arch).tls[idx - GDT_TLS_MIN].to_user_desc(idx as u32);
+
+    Ok(())
+}
+
+/** number of registers in an i386 elf_gregset_t */
+pub const ELF_NGREG: usize = 17;
+
+/** user registers of `thread` laid out as an i386 elf_gregset_t, for core dumps */
+pub unsafe fn arch_thread_gregs(thread: *mut Thread) -> [usize; ELF_NGREG] {
+    let arch = (*thread).arch as *mut X86Thread;
+
+    if arch.is_null() || (*arch).regs.is_null() {
+        return [0; ELF_NGREG];
+    }
+
+    if thread == curthread!() {
+        x86_tls_save(arch);
+    }
+
+    let regs = &*((*arch).regs as *const X86Regs);
+    let fs = x86_tls_selector(arch, (*arch).fs);
+    let gs = x86_tls_selector(arch, (*arch).gs);
+
+    [
+        regs.ebx, regs.ecx, regs.edx, regs.esi, regs.edi, regs.ebp, regs.eax,
+        /* ds, es, fs, gs */
+        X86_SS, X86_SS, fs, gs,
+        (*arch).orig_eax,
+        regs.eip, regs.cs, regs.eflags, regs.esp, regs.ss,
+    ]
+}
+
+/* user changeable eflags: CF, PF, AF, ZF, SF, TF, DF and OF */
+const X86_EFLAGS_USER: usize = 0xDD5;
+
+/** set the user registers of a thread stopped for its tracer */
+pub unsafe fn arch_thread_set_gregs(thread: *mut Thread, gregs: &[usize; ELF_NGREG]) {
+    let arch = (*thread).arch as *mut X86Thread;
+
+    if arch.is_null() || (*arch).regs.is_null() {
+        return;
+    }
+
+    let regs = &mut *((*arch).regs as *mut X86Regs);
+
+    regs.ebx = gregs[0];
+    regs.ecx = gregs[1];
+    regs.edx = gregs[2];
+    regs.esi = gregs[3];
+    regs.edi = gregs[4];
+    regs.ebp = gregs[5];
+    regs.eax = gregs[6];
+    regs.eip = gregs[12];
+    regs.esp = gregs[15];
+
+    /* segments stay ours, only user flags may change */
+    regs.eflags = (regs.eflags & !X86_EFLAGS_USER) | (gregs[14] & X86_EFLAGS_USER);
+
+    /* changing orig_eax at syscall entry changes the syscall */
+    (*arch).orig_eax = gregs[11];
+}
+
+/** set or clear the trap flag in the user registers of a thread */
+pub unsafe fn arch_thread_singlestep(thread: *mut Thread, step: bool) {
+    let arch = (*thread).arch as *mut X86Thread;
+
+    if arch.is_null() || (*arch).regs.is_null() {
+        return;
+    }
+
+    let regs = (*arch).regs as *mut X86Regs;
+
+    if step {
+        (*regs).eflags |= X86_EFLAGS_TF;
+    } else {
+        (*regs).eflags &= !X86_EFLAGS_TF;
+    }
+}
+
 pub unsafe fn arch_thread_kill(thread: *mut Thread) {
     let arch = (*thread).arch as *mut X86Thread;
 
@@ -130,5 +316,6 @@ pub unsafe extern "C" fn internal_arch_sleep() {
     (*arch).eip = ip;
     (*arch).esp = sp;
     (*arch).ebp = bp;
+    x86_tls_save(arch);
     kernel_idle();
 }
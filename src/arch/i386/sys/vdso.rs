@@ -0,0 +1,167 @@
+use prelude::*;
+
+use arch::cpu::init::local_address;
+use arch::sys::sched::arch_rtime_ns;
+use kern::time::gettime;
+use mm::*;
+use sys::binfmt::binfmt::USER_STACK_BASE;
+
+/*
+ * the vdso is two pages right below the user stack: the time page the
+ * kernel updates on every tick and the code page built from vdso.S. both
+ * live in the kernel image and are shared read-only by every process.
+ */
+
+/** user address of the time page, keep in sync with vdso.S */
+pub const VDSO_DATA: usize = USER_STACK_BASE - 2 * PAGE_SIZE;
+
+/** user address of the vdso image */
+pub const VDSO_BASE: usize = USER_STACK_BASE - PAGE_SIZE;
+
+/** the syscall trampoline may use sysenter */
+pub const VDSO_SYSENTER: u32 = 1;
+
+extern "C" {
+    static __x86_vdso_start: u8;
+    static __x86_vdso_end: u8;
+    fn __kernel_vsyscall();
+    fn __x86_vdso_sysenter_return();
+}
+
+/** the time page, offsets are used by vdso.S */
+#[repr(C, align(4096))]
+pub struct VdsoData {
+    /** odd while an update is in progress */
+    pub seq: u32,
+    pub flags: u32,
+
+    /** time since boot */
+    pub mono_sec: u32,
+    pub mono_nsec: u32,
+
+    /** wall clock */
+    pub real_sec: u32,
+    pub real_nsec: u32,
+}
+
+static mut VDSO_TIME: VdsoData = VdsoData {
+    seq: 0,
+    flags: 0,
+    mono_sec: 0,
+    mono_nsec: 0,
+    real_sec: 0,
+    real_nsec: 0,
+};
+
+/** wall clock at boot, in ns */
+static mut VDSO_BOOT_NS: u64 = 0;
+
+/** the object backing the vdso mappings of all processes */
+static mut VDSO_OBJECT: *mut VmObject = core::ptr::null_mut();
+
+/** user address of `sym`, a symbol inside the vdso image */
+unsafe fn vdso_sym(sym: usize) -> usize {
+    VDSO_BASE + sym - &__x86_vdso_start as *const _ as usize
+}
+
+pub unsafe fn vdso_sysenter_return() -> usize {
+    vdso_sym(__x86_vdso_sysenter_return as usize)
+}
+
+/** user address of the vdso image if there is one, passed in AT_SYSINFO_EHDR */
+pub unsafe fn vdso_base() -> Option<usize> {
+    if VDSO_OBJECT.is_null() {
+        None
+    } else {
+        Some(VDSO_BASE)
+    }
+}
+
+/** user address of the syscall trampoline, passed in AT_SYSINFO */
+pub unsafe fn vdso_vsyscall() -> usize {
+    vdso_sym(__kernel_vsyscall as usize)
+}
+
+pub unsafe fn vdso_sysenter_enable() {
+    VDSO_TIME.flags |= VDSO_SYSENTER;
+}
+
+/** wrap the time page and the image in a vm object mapped by every process */
+pub unsafe fn vdso_init() {
+    let image_size = &__x86_vdso_end as *const _ as usize - &__x86_vdso_start as *const _ as usize;
+
+    if image_size > PAGE_SIZE {
+        panic!("vdso image does not fit in a page");
+    }
+
+    VDSO_BOOT_NS = match gettime() {
+        Ok(ts) => ts.tv_sec * 1000000000 + ts.tv_nsec as u64,
+        Err(_) => 0,
+    };
+
+    vdso_update(arch_rtime_ns());
+
+    /* no pager, every page of the object is inserted below */
+    let vm_object = Box::leak(VmObject::alloc(VmObject {
+        pager: core::ptr::null_mut(),
+
+        ..VmObject::zero()
+    }));
+
+    let pages = [
+        local_address::<u8>(&VDSO_TIME as *const _ as usize) as usize,
+        local_address::<u8>(&__x86_vdso_start as *const _ as usize) as usize,
+    ];
+
+    for (i, &paddr) in pages.iter().enumerate() {
+        let vm_page = mm_page(paddr);
+
+        (*vm_page).paddr = paddr;
+        (*vm_page).vm_object = vm_object;
+        (*vm_page).off = (i * PAGE_SIZE) as off_t;
+        (*vm_page).refcnt = 1;
+
+        vm_object.insert(vm_page);
+    }
+
+    /* never released, the pages belong to the kernel image */
+    vm_object.incref();
+    VDSO_OBJECT = vm_object;
+
+    print!("vdso: {} bytes image, mapped at {:p} in every process\n", image_size, VDSO_BASE as *const u8);
+}
+
+/** publish the current time, called from the timer tick */
+pub unsafe fn vdso_update(ns: u64) {
+    let real = VDSO_BOOT_NS + ns;
+    let time = &mut VDSO_TIME;
+
+    core::ptr::write_volatile(&mut time.seq, time.seq.wrapping_add(1));
+
+    core::ptr::write_volatile(&mut time.mono_sec,  (ns / 1000000000) as u32);
+    core::ptr::write_volatile(&mut time.mono_nsec, (ns % 1000000000) as u32);
+    core::ptr::write_volatile(&mut time.real_sec,  (real / 1000000000) as u32);
+    core::ptr::write_volatile(&mut time.real_nsec, (real % 1000000000) as u32);
+
+    core::ptr::write_volatile(&mut time.seq, time.seq.wrapping_add(1));
+}
+
+/** map the vdso into `vm_space`, the entries are kept sorted by the caller */
+pub unsafe fn vdso_map(vm_space: &mut VmSpace) {
+    if VDSO_OBJECT.is_null() {
+        return;
+    }
+
+    for &(base, off, flags) in [(VDSO_DATA, 0, VM_UR), (VDSO_BASE, PAGE_SIZE, VM_URX)].iter() {
+        let vm_entry = Box::leak(VmEntry::alloc(VmEntry::new()));
+
+        vm_entry.base  = base;
+        vm_entry.size  = PAGE_SIZE;
+        vm_entry.flags = flags;
+        vm_entry.off   = off;
+        vm_entry.vm_object = VDSO_OBJECT;
+        vm_entry.qnode = vm_space.vm_entries.enqueue(vm_entry);
+
+        (*VDSO_OBJECT).incref();
+    }
+}
@@ -0,0 +1,37 @@
+use prelude::*;
+use bits::fcntl::O_CLOEXEC;
+
+/* epoll_create1(2) flags */
+pub const EPOLL_CLOEXEC   : usize = O_CLOEXEC;
+
+/* epoll_ctl(2) operations */
+pub const EPOLL_CTL_ADD   : usize = 1;
+pub const EPOLL_CTL_DEL   : usize = 2;
+pub const EPOLL_CTL_MOD   : usize = 3;
+
+/* epoll events, the same bits as the poll(2) ones */
+pub const EPOLLIN         : u32 = 0x0001;
+pub const EPOLLPRI        : u32 = 0x0002;
+pub const EPOLLOUT        : u32 = 0x0004;
+pub const EPOLLERR        : u32 = 0x0008;
+pub const EPOLLHUP        : u32 = 0x0010;
+pub const EPOLLRDNORM     : u32 = 0x0040;
+pub const EPOLLWRNORM     : u32 = 0x0100;
+
+/* epoll modes */
+pub const EPOLLONESHOT    : u32 = 1 << 30;  /* Disable the entry after one event */
+pub const EPOLLET         : u32 = 1 << 31;  /* Report changes only, not the state */
+
+/* events always reported whether requested or not */
+pub const EPOLL_ALWAYS    : u32 = EPOLLERR | EPOLLHUP;
+
+/* the layout is packed on i386 */
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct EpollEvent {
+    /** requested or returned events */
+    pub events: u32,
+
+    /** user data handed back with the events */
+    pub data: u64,
+}
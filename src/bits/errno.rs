@@ -146,6 +146,144 @@ impl Error {
         -self
     }
 
+    /** the error with errno value `errno`, None if no error has that number */
+    pub fn from_errno(errno: isize) -> Option<Error> {
+        Some(match errno {
+            1    => Error::EPERM,
+            2    => Error::ENOENT,
+            3    => Error::ESRCH,
+            4    => Error::EINTR,
+            5    => Error::EIO,
+            6    => Error::ENXIO,
+            7    => Error::E2BIG,
+            8    => Error::ENOEXEC,
+            9    => Error::EBADF,
+            10   => Error::ECHILD,
+            11   => Error::EAGAIN,
+            12   => Error::ENOMEM,
+            13   => Error::EACCES,
+            14   => Error::EFAULT,
+            15   => Error::ENOTBLK,
+            16   => Error::EBUSY,
+            17   => Error::EEXIST,
+            18   => Error::EXDEV,
+            19   => Error::ENODEV,
+            20   => Error::ENOTDIR,
+            21   => Error::EISDIR,
+            22   => Error::EINVAL,
+            23   => Error::ENFILE,
+            24   => Error::EMFILE,
+            25   => Error::ENOTTY,
+            26   => Error::ETXTBSY,
+            27   => Error::EFBIG,
+            28   => Error::ENOSPC,
+            29   => Error::ESPIPE,
+            30   => Error::EROFS,
+            31   => Error::EMLINK,
+            32   => Error::EPIPE,
+            33   => Error::EDOM,
+            34   => Error::ERANGE,
+            35   => Error::EDEADLK,
+            36   => Error::ENAMETOOLONG,
+            37   => Error::ENOLCK,
+            38   => Error::ENOSYS,
+            39   => Error::ENOTEMPTY,
+            40   => Error::ELOOP,
+            42   => Error::ENOMSG,
+            43   => Error::EIDRM,
+            44   => Error::ECHRNG,
+            45   => Error::EL2NSYNC,
+            46   => Error::EL3HLT,
+            47   => Error::EL3RST,
+            48   => Error::ELNRNG,
+            49   => Error::EUNATCH,
+            50   => Error::ENOCSI,
+            51   => Error::EL2HLT,
+            52   => Error::EBADE,
+            53   => Error::EBADR,
+            54   => Error::EXFULL,
+            55   => Error::ENOANO,
+            56   => Error::EBADRQC,
+            57   => Error::EBADSLT,
+            59   => Error::EBFONT,
+            60   => Error::ENOSTR,
+            61   => Error::ENODATA,
+            62   => Error::ETIME,
+            63   => Error::ENOSR,
+            64   => Error::ENONET,
+            65   => Error::ENOPKG,
+            66   => Error::EREMOTE,
+            67   => Error::ENOLINK,
+            68   => Error::EADV,
+            69   => Error::ESRMNT,
+            70   => Error::ECOMM,
+            71   => Error::EPROTO,
+            72   => Error::EMULTIHOP,
+            73   => Error::EDOTDOT,
+            74   => Error::EBADMSG,
+            75   => Error::EOVERFLOW,
+            76   => Error::ENOTUNIQ,
+            77   => Error::EBADFD,
+            78   => Error::EREMCHG,
+            79   => Error::ELIBACC,
+            80   => Error::ELIBBAD,
+            81   => Error::ELIBSCN,
+            82   => Error::ELIBMAX,
+            83   => Error::ELIBEXEC,
+            84   => Error::EILSEQ,
+            85   => Error::ERESTART,
+            86   => Error::ESTRPIPE,
+            87   => Error::EUSERS,
+            88   => Error::ENOTSOCK,
+            89   => Error::EDESTADDRREQ,
+            90   => Error::EMSGSIZE,
+            91   => Error::EPROTOTYPE,
+            92   => Error::ENOPROTOOPT,
+            93   => Error::EPROTONOSUPPORT,
+            94   => Error::ESOCKTNOSUPPORT,
+            95   => Error::EOPNOTSUPP,
+            96   => Error::EPFNOSUPPORT,
+            97   => Error::EAFNOSUPPORT,
+            98   => Error::EADDRINUSE,
+            99   => Error::EADDRNOTAVAIL,
+            100  => Error::ENETDOWN,
+            101  => Error::ENETUNREACH,
+            102  => Error::ENETRESET,
+            103  => Error::ECONNABORTED,
+            104  => Error::ECONNRESET,
+            105  => Error::ENOBUFS,
+            106  => Error::EISCONN,
+            107  => Error::ENOTCONN,
+            108  => Error::ESHUTDOWN,
+            109  => Error::ETOOMANYREFS,
+            110  => Error::ETIMEDOUT,
+            111  => Error::ECONNREFUSED,
+            112  => Error::EHOSTDOWN,
+            113  => Error::EHOSTUNREACH,
+            114  => Error::EALREADY,
+            115  => Error::EINPROGRESS,
+            116  => Error::ESTALE,
+            117  => Error::EUCLEAN,
+            118  => Error::ENOTNAM,
+            119  => Error::ENAVAIL,
+            120  => Error::EISNAM,
+            121  => Error::EREMOTEIO,
+            122  => Error::EDQUOT,
+            123  => Error::ENOMEDIUM,
+            124  => Error::EMEDIUMTYPE,
+            125  => Error::ECANCELED,
+            126  => Error::ENOKEY,
+            127  => Error::EKEYEXPIRED,
+            128  => Error::EKEYREVOKED,
+            129  => Error::EKEYREJECTED,
+            130  => Error::EOWNERDEAD,
+            131  => Error::ENOTRECOVERABLE,
+            132  => Error::ERFKILL,
+            133  => Error::EHWPOISON,
+            _ => return None,
+        })
+    }
+
     pub fn wrap_isize_to_usize(val: isize) -> Result<usize, Error> {
         if val < 0 {
             return Err(unsafe { core::mem::transmute(-val) });
@@ -0,0 +1,7 @@
+use prelude::*;
+use bits::fcntl::{O_NONBLOCK, O_CLOEXEC};
+
+/* eventfd(2) flags */
+pub const EFD_SEMAPHORE   : usize = 0x0001;  /* Reads take one off the counter */
+pub const EFD_NONBLOCK    : usize = O_NONBLOCK;
+pub const EFD_CLOEXEC     : usize = O_CLOEXEC;
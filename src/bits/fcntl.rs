@@ -38,3 +38,38 @@ pub const F_RSETLKW       : usize = 13;  /* Set or Clear remote record-lock(Bloc
 pub const F_DUPFD_CLOEXEC : usize = 14;  /* As F_DUPFD, but set close-on-exec flag */
 
 pub const FD_CLOEXEC      : usize = 1;
+
+/* record lock types */
+pub const F_RDLCK         : usize = 1;   /* Shared or read lock */
+pub const F_UNLCK         : usize = 2;   /* Unlock */
+pub const F_WRLCK         : usize = 3;   /* Exclusive or write lock */
+
+/* origins of lock ranges */
+pub const SEEK_SET        : usize = 0;
+pub const SEEK_CUR        : usize = 1;
+pub const SEEK_END        : usize = 2;
+
+/** record lock, argument of F_GETLK, F_SETLK and F_SETLKW */
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Flock {
+    pub l_type:   i16,     /* F_RDLCK, F_WRLCK or F_UNLCK */
+    pub l_whence: i16,     /* SEEK_* origin of l_start */
+    pub l_start:  off_t,   /* first byte of the range */
+    pub l_len:    off_t,   /* length of the range, 0 up to the end of the file and beyond */
+    pub l_pid:    pid_t,   /* process holding the lock, F_GETLK only */
+}
+
+/* flock(2) operations */
+pub const LOCK_SH         : usize = 0x01;  /* Shared lock */
+pub const LOCK_EX         : usize = 0x02;  /* Exclusive lock */
+pub const LOCK_NB         : usize = 0x04;  /* Do not block when locking */
+pub const LOCK_UN         : usize = 0x08;  /* Unlock */
+
+/* special directory descriptor naming the current working directory in *at calls */
+pub const AT_FDCWD        : isize = -100;
+
+/* flags of the *at calls */
+pub const AT_SYMLINK_NOFOLLOW : usize = 0x100;
+pub const AT_REMOVEDIR        : usize = 0x200;
+pub const AT_EACCESS          : usize = 0x200;
@@ -0,0 +1,16 @@
+use prelude::*;
+
+/* futex(2) operations */
+pub const FUTEX_WAIT          : usize = 0;
+pub const FUTEX_WAKE          : usize = 1;
+pub const FUTEX_REQUEUE       : usize = 3;
+pub const FUTEX_CMP_REQUEUE   : usize = 4;
+pub const FUTEX_WAIT_BITSET   : usize = 9;
+pub const FUTEX_WAKE_BITSET   : usize = 10;
+
+/* futex(2) operation flags */
+pub const FUTEX_PRIVATE_FLAG  : usize = 128;
+pub const FUTEX_CLOCK_REALTIME: usize = 256;
+pub const FUTEX_CMD_MASK      : usize = !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME);
+
+pub const FUTEX_BITSET_MATCH_ANY : u32 = 0xffffffff;
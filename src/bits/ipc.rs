@@ -0,0 +1,45 @@
+use prelude::*;
+
+pub type key_t = isize;
+
+/* ipc keys */
+pub const IPC_PRIVATE : key_t = 0;
+
+/* ipc get flags */
+pub const IPC_CREAT   : usize = 0o01000;    /* create entry if key does not exist */
+pub const IPC_EXCL    : usize = 0o02000;    /* fail if key exists */
+pub const IPC_NOWAIT  : usize = 0o04000;    /* return error on wait */
+
+/* ipc control commands */
+pub const IPC_RMID    : usize = 0;          /* remove identifier */
+pub const IPC_SET     : usize = 1;          /* set options */
+pub const IPC_STAT    : usize = 2;          /* get options */
+
+/**
+ * \ingroup ipc
+ * \brief ipc object permissions
+ */
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct IpcPerm {
+    /** key supplied to the get call */
+    pub key: key_t,
+
+    /** owner user id */
+    pub uid: uid_t,
+
+    /** owner group id */
+    pub gid: gid_t,
+
+    /** creator user id */
+    pub cuid: uid_t,
+
+    /** creator group id */
+    pub cgid: gid_t,
+
+    /** read/write permissions */
+    pub mode: mode_t,
+
+    /** sequence number */
+    pub seq: usize,
+}
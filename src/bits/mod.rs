@@ -3,3 +3,17 @@ pub mod dirent;
 pub mod fcntl;
 pub mod utsname;
 pub mod mman;
+pub mod ipc;
+pub mod shm;
+pub mod msg;
+pub mod sem;
+pub mod futex;
+pub mod resource;
+pub mod ptrace;
+pub mod mount;
+pub mod poll;
+pub mod epoll;
+pub mod eventfd;
+pub mod signalfd;
+pub mod timerfd;
+pub mod uio;
@@ -0,0 +1,17 @@
+use prelude::*;
+
+/* mount flags */
+pub const MS_RDONLY   : usize = 0x0001;  /* Writes are refused */
+pub const MS_NOSUID   : usize = 0x0002;  /* Set-user and set-group ids are ignored */
+pub const MS_NODEV    : usize = 0x0004;  /* Device files can not be opened */
+pub const MS_NOEXEC   : usize = 0x0008;  /* Files can not be executed */
+pub const MS_REMOUNT  : usize = 0x0020;  /* Change the flags of an existing mount */
+pub const MS_BIND     : usize = 0x1000;  /* Mount a directory at another place */
+
+/* flags kept in the mount table, the others select an operation */
+pub const MS_PERMS    : usize = MS_RDONLY | MS_NOSUID | MS_NODEV | MS_NOEXEC;
+
+/* umount2 flags */
+pub const MNT_FORCE       : usize = 0x0001;  /* Unmount even if busy */
+pub const MNT_DETACH      : usize = 0x0002;  /* Remove from the namespace now, release when unused */
+pub const UMOUNT_NOFOLLOW : usize = 0x0008;  /* Do not follow a symbolic link as the target */
@@ -0,0 +1,45 @@
+use prelude::*;
+use bits::ipc::*;
+
+/* msgrcv(2) flags */
+pub const MSG_NOERROR : usize = 0o010000;   /* truncate message if too long */
+pub const MSG_EXCEPT  : usize = 0o020000;   /* receive any message except of the given type */
+
+/* limits */
+pub const MSGMAX      : usize = 8192;       /* max size of a message */
+pub const MSGMNB      : usize = 16384;      /* default max size of a queue */
+
+/**
+ * \ingroup ipc
+ * \brief message queue descriptor
+ */
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct MsqidDs {
+    /** operation permissions */
+    pub msg_perm: IpcPerm,
+
+    /** number of messages currently on queue */
+    pub msg_qnum: usize,
+
+    /** maximum number of bytes allowed on queue */
+    pub msg_qbytes: usize,
+
+    /** number of bytes currently on queue */
+    pub msg_cbytes: usize,
+
+    /** pid of last msgsnd */
+    pub msg_lspid: pid_t,
+
+    /** pid of last msgrcv */
+    pub msg_lrpid: pid_t,
+
+    /** time of last msgsnd */
+    pub msg_stime: time_t,
+
+    /** time of last msgrcv */
+    pub msg_rtime: time_t,
+
+    /** time of last change */
+    pub msg_ctime: time_t,
+}
@@ -0,0 +1,24 @@
+use prelude::*;
+
+/* poll(2) events */
+pub const POLLIN      : usize = 0x0001;  /* Data may be read without blocking */
+pub const POLLPRI     : usize = 0x0002;  /* Urgent data may be read without blocking */
+pub const POLLOUT     : usize = 0x0004;  /* Data may be written without blocking */
+pub const POLLERR     : usize = 0x0008;  /* Error condition, always reported */
+pub const POLLHUP     : usize = 0x0010;  /* Hang up, always reported */
+pub const POLLNVAL    : usize = 0x0020;  /* Descriptor is not open, always reported */
+pub const POLLRDNORM  : usize = 0x0040;  /* Same as POLLIN */
+pub const POLLWRNORM  : usize = 0x0100;  /* Same as POLLOUT */
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct PollFd {
+    /** descriptor to watch, negative entries are skipped */
+    pub fd: i32,
+
+    /** requested events */
+    pub events: i16,
+
+    /** returned events */
+    pub revents: i16,
+}
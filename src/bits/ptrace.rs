@@ -0,0 +1,18 @@
+use prelude::*;
+
+/* ptrace(2) requests, numbered like linux */
+pub const PTRACE_TRACEME    : usize = 0;
+pub const PTRACE_PEEKTEXT   : usize = 1;
+pub const PTRACE_PEEKDATA   : usize = 2;
+pub const PTRACE_PEEKUSER   : usize = 3;
+pub const PTRACE_POKETEXT   : usize = 4;
+pub const PTRACE_POKEDATA   : usize = 5;
+pub const PTRACE_POKEUSER   : usize = 6;
+pub const PTRACE_CONT       : usize = 7;
+pub const PTRACE_KILL       : usize = 8;
+pub const PTRACE_SINGLESTEP : usize = 9;
+pub const PTRACE_GETREGS    : usize = 12;
+pub const PTRACE_SETREGS    : usize = 13;
+pub const PTRACE_ATTACH     : usize = 16;
+pub const PTRACE_DETACH     : usize = 17;
+pub const PTRACE_SYSCALL    : usize = 24;
@@ -0,0 +1,32 @@
+use prelude::*;
+
+/* resources for getrlimit(2)/setrlimit(2), only RLIMIT_CORE is enforced */
+pub const RLIMIT_CPU     : usize = 0;
+pub const RLIMIT_FSIZE   : usize = 1;
+pub const RLIMIT_DATA    : usize = 2;
+pub const RLIMIT_STACK   : usize = 3;
+pub const RLIMIT_CORE    : usize = 4;
+pub const RLIMIT_NOFILE  : usize = 7;
+pub const RLIMIT_AS      : usize = 9;
+pub const RLIM_NLIMITS   : usize = 16;
+
+pub const RLIM_INFINITY  : usize = !0;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct RLimit {
+    /** soft limit */
+    pub rlim_cur: usize,
+
+    /** hard limit, ceiling for rlim_cur */
+    pub rlim_max: usize,
+}
+
+impl RLimit {
+    pub const fn infinity() -> Self {
+        RLimit {
+            rlim_cur: RLIM_INFINITY,
+            rlim_max: RLIM_INFINITY,
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use prelude::*;
+use bits::ipc::*;
+
+/* semop(2) flags */
+pub const SEM_UNDO    : i16 = 0x1000;       /* undo the operation on exit */
+
+/* semctl(2) commands */
+pub const GETPID      : usize = 11;         /* get sempid */
+pub const GETVAL      : usize = 12;         /* get semval */
+pub const GETALL      : usize = 13;         /* get all semval's */
+pub const GETNCNT     : usize = 14;         /* get semncnt */
+pub const GETZCNT     : usize = 15;         /* get semzcnt */
+pub const SETVAL      : usize = 16;         /* set semval */
+pub const SETALL      : usize = 17;         /* set all semval's */
+
+/* limits */
+pub const SEMMSL      : usize = 256;        /* max semaphores per set */
+pub const SEMOPM      : usize = 32;         /* max operations per semop call */
+pub const SEMVMX      : isize = 32767;      /* max semaphore value */
+
+/**
+ * \ingroup ipc
+ * \brief semaphore operation
+ */
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct SemBuf {
+    /** semaphore index in set */
+    pub sem_num: u16,
+
+    /** operation */
+    pub sem_op: i16,
+
+    /** operation flags */
+    pub sem_flg: i16,
+}
+
+/**
+ * \ingroup ipc
+ * \brief semaphore set descriptor
+ */
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct SemidDs {
+    /** operation permissions */
+    pub sem_perm: IpcPerm,
+
+    /** number of semaphores in set */
+    pub sem_nsems: usize,
+
+    /** time of last semop */
+    pub sem_otime: time_t,
+
+    /** time of last change */
+    pub sem_ctime: time_t,
+}
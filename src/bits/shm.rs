@@ -0,0 +1,41 @@
+use prelude::*;
+use bits::ipc::*;
+
+/* shmat(2) flags */
+pub const SHM_RDONLY  : usize = 0o010000;   /* attach read-only */
+pub const SHM_RND     : usize = 0o020000;   /* round attach address to SHMLBA */
+
+/* segment low boundary address multiple */
+pub const SHMLBA      : usize = 4096;
+
+/**
+ * \ingroup ipc
+ * \brief shared memory segment descriptor
+ */
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct ShmidDs {
+    /** operation permissions */
+    pub shm_perm: IpcPerm,
+
+    /** size of segment in bytes */
+    pub shm_segsz: size_t,
+
+    /** pid of last shmat/shmdt */
+    pub shm_lpid: pid_t,
+
+    /** pid of creator */
+    pub shm_cpid: pid_t,
+
+    /** number of current attaches */
+    pub shm_nattch: usize,
+
+    /** time of last shmat */
+    pub shm_atime: time_t,
+
+    /** time of last shmdt */
+    pub shm_dtime: time_t,
+
+    /** time of last change by shmctl */
+    pub shm_ctime: time_t,
+}
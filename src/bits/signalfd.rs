@@ -0,0 +1,30 @@
+use prelude::*;
+use bits::fcntl::{O_NONBLOCK, O_CLOEXEC};
+
+/* signalfd(2) flags */
+pub const SFD_NONBLOCK    : usize = O_NONBLOCK;
+pub const SFD_CLOEXEC     : usize = O_CLOEXEC;
+
+/** record read from a signalfd, 128 bytes */
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SignalFdSiginfo {
+    pub ssi_signo:    u32,
+    pub ssi_errno:    i32,
+    pub ssi_code:     i32,
+    pub ssi_pid:      u32,
+    pub ssi_uid:      u32,
+    pub ssi_fd:       i32,
+    pub ssi_tid:      u32,
+    pub ssi_band:     u32,
+    pub ssi_overrun:  u32,
+    pub ssi_trapno:   u32,
+    pub ssi_status:   i32,
+    pub ssi_int:      i32,
+    pub ssi_ptr:      u64,
+    pub ssi_utime:    u64,
+    pub ssi_stime:    u64,
+    pub ssi_addr:     u64,
+    pub ssi_addr_lsb: u16,
+    pub __pad:        [u8; 46],
+}
@@ -0,0 +1,23 @@
+use prelude::*;
+use bits::fcntl::{O_NONBLOCK, O_CLOEXEC};
+
+/* clock ids */
+pub const CLOCK_REALTIME    : usize = 0;
+pub const CLOCK_MONOTONIC   : usize = 1;
+
+/* timerfd_create(2) flags */
+pub const TFD_NONBLOCK      : usize = O_NONBLOCK;
+pub const TFD_CLOEXEC       : usize = O_CLOEXEC;
+
+/* timerfd_settime(2) flags */
+pub const TFD_TIMER_ABSTIME : usize = 0x0001;  /* The expiry is a time on the clock */
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ITimerSpec {
+    /** period, zero for timers firing once */
+    pub it_interval: TimeSpec,
+
+    /** first expiry, zero disarms the timer */
+    pub it_value: TimeSpec,
+}
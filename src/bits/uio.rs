@@ -0,0 +1,18 @@
+use prelude::*;
+
+/* most buffers readv(2) and friends take */
+pub const IOV_MAX         : usize = 1024;
+
+/** one buffer of a vectored transfer */
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct IoVec {
+    pub iov_base: *mut u8,  /* start of the buffer */
+    pub iov_len:  usize,    /* size of the buffer */
+}
+
+/* splice(2) flags, hints only */
+pub const SPLICE_F_MOVE     : usize = 0x01;  /* Move pages instead of copying */
+pub const SPLICE_F_NONBLOCK : usize = 0x02;  /* Do not block on pipes */
+pub const SPLICE_F_MORE     : usize = 0x04;  /* More data will follow */
+pub const SPLICE_F_GIFT     : usize = 0x08;  /* Pages are gifted */
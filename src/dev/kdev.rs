@@ -2,6 +2,7 @@ use prelude::*;
 
 use dev::dev::*;
 use fs::*;
+use fs::sysfs::sysfs_device_add;
 use mm::*;
 use sys::syscall::file::{FileDescriptor, FileBackend};
 
@@ -372,11 +373,13 @@ pub unsafe fn kdev_file_eof(dd: *mut DeviceDescriptor, file: *mut FileDescriptor
 pub unsafe fn kdev_chrdev_register(major: devid_t, dev: *mut Device) {
     CHRDEV[major as usize] = dev; /* XXX */
     print!("kdev: registered chrdev {}: {}\n", major, (*dev).name);
+    sysfs_device_add(S_IFCHR, major, dev);
 }
 
 pub unsafe fn kdev_blkdev_register(major: devid_t, dev: *mut Device) {
     BLKDEV[major as usize] = dev; /* XXX */
     print!("kdev: registered blkdev {}: {}\n", major, (*dev).name);
+    sysfs_device_add(S_IFBLK, major, dev);
 }
 
 pub unsafe fn kdev_init() {
@@ -3,6 +3,7 @@ use dev::tty::tty::*;
 use fs::ioctl::*;
 use fs::termios::*;
 use mm::*;
+use sys::linux::abi::LinuxTermios;
 use sys::process::*;
 use sys::sched::*;
 use sys::signal::*;
@@ -152,35 +153,38 @@ pub unsafe fn tty_slave_write(tty: *mut Tty, size: usize, buf: *const u8) -> isi
 }
 
 pub unsafe fn tty_ioctl(tty: *mut Tty, request: isize, argp: *mut u8) -> isize {
-    match request as usize {
-        TCGETS => {
-            memcpy(argp, &((*tty).tios) as *const _ as *mut u8, core::mem::size_of::<Termios>());
-        },
-        TCSETS => {
-            memcpy(&((*tty).tios) as *const _ as *mut u8, argp, core::mem::size_of::<Termios>());
-        },
-        TIOCGPGRP => {
-            *(argp as *mut pid_t) = (*(*tty).fg).pgid;
+    /* `argp' points into userspace */
+    let linux = (*curproc!()).personality == Personality::Linux;
+
+    let ret = match request as usize {
+        /* linux passes the shorter kernel termios */
+        TCGETS if linux => copyout(argp as *mut LinuxTermios, &LinuxTermios::new(&(*tty).tios)),
+        TCSETS | TCSETSW | TCSETSF if linux => {
+            copyin(argp as *const LinuxTermios).map(|tios| tios.apply(&mut (*tty).tios))
         },
+        TCGETS => copyout(argp as *mut Termios, &(*tty).tios),
+        TCSETS => copyin(argp as *const Termios).map(|tios| (*tty).tios = tios),
+        TIOCGPGRP => copyout(argp as *mut pid_t, &(*(*tty).fg).pgid),
         TIOCSPGRP => {
             (*tty).fg = (*curproc!()).pgrp;
             /* XXX */ 
+            Ok(())
         },
-        TIOCGWINSZ => {
-            memcpy(argp, &(*tty).ws as *const _ as *mut u8, core::mem::size_of::<Winsize>());
-        },
-        TIOCSWINSZ => {
-            memcpy(&(*tty).ws as *const _ as *mut u8, argp, core::mem::size_of::<Winsize>());
-        },
+        TIOCGWINSZ => copyout(argp as *mut Winsize, &(*tty).ws),
+        TIOCSWINSZ => copyin(argp as *const Winsize).map(|ws| (*tty).ws = ws),
         TIOCSCTTY => {
             /* FIXME */
             (*tty).proc = curproc!();
             (*(*(*curproc!()).pgrp).session).ctty = (*tty).dev as *mut u8;
+            Ok(())
         },
         _ => return -EINVAL,
     };
-    
-    return 0;
+
+    match ret {
+        Ok(_) => 0,
+        Err(err) => err.unwrap(),
+    }
 }
 
 /**
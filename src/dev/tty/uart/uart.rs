@@ -42,6 +42,7 @@ pub unsafe fn uart_recieve_handler(u: *mut Uart, size: usize) {
 
     tty_master_write((*u).tty, size, buf.as_mut_ptr());  
     thread_queue_wakeup((*(*u).vnode).read_queue.as_mut().unwrap().as_mut());
+    poll_notify((*u).vnode);
 }
 
 /* called when data is ready to be transmitted */
@@ -57,6 +58,7 @@ pub unsafe fn uart_transmit_handler(u: *mut Uart, size: usize) {
     }
 
     thread_queue_wakeup((*(*u).vnode).write_queue.as_mut().unwrap().as_mut());
+    poll_notify((*u).vnode);
 }
 
 /* tty interface */
@@ -0,0 +1,91 @@
+use prelude::*;
+use fs::*;
+
+use bits::fcntl::*;
+use kern::time::*;
+use sys::process::*;
+use sys::sched::*;
+use sys::thread::*;
+use sys::syscall::file::{FileDescriptor, FileBackend};
+
+/*
+ * anonymous nodes back descriptors which have no name in the tree, epoll
+ * instances, eventfds and the like. each kind is a nodev filesystem of its
+ * own, the structure of the object is the node data. there is only ever
+ * one open file on an anonymous node, the close hook of the filesystem frees
+ * both with anon_free once it goes away.
+ */
+
+/** next inode number of an anonymous node, they have no filesystem to take one from */
+static ANON_INO: AtomicUsize = AtomicUsize::new(1);
+
+/** an open file on a new anonymous node of `fs` with `data` as node data */
+pub unsafe fn anon_open<T>(fs: Arc<Filesystem>, data: *mut T, flags: usize) -> Result<FileDescriptor, Error> {
+    let mut node = Node::none();
+    let ts = gettime()?;
+
+    node.set_mode(S_IFREG | 0o600);
+    node.set_nlink(1);
+    node.set_uid((*curproc!()).uid);
+    node.set_gid((*curproc!()).gid);
+    node.set_data(data);
+
+    node.ino = ANON_INO.fetch_add(1, SeqCst) as ino_t;
+    node.fs  = Some(fs);
+
+    /* blocking reads and writes sleep on these */
+    node.read_queue  = Some(Queue::alloc(Queue::new()));
+    node.write_queue = Some(Queue::alloc(Queue::new()));
+
+    node.ctime = ts;
+    node.atime = ts;
+    node.mtime = ts;
+
+    Ok(FileDescriptor {
+        refcnt: 0,
+        offset: 0,
+        flags:  O_RDWR | (flags & (O_NONBLOCK | O_CLOEXEC)),
+        backend: FileBackend {
+            vnode: Box::leak(Box::new(node)),
+        }
+    })
+}
+
+/** free the anonymous `node` and its node data of type `T` */
+pub unsafe fn anon_free<T>(node: &Node) {
+    let node = node as *const Node as *mut Node;
+
+    /* epoll instances still watching it see the hook gone */
+    poll_detach(&mut (*node).pollers);
+
+    if let Some(data) = (*node).data::<T>() {
+        drop(Box::from_raw(data as *mut T));
+    }
+
+    drop(Box::from_raw(node));
+}
+
+/** the object behind `node` if it is an anonymous node of the filesystem `name` */
+pub unsafe fn anon_data<T>(node: *mut Node, name: &str) -> Option<&'static mut T> {
+    if node.is_null() {
+        return None;
+    }
+
+    match (*node).fs.as_ref() {
+        Some(fs) if fs.name == name => (*node).data::<T>(),
+        _ => None,
+    }
+}
+
+/** wake up readers, writers and pollers of `node` after its state changed */
+pub unsafe fn anon_wakeup(node: *mut Node) {
+    if let Some(queue) = (*node).read_queue.as_mut() {
+        thread_queue_wakeup(queue.as_mut());
+    }
+
+    if let Some(queue) = (*node).write_queue.as_mut() {
+        thread_queue_wakeup(queue.as_mut());
+    }
+
+    poll_notify(node);
+}
@@ -0,0 +1,279 @@
+use prelude::*;
+use fs::*;
+
+use bits::epoll::*;
+use sys::process::*;
+use sys::sched::*;
+use sys::thread::*;
+use sys::syscall::file::FileDescriptor;
+
+use alloc::collections::btree_map::BTreeMap;
+
+/*
+ * epoll instances are anonymous nodes of epollfs with the Epoll structure
+ * as node data. every registered file gets a PollHook on its node with the
+ * instance as the parent, so an event on the file is passed on to whoever
+ * polls the instance, epoll_wait included. registrations are by descriptor
+ * and resolved against the calling process, an entry whose descriptor was
+ * closed or now refers to another file is dropped on the next scan.
+ */
+
+/** a file registered with an epoll instance */
+struct EpollItem {
+    /** file the descriptor referred to when it was registered */
+    file: *mut u8,
+
+    /** requested EPOLL* events and modes */
+    events: u32,
+
+    /** user data handed back with the events */
+    data: u64,
+
+    /** hook on the node of the file */
+    hook: PollHook,
+
+    /** hook events already reported, edge-triggered entries only report new ones */
+    seen: usize,
+
+    /** an edge is owed since the entry was added or modified */
+    pending: bool,
+
+    /** a oneshot entry which fired, silent until modified */
+    disabled: bool,
+}
+
+/** node data of an epoll instance */
+pub struct Epoll {
+    /** registered files, by descriptor */
+    items: BTreeMap<isize, Box<EpollItem>>,
+}
+
+static mut EPOLLFS: Option<Arc<Filesystem>> = None;
+
+unsafe fn epollfs() -> Arc<Filesystem> {
+    if EPOLLFS.is_none() {
+        EPOLLFS = Some(Arc::new(Filesystem {
+            name:  "epollfs",
+            nodev: 1,
+
+            close: Some(close),
+
+            fops: FileOps {
+                _open:      None,
+                _can_read:  Some(epoll_can_read),
+                _can_write: Some(__vfs_can_never),
+                _eof:       Some(__vfs_eof_never),
+            },
+
+            ..Filesystem::none()
+        }));
+    }
+
+    Arc::clone(EPOLLFS.as_ref().unwrap())
+}
+
+fn close(node: &Node) -> Result<usize, Error> {
+    unsafe {
+        /* the hooks of the entries live in the items about to be freed */
+        if let Some(ep) = node.data::<Epoll>() {
+            for item in ep.items.values_mut() {
+                poll_hook_remove(&mut item.hook);
+            }
+        }
+
+        anon_free::<Epoll>(node);
+    }
+
+    Ok(0)
+}
+
+/** the epoll instance behind `node`, if it is one */
+unsafe fn epoll_of(node: *mut Node) -> Option<&'static mut Epoll> {
+    anon_data::<Epoll>(node, "epollfs")
+}
+
+/** does the instance on `node` watch `target`, directly or through nested instances? */
+unsafe fn epoll_watches(node: *mut Node, target: *mut Node) -> bool {
+    match epoll_of(node) {
+        None => false,
+        Some(ep) => ep.items.values().any(|item| {
+            let watched = item.hook.node;
+            !watched.is_null() && (watched == target || epoll_watches(watched, target))
+        }),
+    }
+}
+
+/** events of `item`, registered as `fd`, to report now, None if the entry went stale */
+unsafe fn epoll_item_events(fd: isize, item: &EpollItem) -> Option<u32> {
+    let file = poll_file(curproc!(), fd)?;
+
+    if (*file).backend.vnode as *mut u8 != item.file {
+        return None;
+    }
+
+    if item.disabled {
+        return Some(0);
+    }
+
+    let mask = item.events & !(EPOLLET | EPOLLONESHOT);
+    let revents = vfs_file_poll(file, mask as usize) as u32;
+
+    if item.events & EPOLLET != 0 && !item.pending && core::ptr::read_volatile(&item.hook.count) == item.seen {
+        /* no new edge */
+        return Some(0);
+    }
+
+    Some(revents)
+}
+
+/**
+ * collect at most `max` events of `ep`, entries reported are rearmed or
+ * disabled according to their mode only if `consume` is set
+ */
+unsafe fn epoll_scan(ep: &mut Epoll, max: usize, consume: bool, events: &mut Vec<EpollEvent>) -> usize {
+    let mut stale = Vec::new();
+
+    for (&fd, item) in ep.items.iter_mut() {
+        if events.len() >= max {
+            break;
+        }
+
+        let revents = match epoll_item_events(fd, item) {
+            None => {
+                stale.push(fd);
+                continue;
+            },
+            Some(revents) => revents,
+        };
+
+        if revents == 0 {
+            continue;
+        }
+
+        events.push(EpollEvent {
+            events: revents,
+            data:   item.data,
+        });
+
+        if consume {
+            item.seen = item.hook.count;
+            item.pending = false;
+            item.disabled = item.events & EPOLLONESHOT != 0;
+        }
+    }
+
+    for fd in stale {
+        if let Some(mut item) = ep.items.remove(&fd) {
+            poll_hook_remove(&mut item.hook);
+        }
+    }
+
+    events.len()
+}
+
+unsafe fn epoll_can_read(file: *mut FileDescriptor, _size: usize) -> isize {
+    match epoll_of((*file).backend.vnode) {
+        None => -EINVAL,
+        Some(ep) => epoll_scan(ep, 1, false, &mut Vec::new()) as isize,
+    }
+}
+
+/** create an epoll instance, returns the open file to install */
+pub unsafe fn epoll_open(flags: usize) -> Result<FileDescriptor, Error> {
+    if flags & !EPOLL_CLOEXEC != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let ep = Box::leak(Box::new(Epoll { items: BTreeMap::new() }));
+
+    anon_open(epollfs(), ep, flags)
+}
+
+/** add, modify or remove the registration of `fd` with the instance open as `epfile` */
+pub unsafe fn epoll_control(epfile: *mut FileDescriptor, op: usize, fd: isize, event: Option<EpollEvent>) -> Result<(), Error> {
+    let epnode = (*epfile).backend.vnode;
+    let ep = epoll_of(epnode).ok_or(Error::EINVAL)?;
+
+    let file = poll_file(curproc!(), fd).ok_or(Error::EBADF)?;
+    let node = poll_node(file);
+
+    if file == epfile {
+        return Err(Error::EINVAL);
+    }
+
+    match op {
+        EPOLL_CTL_ADD => {
+            let event = event.ok_or(Error::EFAULT)?;
+
+            if ep.items.contains_key(&fd) {
+                return Err(Error::EEXIST);
+            }
+
+            /* an instance watching itself would notify forever */
+            if !node.is_null() && (node == epnode || epoll_watches(node, epnode)) {
+                return Err(Error::ELOOP);
+            }
+
+            let mut item = Box::new(EpollItem {
+                file:     (*file).backend.vnode as *mut u8,
+                events:   event.events,
+                data:     event.data,
+                hook:     PollHook::new(core::ptr::null_mut(), epnode),
+                seen:     0,
+                pending:  true,
+                disabled: false,
+            });
+
+            /* the box keeps the hook in place while it is added */
+            poll_hook_add_file(&mut item.hook, file);
+
+            ep.items.insert(fd, item);
+        },
+        EPOLL_CTL_MOD => {
+            let event = event.ok_or(Error::EFAULT)?;
+            let item = ep.items.get_mut(&fd).ok_or(Error::ENOENT)?;
+
+            item.events   = event.events;
+            item.data     = event.data;
+            item.pending  = true;
+            item.disabled = false;
+        },
+        EPOLL_CTL_DEL => {
+            let mut item = ep.items.remove(&fd).ok_or(Error::ENOENT)?;
+            poll_hook_remove(&mut item.hook);
+
+            return Ok(());
+        },
+        _ => return Err(Error::EINVAL),
+    }
+
+    /* the entry may be ready already, let waiters look again */
+    poll_notify(epnode);
+
+    Ok(())
+}
+
+/**
+ * wait for events on the instance open as `epfile`, for at most `timeout`
+ * nanoseconds if given. returns at most `max` events
+ */
+pub unsafe fn epoll_wait_events(epfile: *mut FileDescriptor, max: usize, timeout: Option<u64>) -> Result<Vec<EpollEvent>, Error> {
+    let epnode = (*epfile).backend.vnode;
+    let ep = epoll_of(epnode).ok_or(Error::EINVAL)?;
+
+    if max == 0 {
+        return Err(Error::EINVAL);
+    }
+
+    /* every entry passes its events on to the instance node */
+    let mut queue: Queue<*mut Thread> = Queue::empty();
+    let mut hook = PollHook::new(&mut queue, core::ptr::null_mut());
+    poll_hook_add(&mut hook, epnode);
+
+    let mut events = Vec::new();
+    let ret = poll_wait(core::slice::from_ref(&hook), &mut queue, timeout, || epoll_scan(ep, max, true, &mut events));
+
+    poll_hook_remove(&mut hook);
+
+    ret.map(|_| events)
+}
@@ -0,0 +1,127 @@
+use prelude::*;
+use fs::*;
+
+use bits::eventfd::*;
+use sys::syscall::file::FileDescriptor;
+
+/*
+ * an eventfd is a 64-bit counter behind a descriptor. writes add to the
+ * counter, reads return it and reset it to zero, or take one off it in
+ * semaphore mode. reads block while the counter is zero and writes while
+ * they would overflow it.
+ */
+
+/** largest value the counter can hold */
+const EVENTFD_MAX: u64 = u64::max_value() - 1;
+
+/** node data of an eventfd */
+pub struct EventFd {
+    /** the counter */
+    count: u64,
+
+    /** EFD_SEMAPHORE, reads take one off the counter */
+    semaphore: bool,
+}
+
+static mut EVENTFDFS: Option<Arc<Filesystem>> = None;
+
+unsafe fn eventfdfs() -> Arc<Filesystem> {
+    if EVENTFDFS.is_none() {
+        EVENTFDFS = Some(Arc::new(Filesystem {
+            name:  "eventfd",
+            nodev: 1,
+
+            read:  Some(read),
+            write: Some(write),
+            close: Some(close),
+
+            fops: FileOps {
+                _open:      None,
+                _can_read:  Some(eventfd_can_read),
+                _can_write: Some(eventfd_can_write),
+                _eof:       Some(__vfs_eof_never),
+            },
+
+            ..Filesystem::none()
+        }));
+    }
+
+    Arc::clone(EVENTFDFS.as_ref().unwrap())
+}
+
+fn read(node: &Node, _offset: usize, size: usize, buffer: *mut u8) -> Result<usize, Error> {
+    let efd = node.data::<EventFd>().ok_or(Error::EINVAL)?;
+
+    if size < 8 {
+        return Err(Error::EINVAL);
+    }
+
+    if efd.count == 0 {
+        /* would block */
+        return Ok(0);
+    }
+
+    let value = if efd.semaphore { 1 } else { efd.count };
+    efd.count -= value;
+
+    unsafe { (buffer as *mut u64).write_unaligned(value) };
+
+    Ok(8)
+}
+
+fn write(node: &Node, _offset: usize, size: usize, buffer: *mut u8) -> Result<usize, Error> {
+    let efd = node.data::<EventFd>().ok_or(Error::EINVAL)?;
+
+    if size < 8 {
+        return Err(Error::EINVAL);
+    }
+
+    let value = unsafe { (buffer as *const u64).read_unaligned() };
+
+    if value > EVENTFD_MAX {
+        return Err(Error::EINVAL);
+    }
+
+    if value > EVENTFD_MAX - efd.count {
+        /* would block */
+        return Ok(0);
+    }
+
+    efd.count += value;
+
+    /* only the first 8 bytes count, the rest of the buffer is consumed with them */
+    Ok(size)
+}
+
+fn close(node: &Node) -> Result<usize, Error> {
+    unsafe { anon_free::<EventFd>(node); }
+    Ok(0)
+}
+
+unsafe fn eventfd_can_read(file: *mut FileDescriptor, _size: usize) -> isize {
+    match anon_data::<EventFd>((*file).backend.vnode, "eventfd") {
+        None => -EINVAL,
+        Some(efd) => (efd.count > 0) as isize,
+    }
+}
+
+unsafe fn eventfd_can_write(file: *mut FileDescriptor, _size: usize) -> isize {
+    match anon_data::<EventFd>((*file).backend.vnode, "eventfd") {
+        None => -EINVAL,
+        Some(efd) => (efd.count < EVENTFD_MAX) as isize,
+    }
+}
+
+/** create an eventfd with the counter at `initval`, returns the open file to install */
+pub unsafe fn eventfd_open(initval: u32, flags: usize) -> Result<FileDescriptor, Error> {
+    if flags & !(EFD_SEMAPHORE | EFD_NONBLOCK | EFD_CLOEXEC) != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let efd = Box::leak(Box::new(EventFd {
+        count:     initval as u64,
+        semaphore: flags & EFD_SEMAPHORE != 0,
+    }));
+
+    anon_open(eventfdfs(), efd, flags)
+}
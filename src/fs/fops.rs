@@ -111,6 +111,56 @@ pub unsafe fn vfs_file_write(file: *mut FileDescriptor, buf: *mut u8, nbytes: us
     return (*file).write(buf, nbytes);
 }
 
+/*
+ * \ingroup vfs
+ * \brief can an open file be read and written at any offset, pipes and sockets can not
+ */
+pub unsafe fn vfs_file_seekable(file: *mut FileDescriptor) -> bool {
+    if file.is_null() || (*file).flags & FILE_SOCKET != 0 || (*file).backend.vnode.is_null() {
+        return false;
+    }
+
+    match (*(*file).backend.vnode).node_type() {
+        NodeType::Fifo | NodeType::Socket => false,
+        _ => true,
+    }
+}
+
+/*
+ * \ingroup vfs
+ * \brief read from an open file at `offset`, leaving the offset of the file alone
+ */
+pub unsafe fn vfs_file_pread(file: *mut FileDescriptor, buf: *mut u8, nbytes: usize, offset: off_t) -> isize {
+    if !vfs_file_seekable(file) {
+        return -ESPIPE;
+    }
+
+    if offset < 0 {
+        return -EINVAL;
+    }
+
+    /* a private copy moves instead of the shared open file */
+    let mut at = FileDescriptor { offset: offset, ..*file };
+    return at.read(buf, nbytes);
+}
+
+/*
+ * \ingroup vfs
+ * \brief write to an open file at `offset`, leaving the offset of the file alone
+ */
+pub unsafe fn vfs_file_pwrite(file: *mut FileDescriptor, buf: *mut u8, nbytes: usize, offset: off_t) -> isize {
+    if !vfs_file_seekable(file) {
+        return -ESPIPE;
+    }
+
+    if offset < 0 {
+        return -EINVAL;
+    }
+
+    let mut at = FileDescriptor { offset: offset, ..*file };
+    return at.write(buf, nbytes);
+}
+
 /*
  * \ingroup vfs
  * \brief perform ioctl on an open file
@@ -0,0 +1,336 @@
+use prelude::*;
+use fs::*;
+
+use bits::fcntl::*;
+use net::socket::FILE_SOCKET;
+use sys::process::*;
+use sys::signal::*;
+use sys::thread::*;
+use sys::syscall::file::FileDescriptor;
+
+/*
+ * advisory locks. record locks (fcntl F_SETLK and friends) belong to a
+ * process and cover a byte range, the locks of a process on a file are
+ * merged and split as ranges are locked and unlocked, and all of them go
+ * away once the process closes any descriptor of the file. flock locks
+ * belong to the open file and cover all of it. both kinds are kept in the
+ * node but never conflict with each other. waiters sleep on the lock queue
+ * of the node, a process about to sleep for a record lock held by a
+ * process which is itself waiting for one of its own gets EDEADLK instead.
+ */
+
+/** last byte of a lock reaching to the end of the file and beyond */
+const LOCK_END: u64 = u64::max_value();
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum LockOwner {
+    /** a record lock */
+    Process(*mut Process),
+
+    /** a flock lock */
+    File(*mut FileDescriptor),
+}
+
+#[derive(Copy, Clone)]
+pub struct FileLock {
+    /** F_RDLCK or F_WRLCK, F_UNLCK in requests only */
+    pub kind: usize,
+
+    /** first byte */
+    pub start: u64,
+
+    /** last byte, inclusive */
+    pub end: u64,
+
+    pub owner: LockOwner,
+}
+
+impl FileLock {
+    /** process holding the lock, 0 for flock locks */
+    pub unsafe fn pid(&self) -> pid_t {
+        match self.owner {
+            LockOwner::Process(proc) => (*proc).pid,
+            LockOwner::File(_) => 0,
+        }
+    }
+
+    /** length for F_GETLK, 0 up to the end of the file */
+    pub fn len(&self) -> u64 {
+        if self.end == LOCK_END { 0 } else { self.end - self.start + 1 }
+    }
+}
+
+/** record lock requests of processes sleeping for a lock, for deadlock detection */
+static mut LOCK_WAITS: Vec<(*mut Process, *mut Node, FileLock)> = Vec::new();
+
+fn lock_same_kind(a: &LockOwner, b: &LockOwner) -> bool {
+    match (a, b) {
+        (LockOwner::Process(_), LockOwner::Process(_)) => true,
+        (LockOwner::File(_), LockOwner::File(_)) => true,
+        _ => false,
+    }
+}
+
+/** can `lock` and `req` not be held at the same time? */
+fn lock_conflicts(lock: &FileLock, req: &FileLock) -> bool {
+    lock.owner != req.owner
+        && lock_same_kind(&lock.owner, &req.owner)
+        && lock.start <= req.end && req.start <= lock.end
+        && (lock.kind == F_WRLCK || req.kind == F_WRLCK)
+}
+
+/** first lock on `node` standing in the way of `req` */
+unsafe fn lock_conflict(node: *mut Node, req: &FileLock) -> Option<FileLock> {
+    (*node).locks.iter().find(|lock| lock_conflicts(lock, req)).map(|lock| *lock)
+}
+
+/** would `req` on `node` wait, directly or through other waiters, for a lock of `proc`? */
+unsafe fn lock_waits_for(node: *mut Node, req: &FileLock, proc: *mut Process, depth: usize) -> bool {
+    /* every step goes through another waiter, longer chains loop */
+    if depth > LOCK_WAITS.len() {
+        return false;
+    }
+
+    for lock in (*node).locks.iter().filter(|lock| lock_conflicts(lock, req)) {
+        let owner = match lock.owner {
+            LockOwner::Process(owner) => owner,
+            LockOwner::File(_) => continue,
+        };
+
+        if owner == proc {
+            return true;
+        }
+
+        for &(waiter, wnode, wreq) in LOCK_WAITS.iter() {
+            if waiter == owner && lock_waits_for(wnode, &wreq, proc, depth + 1) {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+/** replace whatever the owner of `req` holds in its range by `req` */
+unsafe fn lock_apply(node: *mut Node, req: &FileLock) {
+    let mut locks = Vec::with_capacity((*node).locks.len() + 2);
+    let mut new = *req;
+
+    for lock in (*node).locks.iter() {
+        if lock.owner != req.owner {
+            locks.push(*lock);
+            continue;
+        }
+
+        /* adjacent locks of the same type become one */
+        let touches = lock.start <= req.end.saturating_add(1) && req.start <= lock.end.saturating_add(1);
+
+        if lock.kind == req.kind && touches {
+            new.start = core::cmp::min(new.start, lock.start);
+            new.end   = core::cmp::max(new.end, lock.end);
+            continue;
+        }
+
+        if lock.end < req.start || lock.start > req.end {
+            locks.push(*lock);
+            continue;
+        }
+
+        /* keep what lies outside the new range */
+        if lock.start < req.start {
+            locks.push(FileLock { end: req.start - 1, ..*lock });
+        }
+
+        if lock.end > req.end {
+            locks.push(FileLock { start: req.end + 1, ..*lock });
+        }
+    }
+
+    if req.kind != F_UNLCK {
+        locks.push(new);
+    }
+
+    (*node).locks = locks;
+
+    /* anything may have been released */
+    thread_queue_wakeup(&mut (*node).lock_queue);
+}
+
+/**
+ * take `req` on `node`, sleeping until no other lock is in the way if
+ * `wait` is set. a request of type F_UNLCK releases the range
+ */
+pub unsafe fn lock_set(node: *mut Node, req: FileLock, wait: bool) -> Result<(), Error> {
+    loop {
+        if req.kind == F_UNLCK || lock_conflict(node, &req).is_none() {
+            lock_apply(node, &req);
+            return Ok(());
+        }
+
+        if !wait {
+            return Err(Error::EAGAIN);
+        }
+
+        /* a signal which came in while we were not sleeping interrupts as well */
+        if signal_pending(curthread!()) {
+            return Err(Error::EINTR);
+        }
+
+        let waiter = match req.owner {
+            LockOwner::Process(proc) => {
+                if lock_waits_for(node, &req, proc, 0) {
+                    return Err(Error::EDEADLK);
+                }
+
+                LOCK_WAITS.push((proc, node, req));
+                Some(proc)
+            },
+            LockOwner::File(_) => None,
+        };
+
+        let slept = thread_queue_sleep(&mut (*node).lock_queue);
+
+        if let Some(proc) = waiter {
+            let pos = LOCK_WAITS.iter().position(|&(p, n, r)| p == proc && n == node && r.start == req.start && r.end == req.end);
+
+            if let Some(pos) = pos {
+                LOCK_WAITS.remove(pos);
+            }
+        }
+
+        if slept < 0 {
+            return Err(Error::EINTR);
+        }
+    }
+}
+
+/** the lock on `node` which would keep `req` from being taken, if any */
+pub unsafe fn lock_get(node: *mut Node, req: &FileLock) -> Option<FileLock> {
+    lock_conflict(node, req)
+}
+
+/** drop all locks of `owner` on `node` */
+pub unsafe fn lock_release(node: *mut Node, owner: LockOwner) {
+    if !(*node).locks.iter().any(|lock| lock.owner == owner) {
+        return;
+    }
+
+    (*node).locks.retain(|lock| lock.owner != owner);
+    thread_queue_wakeup(&mut (*node).lock_queue);
+}
+
+/** node the open `file` takes locks on */
+pub unsafe fn lock_node(file: *mut FileDescriptor) -> Result<*mut Node, Error> {
+    let node = (*file).backend.vnode;
+
+    if (*file).flags & FILE_SOCKET != 0 || node.is_null() {
+        return Err(Error::EBADF);
+    }
+
+    Ok(node)
+}
+
+/**
+ * bytes `start` to `start + len` from `whence` of the open `file` as the
+ * first and last byte of a lock, a zero `len` reaches to the end of the
+ * file and beyond, a negative one ends before `start`
+ */
+pub unsafe fn lock_range(file: *mut FileDescriptor, whence: usize, start: i64, len: i64) -> Result<(u64, u64), Error> {
+    let base = match whence {
+        SEEK_SET => 0,
+        SEEK_CUR => (*file).offset as i64,
+        SEEK_END => (*lock_node(file)?).size() as i64,
+        _ => return Err(Error::EINVAL),
+    };
+
+    let start = base.checked_add(start).ok_or(Error::EOVERFLOW)?;
+
+    let (first, last) = if len > 0 {
+        (start, start.checked_add(len - 1).ok_or(Error::EOVERFLOW)? as u64)
+    } else if len < 0 {
+        (start + len, (start - 1) as u64)
+    } else {
+        (start, LOCK_END)
+    };
+
+    if first < 0 {
+        return Err(Error::EINVAL);
+    }
+
+    Ok((first as u64, last))
+}
+
+/** record lock of type `kind` on `start` to `end` of the open `file` for the current process */
+pub unsafe fn vfs_lock_set(file: *mut FileDescriptor, kind: usize, start: u64, end: u64, wait: bool) -> Result<(), Error> {
+    let node = lock_node(file)?;
+    let mode = (*file).flags & O_ACCMODE;
+
+    match kind {
+        F_RDLCK if mode == O_WRONLY => return Err(Error::EBADF),
+        F_WRLCK if mode == O_RDONLY => return Err(Error::EBADF),
+        F_RDLCK | F_WRLCK | F_UNLCK => {},
+        _ => return Err(Error::EINVAL),
+    }
+
+    lock_set(node, FileLock {
+        kind:  kind,
+        start: start,
+        end:   end,
+        owner: LockOwner::Process(curproc!()),
+    }, wait)
+}
+
+/** the record lock in the way of the current process locking `start` to `end` of the open `file` */
+pub unsafe fn vfs_lock_get(file: *mut FileDescriptor, kind: usize, start: u64, end: u64) -> Result<Option<FileLock>, Error> {
+    let node = lock_node(file)?;
+
+    if kind != F_RDLCK && kind != F_WRLCK {
+        return Err(Error::EINVAL);
+    }
+
+    Ok(lock_get(node, &FileLock {
+        kind:  kind,
+        start: start,
+        end:   end,
+        owner: LockOwner::Process(curproc!()),
+    }))
+}
+
+/** apply the flock(2) operation `op` to the open `file` */
+pub unsafe fn vfs_flock(file: *mut FileDescriptor, op: usize) -> Result<(), Error> {
+    let node = lock_node(file)?;
+    let owner = LockOwner::File(file);
+
+    let kind = match op & !LOCK_NB {
+        LOCK_SH => F_RDLCK,
+        LOCK_EX => F_WRLCK,
+        LOCK_UN => F_UNLCK,
+        _ => return Err(Error::EINVAL),
+    };
+
+    /* converting a lock drops the old one first, so two converting holders do not wait for each other */
+    if kind != F_UNLCK && (*node).locks.iter().any(|lock| lock.owner == owner && lock.kind != kind) {
+        lock_release(node, owner);
+    }
+
+    lock_set(node, FileLock {
+        kind:  kind,
+        start: 0,
+        end:   LOCK_END,
+        owner: owner,
+    }, op & LOCK_NB == 0)
+}
+
+/** `proc` closes a descriptor of the open `file`, its record locks on the file go */
+pub unsafe fn lock_close(proc: *mut Process, file: *mut FileDescriptor) {
+    if let Ok(node) = lock_node(file) {
+        lock_release(node, LockOwner::Process(proc));
+    }
+}
+
+/** the last descriptor of the open `file` is closed, its flock lock goes */
+pub unsafe fn lock_file_close(file: *mut FileDescriptor) {
+    if let Ok(node) = lock_node(file) {
+        lock_release(node, LockOwner::File(file));
+    }
+}
@@ -5,35 +5,170 @@ use mm::*;
 use bits::dirent::*;
 use bits::fcntl::*;
 
-fn follow(node: &mut Node, uio: &UserOp) -> Result<(&'static mut Node, String), Error> {
-    /* TODO enforce limit */
+/** maximum number of symbolic links followed while resolving a single path */
+pub const SYMLOOP_MAX: usize = 40;
 
-    let mut buffer = Buffer::new(1024);
-    let size = node.read(0, 1024, buffer.as_ptr_mut())?;
+/** target of the symbolic link `node` */
+pub fn link_target(node: &Node) -> Result<String, Error> {
+    let mut buffer = Buffer::new(PATH_MAX);
+    let size = node.readlink(buffer.as_ptr_mut(), PATH_MAX)?;
 
-    lookup(&alloc::str::from_utf8(&buffer[..size]).unwrap(), uio)
+    alloc::str::from_utf8(&buffer[..size])
+        .map(|target| target.to_owned())
+        .map_err(|_| Error::EINVAL)
 }
 
 pub fn lookup(path: &str, uio: &UserOp) -> Result<(&'static mut Node, String), Error> {
-    let realpath = fs::realpath(path, uio)?;
-    let Path { node: root, path: relative_path } = fs::mountpoint(&realpath)?;
+    lookup_resolved(fs::realpath(path, uio)?, uio)
+}
+
+/** look up `realpath`, a path already resolved by realpath */
+pub fn lookup_resolved(realpath: String, uio: &UserOp) -> Result<(&'static mut Node, String), Error> {
+    let mut realpath = realpath;
+    let mut links = 0;
+
+    loop {
+        let Path { node: root, path: relative_path } = fs::mountpoint(&realpath)?;
+        let mountpoint = &realpath[..realpath.len() - relative_path.len()];
+
+        let mut root: &mut Node = unsafe { &mut *(root as *const _ as *mut Node) };
+        let mut node: &mut Node = unsafe { &mut *(root as *const _ as *mut Node) };
+
+        let tokens: Vec<&str> = relative_path.split("/").filter(|token| !token.is_empty()).collect();
+        let mut resolved = None;
+
+        for (i, token) in tokens.iter().enumerate() {
+            let dirent = node.finddir(token)?;
+            node = fs::iget(root, dirent.d_ino)?;
 
-    let mut root: &mut Node = unsafe { &mut *(root as *const _ as *mut Node) };
-    let mut node: &mut Node = unsafe { &mut *(root as *const _ as *mut Node) };
+            /* intermediate links are always followed, the last one unless O_NOFOLLOW */
+            if !node.is_symlink() || (i == tokens.len() - 1 && uio.flags as usize & O_NOFOLLOW != 0) {
+                continue;
+            }
+
+            links += 1;
+
+            if links > SYMLOOP_MAX {
+                return Err(Error::ELOOP);
+            }
+
+            /* relative targets are resolved against the directory of the link,
+             * absolute ones against the root directory */
+            let target = link_target(node)?;
+            let base = format!("{}/{}", mountpoint, tokens[..i].join("/"));
+
+            resolved = Some(fs::realpath_at(&base, &format!("{}/{}", target, tokens[i + 1..].join("/")), uio)?);
+            break;
+        }
 
-    for token in relative_path.split("/") {
-        if token.is_empty() {
+        /* start over with the link substituted */
+        if let Some(path) = resolved {
+            realpath = path;
             continue;
         }
 
-        let dirent = node.finddir(token)?;
-        node = fs::iget(root, dirent.d_ino)?;
+        if node.path.as_ref() != Some(&realpath) {
+            node.path = Some(realpath.clone());
+        }
+
+        return Ok((node, realpath));
+    }
+}
+
+/** root of the newest mount hiding the directory `node`, `node` itself if nothing is mounted on it */
+fn mount_cover(node: *mut Node) -> *mut Node {
+    let mut node = node;
+
+    unsafe {
+        /* mounts may be stacked on the same directory, the newest comes first */
+        while let Some(mount) = fs::mount_table().iter()
+            .find(|mount| mount.covered == node as *const Node && &*mount.root as *const Node != node as *const Node) {
+            node = &*mount.root as *const Node as *mut Node;
+        }
     }
 
-    /* resolve symbolic links */
-    if node.is_symlink() && (uio.flags as usize & O_NOFOLLOW == 0) {
-        return follow(node, uio);
+    node
+}
+
+/** look up `path` starting from the directory `dir`, or from the working directory without one
+ *
+ * the components are walked from the node of `dir` itself, the returned path is only rebuilt
+ * from the path `dir` was opened by for reporting and may be stale if it was renamed since */
+pub fn lookup_at(dir: Option<&Node>, path: &str, uio: &UserOp) -> Result<(&'static mut Node, String), Error> {
+    let dir = match dir {
+        Some(dir) if !path.starts_with('/') => dir,
+        _ => return lookup(path, uio),
+    };
+
+    if !dir.is_directory() {
+        return Err(Error::ENOTDIR);
     }
 
-    return Ok((node, realpath));
-}
\ No newline at end of file
+    let base = dir.path.clone().ok_or(Error::ENOENT)?;
+
+    /* components still to walk, the next one last */
+    let mut pending: Vec<String> = path.split("/").rev().filter(|token| !token.is_empty()).map(|token| token.to_owned()).collect();
+    let mut nodes: Vec<*mut Node> = vec![dir as *const Node as *mut Node];
+    let mut names: Vec<String> = Vec::new();
+    let mut links = 0;
+
+    while let Some(token) = pending.pop() {
+        let node = unsafe { &mut **nodes.last().unwrap() };
+
+        match token.as_str() {
+            "." => continue,
+            ".." if nodes.len() > 1 => {
+                nodes.pop();
+                names.pop();
+                continue;
+            },
+            ".." => {
+                /* above `dir` the walk can only go by the path it was opened by */
+                let rest: Vec<String> = pending.into_iter().rev().collect();
+                return lookup(&format!("{}/../{}", fs::root_path(&base, uio), rest.join("/")), uio);
+            },
+            _ => {},
+        }
+
+        if !node.is_directory() {
+            return Err(Error::ENOTDIR);
+        }
+
+        let dirent = node.finddir(&token)?;
+        let child = unsafe { &mut *mount_cover(fs::iget(node, dirent.d_ino)?) };
+
+        /* intermediate links are always followed, the last one unless O_NOFOLLOW */
+        if child.is_symlink() && !(pending.is_empty() && uio.flags as usize & O_NOFOLLOW != 0) {
+            links += 1;
+
+            if links > SYMLOOP_MAX {
+                return Err(Error::ELOOP);
+            }
+
+            let target = link_target(child)?;
+
+            /* absolute targets start over from the root directory */
+            if target.starts_with('/') {
+                let rest: Vec<String> = pending.into_iter().rev().collect();
+                return lookup(&format!("{}/{}", target, rest.join("/")), uio);
+            }
+
+            /* relative ones are walked from the directory of the link */
+            pending.extend(target.split("/").rev().filter(|token| !token.is_empty()).map(|token| token.to_owned()));
+            continue;
+        }
+
+        nodes.push(child);
+        names.push(token);
+    }
+
+    let node = unsafe { &mut **nodes.last().unwrap() };
+
+    let realpath = if names.is_empty() {
+        base
+    } else {
+        format!("{}/{}", base.trim_end_matches('/'), names.join("/"))
+    };
+
+    Ok((node, realpath))
+}
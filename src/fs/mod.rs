@@ -12,6 +12,13 @@ pub mod rofs;
 pub mod mount;
 pub mod vm_object;
 //pub mod pipe;
+pub mod anon;
+pub mod poll;
+pub mod epoll;
+pub mod eventfd;
+pub mod signalfd;
+pub mod timerfd;
+pub mod lock;
 pub mod vfs;
 pub mod termios;
 
@@ -20,6 +27,9 @@ pub mod pseudofs;
 
 pub mod tmpfs;
 pub mod devfs;
+pub mod shmfs;
+pub mod procfs;
+pub mod sysfs;
 pub mod initramfs;
 
 pub use self::node::*;
@@ -31,4 +41,11 @@ pub use self::lookup::*;
 pub use self::mount::*;
 pub use self::vm_object::*;
 //pub use self::pipe::*;
+pub use self::anon::*;
+pub use self::poll::*;
+pub use self::epoll::*;
+pub use self::eventfd::*;
+pub use self::signalfd::*;
+pub use self::timerfd::*;
+pub use self::lock::*;
 pub use self::vfs::*;
@@ -1,28 +1,258 @@
 use prelude::*;
 
+use alloc::collections::btree_set::BTreeSet;
+
 use fs::{self, *};
 use mm::*;
+use bits::fcntl::*;
+use bits::mount::*;
+use net::socket::FILE_SOCKET;
+use sys::process::*;
 
 use kern::print::cstr;
 use crate::{print, malloc_define};
 
 malloc_define!(M_MOUNTPOINT, "mountpoint\0", "mount point structure\0");
 
-pub fn mount(fs_type: &str, dir: &str, flags: isize, data: *mut u8, uio: &UserOp) -> Result<(), Error> {
+/** data passed to the mount hook of filesystems taking options */
+pub struct MountData {
+    /** device or source, null terminated */
+    pub dev: *mut u8,
+
+    /** comma separated options, null terminated */
+    pub opt: *mut u8,
+}
+
+/** resolve the existing directory `dir` a filesystem is mounted on */
+fn mount_target(dir: &str, uio: &UserOp) -> Result<String, Error> {
+    let (node, realpath) = fs::lookup(dir, uio)?;
+
+    if !node.is_directory() {
+        return Err(Error::ENOTDIR);
+    }
+
+    Ok(realpath)
+}
+
+pub fn mount(fs_type: &str, dir: &str, flags: usize, data: *mut u8, uio: &UserOp) -> Result<(), Error> {
+    if flags & MS_REMOUNT != 0 {
+        return remount(dir, flags, uio);
+    }
+
     unsafe {
         match REGISTERED_FS.iter().find(|fs| fs.name == fs_type) {
             None => Err(Error::EINVAL),
             Some(fs) => {
-                let realpath = fs::realpath(dir, uio)?;
-                fs.mount(Arc::clone(fs), &realpath, flags, data)
+                let realpath = mount_target(dir, uio)?;
+                fs.mount(Arc::clone(fs), &realpath, flags as isize, data)?;
+
+                /* the hook binds the new root, the flags belong to the mount */
+                mount_set_flags(&realpath, flags);
+
+                Ok(())
             }
         }
     }
 }
 
+/** make the tree at `source` visible at `dir` as well */
+pub fn bind_mount(source: &str, dir: &str, flags: usize, uio: &UserOp) -> Result<(), Error> {
+    let (node, _) = fs::lookup(source, uio)?;
+    let realpath = mount_target(dir, uio)?;
+
+    if !node.is_directory() {
+        return Err(Error::ENOTDIR);
+    }
+
+    fs::bind(&realpath, unsafe { node.to_arc() })?;
+    mount_set_flags(&realpath, flags);
+
+    Ok(())
+}
+
+/** set the flags of the newest mount on the resolved path `path` */
+fn mount_set_flags(path: &str, flags: usize) -> bool {
+    unsafe {
+        match mount_table().iter_mut().find(|mount| mount.path == path) {
+            Some(mount) => {
+                mount.flags = flags & MS_PERMS;
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+/** change the flags of the mount on `dir` */
+pub fn remount(dir: &str, flags: usize, uio: &UserOp) -> Result<(), Error> {
+    let (_, realpath) = fs::lookup(dir, uio)?;
+
+    /* not a mount point */
+    if !mount_set_flags(&realpath, flags) {
+        return Err(Error::EINVAL);
+    }
+
+    Ok(())
+}
+
+/** add the nodes reachable from the directory `root` to `nodes` */
+unsafe fn mount_nodes(root: *mut Node, nodes: &mut BTreeSet<usize>) {
+    /* directories still to read, kept here rather than on the kernel stack */
+    let mut dirs = vec![root];
+
+    while let Some(dir) = dirs.pop() {
+        let mut offset = 0;
+
+        loop {
+            let (count, dirent) = match (*dir).readdir(offset) {
+                Ok((count, _)) if count == 0 => break,
+                Ok(entry) => entry,
+                Err(_) => break,
+            };
+
+            offset += count;
+
+            let name = cstr(dirent.d_name.as_ptr());
+
+            if name == "." || name == ".." {
+                continue;
+            }
+
+            let node = match fs::iget(&mut *dir, dirent.d_ino) {
+                Ok(node) => node as *mut Node,
+                Err(_) => continue,
+            };
+
+            if nodes.insert(node as usize) && (*node).is_directory() {
+                dirs.push(node);
+            }
+        }
+    }
+}
+
+/** whether a process still uses something inside the mount `mount` */
+unsafe fn mount_busy(mount: &Mount) -> bool {
+    /* the working and root directories are only known by path */
+    let path = &mount.path;
+    let inside = |other: &str| {
+        fs::mountpoint(other)
+            .map(|mnt| other.len() - mnt.path.len() == path.len() && other.starts_with(path.as_str()))
+            .unwrap_or(false)
+    };
+
+    /* open files and mappings are matched by node, whatever path they were reached by */
+    let root = &*mount.root as *const Node as *mut Node;
+    let mut nodes = BTreeSet::new();
+
+    nodes.insert(root as usize);
+    mount_nodes(root, &mut nodes);
+
+    for qnode in PROCS.iter() {
+        let proc = (*qnode).value;
+
+        if (*proc).running == 0 {
+            continue;
+        }
+
+        if inside(&(*proc).cwd) || inside(&(*proc).root) {
+            return true;
+        }
+
+        for fd in 0..(*proc).fds_count {
+            let file = match proc_fd_file(proc, fd as isize) {
+                Some(file) => file,
+                None => continue,
+            };
+
+            if (*file).flags & FILE_SOCKET == 0 && nodes.contains(&((*file).backend.vnode as usize)) {
+                return true;
+            }
+        }
+
+        /* mapped files, the running executable and its interpreter among them */
+        for qnode in (*proc).vm_space.vm_entries.iter() {
+            let vm_object = (*qnode.value).vm_object;
+
+            /* objects of files point back at their node */
+            if !vm_object.is_null() && nodes.contains(&((*vm_object).p as usize)) {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+/** detach the filesystem mounted on `dir` */
+pub fn umount(dir: &str, flags: usize, uio: &UserOp) -> Result<(), Error> {
+    if flags & !(MNT_FORCE | MNT_DETACH | UMOUNT_NOFOLLOW) != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let lookup_uio = UserOp {
+        flags: if flags & UMOUNT_NOFOLLOW != 0 { O_NOFOLLOW } else { 0 },
+        ..*uio
+    };
+
+    let (_, realpath) = fs::lookup(dir, &lookup_uio)?;
+
+    unsafe {
+        let table = mount_table();
+
+        let idx = match table.iter().position(|mount| mount.path == realpath) {
+            Some(idx) => idx,
+            /* not a mount point */
+            None => return Err(Error::EINVAL),
+        };
+
+        if realpath == "/" {
+            return Err(Error::EBUSY);
+        }
+
+        if flags & MNT_DETACH != 0 {
+            /* lazy unmount takes the mounts below along, open files keep their nodes */
+            let detached = table.remove(idx);
+            table.retain(|mount| !(mount.path.len() > detached.path.len() && detached.contains(&mount.path)));
+
+            return Ok(());
+        }
+
+        /* mounts below it would be left unreachable */
+        if table.iter().any(|mount| mount.path.len() > realpath.len() && table[idx].contains(&mount.path)) {
+            return Err(Error::EBUSY);
+        }
+
+        if flags & MNT_FORCE == 0 && mount_busy(&table[idx]) {
+            return Err(Error::EBUSY);
+        }
+
+        table.remove(idx);
+        Ok(())
+    }
+}
+
+/** flags of the mount containing the resolved path `path` */
+pub fn mount_flags(path: &str) -> usize {
+    unsafe {
+        mount_table().iter()
+            .find(|mount| mount.contains(path))
+            .map(|mount| mount.flags)
+            .unwrap_or(0)
+    }
+}
+
+/** refuse modifications below a read-only mount */
+pub fn mount_writable(path: &str) -> Result<(), Error> {
+    if mount_flags(path) & MS_RDONLY != 0 {
+        return Err(Error::EROFS);
+    }
+
+    Ok(())
+}
+
 pub fn get_fs_by_name(name: &str) -> Option<Arc<Filesystem>> {
     unsafe {
         REGISTERED_FS.iter().find(|fs| fs.name == name)
             .map(|fs| Arc::clone(fs))
     }
-}
\ No newline at end of file
+}
@@ -34,8 +34,20 @@ pub struct Node {
     pub read_queue: Option<Box<Queue<*mut Thread>>>,
     pub write_queue: Option<Box<Queue<*mut Thread>>>,
 
+    /** hooks of poll and epoll waiters watching the node */
+    pub pollers: Queue<*mut PollHook>,
+
+    /** advisory locks held on the node, record locks and flock locks */
+    pub locks: Vec<FileLock>,
+
+    /** threads waiting for a lock on the node */
+    pub lock_queue: Queue<*mut Thread>,
+
     /** virtual memory object associated with vnode */
     pub vm_object: *mut VmObject,
+
+    /** last path the node was looked up by, names open files in procfs */
+    pub path: Option<String>,
 }
 
 impl Node {
@@ -57,7 +69,11 @@ impl Node {
             refcnt: 0,
             read_queue: None,
             write_queue: None,
+            pollers: Queue::empty(),
+            locks: Vec::new(),
+            lock_queue: Queue::empty(),
             vm_object: core::ptr::null_mut(),
+            path: None,
         }
     }
 }
@@ -143,6 +159,13 @@ impl Node {
         self.rdev
     }
 
+    /** another reference to this node, nodes always live in an Arc */
+    pub unsafe fn to_arc(&self) -> Arc<Node> {
+        let ptr = self as *const Node;
+        Arc::increment_strong_count(ptr);
+        Arc::from_raw(ptr)
+    }
+
     pub fn mknod(&self, filename: &str, mode: mode_t, dev: dev_t, uio: &UserOp) -> Result<Arc<Node>, Error> {
         /* not a directory */
         if !self.is_directory() {
@@ -175,6 +198,71 @@ impl Node {
         }
     }
 
+    /** move `oldname` in this directory to `newname` in `newdir`, replacing it if it exists */
+    pub fn rename(&self, oldname: &str, newdir: &Node, newname: &str, uio: &UserOp) -> Result<(), Error> {
+        if !self.is_directory() || !newdir.is_directory() {
+            return Err(Error::ENOTDIR);
+        }
+
+        if self.fs.is_none() {
+            return Err(Error::ENOSYS);
+        }
+
+        match self.fs.as_ref().unwrap().rename {
+            Some(f) => f(self, oldname, newdir, newname, uio),
+            None => Err(Error::ENOSYS),
+        }
+    }
+
+    /** add `node` to this directory as `filename` */
+    pub fn link(&self, filename: &str, node: &Node, uio: &UserOp) -> Result<(), Error> {
+        if !self.is_directory() {
+            return Err(Error::ENOTDIR);
+        }
+
+        if self.fs.is_none() {
+            return Err(Error::ENOSYS);
+        }
+
+        match self.fs.as_ref().unwrap().link {
+            Some(f) => f(self, filename, node, uio),
+            None => Err(Error::EPERM),
+        }
+    }
+
+    /** create the symbolic link `filename` pointing to `target` in this directory */
+    pub fn symlink(&self, filename: &str, target: &str, uio: &UserOp) -> Result<Arc<Node>, Error> {
+        if !self.is_directory() {
+            return Err(Error::ENOTDIR);
+        }
+
+        if self.fs.is_none() {
+            return Err(Error::ENOSYS);
+        }
+
+        match self.fs.as_ref().unwrap().symlink {
+            Some(f) => f(self, filename, target, uio),
+            None => Err(Error::EPERM),
+        }
+    }
+
+    /** read the target of a symbolic link, not null terminated */
+    pub fn readlink(&self, buffer: *mut u8, size: usize) -> Result<usize, Error> {
+        if !self.is_symlink() {
+            return Err(Error::EINVAL);
+        }
+
+        if self.fs.is_none() {
+            return Err(Error::ENOSYS);
+        }
+
+        match self.fs.as_ref().unwrap().readlink {
+            Some(f) => f(self, buffer, size),
+            /* the target is the contents of the link */
+            None => self.read(0, size, buffer),
+        }
+    }
+
     pub fn map(&self, vm_space: *mut VmSpace, vm_entry: *mut VmEntry) -> isize {
         if self.fs.is_none() {
             return -ENOSYS;
@@ -4,47 +4,256 @@ use fs::{self, *};
 use dev::kdev::*;
 use dev::*;
 use bits::dirent::*;
+use bits::fcntl::*;
 
 pub fn close(node: &Node) -> Result<usize, Error> {
-    Ok(0)
+    match node.fs.as_ref() {
+        Some(fs) => fs.close(node),
+        None => Ok(0)
+    }
 }
 
 pub fn unlink(path: &str, uio: &UserOp) -> Result<(), Error> {
-    let realpath = fs::realpath(path, uio)?;
-    let tokens: Vec<&str> = realpath.split("/").collect();
+    unlink_at(None, path, uio)
+}
 
-    if tokens.len() == 0 {
-        return Err(Error::EINVAL);
+/** remove the file at `path`, relative paths are taken from `at` */
+pub fn unlink_at(at: Option<&Node>, path: &str, uio: &UserOp) -> Result<(), Error> {
+    let (dir, dirpath, basename) = lookup_parent(at, path, uio)?;
+
+    fs::mount_writable(&dirpath)?;
+
+    /* directories are removed by rmdir */
+    let dirent = dir.finddir(&basename)?;
+
+    if fs::iget(dir, dirent.d_ino)?.is_directory() {
+        return Err(Error::EISDIR);
     }
 
-    let dirname  = tokens[..tokens.len() - 1].join("/");
-    let basename = tokens.last().unwrap();
-    let (dir, _) = fs::lookup(&dirname, uio)?;
+    dir.unlink(&basename, uio)
+}
+
+/** remove the empty directory at `path` */
+pub fn rmdir(path: &str, uio: &UserOp) -> Result<(), Error> {
+    rmdir_at(None, path, uio)
+}
+
+/** remove the empty directory at `path`, relative paths are taken from `at` */
+pub fn rmdir_at(at: Option<&Node>, path: &str, uio: &UserOp) -> Result<(), Error> {
+    let (dir, dirpath, name) = lookup_parent(at, path, uio)?;
+
+    fs::mount_writable(&dirpath)?;
+
+    let dirent = dir.finddir(&name)?;
+    let node = fs::iget(dir, dirent.d_ino)?;
+
+    if !node.is_directory() {
+        return Err(Error::ENOTDIR);
+    }
+
+    /* something is mounted on it */
+    let nodepath = child_path(&dirpath, &name);
+
+    if fs::mountpoints().iter().any(|mount| mount.path == nodepath) {
+        return Err(Error::EBUSY);
+    }
+
+    let mut offset = 0;
+
+    loop {
+        let (count, dirent) = node.readdir(offset)?;
+
+        if count == 0 {
+            break;
+        }
 
-    dir.unlink(basename, uio)
+        match cstr(dirent.d_name.as_ptr()) {
+            "." | ".." => offset += count,
+            _ => return Err(Error::ENOTEMPTY),
+        }
+    }
+
+    dir.unlink(&name, uio)
 }
 
 pub fn mknod(path: &str, mode: mode_t, dev: dev_t, uio: &UserOp) -> Result<Arc<Node>, Error> {
+    mknod_at(None, path, mode, dev, uio)
+}
+
+/** create the node `path`, relative paths are taken from `at` */
+pub fn mknod_at(at: Option<&Node>, path: &str, mode: mode_t, dev: dev_t, uio: &UserOp) -> Result<Arc<Node>, Error> {
+    let (dir, dirpath, basename) = lookup_parent(at, path, uio)?;
+
+    fs::mount_writable(&dirpath)?;
+
+    dir.mknod(&basename, mode, dev, uio)
+}
+
+pub fn mkdir(path: &str, mode: mode_t, uio: &UserOp) -> Result<Arc<Node>, Error> {
+    mkdir_at(None, path, mode, uio)
+}
+
+pub fn mkdir_at(at: Option<&Node>, path: &str, mode: mode_t, uio: &UserOp) -> Result<Arc<Node>, Error> {
+    mknod_at(at, path, S_IFDIR | mode, 0, uio)
+}
+
+pub fn creat(path: &str, mode: mode_t, uio: &UserOp) -> Result<Arc<Node>, Error> {
+    creat_at(None, path, mode, uio)
+}
+
+pub fn creat_at(at: Option<&Node>, path: &str, mode: mode_t, uio: &UserOp) -> Result<Arc<Node>, Error> {
+    mknod_at(at, path, S_IFREG | mode, 0, uio)
+}
+
+/** resolve the directory containing `path`, returns it with its path and the last component,
+ * relative paths are walked from the directory `at` if there is one */
+fn lookup_parent(at: Option<&Node>, path: &str, uio: &UserOp) -> Result<(&'static mut Node, String, String), Error> {
+    let at = match at {
+        Some(at) if !path.starts_with('/') => at,
+        _ => return lookup_parent_path(path, uio),
+    };
+
+    let path = path.trim_end_matches('/');
+
+    let (dirname, basename) = match path.rfind('/') {
+        Some(i) => (&path[..i], &path[i + 1..]),
+        None => (".", path),
+    };
+
+    if basename.is_empty() || basename == "." || basename == ".." {
+        return Err(Error::EINVAL);
+    }
+
+    /* the parent is always followed, only the last component may be a link */
+    let dir_uio = UserOp {
+        flags: uio.flags & !O_NOFOLLOW,
+        ..*uio
+    };
+
+    let (dir, dirpath) = fs::lookup_at(Some(at), dirname, &dir_uio)?;
+
+    if !dir.is_directory() {
+        return Err(Error::ENOTDIR);
+    }
+
+    Ok((dir, dirpath, basename.to_string()))
+}
+
+/** `lookup_parent` for a path taken from the working directory */
+fn lookup_parent_path(path: &str, uio: &UserOp) -> Result<(&'static mut Node, String, String), Error> {
     let realpath = fs::realpath(path, uio)?;
     let tokens: Vec<&str> = realpath.split("/").collect();
 
-    if tokens.len() == 0 {
+    let basename = tokens.last().unwrap();
+
+    if basename.is_empty() {
         return Err(Error::EINVAL);
     }
 
-    let dirname  = tokens[..tokens.len() - 1].join("/");
-    let basename = tokens.last().unwrap();
-    let (dir, _) = fs::lookup(&dirname, uio)?;
+    /* the root directory has no parent to change, even when it is not the global one */
+    if realpath == fs::realpath("/", uio)? {
+        return Err(Error::EBUSY);
+    }
+
+    /* the parent is always followed, only the last component may be a link */
+    let dir_uio = UserOp {
+        flags: uio.flags & !O_NOFOLLOW,
+        ..*uio
+    };
 
-    dir.mknod(basename, mode, dev, uio)
+    let dirname = "/".to_owned() + &tokens[..tokens.len() - 1].join("/");
+    let (dir, dirpath) = fs::lookup_resolved(dirname, &dir_uio)?;
+
+    Ok((dir, dirpath, basename.to_string()))
 }
 
-pub fn mkdir(path: &str, mode: mode_t, uio: &UserOp) -> Result<Arc<Node>, Error> {
-    mknod(path, S_IFDIR | mode, 0, uio)
+/** resolved path of `name` inside the resolved directory `dirpath` */
+fn child_path(dirpath: &str, name: &str) -> String {
+    format!("{}/{}", dirpath.trim_end_matches('/'), name)
 }
 
-pub fn creat(path: &str, mode: mode_t, uio: &UserOp) -> Result<Arc<Node>, Error> {
-    mknod(path, S_IFREG | mode, 0, uio)
+/** root node of the mount containing the resolved path `path` */
+fn mount_root(path: &str) -> Result<*const Node, Error> {
+    fs::mountpoint(path).map(|mount| mount.node as *const Node)
+}
+
+/** move `oldpath` to `newpath`, atomically replacing whatever `newpath` names */
+pub fn rename(oldpath: &str, newpath: &str, uio: &UserOp) -> Result<(), Error> {
+    rename_at(None, oldpath, None, newpath, uio)
+}
+
+/** `rename` with relative paths taken from `oldat` and `newat` respectively */
+pub fn rename_at(oldat: Option<&Node>, oldpath: &str, newat: Option<&Node>, newpath: &str, uio: &UserOp) -> Result<(), Error> {
+    let (olddir, olddirpath, oldname) = lookup_parent(oldat, oldpath, uio)?;
+    let (newdir, newdirpath, newname) = lookup_parent(newat, newpath, uio)?;
+
+    if mount_root(&olddirpath)? != mount_root(&newdirpath)? {
+        return Err(Error::EXDEV);
+    }
+
+    fs::mount_writable(&olddirpath)?;
+    fs::mount_writable(&newdirpath)?;
+
+    /* a directory can not be moved inside itself */
+    let oldfull = child_path(&olddirpath, &oldname);
+    let newfull = child_path(&newdirpath, &newname);
+
+    if newfull.starts_with(&(oldfull.clone() + "/")) {
+        return Err(Error::EINVAL);
+    }
+
+    if fs::mountpoints().iter().any(|mount| mount.path == oldfull || mount.path == newfull) {
+        return Err(Error::EBUSY);
+    }
+
+    olddir.rename(&oldname, newdir, &newname, uio)
+}
+
+/** create `newpath` as another name for the file at `oldpath` */
+pub fn link(oldpath: &str, newpath: &str, uio: &UserOp) -> Result<(), Error> {
+    let nofollow = UserOp {
+        flags: uio.flags | O_NOFOLLOW,
+        ..*uio
+    };
+
+    let (node, nodepath) = fs::lookup(oldpath, &nofollow)?;
+    let (dir, dirpath, name) = lookup_parent(None, newpath, uio)?;
+
+    if mount_root(&nodepath)? != mount_root(&dirpath)? {
+        return Err(Error::EXDEV);
+    }
+
+    fs::mount_writable(&dirpath)?;
+
+    dir.link(&name, node, uio)
+}
+
+/** create the symbolic link `path` pointing to `target` */
+pub fn symlink(target: &str, path: &str, uio: &UserOp) -> Result<Arc<Node>, Error> {
+    if target.is_empty() {
+        return Err(Error::ENOENT);
+    }
+
+    let (dir, dirpath, name) = lookup_parent(None, path, uio)?;
+
+    fs::mount_writable(&dirpath)?;
+    dir.symlink(&name, target, uio)
+}
+
+/** target of the symbolic link at `path` */
+pub fn readlink(path: &str, uio: &UserOp) -> Result<String, Error> {
+    readlink_at(None, path, uio)
+}
+
+/** target of the symbolic link at `path`, relative paths are taken from `at` */
+pub fn readlink_at(at: Option<&Node>, path: &str, uio: &UserOp) -> Result<String, Error> {
+    let nofollow = UserOp {
+        flags: uio.flags | O_NOFOLLOW,
+        ..*uio
+    };
+
+    let (node, _) = fs::lookup_at(at, path, &nofollow)?;
+    fs::link_target(node)
 }
 
 /* sync the metadata and/or data associated with a filesystem */
@@ -0,0 +1,253 @@
+use prelude::*;
+use fs::*;
+
+use arch::sys::sched::arch_rtime_ns;
+use bits::poll::*;
+use net::socket::*;
+use sys::process::*;
+use sys::sched::*;
+use sys::thread::*;
+use sys::syscall::file::FileDescriptor;
+
+/*
+ * readiness notification. whoever waits for a node to become readable or
+ * writable hangs a PollHook on the node, or on the socket for sockets. drivers
+ * and protocols call poll_notify or socket_notify whenever
+ * they wake up the read or write queue of a node, which counts the event on
+ * every hook and wakes up the threads sleeping on the queue of the hook, so
+ * waiters only look at their files again when something happened.
+ */
+
+/** interest of a waiter in a node */
+pub struct PollHook {
+    /** node being watched, null for sockets */
+    pub node: *mut Node,
+
+    /** threads to wake up when the node signals */
+    pub queue: *mut Queue<*mut Thread>,
+
+    /** node whose pollers are told in turn, the epoll instance owning the hook */
+    pub parent: *mut Node,
+
+    /** events signalled since the hook was added */
+    pub count: usize,
+
+    /** queue of pollers the hook is on */
+    pollers: *mut Queue<*mut PollHook>,
+
+    /** node in `pollers`, null while not added */
+    qnode: *mut QueueNode<*mut PollHook>,
+}
+
+impl PollHook {
+    pub const fn new(queue: *mut Queue<*mut Thread>, parent: *mut Node) -> Self {
+        PollHook {
+            node:    core::ptr::null_mut(),
+            queue:   queue,
+            parent:  parent,
+            count:   0,
+            pollers: core::ptr::null_mut(),
+            qnode:   core::ptr::null_mut(),
+        }
+    }
+}
+
+/** start watching `node` through `hook`, which must not move until removed */
+pub unsafe fn poll_hook_add(hook: *mut PollHook, node: *mut Node) {
+    (*hook).node = node;
+    poll_hook_enqueue(hook, &mut (*node).pollers);
+}
+
+/** start watching the open `file` through `hook`, whatever it is backed by */
+pub unsafe fn poll_hook_add_file(hook: *mut PollHook, file: *mut FileDescriptor) {
+    if (*file).flags & FILE_SOCKET != 0 {
+        let socket = (*file).backend.socket;
+
+        if !socket.is_null() {
+            poll_hook_enqueue(hook, &mut (*socket).pollers);
+        }
+
+        return;
+    }
+
+    let node = (*file).backend.vnode;
+
+    if !node.is_null() {
+        poll_hook_add(hook, node);
+    }
+}
+
+unsafe fn poll_hook_enqueue(hook: *mut PollHook, pollers: *mut Queue<*mut PollHook>) {
+    (*hook).pollers = pollers;
+    (*hook).qnode   = (*pollers).enqueue(hook);
+}
+
+/** stop watching the node or socket of `hook`, if any */
+pub unsafe fn poll_hook_remove(hook: *mut PollHook) {
+    if !(*hook).qnode.is_null() {
+        (*(*hook).pollers).node_remove((*hook).qnode);
+        (*hook).qnode = core::ptr::null_mut();
+    }
+}
+
+/** take off every hook on `pollers`, whose node or socket goes away */
+pub unsafe fn poll_detach(pollers: *mut Queue<*mut PollHook>) {
+    while let Some(hook) = (*pollers).dequeue() {
+        (*hook).node    = core::ptr::null_mut();
+        (*hook).pollers = core::ptr::null_mut();
+        (*hook).qnode   = core::ptr::null_mut();
+    }
+}
+
+/** tell everyone watching `node` that its state may have changed */
+pub unsafe fn poll_notify(node: *mut Node) {
+    poll_notify_pollers(&mut (*node).pollers);
+}
+
+/** tell every hook on `pollers` that the state of what they watch may have changed */
+pub unsafe fn poll_notify_pollers(pollers: *mut Queue<*mut PollHook>) {
+    for qnode in (*pollers).iter() {
+        let hook = qnode.value;
+
+        (*hook).count += 1;
+
+        if !(*hook).queue.is_null() {
+            thread_queue_wakeup((*hook).queue);
+        }
+
+        if !(*hook).parent.is_null() {
+            poll_notify((*hook).parent);
+        }
+    }
+}
+
+/** the open file `fd` of `proc` */
+pub unsafe fn poll_file(proc: *mut Process, fd: isize) -> Option<*mut FileDescriptor> {
+    proc_fd_file(proc, fd)
+}
+
+/** node to hook on for the open `file`, null if it has none */
+pub unsafe fn poll_node(file: *mut FileDescriptor) -> *mut Node {
+    if (*file).flags & FILE_SOCKET != 0 {
+        /* sockets carry their hooks themselves, see poll_hook_add_file */
+        return core::ptr::null_mut();
+    }
+
+    (*file).backend.vnode
+}
+
+/** POLL* events out of `events` the open `file` is ready for */
+pub unsafe fn vfs_file_poll(file: *mut FileDescriptor, events: usize) -> usize {
+    let mut revents = 0;
+
+    if events & (POLLIN | POLLRDNORM) != 0 && vfs_file_can_read(file, 1) > 0 {
+        revents |= events & (POLLIN | POLLRDNORM);
+    }
+
+    if events & (POLLOUT | POLLWRNORM) != 0 && vfs_file_can_write(file, 1) > 0 {
+        revents |= events & (POLLOUT | POLLWRNORM);
+    }
+
+    revents
+}
+
+/** fill in the returned events of `fds`, returns the number of entries with events */
+unsafe fn poll_scan(fds: &mut [PollFd]) -> usize {
+    let mut count = 0;
+
+    for pfd in fds.iter_mut() {
+        pfd.revents = 0;
+
+        if pfd.fd < 0 {
+            continue;
+        }
+
+        let revents = match poll_file(curproc!(), pfd.fd as isize) {
+            None => POLLNVAL,
+            Some(file) => vfs_file_poll(file, pfd.events as u16 as usize),
+        };
+
+        pfd.revents = revents as i16;
+
+        if revents != 0 {
+            count += 1;
+        }
+    }
+
+    count
+}
+
+/**
+ * call `scan` until it reports something ready, sleeping on `queue` in
+ * between, which is the queue of `hooks`. gives up after `timeout`
+ * nanoseconds if given, returns what `scan` returned last
+ */
+pub unsafe fn poll_wait<F>(hooks: &[PollHook], queue: *mut Queue<*mut Thread>, timeout: Option<u64>, mut scan: F) -> Result<usize, Error>
+where F: FnMut() -> usize {
+    let deadline = timeout.map(|ns| arch_rtime_ns() + ns);
+
+    /* counts are bumped by drivers, possibly from interrupt context */
+    let signalled = || hooks.iter().map(|hook| core::ptr::read_volatile(&hook.count)).sum::<usize>();
+
+    loop {
+        let before = signalled();
+        let count = scan();
+
+        if count > 0 {
+            return Ok(count);
+        }
+
+        /* something changed while scanning, look again before sleeping */
+        if signalled() != before {
+            continue;
+        }
+
+        let slept = match deadline {
+            None => thread_queue_sleep(queue),
+            Some(deadline) => {
+                let now = arch_rtime_ns();
+
+                if now >= deadline {
+                    return Ok(0);
+                }
+
+                thread_queue_sleep_timeout(queue, deadline - now)
+            },
+        };
+
+        if slept < 0 {
+            return Err(Error::EINTR);
+        }
+    }
+}
+
+/**
+ * wait for any of `fds` of the current process to become ready, for at
+ * most `timeout` nanoseconds if given. returns the number of entries with
+ * returned events
+ */
+pub unsafe fn vfs_poll(fds: &mut [PollFd], timeout: Option<u64>) -> Result<usize, Error> {
+    let mut queue: Queue<*mut Thread> = Queue::empty();
+    let queue_ptr = &mut queue as *mut Queue<*mut Thread>;
+
+    /* one hook per entry, the vector is never resized while hooked */
+    let mut hooks: Vec<PollHook> = fds.iter().map(|_| PollHook::new(queue_ptr, core::ptr::null_mut())).collect();
+
+    for (pfd, hook) in fds.iter().zip(hooks.iter_mut()) {
+        if pfd.fd < 0 {
+            continue;
+        }
+
+        if let Some(file) = poll_file(curproc!(), pfd.fd as isize) {
+            poll_hook_add_file(hook, file);
+        }
+    }
+
+    let ret = poll_wait(&hooks, queue_ptr, timeout, || poll_scan(fds));
+
+    for hook in hooks.iter_mut() {
+        poll_hook_remove(hook);
+    }
+
+    ret
+}
@@ -0,0 +1,4 @@
+pub mod procfs;
+pub mod pid;
+
+pub use self::procfs::*;
@@ -0,0 +1,272 @@
+use prelude::*;
+use fs::*;
+use mm::*;
+use arch::mm::i386::*;
+use arch::sys::vdso::{VDSO_BASE, VDSO_DATA};
+use net::socket::FILE_SOCKET;
+use sys::process::*;
+use sys::ptrace::*;
+use sys::thread::*;
+use sys::syscall::file::FileDescriptor;
+
+use core::fmt::Write;
+
+/** a file inside the per-process directories */
+pub struct PidFile {
+    pub name: &'static str,
+    pub mode: mode_t,
+
+    /** contents of the file or target of the link, None for directories */
+    pub read: Option<unsafe fn(proc: *mut Process) -> Result<Vec<u8>, Error>>,
+}
+
+pub static PID_FILES: [PidFile; 8] = [
+    PidFile { name: "stat",    mode: S_IFREG | 0o444, read: Some(pid_stat) },
+    PidFile { name: "status",  mode: S_IFREG | 0o444, read: Some(pid_status) },
+    PidFile { name: "cmdline", mode: S_IFREG | 0o444, read: Some(pid_cmdline) },
+    PidFile { name: "environ", mode: S_IFREG | 0o400, read: Some(pid_environ) },
+    PidFile { name: "maps",    mode: S_IFREG | 0o444, read: Some(pid_maps) },
+    PidFile { name: "cwd",     mode: S_IFLNK | 0o777, read: Some(pid_cwd) },
+    PidFile { name: "exe",     mode: S_IFLNK | 0o777, read: Some(pid_exe) },
+    PidFile { name: "fd",      mode: S_IFDIR | 0o500, read: None },
+];
+
+/** the executable name without its directory */
+unsafe fn pid_comm(proc: *mut Process) -> &'static str {
+    if (*proc).name.is_null() {
+        return "";
+    }
+
+    let name = cstr((*proc).name);
+    name.rsplit('/').next().unwrap_or(name)
+}
+
+/** one letter state of the process, as in linux */
+unsafe fn pid_state(proc: *mut Process) -> char {
+    if (*proc).running == 0 {
+        return 'Z';
+    }
+
+    if (*proc).ptrace & PT_STOPPED != 0 {
+        return 'T';
+    }
+
+    let states = (*proc).threads.iter()
+        .map(|qnode| (*(*qnode).value).state)
+        .collect::<Vec<ThreadState>>();
+
+    if states.iter().any(|&state| state == ThreadState::RUNNABLE) {
+        'R'
+    } else if states.iter().any(|&state| state == ThreadState::USLEEP) {
+        'D'
+    } else {
+        'S'
+    }
+}
+
+fn pid_state_name(state: char) -> &'static str {
+    match state {
+        'R' => "running",
+        'S' => "sleeping",
+        'D' => "disk sleep",
+        'T' => "stopped",
+        'Z' => "zombie",
+        _   => "unknown",
+    }
+}
+
+unsafe fn pid_ppid(proc: *mut Process) -> pid_t {
+    if (*proc).parent.is_null() { 0 } else { (*(*proc).parent).pid }
+}
+
+/** virtual size and resident pages of the address space, in bytes */
+unsafe fn pid_mem(proc: *mut Process) -> (usize, usize) {
+    if (*proc).running == 0 {
+        return (0, 0);
+    }
+
+    let pmap = (*proc).vm_space.pmap;
+    let mut size = 0;
+    let mut rss = 0;
+
+    for qnode in (*proc).vm_space.vm_entries.iter() {
+        let vm_entry = (*qnode).value;
+        size += (*vm_entry).size;
+
+        let mut va = (*vm_entry).base;
+
+        while va < (*vm_entry).base + (*vm_entry).size {
+            if arch_page_get_mapping(pmap, va) != 0 {
+                rss += PAGE_SIZE;
+            }
+
+            va += PAGE_SIZE;
+        }
+    }
+
+    (size, rss)
+}
+
+unsafe fn pid_stat(proc: *mut Process) -> Result<Vec<u8>, Error> {
+    let (pgid, sid) = if (*proc).pgrp.is_null() {
+        (0, 0)
+    } else {
+        let pgrp = (*proc).pgrp;
+        let sid = if (*pgrp).session.is_null() { 0 } else { (*(*pgrp).session).sid };
+        ((*pgrp).pgid, sid)
+    };
+
+    let (vsize, rss) = pid_mem(proc);
+    let mut s = String::new();
+
+    /* pid comm state ppid pgrp session tty_nr tpgid flags minflt cminflt
+     * majflt cmajflt utime stime cutime cstime priority nice num_threads
+     * itrealvalue starttime vsize rss, times are not accounted yet */
+    let _ = write!(s, "{} ({}) {} {} {} {} 0 -1 0 0 0 0 0 0 0 0 0 20 0 {} 0 0 {} {}\n",
+        (*proc).pid, pid_comm(proc), pid_state(proc), pid_ppid(proc), pgid, sid,
+        (*proc).threads.count(), vsize, rss / PAGE_SIZE);
+
+    Ok(s.into_bytes())
+}
+
+unsafe fn pid_status(proc: *mut Process) -> Result<Vec<u8>, Error> {
+    let state = pid_state(proc);
+    let tracer = if (*proc).tracer.is_null() { 0 } else { (*(*proc).tracer).pid };
+    let (vsize, rss) = pid_mem(proc);
+    let mut s = String::new();
+
+    let _ = write!(s, "Name:\t{}\n", pid_comm(proc));
+    let _ = write!(s, "State:\t{} ({})\n", state, pid_state_name(state));
+    let _ = write!(s, "Tgid:\t{}\n", (*proc).pid);
+    let _ = write!(s, "Pid:\t{}\n", (*proc).pid);
+    let _ = write!(s, "PPid:\t{}\n", pid_ppid(proc));
+    let _ = write!(s, "TracerPid:\t{}\n", tracer);
+    let _ = write!(s, "Uid:\t{0}\t{0}\t{0}\t{0}\n", (*proc).uid);
+    let _ = write!(s, "Gid:\t{0}\t{0}\t{0}\t{0}\n", (*proc).gid);
+    let _ = write!(s, "Umask:\t{:04o}\n", (*proc).mask);
+    let _ = write!(s, "Threads:\t{}\n", (*proc).threads.count());
+    let _ = write!(s, "VmSize:\t{:8} kB\n", vsize / 1024);
+    let _ = write!(s, "VmRSS:\t{:8} kB\n", rss / 1024);
+
+    Ok(s.into_bytes())
+}
+
+/** copy the strings between `start` and `end` out of the process memory */
+unsafe fn pid_strings(proc: *mut Process, start: usize, end: usize) -> Result<Vec<u8>, Error> {
+    /* zombies have no memory left, kernel processes never had any */
+    if (*proc).running == 0 || start == 0 || end <= start {
+        return Ok(Vec::new());
+    }
+
+    let mut buf = vec![0u8; end - start];
+    ptrace_access(proc, start, buf.as_mut_ptr(), buf.len(), false)?;
+
+    Ok(buf)
+}
+
+unsafe fn pid_cmdline(proc: *mut Process) -> Result<Vec<u8>, Error> {
+    pid_strings(proc, (*proc).args.arg_start, (*proc).args.arg_end)
+}
+
+unsafe fn pid_environ(proc: *mut Process) -> Result<Vec<u8>, Error> {
+    pid_strings(proc, (*proc).args.env_start, (*proc).args.env_end)
+}
+
+/** name shown for a mapping in the maps file */
+unsafe fn pid_map_name(proc: *mut Process, vm_entry: *mut VmEntry) -> Option<String> {
+    if vm_entry == (*proc).heap_vm {
+        return Some("[heap]".to_owned());
+    }
+
+    if vm_entry == (*proc).stack_vm {
+        return Some("[stack]".to_owned());
+    }
+
+    match (*vm_entry).base {
+        VDSO_DATA => return Some("[vvar]".to_owned()),
+        VDSO_BASE => return Some("[vdso]".to_owned()),
+        _ => {},
+    }
+
+    let vm_object = (*vm_entry).vm_object;
+
+    if !vm_object.is_null() && (*vm_object).objtype == VMOBJ_FILE as isize && !(*vm_object).p.is_null() {
+        let node = (*vm_object).p as *mut Node;
+        return (*node).path.clone();
+    }
+
+    None
+}
+
+unsafe fn pid_maps(proc: *mut Process) -> Result<Vec<u8>, Error> {
+    let mut s = String::new();
+
+    if (*proc).running == 0 {
+        return Ok(Vec::new());
+    }
+
+    for qnode in (*proc).vm_space.vm_entries.iter() {
+        let vm_entry = (*qnode).value;
+        let flags = (*vm_entry).flags;
+
+        let _ = write!(s, "{:08x}-{:08x} {}{}{}{} {:08x} 00:00 0",
+            (*vm_entry).base, (*vm_entry).base + (*vm_entry).size,
+            if flags & VM_UR != 0 { 'r' } else { '-' },
+            if flags & VM_UW != 0 { 'w' } else { '-' },
+            if flags & VM_UX != 0 { 'x' } else { '-' },
+            if flags & VM_SHARED != 0 { 's' } else { 'p' },
+            (*vm_entry).off);
+
+        match pid_map_name(proc, vm_entry) {
+            Some(name) => { let _ = write!(s, "          {}\n", name); },
+            None => s.push('\n'),
+        }
+    }
+
+    Ok(s.into_bytes())
+}
+
+unsafe fn pid_cwd(proc: *mut Process) -> Result<Vec<u8>, Error> {
+    Ok((*proc).cwd.clone().into_bytes())
+}
+
+unsafe fn pid_exe(proc: *mut Process) -> Result<Vec<u8>, Error> {
+    if (*proc).name.is_null() {
+        return Err(Error::ENOENT);
+    }
+
+    Ok(cstr((*proc).name).to_owned().into_bytes())
+}
+
+/** open descriptor `fd` of the process, None if the slot is unused */
+pub unsafe fn pid_fd(proc: *mut Process, fd: usize) -> Option<*mut FileDescriptor> {
+    if (*proc).running == 0 {
+        return None;
+    }
+
+    proc_fd_file(proc, fd as isize)
+}
+
+/** numbers of the open descriptors of the process */
+pub unsafe fn pid_fds(proc: *mut Process) -> Vec<usize> {
+    (0..(*proc).fds_count).filter(|&fd| pid_fd(proc, fd).is_some()).collect()
+}
+
+/** target of the fd/`fd` link: the path the file was opened by */
+pub unsafe fn pid_fd_target(proc: *mut Process, fd: usize) -> Result<Vec<u8>, Error> {
+    let file = pid_fd(proc, fd).ok_or(Error::ENOENT)?;
+
+    if (*file).flags & FILE_SOCKET != 0 {
+        let socket = (*file).backend.socket;
+        let ino = if socket.is_null() { 0 } else { (*socket).ino };
+
+        return Ok(format!("socket:[{}]", ino).into_bytes());
+    }
+
+    let node = (*file).backend.vnode;
+
+    match (*node).path.as_ref() {
+        Some(path) => Ok(path.clone().into_bytes()),
+        None => Ok(format!("anon_inode:[{}]", (*node).ino).into_bytes()),
+    }
+}
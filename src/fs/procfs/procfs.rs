@@ -0,0 +1,395 @@
+use prelude::*;
+use fs::{self, *};
+use fs::posix::*;
+use fs::procfs::pid::*;
+use mm::*;
+use mm::buddy::buddy_mem_stats;
+use arch::cpu::cpuinfo::arch_cpuinfo;
+use arch::platform::misc::pic::arch_irq_stats;
+use arch::sys::sched::arch_rtime_ns;
+use bits::mount::*;
+use kern::time::*;
+use sys::process::*;
+use sys::sched::*;
+
+use core::fmt::Write;
+use alloc::collections::btree_map::BTreeMap;
+
+/*
+ * procfs exposes kernel state as text files. system wide files live in the
+ * root directory, which is an ordinary pseudofs directory, next to one
+ * directory per process generated on lookup. contents are produced on
+ * every read, so the files always report a size of 0.
+ */
+
+/** what a procfs node stands for, stored as the node data */
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ProcEntry {
+    /** system wide file, indexes GLOBAL_FILES */
+    Global(usize),
+
+    /** directory of a process */
+    PidDir(pid_t),
+
+    /** file inside the directory of a process, indexes PID_FILES */
+    Pid(pid_t, usize),
+
+    /** link to an open file, inside fd/ */
+    Fd(pid_t, usize),
+}
+
+/* procfs root directory */
+pub static mut PROCFS_ROOT: Option<Arc<Node>> = None;
+
+/* per-process nodes, kept around so inode numbers stay valid. pids are
+ * reused, so the set is bounded by the number of pids */
+static mut PROCFS_NODES: BTreeMap<ProcEntry, Arc<Node>> = BTreeMap::new();
+
+static GLOBAL_FILES: [(&str, fn() -> Result<String, Error>); 7] = [
+    ("meminfo",     procfs_meminfo),
+    ("uptime",      procfs_uptime),
+    ("mounts",      procfs_mounts),
+    ("filesystems", procfs_filesystems),
+    ("cpuinfo",     procfs_cpuinfo),
+    ("interrupts",  procfs_interrupts),
+    ("kmalloc",     procfs_kmalloc),
+];
+
+fn procfs_meminfo() -> Result<String, Error> {
+    let (total, used) = unsafe { buddy_mem_stats() };
+    let (heap, objects) = unsafe { kvmem_stats() };
+    let mut s = String::new();
+
+    let _ = write!(s, "MemTotal:\t{:8} kB\n", total / 1024);
+    let _ = write!(s, "MemFree:\t{:8} kB\n", (total - used) / 1024);
+    let _ = write!(s, "MemUsed:\t{:8} kB\n", used / 1024);
+    let _ = write!(s, "KernelHeap:\t{:8} kB\n", heap / 1024);
+    let _ = write!(s, "KernelObjects:\t{:8}\n", objects);
+
+    Ok(s)
+}
+
+fn procfs_uptime() -> Result<String, Error> {
+    let ns = unsafe { arch_rtime_ns() };
+
+    /* idle time is not accounted */
+    Ok(format!("{}.{:02} 0.00\n", ns / 1000000000, (ns % 1000000000) / 10000000))
+}
+
+fn procfs_mounts() -> Result<String, Error> {
+    let mut s = String::new();
+
+    for mount in fs::mountpoints().iter().rev() {
+        let name = mount.root.fs.as_ref().map(|fs| fs.name).unwrap_or("none");
+
+        let mut opts = String::from(if mount.flags & MS_RDONLY != 0 { "ro" } else { "rw" });
+
+        for &(flag, opt) in [(MS_NOSUID, ",nosuid"), (MS_NODEV, ",nodev"), (MS_NOEXEC, ",noexec")].iter() {
+            if mount.flags & flag != 0 {
+                opts.push_str(opt);
+            }
+        }
+
+        let _ = write!(s, "{0} {1} {0} {2} 0 0\n", name, mount.path, opts);
+    }
+
+    Ok(s)
+}
+
+fn procfs_filesystems() -> Result<String, Error> {
+    let mut s = String::new();
+
+    unsafe {
+        for fs in REGISTERED_FS.iter() {
+            let _ = write!(s, "{}\t{}\n", if fs.nodev != 0 { "nodev" } else { "" }, fs.name);
+        }
+    }
+
+    Ok(s)
+}
+
+fn procfs_cpuinfo() -> Result<String, Error> {
+    Ok(unsafe { arch_cpuinfo() })
+}
+
+fn procfs_interrupts() -> Result<String, Error> {
+    let mut s = String::new();
+
+    s.push_str("           CPU0\n");
+
+    for (irq, &(count, handled)) in unsafe { arch_irq_stats() }.iter().enumerate() {
+        if count != 0 || handled {
+            let _ = write!(s, "{:3}: {:10}   XT-PIC\n", irq, count);
+        }
+    }
+
+    Ok(s)
+}
+
+fn procfs_kmalloc() -> Result<String, Error> {
+    let mut s = String::new();
+
+    let _ = write!(s, "{:<20} {:>8} {:>10} {}\n", "type", "objects", "bytes", "description");
+
+    unsafe {
+        for qnode in MALLOC_TYPES.iter() {
+            let m = (*qnode).value;
+            let _ = write!(s, "{:<20} {:>8} {:>10} {}\n", cstr((*m).name), (*m).nr, (*m).total, cstr((*m).desc));
+        }
+    }
+
+    Ok(s)
+}
+
+fn procfs_root() -> &'static Node {
+    unsafe { &**PROCFS_ROOT.as_ref().unwrap() }
+}
+
+fn is_root(node: &Node) -> bool {
+    node as *const Node == procfs_root() as *const Node
+}
+
+fn entry(node: &Node) -> Option<ProcEntry> {
+    if is_root(node) {
+        None
+    } else {
+        node.data::<ProcEntry>().map(|entry| *entry)
+    }
+}
+
+unsafe fn proc_find(pid: pid_t) -> Result<*mut Process, Error> {
+    let proc = proc_pid_find(pid);
+
+    if proc.is_null() {
+        Err(Error::ESRCH)
+    } else {
+        Ok(proc)
+    }
+}
+
+/** look up or create the node for a per-process `entry`, owned by the process */
+unsafe fn proc_node(entry: ProcEntry, proc: *mut Process, name: &str) -> Result<DirectoryEntry, Error> {
+    let mode = match entry {
+        ProcEntry::Global(_)  => return Err(Error::EINVAL),
+        ProcEntry::PidDir(_)  => S_IFDIR | 0o555,
+        ProcEntry::Pid(_, i)  => PID_FILES[i].mode,
+        ProcEntry::Fd(_, _)   => S_IFLNK | 0o700,
+    };
+
+    if !PROCFS_NODES.contains_key(&entry) {
+        let mut node = Node::none();
+        let ts = gettime()?;
+
+        node.set_mode(mode);
+        node.set_nlink(if S_ISDIR!(mode) { 2 } else { 1 });
+        node.set_data(Box::leak(Box::new(entry)));
+
+        node.fs = procfs_root().fs.as_ref().map(|fs| Arc::clone(&fs));
+
+        node.ctime = ts;
+        node.atime = ts;
+        node.mtime = ts;
+
+        PROCFS_NODES.insert(entry, Arc::new(node));
+    }
+
+    let node = &PROCFS_NODES[&entry];
+
+    /* the pid may have been reused by another user */
+    node.set_uid((*proc).uid);
+    node.set_gid((*proc).gid);
+
+    Ok(DirectoryEntry::new(&**node as *const _ as ino_t, name))
+}
+
+fn iget(_superblock: &mut Node, ino: ino_t) -> Result<&'static mut Node, Error> {
+    /* nodes are never released */
+    unsafe { Ok(&mut *(ino as usize as *mut Node)) }
+}
+
+fn iput(_superblock: &mut Node, _node: &mut Node) -> Result<(), Error> {
+    Ok(())
+}
+
+fn read(node: &Node, offset: usize, size: usize, buffer: *mut u8) -> Result<usize, Error> {
+    unsafe {
+        let content = match entry(node) {
+            Some(ProcEntry::Global(i)) => (GLOBAL_FILES[i].1)()?.into_bytes(),
+            Some(ProcEntry::Pid(pid, i)) => match PID_FILES[i].read {
+                Some(f) => f(proc_find(pid)?)?,
+                None => return Err(Error::EISDIR),
+            },
+            Some(ProcEntry::Fd(pid, fd)) => pid_fd_target(proc_find(pid)?, fd)?,
+            _ => return Err(Error::EISDIR),
+        };
+
+        if offset >= content.len() {
+            return Ok(0);
+        }
+
+        let size = core::cmp::min(size, content.len() - offset);
+        memcpy(buffer, content.as_ptr().offset(offset as isize), size);
+
+        Ok(size)
+    }
+}
+
+/** pids of all processes, in order */
+unsafe fn pids() -> Vec<pid_t> {
+    let mut pids = PROCS.iter()
+        .map(|qnode| (*(*qnode).value).pid)
+        .collect::<Vec<pid_t>>();
+
+    pids.sort();
+    pids
+}
+
+fn readdir(dir: &Node, offset: usize) -> Result<(usize, DirectoryEntry), Error> {
+    unsafe {
+        if is_root(dir) {
+            let (n, dirent) = pseudofs::readdir(dir, offset)?;
+
+            if n != 0 {
+                return Ok((n, dirent));
+            }
+
+            /* global files are followed by self and the process directories */
+            return match offset - 2 - GLOBAL_FILES.len() {
+                0 => Ok((1, DirectoryEntry::new(0, "self"))),
+                i => match pids().get(i - 1) {
+                    Some(pid) => Ok((1, DirectoryEntry::new(0, &format!("{}", pid)))),
+                    None => Ok((0, DirectoryEntry::none())),
+                },
+            };
+        }
+
+        let names = match entry(dir) {
+            Some(ProcEntry::PidDir(_)) => PID_FILES.iter().map(|file| file.name.to_owned()).collect(),
+            Some(ProcEntry::Pid(pid, _)) => {
+                let proc = proc_find(pid)?;
+                pid_fds(proc).iter().map(|fd| format!("{}", fd)).collect()
+            },
+            _ => Vec::<String>::new(),
+        };
+
+        match offset {
+            0 => Ok((1, DirectoryEntry::new(0, "."))),
+            1 => Ok((1, DirectoryEntry::new(0, ".."))),
+            offset => match names.get(offset - 2) {
+                Some(name) => Ok((1, DirectoryEntry::new(0, name))),
+                None => Ok((0, DirectoryEntry::none())),
+            },
+        }
+    }
+}
+
+fn finddir(dir: &Node, name: &str) -> Result<DirectoryEntry, Error> {
+    unsafe {
+        if is_root(dir) {
+            if let Ok(dirent) = pseudofs::finddir(dir, name) {
+                return Ok(dirent);
+            }
+
+            /* self is a directory rather than a link, lookup only follows
+             * links in the last component */
+            let pid = match name {
+                "self" => (*curproc!()).pid,
+                name => name.parse::<pid_t>().map_err(|_| Error::ENOENT)?,
+            };
+
+            let proc = proc_find(pid).map_err(|_| Error::ENOENT)?;
+            return proc_node(ProcEntry::PidDir(pid), proc, name);
+        }
+
+        match entry(dir) {
+            Some(ProcEntry::PidDir(pid)) => {
+                let proc = proc_find(pid).map_err(|_| Error::ENOENT)?;
+                let idx = PID_FILES.iter().position(|file| file.name == name).ok_or(Error::ENOENT)?;
+
+                proc_node(ProcEntry::Pid(pid, idx), proc, name)
+            },
+            Some(ProcEntry::Pid(pid, _)) => {
+                let proc = proc_find(pid).map_err(|_| Error::ENOENT)?;
+                let fd = name.parse::<usize>().map_err(|_| Error::ENOENT)?;
+
+                if pid_fd(proc, fd).is_none() {
+                    return Err(Error::ENOENT);
+                }
+
+                proc_node(ProcEntry::Fd(pid, fd), proc, name)
+            },
+            _ => Err(Error::ENOTDIR),
+        }
+    }
+}
+
+fn init() -> Result<(), Error> {
+    unsafe {
+        let procfs = Arc::new(Filesystem {
+            name:    "procfs",
+            nodev:   1,
+
+            init:    Some(init),
+            mount:   Some(mount),
+
+            read:    Some(read),
+
+            readdir: Some(readdir),
+            finddir: Some(finddir),
+
+            iget:    Some(iget),
+            iput:    Some(iput),
+
+            fops: FileOps {
+                _open:      Some(posix_file_open),
+                _can_read:  Some(__vfs_can_always),
+                _can_write: Some(__vfs_can_never),
+                _eof:       Some(__vfs_eof_always),
+            },
+
+            ..Filesystem::none()
+        });
+
+        let ts = gettime()?;
+
+        let mut procfs_root = Node::none();
+
+        procfs_root.set_mode(S_IFDIR | 0o555);
+        procfs_root.set_nlink(2);
+
+        procfs_root.fs     = Some(Arc::clone(&procfs));
+        procfs_root.refcnt = 1;
+
+        procfs_root.ctime = ts;
+        procfs_root.atime = ts;
+        procfs_root.mtime = ts;
+
+        PROCFS_ROOT = Some(Arc::new(procfs_root));
+
+        let uio = UserOp::default();
+
+        for (i, &(name, _)) in GLOBAL_FILES.iter().enumerate() {
+            let node = pseudofs::mknod(procfs_root(), name, S_IFREG | 0o444, 0, &uio)?;
+            node.set_data(Box::leak(Box::new(ProcEntry::Global(i))));
+        }
+
+        fs::install(procfs)
+    }
+}
+
+fn mount(_fs: Arc<Filesystem>, dir: &str, flags: isize, data: *mut u8) -> Result<(), Error> {
+    unsafe {
+        if PROCFS_ROOT.is_none() {
+            return Err(Error::EINVAL);
+        }
+
+        fs::bind(dir, Arc::clone(PROCFS_ROOT.as_ref().unwrap()))
+    }
+}
+
+module_define!{
+    "procfs",
+    None,
+    Some(init),
+    None
+}
@@ -9,7 +9,7 @@ use alloc::collections::btree_map::BTreeMap;
 malloc_define!(M_PSEUDOFS_DENT, "pseudofs-dirent\0", "pseudofs directory entry\0");
 malloc_declare!(M_VNODE);
 
-type PseudoDirectory = BTreeMap<String, Arc<Node>>;
+pub type PseudoDirectory = BTreeMap<String, Arc<Node>>;
 
 pub fn mknod(dir: &Node, name: &str, mode: mode_t, dev: dev_t, uio: &UserOp) -> Result<Arc<Node>, Error> {
     unsafe {
@@ -57,11 +57,76 @@ pub fn unlink(node: &Node, name: &str, uio: &UserOp) -> Result<(), Error> {
 
         node.data::<PseudoDirectory>().unwrap()
             .remove(name)
-            .map(|_| Ok(()))
+            .map(|node| Ok(node.set_nlink(node.nlink().saturating_sub(1))))
             .unwrap_or(Err(Error::ENOENT))
     }
 }
 
+pub fn rename(olddir: &Node, oldname: &str, newdir: &Node, newname: &str, uio: &UserOp) -> Result<(), Error> {
+    if oldname == "." || oldname == ".." || newname == "." || newname == ".." {
+        return Err(Error::EINVAL);
+    }
+
+    let node = olddir.data::<PseudoDirectory>()
+        .and_then(|map| map.get(oldname))
+        .map(|node| Arc::clone(node))
+        .ok_or(Error::ENOENT)?;
+
+    if let None = newdir.data::<PseudoDirectory>() {
+        newdir.set_data(Box::leak(Box::new(PseudoDirectory::new())));
+    }
+
+    let newmap = newdir.data::<PseudoDirectory>().unwrap();
+
+    if let Some(target) = newmap.get(newname) {
+        /* both names refer to the same file, nothing to do */
+        if Arc::ptr_eq(target, &node) {
+            return Ok(());
+        }
+
+        match (node.is_directory(), target.is_directory()) {
+            (false, true) => return Err(Error::EISDIR),
+            (true, false) => return Err(Error::ENOTDIR),
+            (true, true) if target.data::<PseudoDirectory>().map(|map| !map.is_empty()).unwrap_or(false) => {
+                return Err(Error::ENOTEMPTY);
+            },
+            _ => {},
+        }
+    }
+
+    olddir.data::<PseudoDirectory>().unwrap().remove(oldname);
+
+    /* replaces the target, if any, in a single step, dropping the directory's
+     * reference to it, filesystems with open files keep one of their own */
+    if let Some(target) = newmap.insert(newname.to_owned(), node) {
+        target.set_nlink(target.nlink().saturating_sub(1));
+    }
+
+    Ok(())
+}
+
+pub fn link(dir: &Node, name: &str, node: &Node, uio: &UserOp) -> Result<(), Error> {
+    if node.is_directory() {
+        return Err(Error::EPERM);
+    }
+
+    if let Ok(_) = finddir(dir, name) {
+        return Err(Error::EEXIST);
+    }
+
+    /* nodes are owned by the directories they appear in */
+    let node = unsafe { node.to_arc() };
+
+    if let None = dir.data::<PseudoDirectory>() {
+        dir.set_data(Box::leak(Box::new(PseudoDirectory::new())));
+    }
+
+    node.set_nlink(node.nlink() + 1);
+    dir.data::<PseudoDirectory>().unwrap().insert(name.to_owned(), node);
+
+    Ok(())
+}
+
 pub fn readdir(dir: &Node, offset: usize) -> Result<(usize, DirectoryEntry), Error> {
     match offset {
         0 => Ok((1, DirectoryEntry::new(0, "."))),
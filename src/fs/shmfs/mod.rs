@@ -0,0 +1,3 @@
+pub mod shmfs;
+
+pub use self::shmfs::*;
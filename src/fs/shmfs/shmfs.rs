@@ -0,0 +1,336 @@
+use prelude::*;
+use fs::{self, *};
+use fs::posix::*;
+use mm::*;
+use arch::mm::i386::*;
+use kern::time::*;
+use bits::dirent::*;
+use bits::fcntl::*;
+use sys::syscall::file::{FileDescriptor, FileBackend};
+
+/*
+ * shmfs backs POSIX shared memory objects (shm_open/shm_unlink). every
+ * file keeps its contents in a zero-filled vm object which also serves as
+ * the vnode object, so read/write and shared mappings see the same pages.
+ * the refcnt of a node counts its open files, the object counts mappings,
+ * an unlinked object lives on until both drop to zero.
+ */
+
+/* shmfs root directory, objects are looked up here by shm_open */
+pub static mut SHMFS_ROOT: Option<Arc<Node>> = None;
+
+/* unlinked objects still open or mapped somewhere */
+static mut SHMFS_ORPHANS: Vec<Arc<Node>> = Vec::new();
+
+/* zero-filled pages like anonymous memory, released with their last mapping */
+static mut SHMFS_PAGER: VmPager = VmPager {
+    page_in:  zero_page_in,
+    page_out: zero_page_out,
+    release:  Some(shmfs_unmapped),
+};
+
+/** free `node` and its pages if it has no name, open file or mapping left */
+unsafe fn shmfs_release(node: *mut Node) {
+    let vm_object = (*node).vm_object;
+
+    if (*node).nlink() != 0 || (*node).refcnt != 0 || (!vm_object.is_null() && (*vm_object).refcnt() != 0) {
+        return;
+    }
+
+    let pos = match SHMFS_ORPHANS.iter().position(|orphan| &**orphan as *const Node == node as *const Node) {
+        Some(pos) => pos,
+        None => return,
+    };
+
+    if !vm_object.is_null() {
+        (*vm_object).destroy();
+        (*node).vm_object = core::ptr::null_mut();
+        Box::from_raw(vm_object);
+    }
+
+    /* drops the last reference to the node */
+    SHMFS_ORPHANS.remove(pos);
+}
+
+unsafe fn shmfs_unmapped(vm_object: *mut VmObject) {
+    shmfs_release((*vm_object).p as *mut Node);
+}
+
+fn read(node: &Node, offset: usize, size: usize, buffer: *mut u8) -> Result<usize, Error> {
+    unsafe {
+        if offset >= node.size() {
+            return Ok(0);
+        }
+
+        let size = if offset + size > node.size() { node.size() - offset } else { size };
+        let mut done = 0;
+
+        while done < size {
+            let off = offset + done;
+            let poff = off & PAGE_MASK;
+            let chunk = if size - done > PAGE_SIZE - poff { PAGE_SIZE - poff } else { size - done };
+
+            let vm_page = (*node.vm_object).page(off as off_t);
+            if vm_page.is_null() {
+                return Err(Error::ENOMEM);
+            }
+
+            pmap_page_read((*vm_page).paddr, poff, chunk, buffer.offset(done as isize));
+            done += chunk;
+        }
+
+        Ok(done)
+    }
+}
+
+fn write(node: &Node, offset: usize, size: usize, buffer: *mut u8) -> Result<usize, Error> {
+    unsafe {
+        let mut done = 0;
+
+        while done < size {
+            let off = offset + done;
+            let poff = off & PAGE_MASK;
+            let chunk = if size - done > PAGE_SIZE - poff { PAGE_SIZE - poff } else { size - done };
+
+            let vm_page = (*node.vm_object).page(off as off_t);
+            if vm_page.is_null() {
+                break;
+            }
+
+            pmap_page_write((*vm_page).paddr, poff, chunk, buffer.offset(done as isize));
+            done += chunk;
+        }
+
+        if done == 0 && size != 0 {
+            return Err(Error::ENOMEM);
+        }
+
+        if offset + done > node.size() {
+            node.set_size(offset + done);
+        }
+
+        Ok(done)
+    }
+}
+
+fn trunc(node: &Node, len: usize) -> Result<usize, Error> {
+    unsafe {
+        if len < node.size() {
+            /* pages may still be mapped somewhere, so clear the
+             * truncated range instead of releasing the pages */
+            let mut off = len;
+
+            while off < node.size() {
+                let poff = off & PAGE_MASK;
+                let vm_page = (*node.vm_object).page(off as off_t);
+
+                if !vm_page.is_null() {
+                    page_zero((*vm_page).paddr, poff, PAGE_SIZE - poff);
+                }
+
+                off += PAGE_SIZE - poff;
+            }
+        }
+
+        node.set_size(len);
+        Ok(0)
+    }
+}
+
+fn mknod(dir: &Node, name: &str, mode: mode_t, dev: dev_t, uio: &UserOp) -> Result<Arc<Node>, Error> {
+    if !S_ISREG!(mode) {
+        return Err(Error::EINVAL);
+    }
+
+    let node = pseudofs::mknod(dir, name, mode, dev, uio)?;
+
+    unsafe {
+        let vnode = &*node as *const _ as *mut Node;
+        let vm_object = Box::leak(VmObject::alloc(VmObject {
+            objtype: VMOBJ_FILE as isize,
+            pager: &mut SHMFS_PAGER,
+            p: vnode as *mut u8,
+
+            ..VmObject::zero()
+        }));
+
+        (*vnode).vm_object = vm_object;
+    }
+
+    Ok(node)
+}
+
+fn unlink(dir: &Node, name: &str, uio: &UserOp) -> Result<(), Error> {
+    unsafe {
+        pseudofs::finddir(dir, name)?;
+
+        /* open files and mappings keep using the node after it leaves the directory */
+        let node = Arc::clone(&dir.data::<pseudofs::PseudoDirectory>().unwrap()[name]);
+        let vnode = &*node as *const _ as *mut Node;
+
+        pseudofs::unlink(dir, name, uio)?;
+
+        SHMFS_ORPHANS.push(node);
+        shmfs_release(vnode);
+
+        Ok(())
+    }
+}
+
+fn close(node: &Node) -> Result<usize, Error> {
+    unsafe {
+        let vnode = node as *const _ as *mut Node;
+
+        (*vnode).refcnt = (*vnode).refcnt.saturating_sub(1);
+        shmfs_release(vnode);
+
+        Ok(0)
+    }
+}
+
+unsafe fn map(vm_space: *mut VmSpace, vm_entry: *mut VmEntry) -> isize {
+    /* pages are faulted in from the node object on demand */
+    return 0;
+}
+
+unsafe fn shmfs_file_open(file: *mut FileDescriptor) -> isize {
+    let err = posix_file_open(file);
+
+    if err == 0 {
+        /* dropped again by close */
+        (*(*file).backend.vnode).refcnt += 1;
+    }
+
+    return err;
+}
+
+unsafe fn shmfs_file_can_read(file: *mut FileDescriptor, size: size_t) -> isize {
+    return ((*file).offset as usize + size <= (*(*file).backend.vnode).size()) as isize;
+}
+
+/** validate a shared memory object name ("/name") and strip the leading slash */
+fn shm_name(name: &str) -> Result<&str, Error> {
+    if !name.starts_with('/') || name.len() == 1 || name[1..].contains('/') {
+        return Err(Error::EINVAL);
+    }
+
+    if name.len() > MAXNAMELEN {
+        return Err(Error::ENAMETOOLONG);
+    }
+
+    Ok(&name[1..])
+}
+
+fn shm_root() -> Result<&'static mut Node, Error> {
+    unsafe {
+        match SHMFS_ROOT.as_ref() {
+            Some(root) => Ok(&mut *(&**root as *const _ as *mut Node)),
+            None => Err(Error::ENOSYS),
+        }
+    }
+}
+
+/** look up the shared memory object `name`, creating it if `oflags` allow */
+pub fn lookup(name: &str, oflags: usize, mode: mode_t, uio: &UserOp) -> Result<&'static mut Node, Error> {
+    let name = shm_name(name)?;
+    let root = shm_root()?;
+
+    match root.finddir(name) {
+        Ok(dirent) => {
+            if oflags & O_CREAT != 0 && oflags & O_EXCL != 0 {
+                return Err(Error::EEXIST);
+            }
+
+            fs::iget(root, dirent.d_ino)
+        },
+        Err(Error::ENOENT) if oflags & O_CREAT != 0 => {
+            let node = root.mknod(name, S_IFREG | (mode & !uio.mask & 0o777), 0, uio)?;
+            Ok(unsafe { &mut *(&*node as *const _ as *mut Node) })
+        },
+        Err(err) => Err(err),
+    }
+}
+
+/** remove the name of a shared memory object */
+pub fn remove(name: &str, uio: &UserOp) -> Result<(), Error> {
+    let name = shm_name(name)?;
+    let root = shm_root()?;
+
+    let dirent = root.finddir(name)?;
+    let node = fs::iget(root, dirent.d_ino)?;
+
+    if uio.uid != 0 && uio.uid != node.uid() {
+        return Err(Error::EACCES);
+    }
+
+    root.unlink(name, uio)
+}
+
+fn init() -> Result<(), Error> {
+    unsafe {
+        if let Some(tmpfs) = fs::get_fs_by_name("tmpfs") {
+            /* directories and attributes are handled just like tmpfs */
+            let mut shmfs = (*tmpfs).clone();
+            shmfs.name  = "shmfs";
+            shmfs.init  = Some(init);
+            shmfs.mount = Some(mount);
+
+            shmfs.read   = Some(read);
+            shmfs.write  = Some(write);
+            shmfs.trunc  = Some(trunc);
+            shmfs.mknod  = Some(mknod);
+            shmfs.unlink = Some(unlink);
+            shmfs.close  = Some(close);
+            shmfs.map    = Some(map);
+
+            /* objects are flat, named and backed by pages only */
+            shmfs.link    = None;
+            shmfs.symlink = None;
+
+            shmfs.fops = FileOps {
+                _open:      Some(shmfs_file_open),
+                _can_read:  Some(shmfs_file_can_read),
+                _can_write: Some(__vfs_can_always),
+                _eof:       Some(posix_file_eof),
+            };
+
+            let shmfs = Arc::new(shmfs);
+            let ts = gettime()?;
+
+            let mut shmfs_root = Node::none();
+
+            shmfs_root.set_mode(S_IFDIR | 0o1777);
+            shmfs_root.set_nlink(2);
+
+            shmfs_root.fs     = Some(Arc::clone(&shmfs));
+            shmfs_root.refcnt = 1;
+
+            shmfs_root.ctime = ts;
+            shmfs_root.atime = ts;
+            shmfs_root.mtime = ts;
+
+            SHMFS_ROOT = Some(Arc::new(shmfs_root));
+
+            fs::install(shmfs)
+        } else {
+            Err(Error::EINVAL)
+        }
+    }
+}
+
+fn mount(_fs: Arc<Filesystem>, dir: &str, flags: isize, data: *mut u8) -> Result<(), Error> {
+    unsafe {
+        if SHMFS_ROOT.is_none() {
+            return Err(Error::EINVAL);
+        }
+
+        fs::bind(dir, Arc::clone(SHMFS_ROOT.as_ref().unwrap()))
+    }
+}
+
+module_define!{
+    "shmfs",
+    Some(|| { vec!["tmpfs"] }),
+    Some(init),
+    None
+}
@@ -0,0 +1,174 @@
+use prelude::*;
+use fs::*;
+
+use bits::signalfd::*;
+use sys::process::*;
+use sys::sched::*;
+use sys::signal::*;
+use sys::thread::*;
+use sys::syscall::file::FileDescriptor;
+
+/*
+ * a signalfd reads the signals of its mask pending for the calling thread
+ * as SignalFdSiginfo records instead of having them delivered, which only
+ * works while the signals are blocked. thread-directed signals come first,
+ * then those queued on the process. the signalfds open in a process are
+ * notified when a signal is left pending for it so pollers can look again.
+ */
+
+/** node data of a signalfd */
+pub struct SignalFd {
+    /** signals read through the descriptor */
+    mask: sigset_t,
+
+    /** number reported for a signal, set by the personality which created the descriptor */
+    signo: fn(sig: isize) -> isize,
+}
+
+static mut SIGNALFDFS: Option<Arc<Filesystem>> = None;
+
+/** nodes of all open signalfds, each taken off when its file is closed */
+static mut SIGNALFD_NODES: Vec<*mut Node> = Vec::new();
+
+unsafe fn signalfdfs() -> Arc<Filesystem> {
+    if SIGNALFDFS.is_none() {
+        SIGNALFDFS = Some(Arc::new(Filesystem {
+            name:  "signalfd",
+            nodev: 1,
+
+            read:  Some(read),
+            close: Some(close),
+
+            fops: FileOps {
+                _open:      None,
+                _can_read:  Some(signalfd_can_read),
+                _can_write: Some(__vfs_can_never),
+                _eof:       Some(__vfs_eof_never),
+            },
+
+            ..Filesystem::none()
+        }));
+    }
+
+    Arc::clone(SIGNALFDFS.as_ref().unwrap())
+}
+
+/** is any signal of `mask` pending for the current thread? */
+unsafe fn signalfd_pending(mask: sigset_t) -> bool {
+    let thread = curthread!();
+
+    if (*thread).sig_pending & ((mask as u32) << 1) != 0 {
+        return true;
+    }
+
+    match (*(*thread).owner).sig_queue.as_ref() {
+        None => false,
+        Some(queue) => queue.iter().any(|qnode| mask & sig_bit(qnode.value) != 0),
+    }
+}
+
+/** take a signal of `mask` pending for the current thread */
+unsafe fn signalfd_take(mask: sigset_t) -> Option<isize> {
+    let thread = curthread!();
+    let pending = (*thread).sig_pending & ((mask as u32) << 1);
+
+    if pending != 0 {
+        let sig = pending.trailing_zeros();
+        (*thread).sig_pending &= !(1 << sig);
+
+        return Some(sig as isize);
+    }
+
+    signal_dequeue((*thread).owner, mask)
+}
+
+fn read(node: &Node, _offset: usize, size: usize, buffer: *mut u8) -> Result<usize, Error> {
+    let sfd = node.data::<SignalFd>().ok_or(Error::EINVAL)?;
+    let record = core::mem::size_of::<SignalFdSiginfo>();
+
+    if size < record {
+        return Err(Error::EINVAL);
+    }
+
+    let mut done = 0;
+
+    unsafe {
+        while done + record <= size {
+            let sig = match signalfd_take(sfd.mask) {
+                None => break,
+                Some(sig) => sig,
+            };
+
+            let mut info: SignalFdSiginfo = core::mem::zeroed();
+            info.ssi_signo = (sfd.signo)(sig) as u32;
+
+            (buffer.offset(done as isize) as *mut SignalFdSiginfo).write_unaligned(info);
+            done += record;
+        }
+    }
+
+    /* nothing pending would block */
+    Ok(done)
+}
+
+fn close(node: &Node) -> Result<usize, Error> {
+    unsafe {
+        let ptr = node as *const Node as *mut Node;
+        SIGNALFD_NODES.retain(|&other| other != ptr);
+
+        anon_free::<SignalFd>(node);
+    }
+
+    Ok(0)
+}
+
+unsafe fn signalfd_can_read(file: *mut FileDescriptor, _size: usize) -> isize {
+    match anon_data::<SignalFd>((*file).backend.vnode, "signalfd") {
+        None => -EINVAL,
+        Some(sfd) => signalfd_pending(sfd.mask) as isize,
+    }
+}
+
+/** tell the signalfds open in `proc` a signal was left pending for it */
+pub unsafe fn signalfd_notify(proc: *mut Process) {
+    for &node in SIGNALFD_NODES.iter() {
+        let open = (0..(*proc).fds_count as isize)
+            .filter_map(|fd| proc_fd_file(proc, fd))
+            .any(|file| (*file).backend.vnode == node);
+
+        if open {
+            anon_wakeup(node);
+        }
+    }
+}
+
+/**
+ * create a signalfd for the signals in `mask`, `signo` translates signal
+ * numbers for the records. returns the open file to install
+ */
+pub unsafe fn signalfd_open(mask: sigset_t, flags: usize, signo: fn(isize) -> isize) -> Result<FileDescriptor, Error> {
+    if flags & !(SFD_NONBLOCK | SFD_CLOEXEC) != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let sfd = Box::leak(Box::new(SignalFd {
+        mask:  mask & !SIG_UNBLOCKABLE,
+        signo: signo,
+    }));
+
+    let file = anon_open(signalfdfs(), sfd, flags)?;
+    SIGNALFD_NODES.push(file.backend.vnode);
+
+    Ok(file)
+}
+
+/** replace the mask of the signalfd open as `file` */
+pub unsafe fn signalfd_set_mask(file: *mut FileDescriptor, mask: sigset_t) -> Result<(), Error> {
+    let sfd = anon_data::<SignalFd>((*file).backend.vnode, "signalfd").ok_or(Error::EINVAL)?;
+    sfd.mask = mask & !SIG_UNBLOCKABLE;
+
+    /* signals of the new mask may be pending already */
+    anon_wakeup((*file).backend.vnode);
+
+    Ok(())
+}
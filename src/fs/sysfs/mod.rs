@@ -0,0 +1,3 @@
+pub mod sysfs;
+
+pub use self::sysfs::*;
@@ -0,0 +1,280 @@
+use prelude::*;
+use fs::{self, *};
+use fs::posix::*;
+use fs::pseudofs::PseudoDirectory;
+use dev::dev::*;
+use kern::module;
+use kern::time::*;
+use mm::uaccess::PATH_MAX;
+use sys::coredump::CORE_PATTERN;
+use sys::ipc::msg::{MSG_LIMIT, MSG_MAX, MSG_MNB};
+
+/*
+ * sysfs mirrors kernel objects as directories of small attribute files:
+ * devices under devices/, built-in modules under module/, filesystems
+ * under fs/ and tunables under kernel/. the tree is made of pseudofs nodes
+ * and grows as objects register, which usually happens before sysfs itself
+ * is initialized, so the root is created by whoever needs it first.
+ */
+
+/** an attribute file, stored as the node data of regular sysfs files */
+pub struct SysAttr {
+    /** current value, newline terminated */
+    pub show: Box<dyn Fn() -> String>,
+
+    /** parse and apply a new value, None for read-only attributes */
+    pub store: Option<Box<dyn Fn(&str) -> Result<(), Error>>>,
+}
+
+/* sysfs root directory (usually mounted on '/sys') */
+pub static mut SYSFS_ROOT: Option<Arc<Node>> = None;
+
+fn read(node: &Node, offset: usize, size: usize, buffer: *mut u8) -> Result<usize, Error> {
+    let attr = node.data::<SysAttr>().ok_or(Error::EINVAL)?;
+    let value = (attr.show)();
+
+    if offset >= value.len() {
+        return Ok(0);
+    }
+
+    let size = core::cmp::min(size, value.len() - offset);
+    unsafe { memcpy(buffer, value.as_ptr().offset(offset as isize), size); }
+
+    Ok(size)
+}
+
+fn write(node: &Node, _offset: usize, size: usize, buffer: *mut u8) -> Result<usize, Error> {
+    let attr = node.data::<SysAttr>().ok_or(Error::EINVAL)?;
+    let store = attr.store.as_ref().ok_or(Error::EACCES)?;
+
+    /* every write replaces the whole value */
+    let value = unsafe { core::slice::from_raw_parts(buffer, size) };
+    let value = core::str::from_utf8(value).map_err(|_| Error::EINVAL)?;
+
+    store(value.trim_end_matches('\n'))?;
+
+    Ok(size)
+}
+
+fn trunc(_node: &Node, _len: usize) -> Result<usize, Error> {
+    /* opening with O_TRUNC before writing a new value */
+    Ok(0)
+}
+
+fn iget(_superblock: &mut Node, ino: ino_t) -> Result<&'static mut Node, Error> {
+    /* nodes are never released */
+    unsafe { Ok(&mut *(ino as usize as *mut Node)) }
+}
+
+fn iput(_superblock: &mut Node, _node: &mut Node) -> Result<(), Error> {
+    Ok(())
+}
+
+fn sysfs_root() -> Arc<Node> {
+    unsafe {
+        if SYSFS_ROOT.is_none() {
+            let sysfs = Arc::new(Filesystem {
+                name:    "sysfs",
+                nodev:   1,
+
+                init:    Some(init),
+                mount:   Some(mount),
+
+                read:    Some(read),
+                write:   Some(write),
+                trunc:   Some(trunc),
+
+                readdir: Some(pseudofs::readdir),
+                finddir: Some(pseudofs::finddir),
+
+                iget:    Some(iget),
+                iput:    Some(iput),
+
+                fops: FileOps {
+                    _open:      Some(posix_file_open),
+                    _can_read:  Some(__vfs_can_always),
+                    _can_write: Some(__vfs_can_always),
+                    _eof:       Some(__vfs_eof_always),
+                },
+
+                ..Filesystem::none()
+            });
+
+            let ts = gettime().unwrap_or(TimeSpec::none());
+
+            let mut sysfs_root = Node::none();
+
+            sysfs_root.set_mode(S_IFDIR | 0o755);
+            sysfs_root.set_nlink(2);
+
+            sysfs_root.fs     = Some(sysfs);
+            sysfs_root.refcnt = 1;
+
+            sysfs_root.ctime = ts;
+            sysfs_root.atime = ts;
+            sysfs_root.mtime = ts;
+
+            SYSFS_ROOT = Some(Arc::new(sysfs_root));
+        }
+
+        Arc::clone(SYSFS_ROOT.as_ref().unwrap())
+    }
+}
+
+/** look up the directory at `path` relative to the sysfs root, creating it as needed */
+pub fn sysfs_mkdir(path: &str) -> Result<Arc<Node>, Error> {
+    let mut dir = sysfs_root();
+
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        let child = dir.data::<PseudoDirectory>()
+            .and_then(|map| map.get(name))
+            .map(|node| Arc::clone(node));
+
+        dir = match child {
+            Some(node) => node,
+            None => pseudofs::mknod(&dir, name, S_IFDIR | 0o755, 0, &UserOp::default())?,
+        };
+    }
+
+    Ok(dir)
+}
+
+/** add the attribute `name` to the directory at `path`, replacing an older one */
+pub fn sysfs_attr(path: &str, name: &str, show: Box<dyn Fn() -> String>,
+    store: Option<Box<dyn Fn(&str) -> Result<(), Error>>>) -> Result<(), Error> {
+    let dir = sysfs_mkdir(path)?;
+    let mode = if store.is_some() { 0o644 } else { 0o444 };
+
+    let node = match dir.data::<PseudoDirectory>().and_then(|map| map.get(name)) {
+        Some(node) => Arc::clone(node),
+        None => pseudofs::mknod(&dir, name, S_IFREG | mode, 0, &UserOp::default())?,
+    };
+
+    node.set_mode(S_IFREG | mode);
+    node.set_data(Box::leak(Box::new(SysAttr { show, store })));
+
+    Ok(())
+}
+
+/** a read-only attribute with a fixed value */
+pub fn sysfs_value(path: &str, name: &str, value: String) -> Result<(), Error> {
+    sysfs_attr(path, name, Box::new(move || format!("{}\n", value)), None)
+}
+
+/** a writable attribute backed by a kernel variable, only taking values in `min..=max` */
+pub unsafe fn sysfs_tunable(name: &str, var: *mut usize, min: usize, max: usize) -> Result<(), Error> {
+    sysfs_attr("kernel", name,
+        Box::new(move || format!("{}\n", unsafe { *var })),
+        Some(Box::new(move |value| {
+            let value = value.trim().parse::<usize>().map_err(|_| Error::EINVAL)?;
+
+            if value < min || value > max {
+                return Err(Error::EINVAL);
+            }
+
+            unsafe { *var = value; }
+            Ok(())
+        })))
+}
+
+/** add a character or block device as registered with kdev */
+pub unsafe fn sysfs_device_add(devtype: mode_t, major: devid_t, dev: *mut Device) {
+    let class = if devtype == S_IFBLK { "block" } else { "char" };
+
+    let name = if (*dev).name.is_empty() {
+        format!("{}", major)
+    } else {
+        (*dev).name.to_owned()
+    };
+
+    let path = format!("devices/{}/{}", class, name);
+
+    let mut ret = sysfs_value(&path, "name", name.clone())
+        .and_then(|_| sysfs_value(&path, "major", format!("{}", major)))
+        .and_then(|_| sysfs_value(&path, "minor", "0".to_owned()))
+        .and_then(|_| sysfs_value(&path, "dev", format!("{}:0", major)));
+
+    if let (Ok(_), Some(getbs)) = (&ret, (*dev).getbs) {
+        let mut dd = DeviceDescriptor {
+            devtype: devtype,
+            major: major,
+            minor: 0,
+        };
+
+        ret = sysfs_value(&path, "blocksize", format!("{}", getbs(&mut dd)));
+    }
+
+    if let Err(err) = ret {
+        print!("sysfs: failed to add {}: {:?}\n", path, err);
+    }
+}
+
+/** add a filesystem as installed with the vfs */
+pub fn sysfs_fs_add(fs: &Filesystem) {
+    let path = format!("fs/{}", fs.name);
+
+    if let Err(err) = sysfs_value(&path, "nodev", format!("{}", fs.nodev)) {
+        print!("sysfs: failed to add {}: {:?}\n", path, err);
+    }
+}
+
+unsafe fn sysfs_kernel_init() -> Result<(), Error> {
+    sysfs_tunable("msgmax", &mut MSG_MAX, 1, MSG_LIMIT)?;
+    sysfs_tunable("msgmnb", &mut MSG_MNB, 1, MSG_LIMIT)?;
+
+    sysfs_attr("kernel", "core_pattern",
+        Box::new(|| format!("{}\n", unsafe { &CORE_PATTERN })),
+        Some(Box::new(|value| {
+            if value.len() >= PATH_MAX {
+                return Err(Error::ENAMETOOLONG);
+            }
+
+            unsafe { CORE_PATTERN = value.to_owned(); }
+            Ok(())
+        })))
+}
+
+fn sysfs_modules_init() -> Result<(), Error> {
+    for (name, deps, _) in module::modules() {
+        let path = format!("module/{}", name);
+
+        sysfs_value(&path, "deps", deps.join(" "))?;
+
+        /* modules load after sysfs, look the state up on every read */
+        sysfs_attr(&path, "initstate", Box::new(move || {
+            let state = module::modules().into_iter()
+                .find(|module| module.0 == name)
+                .and_then(|module| module.2);
+
+            match state {
+                Some(0) => "live\n".to_owned(),
+                Some(err) => format!("failed {}\n", -err),
+                None => "coming\n".to_owned(),
+            }
+        }), None)?;
+    }
+
+    Ok(())
+}
+
+fn init() -> Result<(), Error> {
+    unsafe {
+        let root = sysfs_root();
+
+        sysfs_modules_init()?;
+        sysfs_kernel_init()?;
+
+        fs::install(Arc::clone(root.fs.as_ref().unwrap()))
+    }
+}
+
+fn mount(_fs: Arc<Filesystem>, dir: &str, flags: isize, data: *mut u8) -> Result<(), Error> {
+    fs::bind(dir, sysfs_root())
+}
+
+module_define!{
+    "sysfs",
+    None,
+    Some(init),
+    None
+}
@@ -0,0 +1,224 @@
+use prelude::*;
+use fs::*;
+
+use arch::sys::sched::arch_rtime_ns;
+use bits::timerfd::*;
+use kern::time::*;
+use sys::timer::*;
+use sys::syscall::file::FileDescriptor;
+
+/*
+ * a timerfd exposes a kernel timer through a descriptor. the timer only
+ * wakes up readers and pollers, expirations are counted from the arming
+ * time and the interval whenever they are asked for, so nothing is lost
+ * while nobody reads. deadlines are kept on the monotonic clock, absolute
+ * CLOCK_REALTIME times are converted when the timer is armed.
+ */
+
+const NSEC_PER_SEC: u64 = 1_000_000_000;
+
+/** node data of a timerfd */
+pub struct TimerFd {
+    /** CLOCK_* the timer was created on */
+    clock: usize,
+
+    /** kernel timer for the next expiry, data is the node */
+    timer: Timer,
+
+    /** next expiry not read yet, in nanoseconds since boot, 0 while disarmed */
+    next: u64,
+
+    /** period in nanoseconds, 0 for timers firing once */
+    interval: u64,
+}
+
+static mut TIMERFDFS: Option<Arc<Filesystem>> = None;
+
+unsafe fn timerfdfs() -> Arc<Filesystem> {
+    if TIMERFDFS.is_none() {
+        TIMERFDFS = Some(Arc::new(Filesystem {
+            name:  "timerfd",
+            nodev: 1,
+
+            read:  Some(read),
+            close: Some(close),
+
+            fops: FileOps {
+                _open:      None,
+                _can_read:  Some(timerfd_can_read),
+                _can_write: Some(__vfs_can_never),
+                _eof:       Some(__vfs_eof_never),
+            },
+
+            ..Filesystem::none()
+        }));
+    }
+
+    Arc::clone(TIMERFDFS.as_ref().unwrap())
+}
+
+fn ts_to_ns(ts: &TimeSpec) -> Result<u64, Error> {
+    if ts.tv_nsec as u64 >= NSEC_PER_SEC {
+        return Err(Error::EINVAL);
+    }
+
+    Ok(ts.tv_sec * NSEC_PER_SEC + ts.tv_nsec as u64)
+}
+
+fn ns_to_ts(ns: u64) -> TimeSpec {
+    TimeSpec {
+        tv_sec:  ns / NSEC_PER_SEC,
+        tv_nsec: (ns % NSEC_PER_SEC) as u32,
+    }
+}
+
+/** expirations of `tfd` not read by `now`, and the expiry following them */
+fn timerfd_expired(tfd: &TimerFd, now: u64) -> (u64, u64) {
+    if tfd.next == 0 || now < tfd.next {
+        return (0, tfd.next);
+    }
+
+    if tfd.interval == 0 {
+        return (1, 0);
+    }
+
+    let count = (now - tfd.next) / tfd.interval + 1;
+
+    (count, tfd.next + count * tfd.interval)
+}
+
+fn read(node: &Node, _offset: usize, size: usize, buffer: *mut u8) -> Result<usize, Error> {
+    let tfd = node.data::<TimerFd>().ok_or(Error::EINVAL)?;
+
+    if size < 8 {
+        return Err(Error::EINVAL);
+    }
+
+    let (count, next) = timerfd_expired(tfd, unsafe { arch_rtime_ns() });
+
+    if count == 0 {
+        /* would block */
+        return Ok(0);
+    }
+
+    tfd.next = next;
+
+    unsafe { (buffer as *mut u64).write_unaligned(count) };
+
+    Ok(8)
+}
+
+fn close(node: &Node) -> Result<usize, Error> {
+    unsafe {
+        /* the timer refers to the node */
+        if let Some(tfd) = node.data::<TimerFd>() {
+            timer_disarm(&mut tfd.timer);
+        }
+
+        anon_free::<TimerFd>(node);
+    }
+
+    Ok(0)
+}
+
+unsafe fn timerfd_can_read(file: *mut FileDescriptor, _size: usize) -> isize {
+    match anon_data::<TimerFd>((*file).backend.vnode, "timerfd") {
+        None => -EINVAL,
+        Some(tfd) => (timerfd_expired(tfd, arch_rtime_ns()).0 > 0) as isize,
+    }
+}
+
+unsafe fn timerfd_fire(timer: *mut Timer) {
+    let node = (*timer).data as *mut Node;
+
+    if let Some(tfd) = anon_data::<TimerFd>(node, "timerfd") {
+        /* periodic timers keep firing until read or disarmed */
+        let (_, next) = timerfd_expired(tfd, arch_rtime_ns());
+
+        if next != 0 {
+            timer_arm(&mut tfd.timer, next);
+        }
+    }
+
+    anon_wakeup(node);
+}
+
+/** create a timerfd on `clock`, returns the open file to install */
+pub unsafe fn timerfd_open(clock: usize, flags: usize) -> Result<FileDescriptor, Error> {
+    if clock != CLOCK_REALTIME && clock != CLOCK_MONOTONIC {
+        return Err(Error::EINVAL);
+    }
+
+    if flags & !(TFD_NONBLOCK | TFD_CLOEXEC) != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let tfd = Box::leak(Box::new(TimerFd {
+        clock:    clock,
+        timer:    Timer::new(timerfd_fire, core::ptr::null_mut()),
+        next:     0,
+        interval: 0,
+    }));
+
+    let file = anon_open(timerfdfs(), tfd as *mut TimerFd, flags)?;
+    tfd.timer.data = file.backend.vnode as *mut u8;
+
+    Ok(file)
+}
+
+/** time left until the next expiry and the interval of the timerfd open as `file` */
+pub unsafe fn timerfd_get(file: *mut FileDescriptor) -> Result<ITimerSpec, Error> {
+    let tfd = anon_data::<TimerFd>((*file).backend.vnode, "timerfd").ok_or(Error::EINVAL)?;
+
+    let now = arch_rtime_ns();
+    let (_, next) = timerfd_expired(tfd, now);
+
+    Ok(ITimerSpec {
+        it_interval: ns_to_ts(tfd.interval),
+        it_value:    ns_to_ts(if next == 0 { 0 } else { next.saturating_sub(now) }),
+    })
+}
+
+/**
+ * arm or disarm the timerfd open as `file` according to `value`, which is
+ * relative to now unless TFD_TIMER_ABSTIME is set. returns the old setting
+ */
+pub unsafe fn timerfd_set(file: *mut FileDescriptor, flags: usize, value: &ITimerSpec) -> Result<ITimerSpec, Error> {
+    let tfd = anon_data::<TimerFd>((*file).backend.vnode, "timerfd").ok_or(Error::EINVAL)?;
+
+    if flags & !TFD_TIMER_ABSTIME != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let expiry   = ts_to_ns(&value.it_value)?;
+    let interval = ts_to_ns(&value.it_interval)?;
+
+    let old = timerfd_get(file)?;
+
+    timer_disarm(&mut tfd.timer);
+
+    /* arming again drops unread expirations */
+    tfd.next = 0;
+    tfd.interval = 0;
+
+    if expiry != 0 {
+        let now = arch_rtime_ns();
+
+        let next = if flags & TFD_TIMER_ABSTIME == 0 {
+            now + expiry
+        } else if tfd.clock == CLOCK_REALTIME {
+            /* times in the past expire right away */
+            let realtime = ts_to_ns(&gettime()?)?;
+            now + expiry.saturating_sub(realtime)
+        } else {
+            expiry
+        };
+
+        tfd.next = core::cmp::max(next, 1);
+        tfd.interval = interval;
+
+        timer_arm(&mut tfd.timer, tfd.next);
+    }
+
+    Ok(old)
+}
@@ -11,6 +11,9 @@ use crate::{malloc_declare};
 
 malloc_declare!(M_VNODE);
 
+/* nodes removed from their directory while still open, freed by the last close */
+static mut TMPFS_ORPHANS: Vec<Arc<Node>> = Vec::new();
+
 fn iget(_superblock: &mut Node, ino: ino_t) -> Result<&'static mut Node, Error> {
     /* node is always present in memory */
     // TODO bounds check?
@@ -25,109 +28,89 @@ fn iput(_superblock: &mut Node, _node: &mut Node) -> Result<(), Error> {
     Ok(())
 }
 
-fn close(node: &Node) -> Result<usize, Error> {
-    /* inode is always present in memory */
-
-    if node.refcnt == 0 && node.nlink() == 0 {
-        /* vnode is no longer referenced */
-        //kfree((*vnode).p);
-        unsafe { pseudofs::close(node); }
+/** free the orphaned `node` once it has no open file left */
+unsafe fn tmpfs_release(node: *mut Node) {
+    if (*node).refcnt != 0 {
+        return;
     }
 
-    return Ok(0);
+    /* drops the last reference to the node */
+    TMPFS_ORPHANS.retain(|orphan| &**orphan as *const Node != node as *const Node);
 }
 
-fn read(node: &Node, offset: usize, size: usize, buffer: *mut u8) -> Result<usize, Error> {
-    unsafe {
-        //printk("tmpfs_read(vnode=%p, offset=%d, size=%d, buf=%p)\n", node, offset, size, buf);
-
-        if node.size() == 0 {
-            return Ok(0);
-        }
+/** keep `node`, just taken out of a directory, alive while it is open */
+unsafe fn tmpfs_orphan(node: Arc<Node>) {
+    /* files linked elsewhere are still owned by those directories */
+    if !node.is_directory() && node.nlink() != 0 {
+        return;
+    }
 
-        let r = min!(node.size() - offset, size);
-        memcpy(buffer, (node.data::<u8>().unwrap() as *mut u8).offset(offset as isize), r);
+    let vnode = &*node as *const _ as *mut Node;
 
-        return Ok(r);
-    }
+    TMPFS_ORPHANS.push(node);
+    tmpfs_release(vnode);
 }
 
-fn write(node: &Node, offset: usize, size: usize, buffer: *mut u8) -> Result<usize, Error> {
+fn close(node: &Node) -> Result<usize, Error> {
     unsafe {
-        if node.size() == 0 {
-            let sz = offset + size;
-            node.set_data(Buffer::new(sz).leak());
-            node.set_size(sz);
-        }
-
-        if offset + size > node.size() {
-            /* reallocate */
-            let sz = offset + size;
-            let new = Buffer::new(sz).leak();
-
-            memcpy(new, node.data::<u8>().unwrap() as *mut u8, node.size());
-            //kfree((*node).p);
+        let vnode = node as *const _ as *mut Node;
 
-            node.set_data(new);
-            node.set_size(sz);
-        }
-
-        memcpy((node.data::<u8>().unwrap() as *mut u8).offset(offset as isize), buffer, size);
+        (*vnode).refcnt = (*vnode).refcnt.saturating_sub(1);
+        tmpfs_release(vnode);
 
-        return Ok(size);
+        Ok(0)
     }
 }
 
-fn trunc(node: &Node, len: usize) -> Result<usize, Error> {
-    unsafe {
-        if len == node.size() {
-            return Ok(0);
-        }
+fn unlink(dir: &Node, name: &str, uio: &UserOp) -> Result<(), Error> {
+    let node = dir.data::<pseudofs::PseudoDirectory>()
+        .and_then(|map| map.get(name))
+        .map(|node| Arc::clone(node));
 
-        if len == 0 {
-            //kfree((*vnode).p);
-            node.set_size(0);
-            return Ok(0);
-        }
+    pseudofs::unlink(dir, name, uio)?;
 
-        let sz = min!(len, node.size());
-        let buf = Buffer::new(len).leak();
+    if let Some(node) = node {
+        unsafe { tmpfs_orphan(node); }
+    }
 
-        if buf.is_null() {
-            panic!("failed to allocate buffer");
-        }
+    Ok(())
+}
 
-        memcpy(buf, node.data::<u8>().unwrap() as *mut u8, sz);
+fn rename(olddir: &Node, oldname: &str, newdir: &Node, newname: &str, uio: &UserOp) -> Result<(), Error> {
+    /* open files keep using a replaced target */
+    let target = newdir.data::<pseudofs::PseudoDirectory>()
+        .and_then(|map| map.get(newname))
+        .map(|node| Arc::clone(node));
 
-        if len > node.size() {
-            core::ptr::write_bytes(buf.offset(node.size() as isize), 0, len - node.size());
-        }
+    pseudofs::rename(olddir, oldname, newdir, newname, uio)?;
 
-        //kfree((*vnode).p);
-        node.set_data(buf);
-        node.set_size(len);
+    if let Some(target) = target {
+        /* still there when both names referred to the same file */
+        let replaced = newdir.data::<pseudofs::PseudoDirectory>()
+            .and_then(|map| map.get(newname))
+            .map(|node| !Arc::ptr_eq(node, &target))
+            .unwrap_or(true);
 
-        return Ok(0);
+        if replaced {
+            unsafe { tmpfs_orphan(target); }
+        }
     }
-}
 
-fn chmod(node: &Node, mode: mode_t) -> Result<mode_t, Error> {
-    let old_mode = node.mode();
-    node.set_mode((old_mode & !0o777) | mode);
-
-    Ok(old_mode)
+    Ok(())
 }
 
-fn chown(node: &Node, uid: uid_t, gid: gid_t) -> Result<(uid_t, gid_t), Error> {
-    let old = (node.uid(), node.gid());
+/* ================ File Operations ================ */
 
-    node.set_uid(uid);
-    node.set_gid(gid);
+unsafe fn tmpfs_file_open(file: *mut FileDescriptor) -> isize {
+    let err = posix_file_open(file);
 
-    Ok(old)
-}
+    if err == 0 {
+        /* dropped again by close */
+        (*(*file).backend.vnode).refcnt += 1;
+    }
 
-/* ================ File Operations ================ */
+    return err;
+}
 
 unsafe fn tmpfs_file_can_read(file: *mut FileDescriptor, size: size_t) -> isize {
     if (*file).offset as usize + size < (*(*file).backend.vnode).size() {
@@ -167,12 +150,16 @@ fn init() -> Result<(), Error> {
         finddir:  Some(pseudofs::finddir),
 
         mknod:    Some(pseudofs::mknod),
-        unlink:   Some(pseudofs::unlink),
+        unlink:   Some(unlink),
+        rename:   Some(rename),
+        link:     Some(pseudofs::link),
+        symlink:  Some(symlink),
+        readlink: Some(readlink),
         iget:     Some(iget),
         iput:     Some(iput),
         
         fops: FileOps {
-            _open:     Some(posix_file_open),
+            _open:     Some(tmpfs_file_open),
             //_close:    Some(posix_file_close),
 
             _can_read:   Some(tmpfs_file_can_read),
@@ -190,14 +177,9 @@ fn mount(_fs: Arc<Filesystem>, dir: &str, flags: isize, data: *mut u8) -> Result
 
         let mut mode: mode_t = 0o777;
 
-        struct MountData {
-            dev: *mut u8,
-            opt: *mut u8,
-        };
-
         let mdata: *mut MountData = data as *const _ as *mut MountData;
 
-        if !(*mdata).opt.is_null() {
+        if !mdata.is_null() && !(*mdata).opt.is_null() {
             let tokens = tokenize((*mdata).opt, b',');
             let mut token_p = tokens; 
 
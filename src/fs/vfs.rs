@@ -17,7 +17,7 @@ malloc_define!(M_FS_LIST, b"fs-list\0", b"filesystems list\0");
 
 /** list of registered filesystems */
 pub static mut REGISTERED_FS: Vec<Arc<Filesystem>> = Vec::new();
-static mut VFSBIND: Vec<(String, Arc<Node>)> = Vec::new();
+static mut VFSBIND: Vec<Mount> = Vec::new();
 
 #[derive(Debug)]
 pub struct UserOp<'a> {
@@ -61,6 +61,10 @@ pub struct NodeOps {
 
     pub mknod:   Option<fn(dir: &Node, filename: &str, mode: mode_t, dev: dev_t, uio: &UserOp) -> Result<Arc<Node>, Error>>,
     pub unlink:  Option<fn(dir: &Node, filename: &str, uio: &UserOp) -> Result<(), Error>>,
+    pub rename:  Option<fn(olddir: &Node, oldname: &str, newdir: &Node, newname: &str, uio: &UserOp) -> Result<(), Error>>,
+    pub link:    Option<fn(dir: &Node, filename: &str, node: &Node, uio: &UserOp) -> Result<(), Error>>,
+    pub symlink: Option<fn(dir: &Node, filename: &str, target: &str, uio: &UserOp) -> Result<Arc<Node>, Error>>,
+    pub readlink: Option<fn(node: &Node, buffer: *mut u8, size: usize) -> Result<usize, Error>>,
 
     pub iget:    Option<fn(superblock: &Node, ino: ino_t) -> Result<&'static Node, Error>>,
     pub iput:    Option<fn(superblock: &Node, node: &mut Node) -> Result<(), Error>>,
@@ -85,6 +89,10 @@ impl NodeOps {
             finddir: None,
             mknod:   None,
             unlink:  None,
+            rename:  None,
+            link:    None,
+            symlink: None,
+            readlink: None,
             iget:    None,
             iput:    None,
             vsync:   None,
@@ -99,6 +107,29 @@ pub struct Path<'a> {
     pub path: &'a str,
 }
 
+/** an entry of the mount table */
+#[derive(Clone)]
+pub struct Mount {
+    /** directory the filesystem is mounted on */
+    pub path: String,
+
+    /** root node of the mounted tree */
+    pub root: Arc<Node>,
+
+    /** directory node hidden by the mount, null if it could not be looked up */
+    pub covered: *const Node,
+
+    /** MS_* flags applying to everything below `path` */
+    pub flags: usize,
+}
+
+impl Mount {
+    /** whether the resolved path `path` is inside this mount */
+    pub fn contains(&self, path: &str) -> bool {
+        self.path == "/" || path == self.path || (path.starts_with(&self.path) && path[self.path.len()..].starts_with('/'))
+    }
+}
+
 /** filesystem structure */
 #[derive(Clone)]
 pub struct Filesystem {
@@ -121,6 +152,10 @@ pub struct Filesystem {
 
     pub(in fs) mknod:   Option<fn(dir: &Node, filename: &str, mode: mode_t, dev: dev_t, uio: &UserOp) -> Result<Arc<Node>, Error>>,
     pub(in fs) unlink:  Option<fn(dir: &Node, filename: &str, uio: &UserOp) -> Result<(), Error>>,
+    pub(in fs) rename:  Option<fn(olddir: &Node, oldname: &str, newdir: &Node, newname: &str, uio: &UserOp) -> Result<(), Error>>,
+    pub(in fs) link:    Option<fn(dir: &Node, filename: &str, node: &Node, uio: &UserOp) -> Result<(), Error>>,
+    pub(in fs) symlink: Option<fn(dir: &Node, filename: &str, target: &str, uio: &UserOp) -> Result<Arc<Node>, Error>>,
+    pub(in fs) readlink: Option<fn(node: &Node, buffer: *mut u8, size: usize) -> Result<usize, Error>>,
 
     pub iget:    Option<fn(superblock: &mut Node, ino: ino_t) -> Result<&'static mut Node, Error>>,
     pub iput:    Option<fn(superblock: &mut Node, node: &mut Node) -> Result<(), Error>>,
@@ -154,6 +189,10 @@ impl Filesystem {
             finddir: None,
             mknod:   None,
             unlink:  None,
+            rename:  None,
+            link:    None,
+            symlink: None,
+            readlink: None,
             iget:    None,
             iput:    None,
             vsync:   None,
@@ -191,6 +230,13 @@ impl Filesystem {
     }
 
     // Node operations
+    pub fn close(&self, node: &Node) -> Result<usize, Error> {
+        match self.close {
+            Some(f) => f(node),
+            None => Ok(0)
+        }
+    }
+
     pub fn read(&self, node: &Node, offset: usize, size: usize, buffer: *mut u8) -> Result<usize, Error> {
         match self.read {
             Some(f) => f(node, offset, size, buffer),
@@ -253,22 +299,30 @@ pub unsafe fn __vfs_can_never (_f: *mut FileDescriptor, _s: usize) -> isize { 0
 pub unsafe fn __vfs_eof_always(_f: *mut FileDescriptor) -> isize { 1 }
 pub unsafe fn __vfs_eof_never (_f: *mut FileDescriptor) -> isize { 0 }
 
-pub fn realpath(path: &str, uio: &UserOp) -> Result<String, Error> {
-    let fullpath = if path.starts_with('/') {
-        path.to_owned()
+/** root directory of `uio`, the global root if none was set */
+fn uio_root<'a>(uio: &UserOp<'a>) -> &'a str {
+    if uio.root.is_empty() { "/" } else { uio.root }
+}
+
+/** resolve `path` against the resolved directory `base`, ".." never leaves the root of `uio` */
+pub fn realpath_at(base: &str, path: &str, uio: &UserOp) -> Result<String, Error> {
+    let root: Vec<&str> = uio_root(uio).split('/').filter(|s| !s.is_empty()).collect();
+    let base: Vec<&str> = base.split('/').filter(|s| !s.is_empty()).collect();
+
+    /* a directory outside the root is only reachable as the root itself */
+    let mut result = if path.starts_with('/') || !base.starts_with(&root) {
+        root.clone()
     } else {
-        uio.cwd.to_owned() + "/" + path
+        base
     };
 
-    let mut result = Vec::new();
-
-    fullpath
+    path
         .split('/')
         .for_each(|s| {
             match s {
                 "" => {},
                 "." => {},
-                ".." => { result.pop(); },
+                ".." => if result.len() > root.len() { result.pop(); },
                 _ => result.push(s),
             }
         });
@@ -276,12 +330,32 @@ pub fn realpath(path: &str, uio: &UserOp) -> Result<String, Error> {
     Ok("/".to_owned() + &result.join("/"))
 }
 
+/** resolve `path` against the working directory of `uio` */
+pub fn realpath(path: &str, uio: &UserOp) -> Result<String, Error> {
+    realpath_at(uio.cwd, path, uio)
+}
+
+/** the resolved path `path` as seen from the root directory of `uio` */
+pub fn root_path(path: &str, uio: &UserOp) -> String {
+    let root = uio_root(uio);
+
+    if root == "/" {
+        return path.to_owned();
+    }
+
+    match path.strip_prefix(root) {
+        Some(rest) if rest.starts_with('/') => rest.to_owned(),
+        /* the root itself, or outside of it */
+        _ => "/".to_owned(),
+    }
+}
+
 pub fn mountpoint(path: &str) -> Result<Path, Error> {
     unsafe {
-        if let Some((root, node)) = VFSBIND.iter().find(|s| path.starts_with(&s.0)) {
+        if let Some(mount) = VFSBIND.iter().find(|mount| mount.contains(path)) {
             Ok(Path {
-                node: &**node,
-                path: &path[root.len()..],
+                node: &*mount.root,
+                path: &path[mount.path.len()..],
             })
         } else {
             // FIXME
@@ -294,13 +368,38 @@ pub fn bind(path: &str, target: Arc<Node>) -> Result<(), Error> {
     unsafe {
         // TODO check for existence
 
-        VFSBIND.push((path.to_owned(), target));
-        VFSBIND.sort_by(|(path1, _), (path2, _)| path2.cmp(path1));
-        
+        /* remembered so lookups walking the nodes can cross into the mount */
+        let covered = match fs::lookup_resolved(path.to_owned(), &UserOp::default()) {
+            Ok((node, _)) => node as *const Node,
+            Err(_) => core::ptr::null(),
+        };
+
+        /* the newest mount on a directory hides the older ones */
+        VFSBIND.insert(0, Mount {
+            path: path.to_owned(),
+            root: target,
+            covered: covered,
+            flags: 0,
+        });
+
+        VFSBIND.sort_by(|mount1, mount2| mount2.path.cmp(&mount1.path));
+
         Ok(())
     }
 }
 
+/** the mount table, longest path first */
+pub fn mountpoints() -> Vec<Mount> {
+    unsafe {
+        VFSBIND.clone()
+    }
+}
+
+/** the mount table itself, kept sorted by `bind` */
+pub(in fs) unsafe fn mount_table() -> &'static mut Vec<Mount> {
+    &mut VFSBIND
+}
+
 pub unsafe fn vfs_init() {
     //vfs_log(LOG_INFO, "initializing\n");
 }
@@ -308,6 +407,7 @@ pub unsafe fn vfs_init() {
 pub fn install(fs: Arc<Filesystem>) -> Result<(), Error> {
     unsafe {
         print!("vfs: registered filesystem {}\n", fs.name);
+        sysfs::sysfs_fs_add(&fs);
         REGISTERED_FS.push(fs);
         Ok(())
     }
@@ -79,4 +79,5 @@ unsafe fn vnode_page_out(vm_object: *mut VmObject, off: off_t) -> isize {
 pub static mut VNODE_PAGER: VmPager = VmPager {
     page_in:  vnode_page_in,
     page_out: vnode_page_out,
+    release:  None,
 };
@@ -16,6 +16,18 @@ pub struct Module {
     fini: Option<fn() -> Result<(), Error>>,
 }
 
+/* return value of the init function of every loaded module, 0 or -errno */
+static mut MODULE_STATE: BTreeMap<&'static str, isize> = BTreeMap::new();
+
+fn module_init(name: &'static str, module: &Module) {
+    let ret = match module.init.map(|f| f()).unwrap_or(Ok(())) {
+        Ok(()) => 0,
+        Err(err) => err.unwrap(),
+    };
+
+    unsafe { MODULE_STATE.insert(name, ret); }
+}
+
 fn load_with_deps(done: &mut BTreeSet<&'static str>, pending: &mut BTreeMap<&'static str, Module>) {
     if pending.is_empty() {
         return;
@@ -26,7 +38,7 @@ fn load_with_deps(done: &mut BTreeSet<&'static str>, pending: &mut BTreeMap<&'st
     let mut loaded = false;
     
     if deps.is_subset(&done) {
-        module.init.map(|f| f()).unwrap_or(Ok(()));
+        module_init(name, &module);
         done.insert(name);
         loaded = true;
     }
@@ -38,24 +50,48 @@ fn load_with_deps(done: &mut BTreeSet<&'static str>, pending: &mut BTreeMap<&'st
     }
 
     if deps.is_subset(&done) {
-        module.init.map(|f| f()).unwrap_or(Ok(()));
+        module_init(name, &module);
         done.insert(name);
     } else {
         panic!("failed to load module: {}", name);
     }
 }
 
-pub fn init() -> isize {
+/** modules linked into the kernel, from the .module section */
+fn builtin_modules() -> &'static [Module] {
     unsafe {
-        print!("kernel: loading builtin modules\n");
-
-        /* initalize built-in modules */
         let modules_start = &__modules_start as *const _ as usize;
         let modules_end   = &__modules_end   as *const _ as usize;
         let modules_ptr   = &__modules_start as *const _ as *const Module;
 
         let nr = (modules_end - modules_start) / core::mem::size_of::<Module>();
-        let modules = core::slice::from_raw_parts(modules_ptr, nr);
+        core::slice::from_raw_parts(modules_ptr, nr)
+    }
+}
+
+/**
+ * name and dependencies of every built-in module, with the return value of
+ * its init function (0 or -errno), None if it was not loaded yet
+ */
+pub fn modules() -> Vec<(&'static str, Vec<&'static str>, Option<isize>)> {
+    builtin_modules().iter()
+        .map(|module| {
+            let module = *module;
+            let name = module.name;
+            let deps = module.deps.map(|f| f()).unwrap_or(Vec::new());
+
+            (name, deps, unsafe { MODULE_STATE.get(name).copied() })
+        })
+        .collect()
+}
+
+pub fn init() -> isize {
+    unsafe {
+        print!("kernel: loading builtin modules\n");
+
+        /* initalize built-in modules */
+        let modules = builtin_modules();
+        let nr = modules.len();
 
         print!("kernel: found {} modules\n", nr);
 
@@ -147,6 +147,11 @@ pub unsafe fn buddy_recursive_free(zone: usize, order: usize, idx: usize) {
     }
 }
 
+/** total and allocated physical memory in bytes */
+pub unsafe fn buddy_mem_stats() -> (usize, usize) {
+    (k_total_mem, k_used_mem)
+}
+
 /* allocate new buddy
  * @param zone zone index
  * @param _sz chunk size
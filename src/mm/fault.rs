@@ -3,7 +3,6 @@ use prelude::*;
 use arch::mm::i386::*;
 use mm::*;
 use sys::sched::*;
-use sys::signal::*;
 
 /** a structure holding parameters relevant to a page fault */
 struct FaultInfo {
@@ -192,6 +191,10 @@ unsafe fn pf_object(info: *mut FaultInfo) -> isize {
     /* look for page inside the object pages hashmap */
     vm_page = vm_object_page(vm_object, &(*info).off);
 
+    if vm_page.is_null() {
+        return 0;
+    }
+
     if (*vm_entry).flags & VM_UW == 0 {
         /* read only page -- just map */
         mm_page_incref((*vm_page).paddr);
@@ -199,6 +202,13 @@ unsafe fn pf_object(info: *mut FaultInfo) -> isize {
         return 1;
     }
 
+    if (*vm_entry).flags & VM_SHARED != 0 {
+        /* shared page -- map the object page itself so writes are seen by all mappings */
+        mm_page_incref((*vm_page).paddr);
+        mm_page_map(pmap, (*info).addr, (*vm_page).paddr, ((*vm_entry).flags & VM_PERM) as isize);
+        return 1;
+    }
+
     /* read-write page -- promote */
 
     /* allocate a new anon if we don't have one */
@@ -271,20 +281,19 @@ unsafe fn pf_zero(info: *mut FaultInfo) -> isize {
     return 1;
 }
 
-pub unsafe fn mm_page_fault(vaddr: usize, flags: isize) -> () {
+/**
+ * resolve a fault on `vaddr` in `vm_space`, the pmap of the space must be
+ * the current one. returns 0 if the page got mapped, -EFAULT otherwise
+ */
+pub unsafe fn vm_space_fault(vm_space: *mut VmSpace, vaddr: usize, flags: usize) -> isize {
     let addr = page_align!(vaddr);
 
-    let vm_space = &mut (*curproc!()).vm_space;
-    let pmap = (*vm_space).pmap;
-
     /* look for vm_entry that contains the page */
-    let vm_entry = vm_space.find(addr);
+    let vm_entry = (*vm_space).find(addr);
 
-    /* segfault if there is no entry or the permissions are incorrect */
-    if vm_entry.is_none() || check_violation(flags as usize, (*vm_entry.unwrap()).flags) != 0 {
-        //print!("will signal process\n");
-        signal_proc_send(curproc!(), SIGSEGV);
-        return;
+    /* fail if there is no entry or the permissions are incorrect */
+    if vm_entry.is_none() || check_violation(flags, (*vm_entry.unwrap()).flags) != 0 {
+        return -EFAULT;
     }
 
     let vm_entry = vm_entry.unwrap() as *const _ as *mut VmEntry;
@@ -294,7 +303,7 @@ pub unsafe fn mm_page_fault(vaddr: usize, flags: isize) -> () {
 
     /* construct page fault structure */
     let mut info = FaultInfo {
-        flags: flags as usize,
+        flags: flags,
         addr: addr,
         vm_space: vm_space,
         vm_entry: vm_entry,
@@ -302,25 +311,31 @@ pub unsafe fn mm_page_fault(vaddr: usize, flags: isize) -> () {
     };
 
     /* try to handle page present case */
-    if (flags as usize & PF_PRESENT) != 0 && pf_present(&mut info) != 0 {
-        return;
+    if (flags & PF_PRESENT) != 0 && pf_present(&mut info) != 0 {
+        return 0;
     }
 
     /* check the anon layer for the page and handle if present */
     if !(*vm_entry).vm_anon.is_null() && pf_anon(&mut info) != 0 {
-        return;
+        return 0;
     }
 
     /* check the backening object for the page and handle if present */
     if !(*vm_entry).vm_object.is_null() && pf_object(&mut info) != 0 {
-        return;
+        return 0;
     }
 
     /* just zero out the page */
     if pf_zero(&mut info) != 0 {
-        return;
+        return 0;
     }
 
-    signal_proc_send(curproc!(), SIGSEGV);
-    return;
+    return -EFAULT;
+}
+
+/** resolve a fault in the current process, returns 0 or -EFAULT */
+pub unsafe fn mm_page_fault(vaddr: usize, flags: isize) -> isize {
+    let vm_space = &mut (*curproc!()).vm_space;
+
+    vm_space_fault(vm_space, vaddr, flags as usize)
 }
@@ -91,6 +91,11 @@ const MAX_NODE_SIZE: usize   = (1 << 26) - 1;
 
 pub static mut NODES: [ObjectNode; LAST_NODE_INDEX] = [ObjectNode::empty(); LAST_NODE_INDEX];
 
+/** bytes allocated from the kernel heap and the number of objects */
+pub unsafe fn kvmem_stats() -> (usize, usize) {
+    (kvmem_used, kvmem_obj_cnt)
+}
+
 pub unsafe fn kvmem_setup() {
     print!("mm: setting up kernel allocator (nodes={:p}, size={:#x})\n", &NODES, core::mem::size_of_val(&NODES));
 
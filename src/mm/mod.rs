@@ -8,6 +8,7 @@ pub mod vm_aref;
 pub mod vmm;
 pub mod fault;
 pub mod kvmem;
+pub mod uaccess;
 
 pub use self::buddy::*;
 pub use self::mm::*;
@@ -19,3 +20,4 @@ pub use self::vm_aref::*;
 pub use self::vmm::*;
 pub use self::fault::*;
 pub use self::kvmem::*;
+pub use self::uaccess::*;
@@ -0,0 +1,187 @@
+use prelude::*;
+
+use arch::mm::i386::*;
+use arch::mm::uaccess::*;
+use mm::*;
+use sys::binfmt::binfmt::USER_STACK;
+use sys::sched::*;
+
+use crate::{page_align, page_round};
+
+/** longest path accepted from userspace, including the terminating null */
+pub const PATH_MAX: usize = 4096;
+
+/** largest bounce buffer used when moving bulk data to or from userspace */
+pub const UACCESS_CHUNK: usize = 64 * 1024;
+
+/**
+ * check that `[addr, addr + n)` lies in mappings of the current process
+ * that allow `flags` (VM_UR, VM_UW), kernel memory is never user accessible
+ */
+pub unsafe fn user_access_ok(addr: usize, n: usize, flags: usize) -> bool {
+    if n == 0 {
+        return true;
+    }
+
+    let end = match addr.checked_add(n) {
+        Some(end) => end,
+        None => return false,
+    };
+
+    /* a mapping grown past the split must not hand out kernel memory */
+    if end > USER_STACK {
+        return false;
+    }
+
+    let vm_space = &(*curproc!()).vm_space;
+    let mut vaddr = addr;
+
+    while vaddr < end {
+        match vm_space.find(vaddr) {
+            Some(vm_entry) if vm_entry.flags & flags == flags => {
+                vaddr = page_round!(vm_entry.base + vm_entry.size);
+            },
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+/** copy `n` bytes from user memory at `src` to the kernel buffer `dst` */
+pub unsafe fn copy_from_user(dst: *mut u8, src: *const u8, n: usize) -> Result<(), Error> {
+    if !user_access_ok(src as usize, n, VM_UR) {
+        return Err(Error::EFAULT);
+    }
+
+    if arch_copy_user(dst, src, n) != 0 {
+        return Err(Error::EFAULT);
+    }
+
+    Ok(())
+}
+
+/** copy `n` bytes from the kernel buffer `src` to user memory at `dst` */
+pub unsafe fn copy_to_user(dst: *mut u8, src: *const u8, n: usize) -> Result<(), Error> {
+    if !user_access_ok(dst as usize, n, VM_UW) {
+        return Err(Error::EFAULT);
+    }
+
+    /* the kernel ignores write protection, break copy-on-write sharing first */
+    let vm_space = &mut (*curproc!()).vm_space as *mut VmSpace;
+    let pmap = (*vm_space).pmap;
+
+    let end = dst as usize + n;
+    let mut page = page_align!(dst);
+
+    while page < end {
+        if !arch_page_writable(pmap, page) {
+            let present = if arch_page_get_mapping(pmap, page) != 0 { PF_PRESENT } else { 0 };
+
+            if vm_space_fault(vm_space, page, PF_WRITE | present) != 0 {
+                return Err(Error::EFAULT);
+            }
+        }
+
+        page += PAGE_SIZE;
+    }
+
+    if arch_copy_user(dst, src, n) != 0 {
+        return Err(Error::EFAULT);
+    }
+
+    Ok(())
+}
+
+/**
+ * copy a null terminated string of at most `n` bytes, null included, from
+ * user memory at `src` to `dst`. returns the length of the string or
+ * ENAMETOOLONG if it is not terminated within `n` bytes
+ */
+pub unsafe fn strncpy_from_user(dst: *mut u8, src: *const u8, n: usize) -> Result<usize, Error> {
+    let mut len = 0;
+
+    /* the length is unknown, check the string page by page as we go */
+    while len < n {
+        let addr = src as usize + len;
+        let chunk = core::cmp::min(n - len, PAGE_SIZE - (addr & PAGE_MASK));
+
+        if !user_access_ok(addr, chunk, VM_UR) {
+            return Err(Error::EFAULT);
+        }
+
+        let ret = arch_strncpy_user(dst.offset(len as isize), addr as *const u8, chunk);
+
+        if ret < 0 {
+            return Err(Error::EFAULT);
+        }
+
+        if (ret as usize) < chunk {
+            return Ok(len + ret as usize);
+        }
+
+        len += chunk;
+    }
+
+    Err(Error::ENAMETOOLONG)
+}
+
+/** copy a `T` from user memory */
+pub unsafe fn copyin<T>(src: *const T) -> Result<T, Error> {
+    let mut val = core::mem::MaybeUninit::<T>::uninit();
+
+    copy_from_user(val.as_mut_ptr() as *mut u8, src as *const u8, core::mem::size_of::<T>())?;
+
+    Ok(val.assume_init())
+}
+
+/** copy `val` to user memory */
+pub unsafe fn copyout<T>(dst: *mut T, val: &T) -> Result<(), Error> {
+    copy_to_user(dst as *mut u8, val as *const T as *const u8, core::mem::size_of::<T>())
+}
+
+/**
+ * copy a null terminated string of at most `max` bytes from user memory,
+ * the returned buffer keeps the terminating null
+ */
+pub unsafe fn user_cstr(src: *const u8, max: usize) -> Result<Vec<u8>, Error> {
+    if src.is_null() {
+        return Err(Error::EFAULT);
+    }
+
+    let mut buf = Vec::new();
+    let mut len = 0;
+
+    /* grow the buffer a page at a time instead of allocating `max` upfront */
+    while len < max {
+        let addr = src as usize + len;
+        let chunk = core::cmp::min(max - len, PAGE_SIZE - (addr & PAGE_MASK));
+
+        buf.resize(len + chunk, 0);
+
+        match strncpy_from_user(buf.as_mut_ptr().offset(len as isize), addr as *const u8, chunk) {
+            Ok(n) => {
+                buf.truncate(len + n + 1);
+                buf[len + n] = 0;
+                return Ok(buf);
+            },
+            Err(err) if err == Error::ENAMETOOLONG => len += chunk,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Err(Error::ENAMETOOLONG)
+}
+
+/** copy a null terminated string of at most `max` bytes from user memory */
+pub unsafe fn user_string(src: *const u8, max: usize) -> Result<String, Error> {
+    let mut buf = user_cstr(src, max)?;
+    buf.pop();
+
+    String::from_utf8(buf).map_err(|_| Error::EINVAL)
+}
+
+/** copy a path from user memory */
+pub unsafe fn user_path(src: *const u8) -> Result<String, Error> {
+    user_string(src, PATH_MAX)
+}
@@ -61,7 +61,15 @@ impl VmEntry {
             }
 
             if !self.vm_object.is_null() {
-                (*self.vm_object).decref();
+                let vm_object = self.vm_object;
+                (*vm_object).decref();
+
+                /* the owner of the object may be waiting for its last mapping to go */
+                if (*vm_object).refcnt() == 0 && !(*vm_object).pager.is_null() {
+                    if let Some(release) = (*(*vm_object).pager).release {
+                        release(vm_object);
+                    }
+                }
             }
         }
     }
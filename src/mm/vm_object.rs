@@ -1,5 +1,7 @@
 use prelude::*;
 
+use arch::mm::i386::*;
+use mm::*;
 use mm::vmm::*;
 use mm::kvmem::*;
 
@@ -26,6 +28,33 @@ pub struct VmObject {
 }
 
 impl VmObject {
+    pub const fn empty() -> VmObject {
+        VmObject {
+            pages: core::ptr::null_mut(),
+            objtype: 0,
+            refcnt: 0,
+            pager: core::ptr::null_mut(),
+            p: core::ptr::null_mut(),
+        }
+    }
+
+    /** create a new zero-filled object, pages are allocated on first access */
+    pub fn zero() -> VmObject {
+        unsafe {
+            VmObject {
+                pages: Box::leak(HashMap::alloc(HashMap::new(0))),
+                objtype: VMOBJ_ZERO as isize,
+                pager: &mut ZERO_PAGER,
+
+                ..VmObject::empty()
+            }
+        }
+    }
+
+    pub fn alloc(val: VmObject) -> Box<VmObject> {
+        Box::new_tagged(&M_VM_OBJECT, val)
+    }
+
     pub fn incref(&mut self) {
         self.refcnt += 1;
     }
@@ -44,4 +73,87 @@ impl VmObject {
             (*pages).insert(&((*vm_page).off as off_t), vm_page);
         }
     }
+
+    /** return the page at offset ```off```, paging it in if needed */
+    pub fn page(&mut self, off: off_t) -> *mut VmPage {
+        unsafe {
+            let off = page_align!(off) as off_t;
+
+            if let Some(node) = (*self.pages).lookup(&off) {
+                return node.value;
+            }
+
+            if self.pager.is_null() {
+                return core::ptr::null_mut();
+            }
+
+            ((*self.pager).page_in)(self, off)
+        }
+    }
+
+    /** release all pages contained in the object */
+    pub fn destroy(&mut self) {
+        unsafe {
+            if self.pages.is_null() {
+                return;
+            }
+
+            for node in (*self.pages).iter() {
+                let vm_page = node.value;
+
+                if !self.pager.is_null() {
+                    ((*self.pager).page_out)(self, (*vm_page).off);
+                }
+
+                mm_page_dealloc((*vm_page).paddr);
+            }
+
+            let pages = self.pages.replace(HashMap::empty());
+            pages.free();
+
+            Box::from_raw(self.pages);
+            self.pages = core::ptr::null_mut();
+        }
+    }
+}
+
+/* ================ Zero Pager ================ */
+
+#[repr(C, align(4096))]
+struct ZeroPage([u8; PAGE_SIZE]);
+
+static ZERO_FILL: ZeroPage = ZeroPage([0; PAGE_SIZE]);
+
+/** clear `size` bytes at `off` in the physical page `paddr` */
+pub unsafe fn page_zero(paddr: paddr_t, off: usize, size: usize) {
+    pmap_page_write(paddr, off, size, ZERO_FILL.0.as_ptr());
 }
+
+pub unsafe fn zero_page_in(vm_object: *mut VmObject, off: off_t) -> *mut VmPage {
+    let vm_page = mm_page_alloc();
+    if vm_page.is_null() {
+        return core::ptr::null_mut();
+    }
+
+    (*vm_page).vm_object = vm_object;
+    (*vm_page).off = page_align!(off) as off_t;
+    (*vm_page).refcnt = 1;
+
+    page_zero((*vm_page).paddr, 0, PAGE_SIZE);
+
+    (*vm_object).insert(vm_page);
+
+    return vm_page;
+}
+
+pub unsafe fn zero_page_out(vm_object: *mut VmObject, off: off_t) -> isize {
+    /* no backing store */
+    return 0;
+}
+
+/** pager for anonymous objects shared between address spaces */
+pub static mut ZERO_PAGER: VmPager = VmPager {
+    page_in:  zero_page_in,
+    page_out: zero_page_out,
+    release:  None,
+};
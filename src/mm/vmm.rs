@@ -15,22 +15,17 @@ pub unsafe fn vm_map(vm_space: *mut VmSpace, vm_entry: *mut VmEntry) -> isize {
 pub unsafe fn vm_unmap(vm_space: *mut VmSpace, vm_entry: *mut VmEntry) -> () {
     //printk(b"vm_unmap(vm_space=%p, vm_entry=%p)\n\0".as_ptr(), vm_space, vm_entry);
 
-    if ((*vm_entry).flags & VM_SHARED) != 0 {
-        /* TODO */
-    } else {
-        mm_unmap((*vm_space).pmap, (*vm_entry).base, (*vm_entry).size);
-    }
+    /* pages of shared mappings are owned by the backing object,
+     * unmapping only removes them from this address space */
+    mm_unmap((*vm_space).pmap, (*vm_entry).base, (*vm_entry).size);
 }
 
 #[no_mangle]
 pub unsafe fn vm_unmap_full(vm_space: *mut VmSpace, vm_entry: *mut VmEntry) -> () {
     //printk(b"vm_unmap(vm_space=%p, vm_entry=%p)\n\0".as_ptr(), vm_space, vm_entry);
 
-    if ((*vm_entry).flags & VM_SHARED) != 0 {
-        /* TODO */
-    } else {
-        mm_unmap_full((*vm_space).pmap, (*vm_entry).base, (*vm_entry).size);
-    }
+    /* shared pages stay with the backing object -- see vm_unmap */
+    mm_unmap_full((*vm_space).pmap, (*vm_entry).base, (*vm_entry).size);
 }
 
 pub use crate::arch::i386::mm::i386::PhysicalMap;
@@ -74,6 +69,9 @@ pub struct VmPager {
 
     /* page out */
     pub page_out: unsafe fn(vm_object: *mut VmObject, off: off_t) -> isize,
+
+    /* the last mapping of the object went away, optional */
+    pub release: Option<unsafe fn(vm_object: *mut VmObject)>,
 }
 
 /* 
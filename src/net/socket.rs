@@ -24,11 +24,17 @@ pub struct Socket {
     pub p: *mut u8,
 
     pub refcnt: isize,
+
+    /* number naming the socket in procfs */
+    pub ino: ino_t,
+
+    /* hooks of poll and epoll waiters */
+    pub pollers: Queue<*mut PollHook>,
 }
 
 #[repr(C)]
 pub struct SocketOps {
-    pub accept:  Option<fn(socket: *mut FileDescriptor, conn: *mut FileDescriptor, addr: *const SocketAddress, len: *mut socklen_t) -> isize>,
+    pub accept:  Option<fn(socket: *mut FileDescriptor, conn: *mut FileDescriptor, addr: *mut SocketAddress, len: *mut socklen_t) -> isize>,
     pub bind:    Option<fn(socket: *mut FileDescriptor, addr: *const SocketAddress, len: socklen_t) -> isize>,
     pub connect: Option<fn(socket: *mut FileDescriptor, addr: *const SocketAddress, len: socklen_t) -> isize>,
     pub listen:  Option<fn(socket: *mut FileDescriptor, backlog: isize) -> isize>,
@@ -69,29 +75,66 @@ pub const SHUT_RD         : usize = 0x0001;
 pub const SHUT_WR         : usize = 0x0002;
 pub const SHUT_RDWR       : usize = (SHUT_RD|SHUT_WR);
 
+/** next socket number, sockets have no node to take one from */
+static SOCKET_INO: AtomicUsize = AtomicUsize::new(1);
+
 pub unsafe fn socket_create(file: *mut FileDescriptor, domain: isize, _type: isize, protocol: isize) -> isize {
-    /*
-    switch (domain) {
-        case AF_UNIX:
-            return socket_unix_create(file, domain, type, protocol);
+    let err = match domain as usize {
+        //AF_UNIX => socket_unix_create(file, domain, _type, protocol),
+        _ => -EAFNOSUPPORT,
+    };
+
+    if err == 0 {
+        (*(*file).backend.socket).ino = SOCKET_INO.fetch_add(1, SeqCst) as ino_t;
     }
-    */
 
-    return -EAFNOSUPPORT;
+    err
 }
 
-pub unsafe fn socket_accept(file: *mut FileDescriptor, conn: *mut FileDescriptor, addr: *const SocketAddress, len: socklen_t) -> isize {
-    /*
-    if (!(file->flags & FILE_SOCKET))
-        return -ENOTSOCK;
+/** tell poll and epoll waiters on `socket` that its state may have changed,
+ * protocols call it for the peer whenever they queue data or connections to it */
+pub unsafe fn socket_notify(socket: *mut Socket) {
+    if !socket.is_null() {
+        poll_notify_pollers(&mut (*socket).pollers);
+    }
+}
 
-    if (!file->socket->ops || !file->socket->ops->accept)
-        return -EOPNOTSUPP;
+/** operations of the socket open as `file` */
+unsafe fn socket_ops(file: *mut FileDescriptor) -> Result<&'static SocketOps, isize> {
+    if file.is_null() {
+        return Err(-EINVAL);
+    }
 
-    return file->socket->ops->accept(file, conn, addr, len);
-    */
+    if (*file).flags & FILE_SOCKET == 0 {
+        return Err(-ENOTSOCK);
+    }
 
-    return -EINVAL;
+    let socket = (*file).backend.socket;
+
+    if socket.is_null() {
+        return Err(-EINVAL);
+    }
+
+    (*socket).ops.as_ref().ok_or(-EOPNOTSUPP)
+}
+
+/** accept a connection on the socket open as `file` into `conn`, the peer address is stored
+ * in the kernel buffer `addr` of `*len` bytes and `*len` set to its size, unless `addr` is null */
+pub unsafe fn socket_accept(file: *mut FileDescriptor, conn: *mut FileDescriptor, addr: *mut SocketAddress, len: *mut socklen_t) -> isize {
+    let accept = match socket_ops(file).map(|ops| ops.accept) {
+        Ok(Some(accept)) => accept,
+        Ok(None) => return -EOPNOTSUPP,
+        Err(err) => return err,
+    };
+
+    let ret = accept(file, conn, addr, len);
+
+    /* one connection less pending */
+    if ret >= 0 {
+        socket_notify((*file).backend.socket);
+    }
+
+    ret
 }
 
 pub unsafe fn socket_bind(file: *mut FileDescriptor, addr: *const SocketAddress, len: usize) -> isize {
@@ -137,37 +180,37 @@ pub unsafe fn socket_listen(file: *mut FileDescriptor, backlog: isize) -> isize
 }
 
 pub unsafe fn socket_send(file: *mut FileDescriptor, buf: *const u8, len: usize, flags: isize) -> isize {
-    /*
-    if (!file)
-        return -EINVAL;
+    let send = match socket_ops(file).map(|ops| ops.send) {
+        Ok(Some(send)) => send,
+        Ok(None) => return -EOPNOTSUPP,
+        Err(err) => return err,
+    };
 
-    if (!(file->flags & FILE_SOCKET))
-        return -ENOTSOCK;
-
-    if (!(file->socket))
-        return -EINVAL;
-
-    if (!file->socket->ops || !file->socket->ops->send)
-        return -EOPNOTSUPP;
+    let ret = send(file, buf as *mut u8, len, flags);
 
-    return file->socket->ops->send(file, buf, len, flags);
-    */
+    /* buffer space was used up */
+    if ret >= 0 {
+        socket_notify((*file).backend.socket);
+    }
 
-    return -EINVAL;
+    ret
 }
 
 pub unsafe fn socket_recv(file: *mut FileDescriptor, buf: *mut u8, len: usize, flags: isize) -> isize {
-    /*
-    if (!(file->flags & FILE_SOCKET))
-        return -ENOTSOCK;
+    let recv = match socket_ops(file).map(|ops| ops.recv) {
+        Ok(Some(recv)) => recv,
+        Ok(None) => return -EOPNOTSUPP,
+        Err(err) => return err,
+    };
 
-    if (!file->socket->ops || !file->socket->ops->recv)
-        return -EOPNOTSUPP;
+    let ret = recv(file, buf, len, flags);
 
-    return file->socket->ops->recv(file, buf, len, flags);
-    */
+    /* data was consumed */
+    if ret >= 0 {
+        socket_notify((*file).backend.socket);
+    }
 
-    return -EINVAL;
+    ret
 }
 
 pub unsafe fn socket_can_read(file: *mut FileDescriptor, len: usize) -> isize {
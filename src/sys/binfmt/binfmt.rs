@@ -1,15 +1,23 @@
 use prelude::*;
 use fs::{self, *};
+use bits::mount::*;
 
 use sys::process::*;
 use sys::binfmt::elf::*;
 use sys::thread::*;
 use mm::*;
+use arch::sys::sched::arch_rtime_ns;
+use arch::sys::vdso::*;
+use kern::time::gettime;
 
 /** binary format */
 pub struct BinaryFormat {
-    pub check: Option<unsafe fn(vnode: *mut Node) -> isize>,
-    pub load:  Option<unsafe fn(proc: *mut Process, path: *const u8, vnode: *mut Node) -> isize>,
+    pub name:   &'static str,
+    pub check:  Option<unsafe fn(vnode: *mut Node) -> isize>,
+    pub load:   Option<unsafe fn(proc: *mut Process, path: *const u8, vnode: *mut Node) -> isize>,
+
+    /** formats run through an interpreter return a rewritten argv instead of loading an image */
+    pub interp: Option<unsafe fn(vnode: *mut Node, path: *const u8, argp: *const *const u8) -> Result<Vec<*mut u8>, Error>>,
 }
 
 /* XXX */
@@ -17,11 +25,18 @@ pub const USER_STACK      : usize = 0xC0000000;
 pub const USER_STACK_SIZE : usize = 8192 * 1024;
 pub const USER_STACK_BASE : usize = USER_STACK - USER_STACK_SIZE;
 
-const NR_BINFMT: usize = 1;
+/** maximum number of nested interpreters, same as linux */
+pub const BINFMT_MAX_DEPTH: usize = 4;
 
-static BINFMT_LIST: [BinaryFormat; NR_BINFMT] = [
-    BinaryFormat { check: Some(binfmt_elf_check), load: Some(binfmt_elf_load) },
-];
+/** list of registered binary formats */
+pub static mut REGISTERED_BINFMT: Vec<Arc<BinaryFormat>> = Vec::new();
+
+pub fn binfmt_install(binfmt: Arc<BinaryFormat>) -> Result<(), Error> {
+    unsafe {
+        REGISTERED_BINFMT.push(binfmt);
+        Ok(())
+    }
+}
 
 unsafe fn binfmt_fmt_load(proc: *mut Process, path: *const u8, vnode: *mut Node, binfmt: *const BinaryFormat, proc_ref: *mut *mut Process) -> isize {
     let mut err = 0;
@@ -52,6 +67,9 @@ unsafe fn binfmt_fmt_load(proc: *mut Process, path: *const u8, vnode: *mut Node,
     
     (*proc).heap_vm  = heap_vm;
 
+    /* the vdso sits right below the stack */
+    vdso_map(&mut (*proc).vm_space);
+
     /* Create stack vm_entry */
     let stack_vm = Box::leak(VmEntry::alloc(VmEntry::new()));
 
@@ -71,29 +89,107 @@ unsafe fn binfmt_fmt_load(proc: *mut Process, path: *const u8, vnode: *mut Node,
     return 0;
 }
 
-pub unsafe fn binfmt_load(proc: *mut Process, path: *const u8, proc_ref: *mut *mut Process) -> isize {
-    let mut err = 0;
-
+/** find the node at `path` and the first registered format accepting it */
+unsafe fn binfmt_lookup(proc: *mut Process, path: *const u8) -> Result<(Arc<BinaryFormat>, *mut Node), Error> {
     let mut uio: UserOp = UserOp::default();
-    //memset(&uio, 0, core::mem::size_of::(struct uio));
 
     if !proc.is_null() {
         uio = proc_uio!(proc);
     }
 
-    match fs::lookup(&cstr(path), &uio) {
+    let (node, realpath) = fs::lookup(&cstr(path), &uio)?;
+
+    if fs::mount_flags(&realpath) & MS_NOEXEC != 0 {
+        return Err(Error::EACCES);
+    }
+
+    for binfmt in REGISTERED_BINFMT.iter() {
+        if binfmt.check.unwrap()(&mut *node) == 0 {
+            return Ok((binfmt.clone(), &mut *node));
+        }
+    }
+
+    Err(Error::ENOEXEC)
+}
+
+pub unsafe fn binfmt_load(proc: *mut Process, path: *const u8, proc_ref: *mut *mut Process) -> isize {
+    match binfmt_lookup(proc, path) {
         Err(err) => err.unwrap(),
-        Ok((node, _)) => {
-            for i in 0..NR_BINFMT {
-                if BINFMT_LIST[i].check.unwrap()(&mut *node) == 0 {
-                    binfmt_fmt_load(proc, path, &mut *node, &BINFMT_LIST[i], proc_ref);
-                    //vfs_close(vnode);
-                    return 0;
-                }
+        Ok((binfmt, vnode)) => {
+            if binfmt.load.is_none() {
+                return -ENOEXEC;
             }
 
+            let err = binfmt_fmt_load(proc, path, vnode, &*binfmt, proc_ref);
             //vfs_close(vnode);
-            return -ENOEXEC;
+            return err;
         }
     }
 }
+
+/**
+ * get the argument vector to run `path` with if its format goes through
+ * an interpreter, the returned argv is null terminated and its strings are
+ * allocated with strdup. returns None for formats loading an image
+ */
+pub unsafe fn binfmt_interp(proc: *mut Process, path: *const u8, argp: *const *const u8) -> Result<Option<Vec<*mut u8>>, Error> {
+    let (binfmt, vnode) = binfmt_lookup(proc, path)?;
+
+    match binfmt.interp {
+        Some(interp) => interp(vnode, path, argp).map(Some),
+        None => Ok(None),
+    }
+}
+
+/**
+ * fill `buf` with bytes for AT_RANDOM, mixed from the uptime and the
+ * wall clock. good enough to seed stack protectors, not for cryptography
+ */
+pub unsafe fn binfmt_random(buf: *mut u8, size: usize) {
+    let mut x = arch_rtime_ns() ^ ((gettime().map(|ts| ts.tv_sec).unwrap_or(0) as u64) << 32) ^ 0x9e3779b97f4a7c15;
+
+    for i in 0..size {
+        /* xorshift64 */
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+
+        *buf.offset(i as isize) = (x >> 24) as u8;
+    }
+}
+
+/**
+ * build the auxiliary vector of `proc` as (type, value) pairs terminated
+ * by AT_NULL, `random` and `execfn` are user addresses already pushed
+ * on the new stack
+ */
+pub unsafe fn binfmt_auxv(proc: *mut Process, random: usize, execfn: usize) -> Vec<usize> {
+    let aux = &(*proc).aux;
+
+    let mut auxv = Vec::new();
+
+    if aux.phdr != 0 {
+        auxv.extend_from_slice(&[AT_PHDR, aux.phdr, AT_PHENT, aux.phent, AT_PHNUM, aux.phnum]);
+    }
+
+    if let Some(ehdr) = vdso_base() {
+        auxv.extend_from_slice(&[AT_SYSINFO, vdso_vsyscall(), AT_SYSINFO_EHDR, ehdr]);
+    }
+
+    auxv.extend_from_slice(&[
+        AT_PAGESZ, PAGE_SIZE,
+        AT_BASE,   aux.base,
+        AT_FLAGS,  0,
+        AT_ENTRY,  aux.entry,
+        AT_UID,    (*proc).uid as usize,
+        AT_EUID,   (*proc).uid as usize,
+        AT_GID,    (*proc).gid as usize,
+        AT_EGID,   (*proc).gid as usize,
+        AT_SECURE, 0,
+        AT_RANDOM, random,
+        AT_EXECFN, execfn,
+        AT_NULL,   0,
+    ]);
+
+    auxv
+}
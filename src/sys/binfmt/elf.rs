@@ -1,6 +1,8 @@
 use prelude::*;
 use fs::{self, *};
+use bits::mount::*;
 use sys::process::*;
+use sys::binfmt::binfmt::*;
 use mm::*;
 
 
@@ -39,7 +41,9 @@ pub const EI_MAG3     : usize = 3;
 pub const EI_CLASS    : usize = 4;
 pub const EI_DATA     : usize = 5;
 pub const EI_VERSION  : usize = 6;
-pub const EI_PAD      : usize = 7;
+pub const EI_OSABI    : usize = 7;
+pub const EI_ABIVERSION : usize = 8;
+pub const EI_PAD      : usize = 9;
 pub const EI_NIDENT   : usize = 16;
 
 pub const ELFMAG0     : u8 = 0x7f;
@@ -51,6 +55,9 @@ pub const ELFCLASSNONE    : u8 = 0;
 pub const ELFCLASS32      : u8 = 1;
 pub const ELFCLASS64      : u8 = 2;
 
+pub const ELFOSABI_NONE   : u8 = 0;
+pub const ELFOSABI_LINUX  : u8 = 3;
+
 pub const SHN_UNDEF       : usize = 0x0000;
 pub const SHN_LORESERVE   : usize = 0xff00;
 pub const SHN_LOPROC      : usize = 0xff00;
@@ -110,9 +117,36 @@ pub const PT_INTERP   : usize = 3;
 pub const PT_NOTE     : usize = 4;
 pub const PT_SHLIB    : usize = 5;
 pub const PT_PHDR     : usize = 6;
+pub const PT_TLS      : usize = 7;
 pub const PT_LOPROC   : usize = 0x70000000;
 pub const PT_HIPROC   : usize = 0x7fffffff;
 
+/* auxiliary vector types */
+pub const AT_NULL     : usize = 0;
+pub const AT_IGNORE   : usize = 1;
+pub const AT_EXECFD   : usize = 2;
+pub const AT_PHDR     : usize = 3;
+pub const AT_PHENT    : usize = 4;
+pub const AT_PHNUM    : usize = 5;
+pub const AT_PAGESZ   : usize = 6;
+pub const AT_BASE     : usize = 7;
+pub const AT_FLAGS    : usize = 8;
+pub const AT_ENTRY    : usize = 9;
+pub const AT_NOTELF   : usize = 10;
+pub const AT_UID      : usize = 11;
+pub const AT_EUID     : usize = 12;
+pub const AT_GID      : usize = 13;
+pub const AT_EGID     : usize = 14;
+pub const AT_SECURE   : usize = 23;
+pub const AT_RANDOM   : usize = 25;
+pub const AT_EXECFN   : usize = 31;
+pub const AT_SYSINFO  : usize = 32;
+pub const AT_SYSINFO_EHDR : usize = 33;
+
+/* note types and GNU ABI tag operating systems */
+pub const NT_GNU_ABI_TAG    : Elf32Word = 1;
+pub const ELF_NOTE_OS_LINUX : Elf32Word = 0;
+
 pub const PF_X        : usize = 0x1;
 pub const PF_W        : usize = 0x2;
 pub const PF_R        : usize = 0x4;
@@ -202,6 +236,14 @@ pub struct Elf32ProgramHeader {
     pub p_align  : Elf32Word,
 }
 
+/** elf32 note header, followed by the name and descriptor padded to 4 bytes */
+#[repr(C)]
+pub struct Elf32Note {
+    pub n_namesz : Elf32Word,
+    pub n_descsz : Elf32Word,
+    pub n_type   : Elf32Word,
+}
+
 /** elf32 dynamic entry */
 #[repr(C)]
 pub struct Elf32Dynamic {
@@ -209,15 +251,68 @@ pub struct Elf32Dynamic {
     pub d_val : Elf32Addr,
 }
 
-unsafe fn binfmt_elf32_load(proc: *mut Process, vnode: *mut Node) -> isize {
-    let mut err = 0;
+/* load addresses of position independent images */
+pub const ELF_ET_DYN_BASE : usize = 0x40000000;
+pub const ELF_INTERP_BASE : usize = 0x80000000;
 
-    let vm_space = &mut (*proc).vm_space;
-    let hdr: Elf32Header = core::mem::uninitialized();
+/** what the loader learned about an image mapped into a process */
+struct Elf32Image {
+    /** offset added to all addresses of the image */
+    bias: usize,
 
-    match (*vnode).read(0, core::mem::size_of_val(&hdr), &hdr as *const _ as *mut u8) {
+    /** entry point */
+    entry: usize,
+
+    /** address of the program headers in memory */
+    phdr: usize,
+
+    /** number of program headers */
+    phnum: usize,
+
+    /** size of a program header */
+    phent: usize,
+
+    /** end of the highest loaded segment */
+    end: usize,
+
+    /** path of the requested interpreter (PT_INTERP) */
+    interp: Option<String>,
+
+    /** the image carries a linux ABI note */
+    linux_note: bool,
+}
+
+/** does the note segment at `buf` hold a GNU ABI tag for linux */
+unsafe fn binfmt_elf32_linux_note(buf: *const u8, size: usize) -> bool {
+    let hdr_size = core::mem::size_of::<Elf32Note>();
+    let mut off = 0;
+
+    while off + hdr_size <= size {
+        let note = &*(buf.offset(off as isize) as *const Elf32Note);
+
+        let name = off + hdr_size;
+        let desc = name + ((note.n_namesz as usize + 3) & !3);
+        let next = desc + ((note.n_descsz as usize + 3) & !3);
+
+        if next > size {
+            break;
+        }
+
+        if note.n_type == NT_GNU_ABI_TAG && note.n_namesz == 4 && note.n_descsz >= 4
+            && core::slice::from_raw_parts(buf.offset(name as isize), 4) == b"GNU\0" {
+            return *(buf.offset(desc as isize) as *const Elf32Word) == ELF_NOTE_OS_LINUX;
+        }
+
+        off = next;
+    }
+
+    false
+}
+
+unsafe fn binfmt_elf32_header(vnode: *mut Node, hdr: *mut Elf32Header) -> isize {
+    match (*vnode).read(0, core::mem::size_of::<Elf32Header>(), hdr as *mut u8) {
         Ok(val) => {
-            if val != core::mem::size_of_val(&hdr) {
+            if val != core::mem::size_of::<Elf32Header>() {
                 return -EINVAL;
             }
         },
@@ -226,9 +321,30 @@ unsafe fn binfmt_elf32_load(proc: *mut Process, vnode: *mut Node) -> isize {
         }
     }
 
-    let mut proc_heap = 0;
+    let e_type = (*hdr).e_type as usize;
+
+    if (e_type != ET_EXEC && e_type != ET_DYN) || (*hdr).e_machine as usize != EM_386 {
+        return -ENOEXEC;
+    }
+
+    return 0;
+}
+
+/** map the segments of the image in `vnode` into `proc` at `bias` */
+unsafe fn binfmt_elf32_map(proc: *mut Process, vnode: *mut Node, hdr: &Elf32Header, bias: usize, image: &mut Elf32Image) -> isize {
+    let vm_space = &mut (*proc).vm_space;
+
     let mut offset = hdr.e_phoff;
-    
+    let mut phdr_addr = 0;
+
+    image.bias  = bias;
+    image.entry = bias + hdr.e_entry as usize;
+    image.phnum = hdr.e_phnum as usize;
+    image.phent = hdr.e_phentsize as usize;
+    image.end   = 0;
+    image.interp = None;
+    image.linux_note = false;
+
     for i in 0..hdr.e_phnum {
         let mut phdr: Elf32ProgramHeader = core::mem::uninitialized();
         
@@ -243,12 +359,64 @@ unsafe fn binfmt_elf32_load(proc: *mut Process, vnode: *mut Node) -> isize {
             }
         }
 
+        if phdr.p_type as usize == PT_PHDR {
+            phdr_addr = bias + phdr.p_vaddr as usize;
+        }
+
+        if phdr.p_type as usize == PT_INTERP {
+            if phdr.p_filesz == 0 || phdr.p_filesz as usize > PAGE_SIZE {
+                return -ENOEXEC;
+            }
+
+            let buf = Buffer::new(phdr.p_filesz as usize);
+            let path = buf.leak();
+
+            let res = (*vnode).read(phdr.p_offset as usize, phdr.p_filesz as usize, path);
+
+            if let Err(err) = res {
+                kfree(path);
+                return err.unwrap();
+            }
+
+            /* the path is nul terminated inside the segment */
+            *path.offset(phdr.p_filesz as isize - 1) = 0;
+            image.interp = Some(cstr(path).to_owned());
+
+            kfree(path);
+        }
+
+        if phdr.p_type as usize == PT_NOTE && phdr.p_filesz != 0 && phdr.p_filesz as usize <= PAGE_SIZE {
+            let buf = Buffer::new(phdr.p_filesz as usize);
+            let notes = buf.leak();
+
+            if let Ok(size) = (*vnode).read(phdr.p_offset as usize, phdr.p_filesz as usize, notes) {
+                image.linux_note |= binfmt_elf32_linux_note(notes, size);
+            }
+
+            kfree(notes);
+        }
+
+        if phdr.p_type as usize == PT_TLS {
+            /* the image lives inside a PT_LOAD segment, execve copies it for the main thread */
+            (*proc).tls = TlsTemplate {
+                image:  bias + phdr.p_vaddr as usize,
+                filesz: phdr.p_filesz as usize,
+                memsz:  phdr.p_memsz as usize,
+                align:  phdr.p_align as usize,
+            };
+        }
+
         if phdr.p_type as usize == PT_LOAD {
-            let mut base   = phdr.p_vaddr;
+            let mut base   = (bias + phdr.p_vaddr as usize) as u32;
             let mut filesz = phdr.p_filesz;
             let mut memsz  = phdr.p_memsz;
             let mut off    = phdr.p_offset;
 
+            /* program headers are usually part of the first segment */
+            if phdr_addr == 0 && hdr.e_phoff >= phdr.p_offset && hdr.e_phoff < phdr.p_offset + phdr.p_filesz {
+                phdr_addr = base as usize + (hdr.e_phoff - phdr.p_offset) as usize;
+            }
+
             /* make sure vaddr is aligned */
             if base as usize & PAGE_MASK != 0 {
                 memsz  += (base as usize & PAGE_MASK) as u32;
@@ -291,8 +459,8 @@ unsafe fn binfmt_elf32_load(proc: *mut Process, vnode: *mut Node) -> isize {
 
             (*vm_entry.vm_object).incref();
 
-            if base + memsz > proc_heap {
-                proc_heap = base + memsz;
+            if base as usize + memsz as usize > image.end {
+                image.end = base as usize + memsz as usize;
             }
 
             /* handle bss */
@@ -323,9 +491,97 @@ unsafe fn binfmt_elf32_load(proc: *mut Process, vnode: *mut Node) -> isize {
         offset += hdr.e_phentsize as u32;
     }
 
-    (*proc).heap_start = proc_heap as usize;
-    (*proc).heap       = proc_heap as usize;
-    (*proc).entry      = hdr.e_entry as usize;
+    image.phdr = phdr_addr;
+
+    return 0;
+}
+
+unsafe fn binfmt_elf32_load(proc: *mut Process, vnode: *mut Node) -> isize {
+    let mut err = 0;
+
+    let hdr: Elf32Header = core::mem::uninitialized();
+
+    err = binfmt_elf32_header(vnode, &hdr as *const _ as *mut Elf32Header);
+    if err != 0 {
+        return err;
+    }
+
+    (*proc).tls = TlsTemplate::none();
+
+    let mut image: Elf32Image = Elf32Image {
+        bias: 0,
+        entry: 0,
+        phdr: 0,
+        phnum: 0,
+        phent: 0,
+        end: 0,
+        interp: None,
+        linux_note: false,
+    };
+
+    /* position independent executables go to a fixed base */
+    let bias = if hdr.e_type as usize == ET_DYN { ELF_ET_DYN_BASE } else { 0 };
+
+    err = binfmt_elf32_map(proc, vnode, &hdr, bias, &mut image);
+    if err != 0 {
+        return err;
+    }
+
+    /* linux binaries are recognized by their OS/ABI byte or an ABI tag note */
+    (*proc).personality = if hdr.e_ident[EI_OSABI] == ELFOSABI_LINUX || image.linux_note {
+        Personality::Linux
+    } else {
+        Personality::Aquila
+    };
+
+    (*proc).heap_start = image.end;
+    (*proc).heap       = image.end;
+    (*proc).entry      = image.entry;
+
+    (*proc).aux = ExecAux {
+        phdr:  image.phdr,
+        phent: image.phent,
+        phnum: image.phnum,
+        entry: image.entry,
+        base:  0,
+    };
+
+    if let Some(path) = image.interp.take() {
+        /* load the interpreter as a second image, it starts instead of the program */
+        let uio = proc_uio!(proc);
+
+        let interp = match fs::lookup(&path, &uio) {
+            Ok((_, realpath)) if fs::mount_flags(&realpath) & MS_NOEXEC != 0 => return -EACCES,
+            Ok((node, _)) => node as *mut Node,
+            Err(err) => return err.unwrap(),
+        };
+
+        let ihdr: Elf32Header = core::mem::uninitialized();
+
+        err = binfmt_elf32_header(interp, &ihdr as *const _ as *mut Elf32Header);
+        if err != 0 {
+            return err;
+        }
+
+        let ibias = if ihdr.e_type as usize == ET_DYN { ELF_INTERP_BASE } else { 0 };
+        let tls = (*proc).tls;
+
+        let mut iimage: Elf32Image = Elf32Image { interp: None, ..image };
+
+        err = binfmt_elf32_map(proc, interp, &ihdr, ibias, &mut iimage);
+        if err != 0 {
+            return err;
+        }
+
+        /* the interpreter can't ask for another one, and its TLS is its own business */
+        if iimage.interp.is_some() {
+            return -ELIBBAD;
+        }
+
+        (*proc).tls      = tls;
+        (*proc).entry    = iimage.entry;
+        (*proc).aux.base = ibias;
+    }
 
     return err;
 }
@@ -372,3 +628,19 @@ pub unsafe fn binfmt_elf_load(proc: *mut Process, _path: *const u8, vnode: *mut
         }
     }
 }
+
+fn init() -> Result<(), Error> {
+    binfmt_install(Arc::new(BinaryFormat {
+        name:   "elf",
+        check:  Some(binfmt_elf_check),
+        load:   Some(binfmt_elf_load),
+        interp: None,
+    }))
+}
+
+module_define!{
+    "binfmt_elf",
+    None,
+    Some(init),
+    None
+}
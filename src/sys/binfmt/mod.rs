@@ -1,5 +1,7 @@
 pub mod binfmt;
 pub mod elf;
+pub mod script;
 
 pub use self::binfmt::*;
 pub use self::elf::*;
+pub use self::script::*;
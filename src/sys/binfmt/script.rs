@@ -0,0 +1,99 @@
+use prelude::*;
+use fs::*;
+use sys::binfmt::binfmt::*;
+
+/** maximum length of the #! line, same as linux */
+const BINFMT_SCRIPT_BUF: usize = 128;
+
+pub unsafe fn binfmt_script_check(vnode: *mut Node) -> isize {
+    let mut magic = [0u8; 2];
+
+    match (*vnode).read(0, 2, magic.as_mut_ptr()) {
+        Ok(2) if &magic == b"#!" => 0,
+        _ => -ENOEXEC,
+    }
+}
+
+/** duplicate a byte slice as a null terminated kernel string */
+unsafe fn binfmt_script_strdup(s: &[u8]) -> *mut u8 {
+    let mut buf = Vec::with_capacity(s.len() + 1);
+    buf.extend_from_slice(s);
+    buf.push(0);
+
+    strdup(buf.as_ptr())
+}
+
+/**
+ * parse `#!interpreter [arg]` and build the argv to run the script with,
+ * like linux everything after the interpreter is passed as a single argument
+ * and argv[0] is replaced with the interpreter and the script path
+ */
+pub unsafe fn binfmt_script_interp(vnode: *mut Node, path: *const u8, argp: *const *const u8) -> Result<Vec<*mut u8>, Error> {
+    let mut buf = [0u8; BINFMT_SCRIPT_BUF];
+
+    let size = (*vnode).read(0, BINFMT_SCRIPT_BUF, buf.as_mut_ptr())?;
+
+    let line = &buf[2..size];
+    let line = match line.iter().position(|&c| c == b'\n') {
+        Some(end) => &line[..end],
+        /* an overlong line would get the interpreter truncated */
+        None if size == BINFMT_SCRIPT_BUF => return Err(Error::ENOEXEC),
+        None => line,
+    };
+
+    let blank = |c: &u8| *c == b' ' || *c == b'\t' || *c == b'\r';
+
+    let start = line.iter().position(|c| !blank(c)).unwrap_or(line.len());
+    let line = &line[start..];
+
+    let end = line.iter().position(blank).unwrap_or(line.len());
+    let (interp, arg) = line.split_at(end);
+
+    if interp.is_empty() {
+        return Err(Error::ENOEXEC);
+    }
+
+    let start = arg.iter().position(|c| !blank(c)).unwrap_or(arg.len());
+    let end = arg.iter().rposition(|c| !blank(c)).map(|i| i + 1).unwrap_or(start);
+    let arg = &arg[start..end];
+
+    let mut iargp = Vec::new();
+
+    iargp.push(binfmt_script_strdup(interp));
+
+    if !arg.is_empty() {
+        iargp.push(binfmt_script_strdup(arg));
+    }
+
+    iargp.push(strdup(path));
+
+    /* original arguments, without argv[0] */
+    if !argp.is_null() && !(*argp).is_null() {
+        let mut arg_p = argp.offset(1);
+
+        while !(*arg_p).is_null() {
+            iargp.push(strdup(*arg_p));
+            arg_p = arg_p.offset(1);
+        }
+    }
+
+    iargp.push(core::ptr::null_mut());
+
+    Ok(iargp)
+}
+
+fn init() -> Result<(), Error> {
+    binfmt_install(Arc::new(BinaryFormat {
+        name:   "script",
+        check:  Some(binfmt_script_check),
+        load:   None,
+        interp: Some(binfmt_script_interp),
+    }))
+}
+
+module_define!{
+    "binfmt_script",
+    None,
+    Some(init),
+    None
+}
@@ -0,0 +1,313 @@
+use prelude::*;
+use fs::{self, *};
+
+use arch::mm::i386::*;
+use arch::sys::thread::*;
+use bits::fcntl::*;
+use mm::*;
+use sys::binfmt::elf::*;
+use sys::process::*;
+use sys::thread::*;
+
+use crate::{page_round};
+
+/* note types */
+pub const NT_PRSTATUS : u32 = 1;
+pub const NT_PRPSINFO : u32 = 3;
+
+/** core dump bit in the wait status of a process killed by a signal */
+pub const WCOREFLAG: isize = 0x80;
+
+/** default name of the core file, created in the working directory of the process */
+const CORE_NAME: &str = "core";
+
+/** name of the core file if set, "%p" is replaced by the pid of the process */
+pub static mut CORE_PATTERN: String = String::new();
+
+/** i386 linux `struct elf_prstatus`, one per thread */
+#[repr(C)]
+struct ElfPrStatus {
+    si_signo:   i32,
+    si_code:    i32,
+    si_errno:   i32,
+    pr_cursig:  i16,
+    _pad:       i16,
+    pr_sigpend: u32,
+    pr_sighold: u32,
+    pr_pid:     i32,
+    pr_ppid:    i32,
+    pr_pgrp:    i32,
+    pr_sid:     i32,
+    pr_times:   [u32; 8],
+    pr_reg:     [usize; ELF_NGREG],
+    pr_fpvalid: i32,
+}
+
+/** i386 linux `struct elf_prpsinfo`, one per process */
+#[repr(C)]
+struct ElfPrPsInfo {
+    pr_state:  u8,
+    pr_sname:  u8,
+    pr_zomb:   u8,
+    pr_nice:   i8,
+    pr_flag:   u32,
+    pr_uid:    u16,
+    pr_gid:    u16,
+    pr_pid:    i32,
+    pr_ppid:   i32,
+    pr_pgrp:   i32,
+    pr_sid:    i32,
+    pr_fname:  [u8; 16],
+    pr_psargs: [u8; 80],
+}
+
+unsafe fn as_bytes<T>(val: &T) -> &[u8] {
+    core::slice::from_raw_parts(val as *const T as *const u8, core::mem::size_of::<T>())
+}
+
+/** append an elf note with name "CORE" to `buf` */
+fn core_note(buf: &mut Vec<u8>, note_type: u32, desc: &[u8]) {
+    let name = b"CORE\0\0\0\0";
+
+    buf.extend_from_slice(&5u32.to_ne_bytes());
+    buf.extend_from_slice(&(desc.len() as u32).to_ne_bytes());
+    buf.extend_from_slice(&note_type.to_ne_bytes());
+    buf.extend_from_slice(name);
+    buf.extend_from_slice(desc);
+    buf.resize((buf.len() + 3) & !3, 0);
+}
+
+/** build the PT_NOTE segment of `proc` killed by `sig` */
+unsafe fn core_notes(proc: *mut Process, sig: usize) -> Vec<u8> {
+    let mut notes = Vec::new();
+
+    let ppid = if (*proc).parent.is_null() { 0 } else { (*(*proc).parent).pid };
+    let pgrp = (*(*proc).pgrp).pgid;
+    let sid  = (*(*(*proc).pgrp).session).sid;
+
+    let mut psinfo = ElfPrPsInfo {
+        pr_state:  0,
+        pr_sname:  b'R',
+        pr_zomb:   0,
+        pr_nice:   0,
+        pr_flag:   0,
+        pr_uid:    (*proc).uid as u16,
+        pr_gid:    (*proc).gid as u16,
+        pr_pid:    (*proc).pid as i32,
+        pr_ppid:   ppid as i32,
+        pr_pgrp:   pgrp as i32,
+        pr_sid:    sid as i32,
+        pr_fname:  [0; 16],
+        pr_psargs: [0; 80],
+    };
+
+    if !(*proc).name.is_null() {
+        let name = cstr((*proc).name);
+        let fname = name.rsplit('/').next().unwrap_or(name).as_bytes();
+
+        for (dst, src) in psinfo.pr_fname.iter_mut().take(15).zip(fname) {
+            *dst = *src;
+        }
+
+        for (dst, src) in psinfo.pr_psargs.iter_mut().take(79).zip(name.as_bytes()) {
+            *dst = *src;
+        }
+    }
+
+    /* gdb takes the first thread as the one that crashed */
+    let mut threads = vec![curthread!()];
+    threads.extend((*proc).threads.iter().map(|qnode| qnode.value).filter(|&thread| thread != curthread!()));
+
+    for thread in threads {
+        let prstatus = ElfPrStatus {
+            si_signo:   sig as i32,
+            si_code:    0,
+            si_errno:   0,
+            pr_cursig:  if thread == curthread!() { sig as i16 } else { 0 },
+            _pad:       0,
+            pr_sigpend: (*thread).sig_pending,
+            pr_sighold: 0,
+            pr_pid:     (*thread).tid as i32,
+            pr_ppid:    ppid as i32,
+            pr_pgrp:    pgrp as i32,
+            pr_sid:     sid as i32,
+            pr_times:   [0; 8],
+            pr_reg:     arch_thread_gregs(thread),
+            pr_fpvalid: 0,
+        };
+
+        core_note(&mut notes, NT_PRSTATUS, as_bytes(&prstatus));
+    }
+
+    core_note(&mut notes, NT_PRPSINFO, as_bytes(&psinfo));
+
+    notes
+}
+
+/**
+ * open the core file of `proc`, truncating it if it already exists. links
+ * are not followed and only a regular file of the process owner is reused
+ */
+unsafe fn core_file(proc: *mut Process) -> Result<&'static mut Node, Error> {
+    let mut uio = proc_uio!(proc);
+    uio.flags = O_NOFOLLOW;
+
+    let name = if CORE_PATTERN.is_empty() {
+        CORE_NAME.to_owned()
+    } else {
+        CORE_PATTERN.replace("%p", &format!("{}", (*proc).pid))
+    };
+
+    match fs::lookup(&name, &uio) {
+        Ok((node, _)) => {
+            if node.is_directory() {
+                return Err(Error::EISDIR);
+            }
+
+            if node.is_symlink() {
+                return Err(Error::ELOOP);
+            }
+
+            if !S_ISREG!(node.mode()) || node.uid() != (*proc).uid {
+                return Err(Error::EACCES);
+            }
+
+            node.trunc(0)?;
+            Ok(node)
+        },
+        Err(err) if err == ENOENT => {
+            fs::creat(&name, 0o600, &uio)?;
+            Ok(fs::lookup(&name, &uio)?.0)
+        },
+        Err(err) => Err(err),
+    }
+}
+
+/**
+ * write an elf core file of `proc` killed by `sig`, the process must be the
+ * current one. nothing is written if the file would exceed RLIMIT_CORE
+ */
+pub unsafe fn proc_coredump(proc: *mut Process, sig: usize) -> Result<(), Error> {
+    let limit = (*proc).rlimit_core.rlim_cur;
+
+    if limit == 0 {
+        return Err(Error::EFBIG);
+    }
+
+    let pmap = (*proc).vm_space.pmap;
+
+    let vm_entries = (*proc).vm_space.vm_entries.iter()
+        .map(|qnode| qnode.value)
+        .filter(|&vm_entry| (*vm_entry).size != 0)
+        .collect::<Vec<*mut VmEntry>>();
+
+    let notes = core_notes(proc, sig);
+
+    let ehsize = core::mem::size_of::<Elf32Header>();
+    let phentsize = core::mem::size_of::<Elf32ProgramHeader>();
+    let phnum = 1 + vm_entries.len();
+
+    let notes_off = ehsize + phnum * phentsize;
+    let data_off = page_round!(notes_off + notes.len());
+
+    /* only readable regions carry data, others are described by memsz only */
+    let filesz = |vm_entry: *mut VmEntry| if (*vm_entry).flags & VM_UR != 0 { (*vm_entry).size } else { 0 };
+    let size = data_off + vm_entries.iter().map(|&vm_entry| filesz(vm_entry)).sum::<usize>();
+
+    if size > limit {
+        return Err(Error::EFBIG);
+    }
+
+    let mut hdr: Elf32Header = core::mem::zeroed();
+
+    hdr.e_ident[EI_MAG0]    = ELFMAG0;
+    hdr.e_ident[EI_MAG1]    = ELFMAG1;
+    hdr.e_ident[EI_MAG2]    = ELFMAG2;
+    hdr.e_ident[EI_MAG3]    = ELFMAG3;
+    hdr.e_ident[EI_CLASS]   = ELFCLASS32;
+    hdr.e_ident[EI_DATA]    = 1; /* ELFDATA2LSB */
+    hdr.e_ident[EI_VERSION] = EV_CURRENT as u8;
+
+    hdr.e_type      = ET_CORE as u16;
+    hdr.e_machine   = EM_386 as u16;
+    hdr.e_version   = EV_CURRENT as u32;
+    hdr.e_phoff     = ehsize as u32;
+    hdr.e_ehsize    = ehsize as u16;
+    hdr.e_phentsize = phentsize as u16;
+    hdr.e_phnum     = phnum as u16;
+
+    let mut buf = Vec::with_capacity(data_off);
+    buf.extend_from_slice(as_bytes(&hdr));
+
+    let note_phdr = Elf32ProgramHeader {
+        p_type:   PT_NOTE as u32,
+        p_offset: notes_off as u32,
+        p_vaddr:  0,
+        p_paddr:  0,
+        p_filesz: notes.len() as u32,
+        p_memsz:  0,
+        p_flags:  0,
+        p_align:  4,
+    };
+
+    buf.extend_from_slice(as_bytes(&note_phdr));
+
+    let mut off = data_off;
+
+    for &vm_entry in vm_entries.iter() {
+        let flags = (*vm_entry).flags;
+
+        let phdr = Elf32ProgramHeader {
+            p_type:   PT_LOAD as u32,
+            p_offset: off as u32,
+            p_vaddr:  (*vm_entry).base as u32,
+            p_paddr:  0,
+            p_filesz: filesz(vm_entry) as u32,
+            p_memsz:  (*vm_entry).size as u32,
+            p_flags:  ((if flags & VM_UR != 0 { PF_R } else { 0 }) |
+                       (if flags & VM_UW != 0 { PF_W } else { 0 }) |
+                       (if flags & VM_UX != 0 { PF_X } else { 0 })) as u32,
+            p_align:  PAGE_SIZE as u32,
+        };
+
+        buf.extend_from_slice(as_bytes(&phdr));
+        off += filesz(vm_entry);
+    }
+
+    buf.extend_from_slice(&notes);
+    buf.resize(data_off, 0);
+
+    let node = core_file(proc)?;
+    node.write(0, buf.len(), buf.as_mut_ptr())?;
+
+    /* pages not mapped yet are brought in from the anon layer or the object,
+     * only those never touched in a purely anonymous region are dumped as zeros */
+    let mut page = Buffer::new(PAGE_SIZE);
+    let mut off = data_off;
+
+    for &vm_entry in vm_entries.iter() {
+        let base = (*vm_entry).base;
+        let backed = !(*vm_entry).vm_object.is_null() || !(*vm_entry).vm_anon.is_null();
+
+        for vaddr in (base..base + filesz(vm_entry)).step_by(PAGE_SIZE) {
+            let mut paddr = arch_page_get_mapping(pmap, vaddr);
+
+            if paddr == 0 && backed && vm_space_fault(&mut (*proc).vm_space, vaddr, PF_READ | PF_USER) == 0 {
+                paddr = arch_page_get_mapping(pmap, vaddr);
+            }
+
+            if paddr != 0 {
+                pmap_page_read(paddr, 0, PAGE_SIZE, page.as_ptr_mut());
+            } else {
+                memset(page.as_ptr_mut(), 0, PAGE_SIZE);
+            }
+
+            node.write(off, PAGE_SIZE, page.as_ptr_mut())?;
+            off += PAGE_SIZE;
+        }
+    }
+
+    print!("kernel: pid {} dumped core\n", (*proc).pid);
+
+    Ok(())
+}
@@ -2,13 +2,32 @@ use prelude::*;
 
 use kern::string::*;
 use arch::i386::sys::*;
+use sys::process::*;
 use sys::thread::*;
 use sys::binfmt::*;
 use mm::*;
 
 use crate::{malloc_declare};
 
+/** free a null terminated argument array of `cnt` strings */
+unsafe fn execve_args_free(args: *mut *mut u8, cnt: isize) {
+    for i in 0..cnt {
+        kfree(*args.offset(i));
+    }
+
+    kfree(args as *mut u8);
+}
+
 pub unsafe fn proc_execve(thread: *mut Thread, path: *const u8, argp: *const *const u8, envp: *const *const u8) -> isize {
+    proc_execve_depth(thread, path, argp, envp, 0)
+}
+
+/** `depth` counts the interpreters already gone through, scripts re-exec their interpreter */
+unsafe fn proc_execve_depth(thread: *mut Thread, path: *const u8, argp: *const *const u8, envp: *const *const u8, depth: usize) -> isize {
+    if depth > BINFMT_MAX_DEPTH {
+        return -ELOOP;
+    }
+
     let proc = (*thread).owner;
     let u_argp = argp;
     let u_envp = envp;
@@ -48,20 +67,28 @@ pub unsafe fn proc_execve(thread: *mut Thread, path: *const u8, argp: *const *co
 
     let mut err = 0;
 
-    err = binfmt_load(proc, path, core::ptr::null_mut());
-    if err != 0 {
-        /* free used resources */
-        for i in 0..=argc {
-            kfree(*argp.offset(i));
-        }
+    match binfmt_interp(proc, path, argp as *const *const u8) {
+        Err(e) => err = e.unwrap(),
+        Ok(Some(iargp)) => {
+            /* run the interpreter instead, with the rewritten argv */
+            err = proc_execve_depth(thread, iargp[0], iargp.as_ptr() as *const *const u8, envp as *const *const u8, depth + 1);
 
-        kfree(argp as *mut u8);
+            for arg in iargp {
+                kfree(arg);
+            }
 
-        for i in 0..=envc {
-            kfree(*envp.offset(i));
-        }
+            execve_args_free(argp, argc);
+            execve_args_free(envp, envc);
 
-        kfree(envp as *mut u8);
+            return err;
+        },
+        Ok(None) => err = binfmt_load(proc, path, core::ptr::null_mut()),
+    }
+
+    if err != 0 {
+        /* free used resources */
+        execve_args_free(argp, argc);
+        execve_args_free(envp, envc);
 
         return err;
     }
@@ -71,18 +98,12 @@ pub unsafe fn proc_execve(thread: *mut Thread, path: *const u8, argp: *const *co
     arch_sys_execve(proc, (argc + 1) as usize, argp as *const *const u8, (envc + 1) as usize, envp as *const *const u8);
     core::ptr::write_bytes(&(*proc).sigaction as *const _ as *mut u8, 0, core::mem::size_of_val(&(*proc).sigaction));
 
-    /* free used resources */
-    for i in 0..argc {
-        kfree(*argp.offset(i));
-    }
-
-    kfree(argp as *mut u8);
+    /* descriptors marked close-on-exec do not survive the new image */
+    proc_fd_cloexec(proc);
 
-    for i in 0..envc {
-        kfree(*envp.offset(i));
-    }
-
-    kfree(envp as *mut u8);
+    /* free used resources */
+    execve_args_free(argp, argc);
+    execve_args_free(envp, envc);
     
     return 0;
 }
@@ -132,6 +153,18 @@ pub unsafe fn thread_execve(thread: *mut Thread, argp: *const *const u8, envp: *
         *u_argp.offset(i) = stack as *mut u8;
     }
 
+    /* bytes for AT_RANDOM */
+    stack -= 16;
+    binfmt_random(stack as *mut u8, 16);
+    let random = stack;
+
+    /* auxiliary vector, follows the envp array */
+    let auxv = binfmt_auxv(proc, random, *u_argp);
+
+    stack &= !(core::mem::size_of::<usize>() - 1);
+    stack -= auxv.len() * core::mem::size_of::<usize>();
+    memcpy(stack as *mut u8, auxv.as_ptr() as *const u8, auxv.len() * core::mem::size_of::<usize>());
+
     /* push envp array */
     stack -= ((envc+1) as usize) * core::mem::size_of::<*const u8>();
     memcpy(stack as *mut u8, u_envp as *mut u8, ((envc+1) as usize) * core::mem::size_of::<*const u8>());
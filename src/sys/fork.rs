@@ -5,7 +5,7 @@ use sys::session::*;
 use sys::pgroup::*;
 use sys::process::*;
 use sys::thread::*;
-use sys::syscall::file::{FileDescriptor, FileBackend};
+use sys::syscall::file::file_ref;
 use mm::*;
 use net::socket::*;
 
@@ -16,29 +16,28 @@ use crate::{malloc_declare};
 
 malloc_declare!(M_FDS);
 
-// FIXME
-const FDS_COUNT: usize = 64;
-
 pub unsafe fn copy_fds(parent: *mut Process, fork: *mut Process) -> isize {
-    /* copy open files descriptors */
-    (*fork).fds = kmalloc(FDS_COUNT * core::mem::size_of::<FileDescriptor>(), &M_FDS, 0) as *mut FileDescriptor;
+    /* copy open files descriptors, the open files themselves are shared */
+    let count = (*parent).fds_count;
+
+    (*fork).fds = kmalloc(count * core::mem::size_of::<FdEntry>(), &M_FDS, M_ZERO) as *mut FdEntry;
 
     if (*fork).fds.is_null() {
         return -ENOMEM;
     }
 
-    memcpy((*fork).fds as *mut u8, (*parent).fds as *const u8, FDS_COUNT * core::mem::size_of::<FileDescriptor>());
-    //*(*fork).fds = *(*parent).fds;
-
-    for i in 0..FDS_COUNT {
-        let file = (*fork).fds.offset(i as isize);
-        if !(*file).backend.vnode.is_null() && (*file).backend.vnode != (-1isize) as *mut Node {
-            if (*file).flags & FILE_SOCKET != 0 {
-                (*(*file).backend.socket).refcnt += 1;
-            } else {
-                (*(*file).backend.vnode).refcnt += 1;
-            }
+    (*fork).fds_count = count;
+
+    for fd in 0..count {
+        let entry = *(*parent).fds.offset(fd as isize);
+
+        /* slots reserved in the parent are not open yet */
+        if entry.file.is_null() || entry.file == FD_RESERVED {
+            continue;
         }
+
+        file_ref(entry.file);
+        (*fork).fds.offset(fd as isize).write(entry);
     }
 
     return 0;
@@ -55,6 +54,12 @@ pub unsafe fn fork_proc_copy(parent: *mut Process, fork: *mut Process) -> isize
     (*fork).heap_start = (*parent).heap_start;
     (*fork).heap  = (*parent).heap;
     (*fork).entry = (*parent).entry;
+    (*fork).tls   = (*parent).tls;
+    (*fork).aux   = (*parent).aux;
+    (*fork).args  = (*parent).args;
+
+    (*fork).rlimit_core = (*parent).rlimit_core;
+    (*fork).personality = (*parent).personality;
 
     memcpy(&(*fork).sigaction as *const _ as *mut u8, &(*parent).sigaction as *const _ as *const u8, core::mem::size_of_val(&(*parent).sigaction));
 
@@ -105,8 +110,12 @@ pub unsafe fn proc_fork(thread: *mut Thread, proc_ref: *mut *mut Process) -> isi
      * fork continues execution from a spawned thread */
     (*fork_thread).spawned = 1;
 
+    /* the child inherits the signal mask */
+    (*fork_thread).sig_mask = (*thread).sig_mask;
+
     /* copy current working directory */
     (*fork).cwd = (*proc).cwd.clone();
+    (*fork).root = (*proc).root.clone();
 
     /* allocate new signals queue */
     (*fork).sig_queue = Some(Queue::alloc(Queue::new()));
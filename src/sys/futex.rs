@@ -0,0 +1,205 @@
+use prelude::*;
+
+use bits::futex::*;
+use mm::*;
+use sys::process::*;
+use sys::thread::*;
+
+/** number of buckets in the futex wait-queue table */
+const FUTEX_HASH_SIZE: usize = 64;
+
+/** identifies a futex word independently of the address it is mapped at */
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FutexKey {
+    /** word in a private mapping, only visible inside a single address space */
+    Private(*mut VmSpace, usize),
+
+    /** word in a shared mapping, visible to every process mapping the object */
+    Shared(*mut VmObject, usize),
+}
+
+impl FutexKey {
+    fn hash(&self) -> usize {
+        let (obj, off) = match *self {
+            FutexKey::Private(vm_space, vaddr) => (vm_space as usize, vaddr),
+            FutexKey::Shared(vm_object, off) => (vm_object as usize, off),
+        };
+
+        let h = (obj ^ (off >> 2)).wrapping_mul(0x9e3779b9);
+        (h >> 16) % FUTEX_HASH_SIZE
+    }
+}
+
+/** a thread blocked in FUTEX_WAIT, lives on the stack of the waiting thread */
+pub struct FutexWaiter {
+    /** futex the thread is waiting on */
+    pub key: FutexKey,
+
+    /** wake only if a FUTEX_WAKE_BITSET bitset intersects this */
+    pub bitset: u32,
+
+    /** private sleep queue of the waiting thread */
+    pub queue: Queue<*mut Thread>,
+
+    /** node in the hash bucket, null once a waker took the waiter off */
+    pub qnode: *mut QueueNode<*mut FutexWaiter>,
+}
+
+const FUTEX_BUCKET: Queue<*mut FutexWaiter> = Queue::empty();
+
+/** hashed wait queues of all futex waiters */
+static mut FUTEX_TABLE: [Queue<*mut FutexWaiter>; FUTEX_HASH_SIZE] = [FUTEX_BUCKET; FUTEX_HASH_SIZE];
+
+/** resolve the key of the futex word at `uaddr` in the address space of `proc` */
+unsafe fn futex_key(proc: *mut Process, uaddr: usize, private: bool) -> Result<FutexKey, Error> {
+    if uaddr & 3 != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let vm_space = &mut (*proc).vm_space;
+
+    let (base, off, flags, vm_object) = match vm_space.find(uaddr) {
+        Some(vm_entry) => (vm_entry.base, vm_entry.off, vm_entry.flags, vm_entry.vm_object),
+        None => return Err(Error::EFAULT),
+    };
+
+    if !private && flags & VM_SHARED != 0 && !vm_object.is_null() {
+        return Ok(FutexKey::Shared(vm_object, off + (uaddr - base)));
+    }
+
+    Ok(FutexKey::Private(vm_space as *mut VmSpace, uaddr))
+}
+
+/** take `waiter` off its hash bucket, if still there */
+unsafe fn futex_unqueue(waiter: *mut FutexWaiter) {
+    if !(*waiter).qnode.is_null() {
+        FUTEX_TABLE[(*waiter).key.hash()].node_remove((*waiter).qnode);
+        (*waiter).qnode = core::ptr::null_mut();
+    }
+}
+
+/** waiters on `key` in its bucket, in the order they started waiting */
+unsafe fn futex_waiters(key: FutexKey, bitset: u32) -> Vec<*mut FutexWaiter> {
+    FUTEX_TABLE[key.hash()].iter()
+        .map(|qnode| qnode.value)
+        .filter(|&waiter| (*waiter).key == key && (*waiter).bitset & bitset != 0)
+        .collect()
+}
+
+/**
+ * sleep on the futex at `uaddr` if it still holds `val`, for at most
+ * `timeout` nanoseconds if given
+ */
+pub unsafe fn futex_wait(proc: *mut Process, uaddr: usize, val: u32, timeout: Option<u64>, bitset: u32, private: bool) -> Result<(), Error> {
+    if bitset == 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let key = futex_key(proc, uaddr, private)?;
+
+    if copyin(uaddr as *const u32)? != val {
+        return Err(Error::EAGAIN);
+    }
+
+    let mut waiter = FutexWaiter {
+        key:    key,
+        bitset: bitset,
+        queue:  Queue::empty(),
+        qnode:  core::ptr::null_mut(),
+    };
+
+    let waiter_ptr = &mut waiter as *mut FutexWaiter;
+    waiter.qnode = FUTEX_TABLE[key.hash()].enqueue(waiter_ptr);
+
+    let ret = match timeout {
+        Some(ns) => thread_queue_sleep_timeout(&mut waiter.queue, ns),
+        None => thread_queue_sleep(&mut waiter.queue),
+    };
+
+    /* a waker takes us off the bucket before waking us up */
+    if waiter.qnode.is_null() {
+        return Ok(());
+    }
+
+    futex_unqueue(waiter_ptr);
+
+    if ret == 1 {
+        return Err(Error::ETIMEDOUT);
+    }
+
+    Err(Error::EINTR)
+}
+
+/** wake up at most `nr` waiters on the futex at `uaddr`, returns the number woken */
+pub unsafe fn futex_wake(proc: *mut Process, uaddr: usize, nr: usize, bitset: u32, private: bool) -> Result<usize, Error> {
+    if bitset == 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let key = futex_key(proc, uaddr, private)?;
+    let mut count = 0;
+
+    for waiter in futex_waiters(key, bitset).into_iter().take(nr) {
+        futex_unqueue(waiter);
+        thread_queue_wakeup(&mut (*waiter).queue);
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/**
+ * wake up at most `nr_wake` waiters on `uaddr` and move at most `nr_requeue`
+ * of the remaining ones to `uaddr2`, if `cmpval` is given the futex at `uaddr`
+ * must still hold it. returns the number of woken and requeued waiters
+ */
+pub unsafe fn futex_requeue(proc: *mut Process, uaddr: usize, nr_wake: usize, nr_requeue: usize, uaddr2: usize, cmpval: Option<u32>, private: bool) -> Result<(usize, usize), Error> {
+    let key  = futex_key(proc, uaddr, private)?;
+    let key2 = futex_key(proc, uaddr2, private)?;
+
+    if let Some(val) = cmpval {
+        if copyin(uaddr as *const u32)? != val {
+            return Err(Error::EAGAIN);
+        }
+    }
+
+    let mut woken = 0;
+    let mut requeued = 0;
+
+    for waiter in futex_waiters(key, FUTEX_BITSET_MATCH_ANY) {
+        if woken < nr_wake {
+            futex_unqueue(waiter);
+            thread_queue_wakeup(&mut (*waiter).queue);
+            woken += 1;
+        } else if requeued < nr_requeue {
+            futex_unqueue(waiter);
+            (*waiter).key = key2;
+            (*waiter).qnode = FUTEX_TABLE[key2.hash()].enqueue(waiter);
+            requeued += 1;
+        } else {
+            break;
+        }
+    }
+
+    Ok((woken, requeued))
+}
+
+/** drop the futex waiter of a thread being killed, its stack is going away */
+pub unsafe fn futex_thread_kill(thread: *mut Thread) {
+    let queue = (*thread).sleep_queue;
+
+    if queue.is_null() {
+        return;
+    }
+
+    for bucket in FUTEX_TABLE.iter_mut() {
+        let waiter = bucket.iter()
+            .map(|qnode| qnode.value)
+            .find(|&waiter| &mut (*waiter).queue as *mut _ == queue);
+
+        if let Some(waiter) = waiter {
+            futex_unqueue(waiter);
+            return;
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use prelude::*;
+
+use bits::ipc::*;
+use kern::time::*;
+use sys::ipc::msg::*;
+use sys::ipc::sem::*;
+use sys::ipc::shm::*;
+use sys::process::*;
+
+/** common interface of System V ipc objects */
+pub trait IpcObject {
+    fn perm(&self) -> &IpcPerm;
+    fn perm_mut(&mut self) -> &mut IpcPerm;
+}
+
+/** registry of ipc objects of a single kind, indexed by identifier */
+pub struct IpcRegistry<T: IpcObject> {
+    objects: Vec<(isize, Box<T>)>,
+
+    /** next identifier to hand out */
+    next_id: isize,
+}
+
+impl<T: IpcObject> IpcRegistry<T> {
+    pub const fn new() -> Self {
+        IpcRegistry {
+            objects: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /** look for the object registered with `key` */
+    pub fn find_key(&self, key: key_t) -> Option<isize> {
+        if key == IPC_PRIVATE {
+            return None;
+        }
+
+        self.objects.iter()
+            .find(|(_, obj)| obj.perm().key == key)
+            .map(|(id, _)| *id)
+    }
+
+    pub fn get(&mut self, id: isize) -> Result<&mut T, Error> {
+        self.objects.iter_mut()
+            .find(|(_id, _)| *_id == id)
+            .map(|(_, obj)| &mut **obj)
+            .ok_or(Error::EINVAL)
+    }
+
+    pub fn insert(&mut self, obj: Box<T>) -> isize {
+        let id = self.next_id;
+
+        self.next_id += 1;
+        self.objects.push((id, obj));
+
+        id
+    }
+
+    pub fn remove(&mut self, id: isize) -> Option<Box<T>> {
+        let idx = self.objects.iter().position(|(_id, _)| *_id == id)?;
+        Some(self.objects.remove(idx).1)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (isize, &mut T)> {
+        self.objects.iter_mut().map(|(id, obj)| (*id, &mut **obj))
+    }
+}
+
+/** System V ipc objects visible to processes, one registry per kind */
+pub struct IpcNamespace {
+    /** shared memory segments */
+    pub shm: IpcRegistry<ShmSegment>,
+
+    /** message queues */
+    pub msg: IpcRegistry<MsgQueue>,
+
+    /** semaphore sets */
+    pub sem: IpcRegistry<SemSet>,
+}
+
+pub static mut IPC_NS: IpcNamespace = IpcNamespace {
+    shm: IpcRegistry::new(),
+    msg: IpcRegistry::new(),
+    sem: IpcRegistry::new(),
+};
+
+pub fn ipc_time() -> time_t {
+    gettime().map(|ts| ts.tv_sec).unwrap_or(0)
+}
+
+/** initialize permissions of a newly created ipc object */
+pub unsafe fn ipc_perm_new(proc: *mut Process, key: key_t, flags: usize) -> IpcPerm {
+    IpcPerm {
+        key:  key,
+        uid:  (*proc).uid,
+        gid:  (*proc).gid,
+        cuid: (*proc).uid,
+        cgid: (*proc).gid,
+        mode: (flags & 0o777) as mode_t,
+        seq:  0,
+    }
+}
+
+/**
+ * check that `proc` is granted the access requested in `flags`,
+ * only the permission bits (0o777) of `flags` are considered
+ */
+pub unsafe fn ipc_perms_check(perm: &IpcPerm, proc: *mut Process, flags: usize) -> Result<(), Error> {
+    if (*proc).uid == 0 {
+        /* root */
+        return Ok(());
+    }
+
+    let requested = ((flags >> 6) | (flags >> 3) | flags) as mode_t & 0o7;
+
+    let granted = (if (*proc).uid == perm.uid || (*proc).uid == perm.cuid {
+        perm.mode >> 6
+    } else if (*proc).gid == perm.gid || (*proc).gid == perm.cgid {
+        perm.mode >> 3
+    } else {
+        perm.mode
+    }) & 0o7;
+
+    if requested & !granted != 0 {
+        return Err(Error::EACCES);
+    }
+
+    Ok(())
+}
+
+/** only the owner, the creator or root may change or remove an ipc object */
+pub unsafe fn ipc_owner_check(perm: &IpcPerm, proc: *mut Process) -> Result<(), Error> {
+    if (*proc).uid == 0 || (*proc).uid == perm.uid || (*proc).uid == perm.cuid {
+        return Ok(());
+    }
+
+    Err(Error::EPERM)
+}
+
+/** apply the user supplied fields of an IPC_SET request */
+pub fn ipc_perm_set(perm: &mut IpcPerm, new: &IpcPerm) {
+    perm.uid  = new.uid;
+    perm.gid  = new.gid;
+    perm.mode = (perm.mode & !0o777) | (new.mode & 0o777);
+}
+
+/**
+ * common `xxxget` logic: look up `key` in `registry`, or create a new
+ * object using `create` when permitted by `flags`
+ */
+pub unsafe fn ipc_get<T, F>(registry: &mut IpcRegistry<T>, proc: *mut Process, key: key_t, flags: usize, create: F) -> Result<isize, Error>
+    where T: IpcObject,
+          F: FnOnce(IpcPerm) -> Result<Box<T>, Error> {
+
+    if let Some(id) = registry.find_key(key) {
+        if flags & IPC_CREAT != 0 && flags & IPC_EXCL != 0 {
+            return Err(Error::EEXIST);
+        }
+
+        ipc_perms_check(registry.get(id)?.perm(), proc, flags)?;
+        return Ok(id);
+    }
+
+    if key != IPC_PRIVATE && flags & IPC_CREAT == 0 {
+        return Err(Error::ENOENT);
+    }
+
+    let obj = create(ipc_perm_new(proc, key, flags))?;
+    Ok(registry.insert(obj))
+}
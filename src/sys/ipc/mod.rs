@@ -0,0 +1,9 @@
+pub mod ipc;
+pub mod shm;
+pub mod msg;
+pub mod sem;
+
+pub use self::ipc::*;
+pub use self::shm::*;
+pub use self::msg::*;
+pub use self::sem::*;
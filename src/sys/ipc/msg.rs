@@ -0,0 +1,265 @@
+use prelude::*;
+
+use bits::ipc::*;
+use bits::msg::*;
+use mm::*;
+use sys::ipc::ipc::*;
+use sys::process::*;
+use sys::thread::*;
+
+malloc_define!(M_MSG_QUEUE, "msg-queue\0", "message queue\0");
+
+/** upper bound of the tunables, messages and queues are kept in kernel memory */
+pub const MSG_LIMIT: usize = 1024 * 1024;
+
+/** max size of a message, tunable through sysfs */
+pub static mut MSG_MAX: usize = MSGMAX;
+
+/** max size of newly created queues, tunable through sysfs */
+pub static mut MSG_MNB: usize = MSGMNB;
+
+/** a message waiting on a queue */
+pub struct Message {
+    /** message type, always positive */
+    pub mtype: isize,
+
+    /** message body */
+    pub mtext: Vec<u8>,
+}
+
+/** System V message queue */
+pub struct MsgQueue {
+    /** ipc permissions */
+    pub perm: IpcPerm,
+
+    /** queued messages, oldest first */
+    pub messages: Vec<Message>,
+
+    /** number of bytes currently on queue */
+    pub cbytes: usize,
+
+    /** maximum number of bytes allowed on queue */
+    pub qbytes: usize,
+
+    /** pid of last msgsnd */
+    pub lspid: pid_t,
+
+    /** pid of last msgrcv */
+    pub lrpid: pid_t,
+
+    /** time of last msgsnd */
+    pub stime: time_t,
+
+    /** time of last msgrcv */
+    pub rtime: time_t,
+
+    /** time of last change */
+    pub ctime: time_t,
+
+    /** threads waiting for a message */
+    pub readers: Queue<*mut Thread>,
+
+    /** threads waiting for room on the queue */
+    pub writers: Queue<*mut Thread>,
+}
+
+unsafe impl Sync for MsgQueue {}
+
+impl MsgQueue {
+    pub fn alloc(val: MsgQueue) -> Box<MsgQueue> {
+        Box::new_tagged(&M_MSG_QUEUE, val)
+    }
+
+    /** find the index of the first message matching `msgtyp` */
+    fn find(&self, msgtyp: isize, flags: usize) -> Option<usize> {
+        if msgtyp == 0 {
+            /* first message on queue */
+            return if self.messages.is_empty() { None } else { Some(0) };
+        }
+
+        if msgtyp > 0 {
+            /* first message of type msgtyp, or not of it with MSG_EXCEPT */
+            let except = flags & MSG_EXCEPT != 0;
+            return self.messages.iter().position(|msg| (msg.mtype == msgtyp) != except);
+        }
+
+        /* first message with the lowest type less than or equal to |msgtyp| */
+        let mut found: Option<usize> = None;
+
+        for (idx, msg) in self.messages.iter().enumerate() {
+            if msg.mtype <= -msgtyp && found.map(|i| msg.mtype < self.messages[i].mtype).unwrap_or(true) {
+                found = Some(idx);
+            }
+        }
+
+        found
+    }
+}
+
+impl IpcObject for MsgQueue {
+    fn perm(&self) -> &IpcPerm {
+        &self.perm
+    }
+
+    fn perm_mut(&mut self) -> &mut IpcPerm {
+        &mut self.perm
+    }
+}
+
+/** get the queue `msqid` again after sleeping, it may have been removed meanwhile */
+unsafe fn msg_queue_get(msqid: isize, slept: bool) -> Result<&'static mut MsgQueue, Error> {
+    IPC_NS.msg.get(msqid).map_err(|err| if slept { Error::EIDRM } else { err })
+}
+
+pub unsafe fn msg_get(proc: *mut Process, key: key_t, flags: usize) -> Result<isize, Error> {
+    ipc_get(&mut IPC_NS.msg, proc, key, flags, |perm| {
+        Ok(MsgQueue::alloc(MsgQueue {
+            perm:     perm,
+            messages: Vec::new(),
+            cbytes:   0,
+            qbytes:   MSG_MNB,
+            lspid:    0,
+            lrpid:    0,
+            stime:    0,
+            rtime:    0,
+            ctime:    ipc_time(),
+            readers:  Queue::empty(),
+            writers:  Queue::empty(),
+        }))
+    })
+}
+
+pub unsafe fn msg_send(proc: *mut Process, msqid: isize, mtype: isize, mtext: *const u8, size: usize, flags: usize) -> Result<(), Error> {
+    if mtype < 1 || size > MSG_MAX {
+        return Err(Error::EINVAL);
+    }
+
+    let mut slept = false;
+
+    loop {
+        let queue = msg_queue_get(msqid, slept)?;
+
+        ipc_perms_check(&queue.perm, proc, 0o222)?;
+
+        if queue.cbytes + size <= queue.qbytes {
+            let mut msg = Message {
+                mtype: mtype,
+                mtext: vec![0; size],
+            };
+
+            copy_from_user(msg.mtext.as_mut_ptr(), mtext, size)?;
+
+            queue.messages.push(msg);
+            queue.cbytes += size;
+            queue.lspid = (*proc).pid;
+            queue.stime = ipc_time();
+
+            thread_queue_wakeup(&mut queue.readers);
+            return Ok(());
+        }
+
+        if flags & IPC_NOWAIT != 0 {
+            return Err(Error::EAGAIN);
+        }
+
+        if thread_queue_sleep(&mut queue.writers) != 0 {
+            return Err(Error::EINTR);
+        }
+
+        slept = true;
+    }
+}
+
+/** receive a message into `mtext`, returns the message type and the number of bytes copied */
+pub unsafe fn msg_recv(proc: *mut Process, msqid: isize, mtext: *mut u8, size: usize, msgtyp: isize, flags: usize) -> Result<(isize, usize), Error> {
+    let mut slept = false;
+
+    loop {
+        let queue = msg_queue_get(msqid, slept)?;
+
+        ipc_perms_check(&queue.perm, proc, 0o444)?;
+
+        if let Some(idx) = queue.find(msgtyp, flags) {
+            let len = queue.messages[idx].mtext.len();
+
+            if len > size && flags & MSG_NOERROR == 0 {
+                return Err(Error::E2BIG);
+            }
+
+            let len = if len > size { size } else { len };
+
+            /* copy before dequeueing, a bad buffer must not lose the message */
+            copy_to_user(mtext, queue.messages[idx].mtext.as_ptr(), len)?;
+
+            let msg = queue.messages.remove(idx);
+
+            queue.cbytes -= msg.mtext.len();
+            queue.lrpid = (*proc).pid;
+            queue.rtime = ipc_time();
+
+            thread_queue_wakeup(&mut queue.writers);
+            return Ok((msg.mtype, len));
+        }
+
+        if flags & IPC_NOWAIT != 0 {
+            return Err(Error::ENOMSG);
+        }
+
+        if thread_queue_sleep(&mut queue.readers) != 0 {
+            return Err(Error::EINTR);
+        }
+
+        slept = true;
+    }
+}
+
+pub unsafe fn msg_ctl(proc: *mut Process, msqid: isize, cmd: usize, buf: *mut MsqidDs) -> Result<(), Error> {
+    let queue = IPC_NS.msg.get(msqid)?;
+
+    match cmd {
+        IPC_STAT => {
+            ipc_perms_check(&queue.perm, proc, 0o444)?;
+
+            copyout(buf, &MsqidDs {
+                msg_perm:   queue.perm,
+                msg_qnum:   queue.messages.len(),
+                msg_qbytes: queue.qbytes,
+                msg_cbytes: queue.cbytes,
+                msg_lspid:  queue.lspid,
+                msg_lrpid:  queue.lrpid,
+                msg_stime:  queue.stime,
+                msg_rtime:  queue.rtime,
+                msg_ctime:  queue.ctime,
+            })?;
+        },
+        IPC_SET => {
+            let ds = copyin(buf as *const MsqidDs)?;
+
+            ipc_owner_check(&queue.perm, proc)?;
+
+            /* only root may raise the queue size limit */
+            if ds.msg_qbytes > queue.qbytes && (*proc).uid != 0 {
+                return Err(Error::EPERM);
+            }
+
+            ipc_perm_set(&mut queue.perm, &ds.msg_perm);
+            queue.qbytes = ds.msg_qbytes;
+            queue.ctime  = ipc_time();
+
+            /* a larger queue may unblock writers */
+            thread_queue_wakeup(&mut queue.writers);
+        },
+        IPC_RMID => {
+            ipc_owner_check(&queue.perm, proc)?;
+
+            /* sleepers look the queue up again on wakeup and fail with EIDRM */
+            thread_queue_wakeup(&mut queue.readers);
+            thread_queue_wakeup(&mut queue.writers);
+
+            IPC_NS.msg.remove(msqid);
+        },
+        _ => return Err(Error::EINVAL),
+    }
+
+    Ok(())
+}
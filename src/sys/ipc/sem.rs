@@ -0,0 +1,334 @@
+use prelude::*;
+
+use bits::ipc::*;
+use bits::sem::*;
+use mm::*;
+use sys::ipc::ipc::*;
+use sys::process::*;
+use sys::thread::*;
+
+malloc_define!(M_SEM_SET, "sem-set\0", "semaphore set\0");
+
+/** a single semaphore in a set */
+#[derive(Copy, Clone)]
+pub struct Semaphore {
+    /** semaphore value */
+    pub semval: isize,
+
+    /** pid of last operation */
+    pub sempid: pid_t,
+
+    /** number of threads waiting for semval to increase */
+    pub semncnt: usize,
+
+    /** number of threads waiting for semval to become zero */
+    pub semzcnt: usize,
+}
+
+/** System V semaphore set */
+pub struct SemSet {
+    /** ipc permissions */
+    pub perm: IpcPerm,
+
+    /** semaphores in set */
+    pub sems: Vec<Semaphore>,
+
+    /** time of last semop */
+    pub otime: time_t,
+
+    /** time of last change */
+    pub ctime: time_t,
+
+    /** threads blocked in semop on any semaphore of the set */
+    pub queue: Queue<*mut Thread>,
+}
+
+unsafe impl Sync for SemSet {}
+
+impl SemSet {
+    pub fn alloc(val: SemSet) -> Box<SemSet> {
+        Box::new_tagged(&M_SEM_SET, val)
+    }
+}
+
+impl IpcObject for SemSet {
+    fn perm(&self) -> &IpcPerm {
+        &self.perm
+    }
+
+    fn perm_mut(&mut self) -> &mut IpcPerm {
+        &mut self.perm
+    }
+}
+
+/** adjustment to apply to a semaphore when process `pid` exits */
+pub struct SemUndo {
+    pub pid: pid_t,
+    pub semid: isize,
+    pub semnum: usize,
+    pub adj: isize,
+}
+
+/** pending SEM_UNDO adjustments of all processes */
+pub static mut SEM_UNDO_LIST: Vec<SemUndo> = Vec::new();
+
+unsafe fn sem_undo_add(pid: pid_t, semid: isize, semnum: usize, adj: isize) {
+    match SEM_UNDO_LIST.iter_mut().find(|u| u.pid == pid && u.semid == semid && u.semnum == semnum) {
+        Some(undo) => undo.adj += adj,
+        None => SEM_UNDO_LIST.push(SemUndo { pid, semid, semnum, adj }),
+    }
+
+    SEM_UNDO_LIST.retain(|u| u.adj != 0);
+}
+
+/** drop adjustments of semaphore `semnum` in `semid`, or of the whole set if `semnum` is None */
+unsafe fn sem_undo_clear(semid: isize, semnum: Option<usize>) {
+    SEM_UNDO_LIST.retain(|u| u.semid != semid || semnum.map(|n| n != u.semnum).unwrap_or(false));
+}
+
+/** get the set `semid` again after sleeping, it may have been removed meanwhile */
+unsafe fn sem_set_get(semid: isize, slept: bool) -> Result<&'static mut SemSet, Error> {
+    IPC_NS.sem.get(semid).map_err(|err| if slept { Error::EIDRM } else { err })
+}
+
+pub unsafe fn sem_get(proc: *mut Process, key: key_t, nsems: usize, flags: usize) -> Result<isize, Error> {
+    if nsems > SEMMSL {
+        return Err(Error::EINVAL);
+    }
+
+    let id = ipc_get(&mut IPC_NS.sem, proc, key, flags, |perm| {
+        if nsems == 0 {
+            return Err(Error::EINVAL);
+        }
+
+        let sem = Semaphore {
+            semval:  0,
+            sempid:  0,
+            semncnt: 0,
+            semzcnt: 0,
+        };
+
+        Ok(SemSet::alloc(SemSet {
+            perm:  perm,
+            sems:  vec![sem; nsems],
+            otime: 0,
+            ctime: ipc_time(),
+            queue: Queue::empty(),
+        }))
+    })?;
+
+    if nsems > IPC_NS.sem.get(id)?.sems.len() {
+        return Err(Error::EINVAL);
+    }
+
+    Ok(id)
+}
+
+/**
+ * perform all operations in `sops` atomically, blocking until
+ * they can all be applied unless IPC_NOWAIT is given
+ */
+pub unsafe fn sem_op(proc: *mut Process, semid: isize, sops: &[SemBuf]) -> Result<(), Error> {
+    if sops.is_empty() || sops.len() > SEMOPM {
+        return Err(Error::EINVAL);
+    }
+
+    let alter = sops.iter().any(|op| op.sem_op != 0);
+    let mut slept = false;
+
+    loop {
+        let set = sem_set_get(semid, slept)?;
+
+        if sops.iter().any(|op| op.sem_num as usize >= set.sems.len()) {
+            return Err(Error::EFBIG);
+        }
+
+        ipc_perms_check(&set.perm, proc, if alter { 0o222 } else { 0o444 })?;
+
+        /* try the operations on a copy of the values first */
+        let mut vals = set.sems.iter().map(|sem| sem.semval).collect::<Vec<isize>>();
+        let mut blocked: Option<&SemBuf> = None;
+
+        for op in sops {
+            let val = &mut vals[op.sem_num as usize];
+            let sem_op = op.sem_op as isize;
+
+            if sem_op == 0 && *val != 0 || *val + sem_op < 0 {
+                blocked = Some(op);
+                break;
+            }
+
+            *val += sem_op;
+
+            if *val > SEMVMX {
+                return Err(Error::ERANGE);
+            }
+        }
+
+        let op = match blocked {
+            None => {
+                for op in sops {
+                    let num = op.sem_num as usize;
+
+                    set.sems[num].semval = vals[num];
+                    set.sems[num].sempid = (*proc).pid;
+
+                    if op.sem_flg & SEM_UNDO != 0 && op.sem_op != 0 {
+                        sem_undo_add((*proc).pid, semid, num, -(op.sem_op as isize));
+                    }
+                }
+
+                set.otime = ipc_time();
+
+                thread_queue_wakeup(&mut set.queue);
+                return Ok(());
+            },
+            Some(op) => op,
+        };
+
+        if op.sem_flg as usize & IPC_NOWAIT != 0 {
+            return Err(Error::EAGAIN);
+        }
+
+        let num = op.sem_num as usize;
+        let zero = op.sem_op == 0;
+
+        if zero {
+            set.sems[num].semzcnt += 1;
+        } else {
+            set.sems[num].semncnt += 1;
+        }
+
+        let interrupted = thread_queue_sleep(&mut set.queue) != 0;
+
+        /* the set may be gone by now, only account if it is still there */
+        if let Ok(set) = IPC_NS.sem.get(semid) {
+            if zero {
+                set.sems[num].semzcnt -= 1;
+            } else {
+                set.sems[num].semncnt -= 1;
+            }
+        }
+
+        if interrupted {
+            return Err(Error::EINTR);
+        }
+
+        slept = true;
+    }
+}
+
+pub unsafe fn sem_ctl(proc: *mut Process, semid: isize, semnum: usize, cmd: usize, arg: usize) -> Result<isize, Error> {
+    let set = IPC_NS.sem.get(semid)?;
+
+    match cmd {
+        GETVAL | GETPID | GETNCNT | GETZCNT | SETVAL => {
+            if semnum >= set.sems.len() {
+                return Err(Error::EINVAL);
+            }
+        },
+        _ => {},
+    }
+
+    match cmd {
+        GETVAL | GETPID | GETNCNT | GETZCNT | GETALL | IPC_STAT => {
+            ipc_perms_check(&set.perm, proc, 0o444)?;
+        },
+        SETVAL | SETALL => {
+            ipc_perms_check(&set.perm, proc, 0o222)?;
+        },
+        _ => {},
+    }
+
+    match cmd {
+        GETVAL  => return Ok(set.sems[semnum].semval),
+        GETPID  => return Ok(set.sems[semnum].sempid as isize),
+        GETNCNT => return Ok(set.sems[semnum].semncnt as isize),
+        GETZCNT => return Ok(set.sems[semnum].semzcnt as isize),
+        GETALL  => {
+            let array: Vec<u16> = set.sems.iter().map(|sem| sem.semval as u16).collect();
+
+            copy_to_user(arg as *mut u8, array.as_ptr() as *const u8, array.len() * core::mem::size_of::<u16>())?;
+        },
+        SETVAL => {
+            let val = arg as isize;
+
+            if val < 0 || val > SEMVMX {
+                return Err(Error::ERANGE);
+            }
+
+            set.sems[semnum].semval = val;
+            set.sems[semnum].sempid = (*proc).pid;
+            set.ctime = ipc_time();
+
+            sem_undo_clear(semid, Some(semnum));
+            thread_queue_wakeup(&mut set.queue);
+        },
+        SETALL => {
+            let mut array = vec![0u16; set.sems.len()];
+
+            copy_from_user(array.as_mut_ptr() as *mut u8, arg as *const u8, array.len() * core::mem::size_of::<u16>())?;
+
+            if array.iter().any(|&val| val as isize > SEMVMX) {
+                return Err(Error::ERANGE);
+            }
+
+            for (i, sem) in set.sems.iter_mut().enumerate() {
+                sem.semval = array[i] as isize;
+                sem.sempid = (*proc).pid;
+            }
+
+            set.ctime = ipc_time();
+
+            sem_undo_clear(semid, None);
+            thread_queue_wakeup(&mut set.queue);
+        },
+        IPC_STAT => {
+            copyout(arg as *mut SemidDs, &SemidDs {
+                sem_perm:  set.perm,
+                sem_nsems: set.sems.len(),
+                sem_otime: set.otime,
+                sem_ctime: set.ctime,
+            })?;
+        },
+        IPC_SET => {
+            let ds = copyin(arg as *const SemidDs)?;
+
+            ipc_owner_check(&set.perm, proc)?;
+            ipc_perm_set(&mut set.perm, &ds.sem_perm);
+            set.ctime = ipc_time();
+        },
+        IPC_RMID => {
+            ipc_owner_check(&set.perm, proc)?;
+
+            /* sleepers look the set up again on wakeup and fail with EIDRM */
+            thread_queue_wakeup(&mut set.queue);
+
+            sem_undo_clear(semid, None);
+            IPC_NS.sem.remove(semid);
+        },
+        _ => return Err(Error::EINVAL),
+    }
+
+    Ok(0)
+}
+
+/** apply and drop the SEM_UNDO adjustments of an exiting process */
+pub unsafe fn sem_exit(proc: *mut Process) {
+    let pid = (*proc).pid;
+
+    for undo in SEM_UNDO_LIST.iter().filter(|u| u.pid == pid) {
+        if let Ok(set) = IPC_NS.sem.get(undo.semid) {
+            let sem = &mut set.sems[undo.semnum];
+
+            /* clamp like linux does instead of blocking the exiting process */
+            let val = sem.semval + undo.adj;
+            sem.semval = if val < 0 { 0 } else if val > SEMVMX { SEMVMX } else { val };
+            sem.sempid = pid;
+
+            thread_queue_wakeup(&mut set.queue);
+        }
+    }
+
+    SEM_UNDO_LIST.retain(|u| u.pid != pid);
+}
@@ -0,0 +1,226 @@
+use prelude::*;
+
+use bits::ipc::*;
+use bits::shm::*;
+use mm::*;
+use sys::ipc::ipc::*;
+use sys::process::*;
+
+malloc_define!(M_SHM, "shm\0", "shared memory segment\0");
+
+/** System V shared memory segment */
+pub struct ShmSegment {
+    /** ipc permissions */
+    pub perm: IpcPerm,
+
+    /** requested size of the segment */
+    pub segsz: usize,
+
+    /** backing memory object, shared by all attachments */
+    pub vm_object: *mut VmObject,
+
+    /** pid of creator */
+    pub cpid: pid_t,
+
+    /** pid of last shmat/shmdt */
+    pub lpid: pid_t,
+
+    /** time of last shmat */
+    pub atime: time_t,
+
+    /** time of last shmdt */
+    pub dtime: time_t,
+
+    /** time of last change */
+    pub ctime: time_t,
+
+    /** segment is marked for removal once it is no longer attached */
+    pub removed: bool,
+}
+
+unsafe impl Sync for ShmSegment {}
+
+impl ShmSegment {
+    pub fn alloc(val: ShmSegment) -> Box<ShmSegment> {
+        Box::new_tagged(&M_SHM, val)
+    }
+
+    /** number of mappings referencing the segment */
+    pub fn nattch(&self) -> usize {
+        unsafe { (*self.vm_object).refcnt() }
+    }
+}
+
+impl IpcObject for ShmSegment {
+    fn perm(&self) -> &IpcPerm {
+        &self.perm
+    }
+
+    fn perm_mut(&mut self) -> &mut IpcPerm {
+        &mut self.perm
+    }
+}
+
+/* zero-filled pages, a removed segment goes with its last attachment */
+static mut SHM_PAGER: VmPager = VmPager {
+    page_in:  zero_page_in,
+    page_out: zero_page_out,
+    release:  Some(shm_unmapped),
+};
+
+/** free segments marked for removal that are no longer attached */
+unsafe fn shm_reap() {
+    let dead = IPC_NS.shm.iter_mut()
+        .filter(|(_, shm)| shm.removed && shm.nattch() == 0)
+        .map(|(id, _)| id)
+        .collect::<Vec<isize>>();
+
+    for id in dead {
+        if let Some(shm) = IPC_NS.shm.remove(id) {
+            (*shm.vm_object).destroy();
+            Box::from_raw(shm.vm_object);
+        }
+    }
+}
+
+/** the last attachment of a segment went away, by shmdt, exit or exec */
+unsafe fn shm_unmapped(_vm_object: *mut VmObject) {
+    shm_reap();
+}
+
+pub unsafe fn shm_get(proc: *mut Process, key: key_t, size: usize, flags: usize) -> Result<isize, Error> {
+    let id = ipc_get(&mut IPC_NS.shm, proc, key, flags, |perm| {
+        if size == 0 {
+            return Err(Error::EINVAL);
+        }
+
+        let vm_object = Box::leak(VmObject::alloc(VmObject {
+            pager: &mut SHM_PAGER,
+            ..VmObject::zero()
+        }));
+
+        Ok(ShmSegment::alloc(ShmSegment {
+            perm:  perm,
+            segsz: size,
+            vm_object: vm_object,
+            cpid:  (*proc).pid,
+            lpid:  0,
+            atime: 0,
+            dtime: 0,
+            ctime: ipc_time(),
+            removed: false,
+        }))
+    })?;
+
+    if size > IPC_NS.shm.get(id)?.segsz {
+        return Err(Error::EINVAL);
+    }
+
+    Ok(id)
+}
+
+pub unsafe fn shm_attach(proc: *mut Process, shmid: isize, addr: usize, flags: usize) -> Result<usize, Error> {
+    let shm = IPC_NS.shm.get(shmid)?;
+
+    if shm.removed {
+        return Err(Error::EIDRM);
+    }
+
+    let rdonly = flags & SHM_RDONLY != 0;
+    ipc_perms_check(&shm.perm, proc, if rdonly { 0o444 } else { 0o666 })?;
+
+    let mut base = addr;
+
+    if flags & SHM_RND != 0 {
+        base = base & !(SHMLBA - 1);
+    } else if base & PAGE_MASK != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let vm_entry = Box::leak(VmEntry::alloc(VmEntry::new()));
+
+    vm_entry.base  = base;
+    vm_entry.size  = page_round!(shm.segsz);
+    vm_entry.flags = VM_UR | VM_SHARED | if rdonly { 0 } else { VM_UW };
+    vm_entry.vm_object = shm.vm_object;
+    vm_entry.off   = 0;
+
+    let vm_space = &mut (*proc).vm_space;
+
+    if vm_space.insert(vm_entry) != 0 {
+        Box::from_raw(vm_entry);
+        return Err(if base == 0 { Error::ENOMEM } else { Error::EINVAL });
+    }
+
+    (*shm.vm_object).incref();
+
+    shm.lpid  = (*proc).pid;
+    shm.atime = ipc_time();
+
+    Ok(vm_entry.base)
+}
+
+pub unsafe fn shm_detach(proc: *mut Process, addr: usize) -> Result<(), Error> {
+    let vm_space = &mut (*proc).vm_space;
+
+    let vm_entry = match vm_space.find(addr) {
+        Some(vm_entry) if vm_entry.base == addr => vm_entry as *const _ as *mut VmEntry,
+        _ => return Err(Error::EINVAL),
+    };
+
+    let shm = IPC_NS.shm.iter_mut()
+        .find(|(_, shm)| shm.vm_object == (*vm_entry).vm_object)
+        .map(|(_, shm)| shm)
+        .ok_or(Error::EINVAL)?;
+
+    shm.lpid  = (*proc).pid;
+    shm.dtime = ipc_time();
+
+    vm_space.vm_entries.node_remove((*vm_entry).qnode);
+    vm_unmap_full(vm_space, vm_entry);
+    (*vm_entry).destroy();
+    Box::from_raw(vm_entry);
+
+    Ok(())
+}
+
+pub unsafe fn shm_ctl(proc: *mut Process, shmid: isize, cmd: usize, buf: *mut ShmidDs) -> Result<(), Error> {
+    let shm = IPC_NS.shm.get(shmid)?;
+
+    match cmd {
+        IPC_STAT => {
+            ipc_perms_check(&shm.perm, proc, 0o444)?;
+
+            copyout(buf, &ShmidDs {
+                shm_perm:   shm.perm,
+                shm_segsz:  shm.segsz,
+                shm_lpid:   shm.lpid,
+                shm_cpid:   shm.cpid,
+                shm_nattch: shm.nattch(),
+                shm_atime:  shm.atime,
+                shm_dtime:  shm.dtime,
+                shm_ctime:  shm.ctime,
+            })?;
+        },
+        IPC_SET => {
+            let ds = copyin(buf as *const ShmidDs)?;
+
+            ipc_owner_check(&shm.perm, proc)?;
+            ipc_perm_set(&mut shm.perm, &ds.shm_perm);
+            shm.ctime = ipc_time();
+        },
+        IPC_RMID => {
+            ipc_owner_check(&shm.perm, proc)?;
+
+            /* the key is released now, the memory when the last attachment goes away */
+            shm.perm.key = IPC_PRIVATE;
+            shm.removed  = true;
+            shm.ctime    = ipc_time();
+
+            shm_reap();
+        },
+        _ => return Err(Error::EINVAL),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,383 @@
+use prelude::*;
+
+use bits::fcntl::*;
+use bits::mman::*;
+use bits::timerfd::ITimerSpec;
+use fs::Stat;
+use fs::termios::*;
+
+/*
+ * structure layouts and flag values of the linux i386 ABI. errno values
+ * need no translation, the native numbering is the linux one.
+ */
+
+/* open(2) flags */
+pub const LINUX_O_ACCMODE   : usize = 0o00000003;
+pub const LINUX_O_CREAT     : usize = 0o00000100;
+pub const LINUX_O_EXCL      : usize = 0o00000200;
+pub const LINUX_O_NOCTTY    : usize = 0o00000400;
+pub const LINUX_O_TRUNC     : usize = 0o00001000;
+pub const LINUX_O_APPEND    : usize = 0o00002000;
+pub const LINUX_O_NONBLOCK  : usize = 0o00004000;
+pub const LINUX_O_DSYNC     : usize = 0o00010000;
+pub const LINUX_O_LARGEFILE : usize = 0o00100000;
+pub const LINUX_O_DIRECTORY : usize = 0o00200000;
+pub const LINUX_O_NOFOLLOW  : usize = 0o00400000;
+pub const LINUX_O_CLOEXEC   : usize = 0o02000000;
+pub const LINUX_O_SYNC      : usize = 0o04010000;
+
+/* fcntl(2) requests which differ from the native ones */
+pub const LINUX_F_GETLK         : usize = 5;
+pub const LINUX_F_SETLK         : usize = 6;
+pub const LINUX_F_SETLKW        : usize = 7;
+pub const LINUX_F_SETOWN        : usize = 8;
+pub const LINUX_F_GETOWN        : usize = 9;
+pub const LINUX_F_GETLK64       : usize = 12;
+pub const LINUX_F_SETLK64       : usize = 13;
+pub const LINUX_F_SETLKW64      : usize = 14;
+pub const LINUX_F_DUPFD_CLOEXEC : usize = 1030;
+
+/* record lock types */
+pub const LINUX_F_RDLCK : i16 = 0;
+pub const LINUX_F_WRLCK : i16 = 1;
+pub const LINUX_F_UNLCK : i16 = 2;
+
+/* epoll_create1(2) flags */
+pub const LINUX_EPOLL_CLOEXEC : usize = LINUX_O_CLOEXEC;
+
+/* eventfd2(2), signalfd4(2) and timerfd_create(2) flags, besides these LINUX_O_NONBLOCK and LINUX_O_CLOEXEC */
+pub const LINUX_EFD_SEMAPHORE     : usize = 0x0001;
+pub const LINUX_TFD_TIMER_ABSTIME : usize = 0x0001;
+
+/* mmap(2) flags, protections are the same */
+pub const LINUX_MAP_SHARED    : usize = 0x01;
+pub const LINUX_MAP_PRIVATE   : usize = 0x02;
+pub const LINUX_MAP_FIXED     : usize = 0x10;
+pub const LINUX_MAP_ANONYMOUS : usize = 0x20;
+
+/* sigaction(2) */
+pub const LINUX_SIG_DFL : usize = 0;
+pub const LINUX_SIG_IGN : usize = 1;
+
+/* rt_sigprocmask(2) operations */
+pub const LINUX_SIG_BLOCK   : isize = 0;
+pub const LINUX_SIG_UNBLOCK : isize = 1;
+pub const LINUX_SIG_SETMASK : isize = 2;
+
+/* clock ids */
+pub const LINUX_CLOCK_REALTIME  : usize = 0;
+pub const LINUX_CLOCK_MONOTONIC : usize = 1;
+
+/* (open flag, native open flag) pairs */
+static LINUX_OFLAGS: [(usize, usize); 10] = [
+    (LINUX_O_CREAT,     O_CREAT),
+    (LINUX_O_EXCL,      O_EXCL),
+    (LINUX_O_NOCTTY,    O_NOCTTY),
+    (LINUX_O_TRUNC,     O_TRUNC),
+    (LINUX_O_APPEND,    O_APPEND),
+    (LINUX_O_NONBLOCK,  O_NONBLOCK),
+    (LINUX_O_DIRECTORY, O_DIRECTORY),
+    (LINUX_O_NOFOLLOW,  O_NOFOLLOW),
+    (LINUX_O_CLOEXEC,   O_CLOEXEC),
+    (LINUX_O_SYNC,      O_SYNC),
+];
+
+/** native open flags for linux `flags`, the access mode is encoded the same */
+pub fn linux_oflags_to_native(flags: usize) -> usize {
+    let mut native = flags & LINUX_O_ACCMODE;
+
+    for &(lflag, flag) in LINUX_OFLAGS.iter() {
+        if flags & lflag == lflag {
+            native |= flag;
+        }
+    }
+
+    native
+}
+
+pub fn linux_oflags_from_native(flags: usize) -> usize {
+    let mut lflags = (flags & O_ACCMODE) | LINUX_O_LARGEFILE;
+
+    for &(lflag, flag) in LINUX_OFLAGS.iter() {
+        if flags & flag == flag {
+            lflags |= lflag;
+        }
+    }
+
+    lflags
+}
+
+/** native mmap flags for linux `flags`, and whether the mapping is anonymous */
+pub fn linux_mflags_to_native(flags: usize) -> (usize, bool) {
+    let mut native = 0;
+
+    native |= if flags & LINUX_MAP_SHARED  != 0 { MAP_SHARED  } else { 0 };
+    native |= if flags & LINUX_MAP_PRIVATE != 0 { MAP_PRIVATE } else { 0 };
+    native |= if flags & LINUX_MAP_FIXED   != 0 { MAP_FIXED   } else { 0 };
+
+    (native, flags & LINUX_MAP_ANONYMOUS != 0)
+}
+
+/** struct stat64 */
+#[repr(C, packed)]
+pub struct LinuxStat64 {
+    pub st_dev: u64,
+    pub __pad0: [u8; 4],
+    pub __st_ino: u32,
+    pub st_mode: u32,
+    pub st_nlink: u32,
+    pub st_uid: u32,
+    pub st_gid: u32,
+    pub st_rdev: u64,
+    pub __pad3: [u8; 4],
+    pub st_size: i64,
+    pub st_blksize: u32,
+    pub st_blocks: u64,
+    pub st_atime: u32,
+    pub st_atime_nsec: u32,
+    pub st_mtime: u32,
+    pub st_mtime_nsec: u32,
+    pub st_ctime: u32,
+    pub st_ctime_nsec: u32,
+    pub st_ino: u64,
+}
+
+impl LinuxStat64 {
+    pub fn new(stat: &Stat) -> Self {
+        LinuxStat64 {
+            st_dev: stat.st_dev as u64,
+            __pad0: [0; 4],
+            __st_ino: stat.st_ino as u32,
+            st_mode: stat.st_mode,
+            st_nlink: stat.st_nlink as u32,
+            st_uid: stat.st_uid,
+            st_gid: stat.st_gid,
+            st_rdev: stat.st_rdev as u64,
+            __pad3: [0; 4],
+            st_size: stat.st_size as i64,
+            st_blksize: stat.st_blksize,
+            st_blocks: stat.st_blocks as u64,
+            st_atime: stat.st_atime.tv_sec as u32,
+            st_atime_nsec: stat.st_atime.tv_nsec,
+            st_mtime: stat.st_mtime.tv_sec as u32,
+            st_mtime_nsec: stat.st_mtime.tv_nsec,
+            st_ctime: stat.st_ctime.tv_sec as u32,
+            st_ctime_nsec: stat.st_ctime.tv_nsec,
+            st_ino: stat.st_ino as u64,
+        }
+    }
+}
+
+/** native lock type for a linux one */
+pub fn linux_lock_type_to_native(l_type: i16) -> Option<usize> {
+    match l_type {
+        LINUX_F_RDLCK => Some(F_RDLCK),
+        LINUX_F_WRLCK => Some(F_WRLCK),
+        LINUX_F_UNLCK => Some(F_UNLCK),
+        _ => None,
+    }
+}
+
+pub fn linux_lock_type_from_native(kind: usize) -> i16 {
+    match kind {
+        F_RDLCK => LINUX_F_RDLCK,
+        F_WRLCK => LINUX_F_WRLCK,
+        _ => LINUX_F_UNLCK,
+    }
+}
+
+/** struct flock */
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct LinuxFlock {
+    pub l_type:   i16,
+    pub l_whence: i16,
+    pub l_start:  i32,
+    pub l_len:    i32,
+    pub l_pid:    i32,
+}
+
+/** struct flock64 */
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct LinuxFlock64 {
+    pub l_type:   i16,
+    pub l_whence: i16,
+    pub l_start:  i64,
+    pub l_len:    i64,
+    pub l_pid:    i32,
+}
+
+impl LinuxFlock {
+    pub fn to_flock64(&self) -> LinuxFlock64 {
+        LinuxFlock64 {
+            l_type:   self.l_type,
+            l_whence: self.l_whence,
+            l_start:  self.l_start as i64,
+            l_len:    self.l_len as i64,
+            l_pid:    self.l_pid,
+        }
+    }
+
+    /** `lock` if its range fits 32 bits */
+    pub fn from_flock64(lock: &LinuxFlock64) -> Result<Self, Error> {
+        let (start, len) = (lock.l_start, lock.l_len);
+
+        if start > i32::max_value() as i64 || len > i32::max_value() as i64 {
+            return Err(Error::EOVERFLOW);
+        }
+
+        Ok(LinuxFlock {
+            l_type:   lock.l_type,
+            l_whence: lock.l_whence,
+            l_start:  start as i32,
+            l_len:    len as i32,
+            l_pid:    lock.l_pid,
+        })
+    }
+}
+
+/** struct linux_dirent64, the name follows nul terminated */
+#[repr(C, packed)]
+pub struct LinuxDirent64 {
+    pub d_ino: u64,
+    pub d_off: i64,
+    pub d_reclen: u16,
+    pub d_type: u8,
+}
+
+pub const LINUX_DT_UNKNOWN: u8 = 0;
+
+/** number of control characters in the kernel termios */
+pub const LINUX_NCCS: usize = 19;
+
+/** struct termios as used by TCGETS and TCSETS, without the speeds */
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct LinuxTermios {
+    pub c_iflag: u32,
+    pub c_oflag: u32,
+    pub c_cflag: u32,
+    pub c_lflag: u32,
+    pub c_line:  u8,
+    pub c_cc:    [u8; LINUX_NCCS],
+}
+
+impl LinuxTermios {
+    /* flag bits and control character indices are the same as linux */
+
+    pub fn new(tios: &Termios) -> Self {
+        let mut c_cc = [0; LINUX_NCCS];
+        c_cc.copy_from_slice(&tios.c_cc[..LINUX_NCCS]);
+
+        LinuxTermios {
+            c_iflag: tios.c_iflag as u32,
+            c_oflag: tios.c_oflag as u32,
+            c_cflag: tios.c_cflag as u32,
+            c_lflag: tios.c_lflag as u32,
+            c_line:  tios.c_line,
+            c_cc,
+        }
+    }
+
+    /** update `tios` from this one, the speeds are left alone */
+    pub fn apply(&self, tios: &mut Termios) {
+        tios.c_iflag = self.c_iflag as tcflag_t;
+        tios.c_oflag = self.c_oflag as tcflag_t;
+        tios.c_cflag = self.c_cflag as tcflag_t;
+        tios.c_lflag = self.c_lflag as tcflag_t;
+        tios.c_line  = self.c_line;
+        tios.c_cc[..LINUX_NCCS].copy_from_slice(&self.c_cc);
+    }
+}
+
+/** struct sigaction as taken by rt_sigaction */
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct LinuxSigaction {
+    pub sa_handler:  u32,
+    pub sa_flags:    u32,
+    pub sa_restorer: u32,
+    pub sa_mask:     [u32; 2],
+}
+
+/** struct timeval */
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct LinuxTimeVal {
+    pub tv_sec:  i32,
+    pub tv_usec: i32,
+}
+
+/** struct timespec */
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct LinuxTimeSpec {
+    pub tv_sec:  i32,
+    pub tv_nsec: i32,
+}
+
+impl LinuxTimeSpec {
+    pub fn new(ts: &TimeSpec) -> Self {
+        LinuxTimeSpec {
+            tv_sec:  ts.tv_sec as i32,
+            tv_nsec: ts.tv_nsec as i32,
+        }
+    }
+
+    pub fn to_native(&self) -> Result<TimeSpec, Error> {
+        if self.tv_sec < 0 || self.tv_nsec < 0 || self.tv_nsec >= 1000000000 {
+            return Err(Error::EINVAL);
+        }
+
+        Ok(TimeSpec {
+            tv_sec:  self.tv_sec as time_t,
+            tv_nsec: self.tv_nsec as u32,
+        })
+    }
+}
+
+/** struct itimerspec */
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct LinuxITimerSpec {
+    pub it_interval: LinuxTimeSpec,
+    pub it_value:    LinuxTimeSpec,
+}
+
+impl LinuxITimerSpec {
+    pub fn new(its: &ITimerSpec) -> Self {
+        LinuxITimerSpec {
+            it_interval: LinuxTimeSpec::new(&its.it_interval),
+            it_value:    LinuxTimeSpec::new(&its.it_value),
+        }
+    }
+
+    pub fn to_native(&self) -> Result<ITimerSpec, Error> {
+        Ok(ITimerSpec {
+            it_interval: self.it_interval.to_native()?,
+            it_value:    self.it_value.to_native()?,
+        })
+    }
+}
+
+/** struct iovec */
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct LinuxIovec {
+    pub iov_base: *mut u8,
+    pub iov_len:  usize,
+}
+
+pub const LINUX_UTSNAME_LENGTH: usize = 65;
+
+/** struct new_utsname */
+#[repr(C)]
+pub struct LinuxUtsName {
+    pub sysname:    [u8; LINUX_UTSNAME_LENGTH],
+    pub nodename:   [u8; LINUX_UTSNAME_LENGTH],
+    pub release:    [u8; LINUX_UTSNAME_LENGTH],
+    pub version:    [u8; LINUX_UTSNAME_LENGTH],
+    pub machine:    [u8; LINUX_UTSNAME_LENGTH],
+    pub domainname: [u8; LINUX_UTSNAME_LENGTH],
+}
@@ -0,0 +1,7 @@
+pub mod abi;
+pub mod signal;
+pub mod syscall;
+
+pub use self::abi::*;
+pub use self::signal::*;
+pub use self::syscall::*;
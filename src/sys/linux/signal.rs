@@ -0,0 +1,168 @@
+use prelude::*;
+
+use sys::linux::abi::*;
+use sys::process::*;
+use sys::signal::*;
+
+/*
+ * linux numbers its signals differently, every signal number crossing the
+ * boundary with a linux process goes through these tables. linux signals
+ * without a native counterpart (SIGSTKFLT, SIGXCPU, ...) map to 0.
+ */
+
+/** highest classic linux signal, realtime signals are not supported */
+pub const LINUX_SIG_MAX: usize = 31;
+
+/** native number of each linux signal */
+static LINUX_SIGNALS: [isize; LINUX_SIG_MAX + 1] = [
+    /* 00 */ 0,
+    /* 01 SIGHUP    */ SIGHUP,
+    /* 02 SIGINT    */ SIGINT,
+    /* 03 SIGQUIT   */ SIGQUIT,
+    /* 04 SIGILL    */ SIGILL,
+    /* 05 SIGTRAP   */ SIGTRAP,
+    /* 06 SIGABRT   */ SIGABRT,
+    /* 07 SIGBUS    */ SIGBUS,
+    /* 08 SIGFPE    */ SIGFPE,
+    /* 09 SIGKILL   */ SIGKILL,
+    /* 10 SIGUSR1   */ SIGUSR1,
+    /* 11 SIGSEGV   */ SIGSEGV,
+    /* 12 SIGUSR2   */ SIGUSR2,
+    /* 13 SIGPIPE   */ SIGPIPE,
+    /* 14 SIGALRM   */ SIGALRM,
+    /* 15 SIGTERM   */ SIGTERM,
+    /* 16 SIGSTKFLT */ 0,
+    /* 17 SIGCHLD   */ SIGCHLD,
+    /* 18 SIGCONT   */ SIGCONT,
+    /* 19 SIGSTOP   */ SIGSTOP,
+    /* 20 SIGTSTP   */ SIGTSTP,
+    /* 21 SIGTTIN   */ SIGTTIN,
+    /* 22 SIGTTOU   */ SIGTTOU,
+    /* 23 SIGURG    */ SIGURG,
+    /* 24 SIGXCPU   */ 0,
+    /* 25 SIGXFSZ   */ 0,
+    /* 26 SIGVTALRM */ 0,
+    /* 27 SIGPROF   */ 0,
+    /* 28 SIGWINCH  */ SIGWINCH,
+    /* 29 SIGIO     */ SIGIO,
+    /* 30 SIGPWR    */ 0,
+    /* 31 SIGSYS    */ SIGSYS,
+];
+
+/** native number of linux signal `sig`, 0 stays 0, None if there is none */
+pub fn linux_signal_to_native(sig: isize) -> Option<isize> {
+    if sig == 0 {
+        return Some(0);
+    }
+
+    if sig < 0 || sig as usize > LINUX_SIG_MAX || LINUX_SIGNALS[sig as usize] == 0 {
+        return None;
+    }
+
+    Some(LINUX_SIGNALS[sig as usize])
+}
+
+/** linux number of native signal `sig`, 0 if linux has no such signal */
+pub fn linux_signal_from_native(sig: isize) -> isize {
+    if sig <= 0 {
+        return 0;
+    }
+
+    match LINUX_SIGNALS.iter().position(|&native| native == sig) {
+        Some(lsig) => lsig as isize,
+        None => 0,
+    }
+}
+
+/** convert a linux signal set to a native one, unknown signals are dropped */
+pub fn linux_sigset_to_native(set: u64) -> sigset_t {
+    let mut native = 0;
+
+    for lsig in 1..=LINUX_SIG_MAX {
+        if set & (1 << (lsig - 1)) != 0 {
+            if let Some(sig) = linux_signal_to_native(lsig as isize) {
+                native |= 1 << (sig - 1);
+            }
+        }
+    }
+
+    native
+}
+
+pub fn linux_sigset_from_native(set: sigset_t) -> u64 {
+    let mut lset = 0;
+
+    for sig in 1..=SIG_MAX {
+        if set & (1 << (sig - 1)) != 0 {
+            let lsig = linux_signal_from_native(sig as isize);
+
+            if lsig != 0 {
+                lset |= 1 << (lsig - 1);
+            }
+        }
+    }
+
+    lset
+}
+
+/**
+ * convert a linux sigaction, SIG_IGN is stored as the ignore default
+ * action. flags are dropped, delivery does not look at them
+ */
+pub fn linux_sigaction_to_native(act: &LinuxSigaction) -> SignalAction {
+    let handler = match act.sa_handler as usize {
+        LINUX_SIG_IGN => SignalDefaultAction::SIGACT_IGNORE as usize,
+        handler => handler,
+    };
+
+    SignalAction {
+        sa_handler: handler,
+        sa_mask:    linux_sigset_to_native(act.sa_mask[0] as u64 | (act.sa_mask[1] as u64) << 32),
+        sa_flags:   0,
+    }
+}
+
+pub fn linux_sigaction_from_native(act: &SignalAction) -> LinuxSigaction {
+    let handler = if act.sa_handler == SignalDefaultAction::SIGACT_IGNORE as usize {
+        LINUX_SIG_IGN
+    } else {
+        act.sa_handler
+    };
+
+    let mask = linux_sigset_from_native(act.sa_mask);
+
+    LinuxSigaction {
+        sa_handler:  handler as u32,
+        sa_flags:    0,
+        sa_restorer: 0,
+        sa_mask:     [mask as u32, (mask >> 32) as u32],
+    }
+}
+
+/**
+ * convert a wait status to linux, exit codes are encoded the same way but
+ * stop and termination signals are renumbered
+ */
+pub fn linux_wait_status(status: isize) -> isize {
+    let low = status & 0x7f;
+
+    if low == 0 {
+        /* exited */
+        status
+    } else if low == 0x7f {
+        /* stopped, the signal is in the second byte, 0x80 marks syscall stops */
+        let sig = (status >> 8) & 0xff;
+        ((linux_signal_from_native(sig & 0x7f) | (sig & 0x80)) << 8) | 0x7f
+    } else {
+        /* killed by a signal, keep the core dump flag */
+        linux_signal_from_native(low) | (status & 0x80)
+    }
+}
+
+/** number `proc` knows signal `sig` under */
+pub unsafe fn linux_proc_signal(proc: *mut Process, sig: isize) -> isize {
+    match (*proc).personality {
+        Personality::Linux => linux_signal_from_native(sig),
+        Personality::Aquila => sig,
+    }
+}
@@ -0,0 +1,785 @@
+use prelude::*;
+
+use arch::sys::sched::arch_rtime_ns;
+use bits::dirent::*;
+use bits::epoll::*;
+use bits::eventfd::*;
+use bits::fcntl::*;
+use bits::mount::*;
+use bits::poll::*;
+use bits::timerfd::*;
+use bits::uio::*;
+use fs::{self, *};
+use kern::time::*;
+use mm::*;
+use sys::linux::abi::*;
+use sys::linux::signal::*;
+use sys::process::*;
+use sys::sched::*;
+use sys::signal::*;
+use sys::syscall::*;
+use sys::syscall::file::*;
+use sys::syscall::table::*;
+use sys::syscall::trace::TraceArg::*;
+
+/** tag old programs put in the upper half of the mount flags */
+const LINUX_MS_MGC_MSK: usize = 0xffff0000;
+const LINUX_MS_MGC_VAL: usize = 0xc0ed0000;
+
+/** most buffers readv and writev take */
+const LINUX_IOV_MAX: usize = 1024;
+
+/** size of struct rusage in words, wait4 has no accounting to report */
+const LINUX_RUSAGE_WORDS: usize = 18;
+
+const LINUX_UTSNAME_SYSNAME:  &str = "Linux";
+const LINUX_UTSNAME_NODENAME: &str = "aquila";
+const LINUX_UTSNAME_RELEASE:  &str = "4.4.0";
+const LINUX_UTSNAME_VERSION:  &str = "AquilaOS v0.1.0";
+const LINUX_UTSNAME_MACHINE:  &str = "i386";
+
+unsafe fn linux_file(fd: isize) -> Result<*mut FileDescriptor, Error> {
+    let file = proc_fd_file(curproc!(), fd).ok_or(Error::EBADF)?;
+
+    if (*file).backend.vnode.is_null() {
+        return Err(Error::EBADF);
+    }
+
+    Ok(file)
+}
+
+unsafe fn linux_open(path: *const u8, flags: usize, mode: mode_t) -> SyscallResult {
+    open(path, linux_oflags_to_native(flags), mode)
+}
+
+unsafe fn linux_openat(dirfd: isize, path: *const u8, flags: usize, mode: mode_t) -> SyscallResult {
+    openat(dirfd, path, linux_oflags_to_native(flags), mode)
+}
+
+unsafe fn linux_wait4(pid: pid_t, stat_loc: *mut isize, options: usize, rusage: *mut u32) -> SyscallResult {
+    let pid = sys_waitpid(pid, stat_loc, options)?;
+
+    /* the status was written in native form */
+    if pid != 0 && !stat_loc.is_null() {
+        let status = copyin(stat_loc)?;
+        copyout(stat_loc, &linux_wait_status(status))?;
+    }
+
+    if !rusage.is_null() {
+        copyout(rusage as *mut [u32; LINUX_RUSAGE_WORDS], &[0; LINUX_RUSAGE_WORDS])?;
+    }
+
+    Ok(pid)
+}
+
+unsafe fn linux_waitpid(pid: pid_t, stat_loc: *mut isize, options: usize) -> SyscallResult {
+    linux_wait4(pid, stat_loc, options, core::ptr::null_mut())
+}
+
+unsafe fn linux_time(tloc: *mut i32) -> SyscallResult {
+    let now = gettime()?.tv_sec as i32;
+
+    if !tloc.is_null() {
+        copyout(tloc, &now)?;
+    }
+
+    Ok(now as usize)
+}
+
+unsafe fn linux_kill(pid: pid_t, sig: isize) -> SyscallResult {
+    let sig = linux_signal_to_native(sig).ok_or(Error::EINVAL)?;
+    Error::wrap_isize_to_usize(signal_send(pid, sig))
+}
+
+unsafe fn linux_tkill(tid: isize, sig: isize) -> SyscallResult {
+    sys_thread_kill(tid, linux_signal_to_native(sig).ok_or(Error::EINVAL)?)
+}
+
+unsafe fn linux_tgkill(tgid: pid_t, tid: isize, sig: isize) -> SyscallResult {
+    sys_tgkill(tgid, tid, linux_signal_to_native(sig).ok_or(Error::EINVAL)?)
+}
+
+unsafe fn linux_brk(addr: usize) -> SyscallResult {
+    let proc = curproc!();
+
+    /* brk(0) and breaks which can not be set return the current one */
+    if proc_heap_set(proc, addr).is_err() {
+        return Ok((*proc).heap);
+    }
+
+    Ok(addr)
+}
+
+unsafe fn linux_getppid() -> SyscallResult {
+    let parent = (*curproc!()).parent;
+
+    if parent.is_null() {
+        return Ok(0);
+    }
+
+    Ok((*parent).pid as usize)
+}
+
+unsafe fn linux_gettimeofday(tv: *mut LinuxTimeVal, tz: *mut TimeZone) -> SyscallResult {
+    let (now, zone) = gettimeofday()?;
+
+    if !tv.is_null() {
+        copyout(tv, &LinuxTimeVal { tv_sec: now.tv_sec as i32, tv_usec: now.tv_usec as i32 })?;
+    }
+
+    if !tz.is_null() {
+        copyout(tz, &zone)?;
+    }
+
+    Ok(0)
+}
+
+unsafe fn linux_clock_gettime(clk: usize, ts: *mut LinuxTimeSpec) -> SyscallResult {
+    let now = match clk {
+        LINUX_CLOCK_REALTIME => gettime()?,
+        LINUX_CLOCK_MONOTONIC => {
+            let ns = arch_rtime_ns();
+
+            TimeSpec {
+                tv_sec:  (ns / 1000000000) as time_t,
+                tv_nsec: (ns % 1000000000) as u32,
+            }
+        },
+        _ => return Err(Error::EINVAL),
+    };
+
+    copyout(ts, &LinuxTimeSpec::new(&now))?;
+
+    Ok(0)
+}
+
+unsafe fn linux_uname(name: *mut LinuxUtsName) -> SyscallResult {
+    let mut uts: LinuxUtsName = core::mem::zeroed();
+
+    /* the fields are zeroed, copying at most 64 bytes keeps them nul terminated */
+    let fields = [
+        (&mut uts.sysname,  LINUX_UTSNAME_SYSNAME),
+        (&mut uts.nodename, LINUX_UTSNAME_NODENAME),
+        (&mut uts.release,  LINUX_UTSNAME_RELEASE),
+        (&mut uts.version,  LINUX_UTSNAME_VERSION),
+        (&mut uts.machine,  LINUX_UTSNAME_MACHINE),
+    ];
+
+    for (field, val) in fields.iter_mut() {
+        let len = core::cmp::min(val.len(), LINUX_UTSNAME_LENGTH - 1);
+        field[..len].copy_from_slice(&val.as_bytes()[..len]);
+    }
+
+    copyout(name, &uts)?;
+
+    Ok(0)
+}
+
+unsafe fn linux_mount(source: *const u8, target: *const u8, fstype: *const u8, flags: usize, data: *mut u8) -> SyscallResult {
+    if (*curproc!()).uid != 0 {
+        return Err(Error::EPERM);
+    }
+
+    /* old programs tag the flags with a magic number */
+    let flags = if flags & LINUX_MS_MGC_MSK == LINUX_MS_MGC_VAL { flags & !LINUX_MS_MGC_MSK } else { flags };
+
+    let dir = user_path(target)?;
+    let uio = proc_uio!(curproc!());
+
+    if flags & MS_BIND != 0 && flags & MS_REMOUNT == 0 {
+        let source = user_path(source)?;
+        return fs::bind_mount(&source, &dir, flags, &uio).map(|_| 0);
+    }
+
+    let fstype = if fstype.is_null() { String::new() } else { user_string(fstype, PATH_MAX)? };
+
+    /* the source and the option string are passed on the way native mount does */
+    let mut dev = if source.is_null() { Vec::new() } else { user_cstr(source, PATH_MAX)? };
+    let mut opt = if data.is_null() { Vec::new() } else { user_cstr(data, PAGE_SIZE)? };
+
+    let mut mdata = MountData {
+        dev: if dev.is_empty() { core::ptr::null_mut() } else { dev.as_mut_ptr() },
+        opt: if opt.is_empty() { core::ptr::null_mut() } else { opt.as_mut_ptr() },
+    };
+
+    fs::mount(&fstype, &dir, flags, &mut mdata as *mut MountData as *mut u8, &uio).map(|_| 0)
+}
+
+unsafe fn linux_getcwd(buf: *mut u8, size: size_t) -> SyscallResult {
+    sys_getcwd(buf, size)?;
+
+    /* linux returns the length of the path, terminator included */
+    Ok(fs::root_path(&(*curproc!()).cwd, &proc_uio!(curproc!())).len() + 1)
+}
+
+/** 64-bit file offset passed in two registers */
+fn linux_loff(high: u32, low: u32) -> Result<off_t, Error> {
+    let offset = ((high as u64) << 32 | low as u64) as i64;
+
+    if offset > isize::max_value() as i64 || offset < isize::min_value() as i64 {
+        return Err(Error::EOVERFLOW);
+    }
+
+    Ok(offset as off_t)
+}
+
+unsafe fn linux_llseek(fd: isize, offset_high: u32, offset_low: u32, result: *mut i64, whence: isize) -> SyscallResult {
+    let offset = linux_loff(offset_high, offset_low)?;
+
+    let pos = lseek(fd, offset, whence)?;
+    copyout(result, &(pos as i64))?;
+
+    Ok(0)
+}
+
+/** copy in the `iovcnt` buffers at `iov` */
+unsafe fn linux_iovec(iov: *const LinuxIovec, iovcnt: usize) -> Result<Vec<IoVec>, Error> {
+    if iovcnt > LINUX_IOV_MAX {
+        return Err(Error::EINVAL);
+    }
+
+    let mut vecs = Vec::with_capacity(iovcnt);
+
+    for i in 0..iovcnt {
+        let vec = copyin(iov.offset(i as isize))?;
+
+        vecs.push(IoVec {
+            iov_base: vec.iov_base,
+            iov_len:  vec.iov_len,
+        });
+    }
+
+    Ok(vecs)
+}
+
+unsafe fn linux_readv(fd: isize, iov: *const LinuxIovec, iovcnt: usize) -> SyscallResult {
+    vectored_io(fd, &linux_iovec(iov, iovcnt)?, None, false)
+}
+
+unsafe fn linux_writev(fd: isize, iov: *const LinuxIovec, iovcnt: usize) -> SyscallResult {
+    vectored_io(fd, &linux_iovec(iov, iovcnt)?, None, true)
+}
+
+unsafe fn linux_pread64(fd: isize, buf: *mut u8, count: usize, pos_low: u32, pos_high: u32) -> SyscallResult {
+    pread(fd, buf, count, linux_loff(pos_high, pos_low)?)
+}
+
+unsafe fn linux_pwrite64(fd: isize, buf: *mut u8, count: usize, pos_low: u32, pos_high: u32) -> SyscallResult {
+    pwrite(fd, buf, count, linux_loff(pos_high, pos_low)?)
+}
+
+unsafe fn linux_preadv(fd: isize, iov: *const LinuxIovec, iovcnt: usize, pos_low: u32, pos_high: u32) -> SyscallResult {
+    vectored_io(fd, &linux_iovec(iov, iovcnt)?, Some(linux_loff(pos_high, pos_low)?), false)
+}
+
+unsafe fn linux_pwritev(fd: isize, iov: *const LinuxIovec, iovcnt: usize, pos_low: u32, pos_high: u32) -> SyscallResult {
+    vectored_io(fd, &linux_iovec(iov, iovcnt)?, Some(linux_loff(pos_high, pos_low)?), true)
+}
+
+/** copy in a loff_t, None for a null pointer */
+unsafe fn linux_loff_in(offset: *mut i64) -> Result<Option<off_t>, Error> {
+    if offset.is_null() {
+        return Ok(None);
+    }
+
+    let offset: i64 = copyin(offset)?;
+
+    if offset > isize::max_value() as i64 || offset < 0 {
+        return Err(Error::EINVAL);
+    }
+
+    Ok(Some(offset as off_t))
+}
+
+unsafe fn linux_sendfile64(out_fd: isize, in_fd: isize, offset: *mut i64, count: usize) -> SyscallResult {
+    let input  = linux_file(in_fd)?;
+    let output = linux_file(out_fd)?;
+
+    let mut pos = linux_loff_in(offset)?;
+    let ret = file_transfer(input, pos.as_mut(), output, None, count, false);
+
+    if let Some(pos) = pos {
+        copyout(offset, &(pos as i64))?;
+    }
+
+    ret
+}
+
+unsafe fn linux_splice(fd_in: isize, off_in: *mut i64, fd_out: isize, off_out: *mut i64, len: usize, flags: usize) -> SyscallResult {
+    /* the SPLICE_F_* flags are the same */
+    if flags & !(SPLICE_F_MOVE | SPLICE_F_NONBLOCK | SPLICE_F_MORE | SPLICE_F_GIFT) != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let input  = linux_file(fd_in)?;
+    let output = linux_file(fd_out)?;
+
+    let mut in_pos  = linux_loff_in(off_in)?;
+    let mut out_pos = linux_loff_in(off_out)?;
+
+    let ret = file_transfer(input, in_pos.as_mut(), output, out_pos.as_mut(), len, flags & SPLICE_F_NONBLOCK != 0);
+
+    if let Some(pos) = in_pos {
+        copyout(off_in, &(pos as i64))?;
+    }
+
+    if let Some(pos) = out_pos {
+        copyout(off_out, &(pos as i64))?;
+    }
+
+    ret
+}
+
+unsafe fn linux_rt_sigaction(sig: isize, act: *const LinuxSigaction, oact: *mut LinuxSigaction, sigsetsize: usize) -> SyscallResult {
+    if sigsetsize != core::mem::size_of::<u64>() {
+        return Err(Error::EINVAL);
+    }
+
+    let sig = match linux_signal_to_native(sig) {
+        Some(sig) if sig > 0 => sig,
+        _ => return Err(Error::EINVAL),
+    };
+
+    /* fetch the new action first, a bad `act` must leave everything untouched */
+    let new = if act.is_null() { None } else { Some(copyin(act)?) };
+
+    if new.is_some() && (sig == SIGKILL || sig == SIGSTOP) {
+        return Err(Error::EINVAL);
+    }
+
+    if !oact.is_null() {
+        copyout(oact, &linux_sigaction_from_native(&(*curproc!()).sigaction[sig as usize]))?;
+    }
+
+    if let Some(new) = new {
+        (*curproc!()).sigaction[sig as usize] = linux_sigaction_to_native(&new);
+    }
+
+    Ok(0)
+}
+
+unsafe fn linux_rt_sigprocmask(how: isize, set: *const u64, oset: *mut u64, sigsetsize: usize) -> SyscallResult {
+    if sigsetsize != core::mem::size_of::<u64>() {
+        return Err(Error::EINVAL);
+    }
+
+    let how = match how {
+        LINUX_SIG_BLOCK   => SIG_BLOCK,
+        LINUX_SIG_UNBLOCK => SIG_UNBLOCK,
+        LINUX_SIG_SETMASK => SIG_SETMASK,
+        _ => return Err(Error::EINVAL),
+    };
+
+    let set = if set.is_null() { None } else { Some(linux_sigset_to_native(copyin(set)?)) };
+    let old = signal_mask(how, set)?;
+
+    if !oset.is_null() {
+        copyout(oset, &linux_sigset_from_native(old))?;
+    }
+
+    Ok(0)
+}
+
+unsafe fn linux_mmap2(addr: usize, len: size_t, prot: usize, flags: usize, fd: isize, pgoff: usize) -> SyscallResult {
+    if len == 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let (flags, anonymous) = linux_mflags_to_native(flags);
+
+    let file = if anonymous {
+        core::ptr::null_mut()
+    } else {
+        linux_file(fd)?
+    };
+
+    mmap_region(addr, page_round!(len), prot, flags, file, (pgoff * PAGE_SIZE) as off_t)
+}
+
+unsafe fn linux_stat64(path: *const u8, buf: *mut LinuxStat64) -> SyscallResult {
+    linux_fstatat64(AT_FDCWD, path, buf, 0)
+}
+
+unsafe fn linux_lstat64(path: *const u8, buf: *mut LinuxStat64) -> SyscallResult {
+    /* stat the link itself rather than its target */
+    linux_fstatat64(AT_FDCWD, path, buf, AT_SYMLINK_NOFOLLOW)
+}
+
+unsafe fn linux_fstatat64(dirfd: isize, path: *const u8, buf: *mut LinuxStat64, flags: usize) -> SyscallResult {
+    let (dir, path) = user_path_at(dirfd, path)?;
+
+    let mut uio = proc_uio!(curproc!());
+    uio.flags = if flags & AT_SYMLINK_NOFOLLOW != 0 { O_NOFOLLOW } else { 0 };
+
+    let (node, _) = fs::lookup_at(dir, &path, &uio)?;
+    let stat = fs::stat(node)?;
+
+    copyout(buf, &LinuxStat64::new(&stat))?;
+
+    Ok(0)
+}
+
+unsafe fn linux_fstat64(fd: isize, buf: *mut LinuxStat64) -> SyscallResult {
+    let file = linux_file(fd)?;
+    let stat = fs::stat(&*(*file).backend.vnode)?;
+
+    copyout(buf, &LinuxStat64::new(&stat))?;
+
+    Ok(0)
+}
+
+unsafe fn linux_getdents64(fd: isize, buf: *mut u8, count: usize) -> SyscallResult {
+    let file = linux_file(fd)?;
+    let hdr = core::mem::size_of::<LinuxDirent64>();
+    let pad = [0u8; 8];
+
+    let mut off = 0;
+
+    loop {
+        let pos = (*file).offset;
+        let mut ent: DirectoryEntry = core::mem::zeroed();
+
+        let ret = vfs_file_readdir(file, &mut ent);
+
+        if ret < 0 && off == 0 {
+            return Error::wrap_isize_to_usize(ret);
+        }
+
+        if ret <= 0 {
+            break;
+        }
+
+        let namelen = ent.d_name.iter().position(|&c| c == 0).unwrap_or(MAXNAMELEN);
+        let reclen = (hdr + namelen + 1 + 7) & !7;
+
+        if off + reclen > count {
+            /* does not fit, it comes first next time */
+            (*file).offset = pos;
+
+            if off == 0 {
+                return Err(Error::EINVAL);
+            }
+
+            break;
+        }
+
+        let dirent = LinuxDirent64 {
+            d_ino:    ent.d_ino as u64,
+            d_off:    (*file).offset as i64,
+            d_reclen: reclen as u16,
+            d_type:   LINUX_DT_UNKNOWN,
+        };
+
+        let rec = buf.offset(off as isize);
+
+        copyout(rec as *mut LinuxDirent64, &dirent)?;
+        copy_to_user(rec.offset(hdr as isize), ent.d_name.as_ptr(), namelen)?;
+        copy_to_user(rec.offset((hdr + namelen) as isize), pad.as_ptr(), reclen - hdr - namelen)?;
+
+        off += reclen;
+    }
+
+    Ok(off)
+}
+
+/**
+ * record lock request `cmd`, one of the native F_GETLK, F_SETLK and
+ * F_SETLKW, on `fd` for `lock`. F_GETLK fills in `lock` with the lock in
+ * the way or sets it to F_UNLCK
+ */
+unsafe fn linux_fcntl_lock(fd: isize, cmd: usize, lock: &mut LinuxFlock64) -> Result<(), Error> {
+    let file = fs::poll_file(curproc!(), fd).ok_or(Error::EBADF)?;
+    let kind = linux_lock_type_to_native(lock.l_type).ok_or(Error::EINVAL)?;
+    let (start, end) = fs::lock_range(file, lock.l_whence as usize, lock.l_start, lock.l_len)?;
+
+    if cmd != F_GETLK {
+        return fs::vfs_lock_set(file, kind, start, end, cmd == F_SETLKW);
+    }
+
+    match fs::vfs_lock_get(file, kind, start, end)? {
+        None => lock.l_type = LINUX_F_UNLCK,
+        Some(held) => {
+            lock.l_type   = linux_lock_type_from_native(held.kind);
+            lock.l_whence = SEEK_SET as i16;
+            lock.l_start  = held.start as i64;
+            lock.l_len    = held.len() as i64;
+            lock.l_pid    = held.pid() as i32;
+        },
+    }
+
+    Ok(())
+}
+
+unsafe fn linux_fcntl64(fd: isize, cmd: usize, arg: usize) -> SyscallResult {
+    match cmd {
+        F_DUPFD | F_GETFD | F_SETFD => fcntl(fd, cmd as isize, arg),
+        F_GETFL => fcntl(fd, F_GETFL as isize, 0).map(linux_oflags_from_native),
+        F_SETFL => fcntl(fd, F_SETFL as isize, linux_oflags_to_native(arg)),
+        LINUX_F_GETLK | LINUX_F_SETLK | LINUX_F_SETLKW => {
+            let native = match cmd {
+                LINUX_F_GETLK => F_GETLK,
+                LINUX_F_SETLK => F_SETLK,
+                _ => F_SETLKW,
+            };
+
+            let mut lock = copyin(arg as *const LinuxFlock)?.to_flock64();
+            linux_fcntl_lock(fd, native, &mut lock)?;
+
+            if cmd == LINUX_F_GETLK {
+                copyout(arg as *mut LinuxFlock, &LinuxFlock::from_flock64(&lock)?)?;
+            }
+
+            Ok(0)
+        },
+        LINUX_F_GETLK64 | LINUX_F_SETLK64 | LINUX_F_SETLKW64 => {
+            let native = match cmd {
+                LINUX_F_GETLK64 => F_GETLK,
+                LINUX_F_SETLK64 => F_SETLK,
+                _ => F_SETLKW,
+            };
+
+            let mut lock = copyin(arg as *const LinuxFlock64)?;
+            linux_fcntl_lock(fd, native, &mut lock)?;
+
+            if cmd == LINUX_F_GETLK64 {
+                copyout(arg as *mut LinuxFlock64, &lock)?;
+            }
+
+            Ok(0)
+        },
+        LINUX_F_DUPFD_CLOEXEC => fcntl(fd, F_DUPFD_CLOEXEC as isize, arg),
+        _ => Err(Error::EINVAL),
+    }
+}
+
+unsafe fn linux_futex(uaddr: usize, op: usize, val: u32, timeout: *const LinuxTimeSpec, uaddr2: usize, val3: u32) -> SyscallResult {
+    let ts = if futex_op_waits(op) && !timeout.is_null() {
+        Some(copyin(timeout)?.to_native()?)
+    } else {
+        None
+    };
+
+    futex(uaddr, op, val, ts, timeout as usize, uaddr2, val3)
+}
+
+unsafe fn linux_ppoll(fds: *mut PollFd, nfds: usize, timeout: *const LinuxTimeSpec, sigmask: *const u64, sigsetsize: usize) -> SyscallResult {
+    if !sigmask.is_null() && sigsetsize != core::mem::size_of::<u64>() {
+        return Err(Error::EINVAL);
+    }
+
+    let ts = if timeout.is_null() { None } else { Some(copyin(timeout)?.to_native()?) };
+    let mask = if sigmask.is_null() { None } else { Some(linux_sigset_to_native(copyin(sigmask)?)) };
+
+    poll_fds_masked(fds, nfds, ts, mask)
+}
+
+unsafe fn linux_epoll_create1(flags: usize) -> SyscallResult {
+    if flags & !LINUX_EPOLL_CLOEXEC != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    epoll_create1(if flags & LINUX_EPOLL_CLOEXEC != 0 { EPOLL_CLOEXEC } else { 0 })
+}
+
+/** native open flags out of the LINUX_O_NONBLOCK and LINUX_O_CLOEXEC bits of `flags` */
+fn linux_fdflags_to_native(flags: usize) -> usize {
+    linux_oflags_to_native(flags & (LINUX_O_NONBLOCK | LINUX_O_CLOEXEC))
+}
+
+unsafe fn linux_eventfd(initval: u32) -> SyscallResult {
+    linux_eventfd2(initval, 0)
+}
+
+unsafe fn linux_eventfd2(initval: u32, flags: usize) -> SyscallResult {
+    if flags & !(LINUX_EFD_SEMAPHORE | LINUX_O_NONBLOCK | LINUX_O_CLOEXEC) != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let semaphore = if flags & LINUX_EFD_SEMAPHORE != 0 { EFD_SEMAPHORE } else { 0 };
+
+    eventfd(initval, semaphore | linux_fdflags_to_native(flags))
+}
+
+unsafe fn linux_signalfd(fd: isize, mask: *const u64, sizemask: usize) -> SyscallResult {
+    linux_signalfd4(fd, mask, sizemask, 0)
+}
+
+unsafe fn linux_signalfd4(fd: isize, mask: *const u64, sizemask: usize, flags: usize) -> SyscallResult {
+    if sizemask != core::mem::size_of::<u64>() {
+        return Err(Error::EINVAL);
+    }
+
+    if flags & !(LINUX_O_NONBLOCK | LINUX_O_CLOEXEC) != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let mask = linux_sigset_to_native(copyin(mask)?);
+
+    /* records carry linux signal numbers */
+    signalfd_with(fd, mask, linux_fdflags_to_native(flags), linux_signal_from_native)
+}
+
+unsafe fn linux_timerfd_create(clock: usize, flags: usize) -> SyscallResult {
+    let clock = match clock {
+        LINUX_CLOCK_REALTIME  => CLOCK_REALTIME,
+        LINUX_CLOCK_MONOTONIC => CLOCK_MONOTONIC,
+        _ => return Err(Error::EINVAL),
+    };
+
+    if flags & !(LINUX_O_NONBLOCK | LINUX_O_CLOEXEC) != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    timerfd_create(clock, linux_fdflags_to_native(flags))
+}
+
+unsafe fn linux_timerfd_settime(fd: isize, flags: usize, new_value: *const LinuxITimerSpec, old_value: *mut LinuxITimerSpec) -> SyscallResult {
+    let file = fs::poll_file(curproc!(), fd).ok_or(Error::EBADF)?;
+
+    if flags & !LINUX_TFD_TIMER_ABSTIME != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let abstime = if flags & LINUX_TFD_TIMER_ABSTIME != 0 { TFD_TIMER_ABSTIME } else { 0 };
+    let old = fs::timerfd_set(file, abstime, &copyin(new_value)?.to_native()?)?;
+
+    if !old_value.is_null() {
+        copyout(old_value, &LinuxITimerSpec::new(&old))?;
+    }
+
+    Ok(0)
+}
+
+unsafe fn linux_timerfd_gettime(fd: isize, curr_value: *mut LinuxITimerSpec) -> SyscallResult {
+    let file = fs::poll_file(curproc!(), fd).ok_or(Error::EBADF)?;
+    let curr = fs::timerfd_get(file)?;
+
+    copyout(curr_value, &LinuxITimerSpec::new(&curr))?;
+
+    Ok(0)
+}
+
+unsafe fn linux_dup3(oldfd: isize, newfd: isize, flags: usize) -> SyscallResult {
+    if flags & !LINUX_O_CLOEXEC != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    dup3(oldfd, newfd, linux_fdflags_to_native(flags))
+}
+
+unsafe fn linux_set_tid_address(tidptr: *mut u32) -> SyscallResult {
+    /* clearing the tid on thread exit is not supported */
+    sys_gettid()
+}
+
+/** linux i386 syscalls by number, sorted */
+static LINUX_SYSCALL_TABLE: &[(usize, SyscallDef)] = &[
+    (  1, syscall_def!(noreturn "exit", sys_exit, [Int])),
+    (  2, syscall_def!("fork", sys_fork, [])),
+    (  3, syscall_def!("read", read, [Int, Hex, Int])),
+    (  4, syscall_def!("write", write, [Int, Hex, Int])),
+    (  5, syscall_def!("open", linux_open, [Str, Hex, Oct])),
+    (  6, syscall_def!("close", close, [Int])),
+    (  7, syscall_def!("waitpid", linux_waitpid, [Int, Hex, Hex])),
+    (  9, syscall_def!("link", link, [Str, Str])),
+    ( 10, syscall_def!("unlink", unlink, [Str])),
+    ( 11, syscall_def!(noreturn "execve", sys_execve, [Str, Hex, Hex])),
+    ( 12, syscall_def!("chdir", sys_chdir, [Str])),
+    ( 13, syscall_def!("time", linux_time, [Hex])),
+    ( 14, syscall_def!("mknod", mknod, [Str, Oct, Hex])),
+    ( 15, syscall_def!("chmod", chmod, [Str, Oct])),
+    ( 19, syscall_def!("lseek", lseek, [Int, Int, Int])),
+    ( 20, syscall_def!("getpid", sys_getpid, [])),
+    ( 21, syscall_def!("mount", linux_mount, [Str, Str, Str, Hex, Hex])),
+    ( 22, syscall_def!("umount", umount, [Str])),
+    ( 24, syscall_def!("getuid", sys_getuid, [])),
+    ( 33, syscall_def!("access", access, [Str, Oct])),
+    ( 37, syscall_def!("kill", linux_kill, [Int, Int])),
+    ( 38, syscall_def!("rename", rename, [Str, Str])),
+    ( 39, syscall_def!("mkdir", mkdir, [Str, Oct])),
+    ( 40, syscall_def!("rmdir", rmdir, [Str])),
+    ( 41, syscall_def!("dup", dup, [Int])),
+    ( 42, syscall_def!("pipe", pipe, [Hex])),
+    ( 45, syscall_def!("brk", linux_brk, [Hex])),
+    ( 47, syscall_def!("getgid", sys_getgid, [])),
+    ( 49, syscall_def!("geteuid", sys_getuid, [])),
+    ( 50, syscall_def!("getegid", sys_getgid, [])),
+    ( 52, syscall_def!("umount2", umount2, [Str, Hex])),
+    ( 54, syscall_def!("ioctl", ioctl, [Int, Hex, Hex])),
+    ( 57, syscall_def!("setpgid", sys_setpgid, [Int, Int])),
+    ( 60, syscall_def!("umask", sys_umask, [Oct])),
+    ( 61, syscall_def!("chroot", sys_chroot, [Str])),
+    ( 63, syscall_def!("dup2", dup2, [Int, Int])),
+    ( 64, syscall_def!("getppid", linux_getppid, [])),
+    ( 65, syscall_def!("getpgrp", sys_getpgrp, [])),
+    ( 78, syscall_def!("gettimeofday", linux_gettimeofday, [Hex, Hex])),
+    ( 83, syscall_def!("symlink", symlink, [Str, Str])),
+    ( 85, syscall_def!("readlink", readlink, [Str, Hex, Int])),
+    ( 91, syscall_def!("munmap", munmap, [Hex, Int])),
+    (114, syscall_def!("wait4", linux_wait4, [Int, Hex, Hex, Hex])),
+    (122, syscall_def!("uname", linux_uname, [Hex])),
+    (133, syscall_def!("fchdir", sys_fchdir, [Int])),
+    (140, syscall_def!("_llseek", linux_llseek, [Int, Hex, Hex, Hex, Int])),
+    (143, syscall_def!("flock", flock, [Int, Hex])),
+    (145, syscall_def!("readv", linux_readv, [Int, Hex, Int])),
+    (146, syscall_def!("writev", linux_writev, [Int, Hex, Int])),
+    (168, syscall_def!("poll", poll, [Hex, Int, Int])),
+    (174, syscall_def!("rt_sigaction", linux_rt_sigaction, [Int, Hex, Hex, Int])),
+    (175, syscall_def!("rt_sigprocmask", linux_rt_sigprocmask, [Int, Hex, Hex, Int])),
+    (180, syscall_def!("pread64", linux_pread64, [Int, Hex, Int, Hex, Hex])),
+    (181, syscall_def!("pwrite64", linux_pwrite64, [Int, Hex, Int, Hex, Hex])),
+    (183, syscall_def!("getcwd", linux_getcwd, [Hex, Int])),
+    (187, syscall_def!("sendfile", sendfile, [Int, Int, Hex, Int])),
+    (192, syscall_def!("mmap2", linux_mmap2, [Hex, Int, Hex, Hex, Int, Hex])),
+    (195, syscall_def!("stat64", linux_stat64, [Str, Hex])),
+    (196, syscall_def!("lstat64", linux_lstat64, [Str, Hex])),
+    (197, syscall_def!("fstat64", linux_fstat64, [Int, Hex])),
+    (199, syscall_def!("getuid32", sys_getuid, [])),
+    (200, syscall_def!("getgid32", sys_getgid, [])),
+    (201, syscall_def!("geteuid32", sys_getuid, [])),
+    (202, syscall_def!("getegid32", sys_getgid, [])),
+    (220, syscall_def!("getdents64", linux_getdents64, [Int, Hex, Int])),
+    (221, syscall_def!("fcntl64", linux_fcntl64, [Int, Int, Hex])),
+    (224, syscall_def!("gettid", sys_gettid, [])),
+    (238, syscall_def!("tkill", linux_tkill, [Int, Int])),
+    (239, syscall_def!("sendfile64", linux_sendfile64, [Int, Int, Hex, Int])),
+    (240, syscall_def!("futex", linux_futex, [Hex, Int, Int, Hex, Hex, Int])),
+    (243, syscall_def!("set_thread_area", sys_set_thread_area, [Hex])),
+    (244, syscall_def!("get_thread_area", sys_get_thread_area, [Hex])),
+    (252, syscall_def!(noreturn "exit_group", sys_exit, [Int])),
+    (254, syscall_def!("epoll_create", epoll_create, [Int])),
+    (255, syscall_def!("epoll_ctl", epoll_ctl, [Int, Int, Int, Hex])),
+    (256, syscall_def!("epoll_wait", epoll_wait, [Int, Hex, Int, Int])),
+    (258, syscall_def!("set_tid_address", linux_set_tid_address, [Hex])),
+    (265, syscall_def!("clock_gettime", linux_clock_gettime, [Int, Hex])),
+    (270, syscall_def!("tgkill", linux_tgkill, [Int, Int, Int])),
+    (295, syscall_def!("openat", linux_openat, [Int, Str, Hex, Oct])),
+    (296, syscall_def!("mkdirat", mkdirat, [Int, Str, Oct])),
+    (298, syscall_def!("fchownat", fchownat, [Int, Str, Int, Int, Hex])),
+    (300, syscall_def!("fstatat64", linux_fstatat64, [Int, Str, Hex, Hex])),
+    (301, syscall_def!("unlinkat", unlinkat, [Int, Str, Hex])),
+    (302, syscall_def!("renameat", renameat, [Int, Str, Int, Str])),
+    (305, syscall_def!("readlinkat", readlinkat, [Int, Str, Hex, Int])),
+    (306, syscall_def!("fchmodat", fchmodat, [Int, Str, Oct, Hex])),
+    (307, syscall_def!("faccessat", faccessat, [Int, Str, Oct, Hex])),
+    (309, syscall_def!("ppoll", linux_ppoll, [Hex, Int, Hex, Hex, Int])),
+    (313, syscall_def!("splice", linux_splice, [Int, Hex, Int, Hex, Int, Hex])),
+    (321, syscall_def!("signalfd", linux_signalfd, [Int, Hex, Int])),
+    (322, syscall_def!("timerfd_create", linux_timerfd_create, [Int, Hex])),
+    (323, syscall_def!("eventfd", linux_eventfd, [Int])),
+    (325, syscall_def!("timerfd_settime", linux_timerfd_settime, [Int, Hex, Hex, Hex])),
+    (326, syscall_def!("timerfd_gettime", linux_timerfd_gettime, [Int, Hex])),
+    (327, syscall_def!("signalfd4", linux_signalfd4, [Int, Hex, Int, Hex])),
+    (328, syscall_def!("eventfd2", linux_eventfd2, [Int, Hex])),
+    (329, syscall_def!("epoll_create1", linux_epoll_create1, [Hex])),
+    (330, syscall_def!("dup3", linux_dup3, [Int, Int, Hex])),
+    (333, syscall_def!("preadv", linux_preadv, [Int, Hex, Int, Hex, Hex])),
+    (334, syscall_def!("pwritev", linux_pwritev, [Int, Hex, Int, Hex, Hex])),
+];
+
+/** linux syscall `nr` */
+pub fn linux_syscall_lookup(nr: usize) -> Option<&'static SyscallDef> {
+    LINUX_SYSCALL_TABLE.binary_search_by_key(&nr, |&(lnr, _)| lnr)
+        .ok()
+        .map(|idx| &LINUX_SYSCALL_TABLE[idx].1)
+}
@@ -4,6 +4,12 @@ pub mod fork;
 pub mod syscall;
 pub mod execve;
 pub mod binfmt;
+pub mod ipc;
+pub mod futex;
+pub mod timer;
+pub mod coredump;
+pub mod ptrace;
+pub mod linux;
 
 pub mod nest;
 pub mod session;
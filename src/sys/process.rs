@@ -3,18 +3,131 @@ use prelude::*;
 use arch::mm::i386::*;
 use arch::platform::pc::reboot::arch_reboot;
 use arch::sys::*;
+use bits::fcntl::*;
+use bits::resource::*;
 use fs::*;
 use mm::*;
+use sys::ipc::*;
 use sys::pgroup::*;
+use sys::ptrace::*;
 use sys::sched::*;
 use sys::session::*;
 use sys::signal::*;
 use sys::thread::*;
-use sys::syscall::file::{FileDescriptor, FileBackend};
+use sys::binfmt::binfmt::USER_STACK;
+use sys::syscall::file::{FileDescriptor, FileBackend, file_alloc, file_ref, file_unref};
 
 malloc_define!(M_PROC, "proc\0", "process structure\0");
 malloc_define!(M_FDS, "fds\0", "file descriptor array\0"); /* FIXME */
 
+/** thread-local storage template of the executable (PT_TLS) */
+#[derive(Copy, Clone, Debug)]
+pub struct TlsTemplate {
+    /** address of the initialization image */
+    pub image: usize,
+
+    /** size of the initialization image */
+    pub filesz: usize,
+
+    /** size of the TLS block */
+    pub memsz: usize,
+
+    /** alignment of the TLS block */
+    pub align: usize,
+}
+
+impl TlsTemplate {
+    pub const fn none() -> Self {
+        TlsTemplate {
+            image: 0,
+            filesz: 0,
+            memsz: 0,
+            align: 0,
+        }
+    }
+}
+
+/** image information passed to the program in the auxiliary vector */
+#[derive(Copy, Clone, Debug)]
+pub struct ExecAux {
+    /** address of the program headers */
+    pub phdr: usize,
+
+    /** size of a program header */
+    pub phent: usize,
+
+    /** number of program headers */
+    pub phnum: usize,
+
+    /** entry point of the program, `Process::entry` may be the interpreter's */
+    pub entry: usize,
+
+    /** base address of the interpreter, 0 if none */
+    pub base: usize,
+}
+
+impl ExecAux {
+    pub const fn none() -> Self {
+        ExecAux {
+            phdr: 0,
+            phent: 0,
+            phnum: 0,
+            entry: 0,
+            base: 0,
+        }
+    }
+}
+
+/** user address ranges of the argument and environment strings */
+#[derive(Copy, Clone, Debug)]
+pub struct ExecArgs {
+    pub arg_start: usize,
+    pub arg_end: usize,
+    pub env_start: usize,
+    pub env_end: usize,
+}
+
+impl ExecArgs {
+    pub const fn none() -> Self {
+        ExecArgs {
+            arg_start: 0,
+            arg_end: 0,
+            env_start: 0,
+            env_end: 0,
+        }
+    }
+}
+
+/** syscall numbering and structure layouts the process expects */
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Personality {
+    /** native aquila binaries */
+    Aquila = 0,
+
+    /** statically linked linux i386 binaries */
+    Linux  = 1,
+}
+
+/** slot of a descriptor table */
+#[derive(Copy, Clone, Debug)]
+pub struct FdEntry {
+    /** open file, shared with dups and forks, null while the slot is free */
+    pub file: *mut FileDescriptor,
+
+    /** FD_* flags of the descriptor */
+    pub flags: usize,
+}
+
+/** file of a slot handed out by proc_fd_get but not opened yet */
+pub const FD_RESERVED: *mut FileDescriptor = -1isize as usize as *mut FileDescriptor;
+
+/** largest descriptor table, RLIMIT_NOFILE */
+pub const FD_MAX: usize = 1024;
+
+/** slots of a new descriptor table */
+const FDS_INITIAL: usize = 64;
+
 #[derive(Debug)]
 pub struct Process {
     /** process id */
@@ -27,8 +140,11 @@ pub struct Process {
     /** process name - XXX */
     pub name: *mut u8,
 
-    /** open file descriptors */
-    pub fds: *mut FileDescriptor,
+    /** descriptor table, grown as needed */
+    pub fds: *mut FdEntry,
+
+    /** number of slots in the descriptor table */
+    pub fds_count: usize,
 
     /** parent process */
     pub parent: *mut Process,
@@ -36,6 +152,9 @@ pub struct Process {
     /** current working directory */
     pub cwd: String,
 
+    /** root directory, paths never resolve above it */
+    pub root: String,
+
     /** file mode creation mask */
     pub mask: mode_t,
 
@@ -54,6 +173,21 @@ pub struct Process {
     /** process entry point */  
     pub entry: usize,
 
+    /** thread-local storage template */
+    pub tls: TlsTemplate,
+
+    /** auxiliary vector information of the loaded image */
+    pub aux: ExecAux,
+
+    /** argument and environment strings on the user stack */
+    pub args: ExecArgs,
+
+    /** syscall ABI of the loaded image */
+    pub personality: Personality,
+
+    /** maximum size of a core dump (RLIMIT_CORE) */
+    pub rlimit_core: RLimit,
+
     /** virtual memory regions */
     pub vm_space: VmSpace,
 
@@ -78,6 +212,21 @@ pub struct Process {
     /** exit status of process */
     pub exit: isize,
 
+    /** process tracing this one, null if not traced */
+    pub tracer: *mut Process,
+
+    /** ptrace state flags (PT_*) */
+    pub ptrace: usize,
+
+    /** signal reported to the tracer while stopped, signal to deliver once resumed */
+    pub ptrace_sig: isize,
+
+    /** thread stopped for the tracer */
+    pub ptrace_thread: *mut Thread,
+
+    /** stopped threads wait here for the tracer */
+    pub ptrace_queue: Queue<*mut Thread>,
+
     /** process is running? */
     pub running: isize,
 }
@@ -96,7 +245,7 @@ pub macro proc_uio {
             gid:  (*$proc).gid,
             mask: (*$proc).mask,
             flags: 0,
-            root: &"",
+            root: &(*$proc).root,
         }
     }
 }
@@ -107,10 +256,16 @@ impl Process {
     }
 
     pub fn new_thread(&mut self, thread_ref: *mut *mut Thread) -> isize {
+        let tid = unsafe { thread_tid_alloc() };
+
+        if tid == -1 {
+            return -EAGAIN;
+        }
+
         let mut thread = Box::leak(Thread::alloc());
 
         thread.owner = self;
-        thread.tid = (self.threads.count() + 1) as tid_t;
+        thread.tid = tid as tid_t;
 
         self.threads.enqueue(thread);
 
@@ -120,6 +275,13 @@ impl Process {
 
         return 0;
     }
+
+    /** look for the thread with id `tid` in the process */
+    pub fn find_thread(&self, tid: tid_t) -> Option<*mut Thread> {
+        self.threads.iter()
+            .map(|qnode| qnode.value)
+            .find(|&thread| unsafe { (*thread).tid == tid })
+    }
 }
 
 /* all processes */
@@ -183,6 +345,7 @@ pub unsafe fn proc_new(proc_ref: *mut *mut Process) -> isize {
         //(*proc).sigaction[i].sa_handler = SIG_DFL;
     }
 
+    proc.rlimit_core = RLimit::infinity();
     proc.running = 1;
 
     /* add process to all processes queue */
@@ -209,9 +372,6 @@ pub unsafe extern "C" fn proc_pid_find(pid: pid_t) -> *mut Process {
     return core::ptr::null_mut();
 }
 
-// FIXME
-const FDS_COUNT: usize = 64;
-
 #[no_mangle]
 pub unsafe extern "C" fn proc_init(proc: *mut Process) -> isize {
     if proc.is_null() {
@@ -221,7 +381,8 @@ pub unsafe extern "C" fn proc_init(proc: *mut Process) -> isize {
     let mut err = 0;
 
     (*proc).pid = proc_pid_alloc();
-    (*proc).fds = kmalloc(FDS_COUNT * core::mem::size_of::<FileDescriptor>(), &M_FDS, M_ZERO) as *mut FileDescriptor;
+    (*proc).fds = kmalloc(FDS_INITIAL * core::mem::size_of::<FdEntry>(), &M_FDS, M_ZERO) as *mut FdEntry;
+    (*proc).fds_count = FDS_INITIAL;
 
     if (*proc).fds.is_null() {
         err = -ENOMEM;
@@ -261,21 +422,16 @@ pub unsafe extern "C" fn proc_kill(proc: *mut Process) {
 
     (*proc).running = 0;
 
+    /* detach from our tracer and from our tracees */
+    ptrace_exit(proc);
+
     let mut kill_curthread = 0;
 
     /* kill all threads */
     while (*proc).threads.count() > 0 {
         let thread = (*proc).threads.dequeue().unwrap();
 
-        if !(*thread).sleep_node.is_null() {
-            /* thread is sleeping on some queue */
-            (*(*thread).sleep_queue).node_remove((*thread).sleep_node);
-        }
-
-        if !(*thread).sched_node.is_null() {
-            /* thread is in the scheduler queue */
-            (*(*thread).sched_queue).node_remove((*thread).sched_node);
-        }
+        thread_tid_free((*thread).tid as isize);
 
         if thread == curthread!() {
             kill_curthread = 1;
@@ -287,14 +443,13 @@ pub unsafe extern "C" fn proc_kill(proc: *mut Process) {
     }
 
     /* close all file descriptors */
-    for i in 0..FDS_COUNT {
-        //let file = &(*proc).fds[i];
-        //if (*file).backend.vnode && (*file).vnode != -1 as *const _ {
-        //    vfs_file_close(file);
-        //    file->vnode = NULL;
-        //}
+    for fd in 0..(*proc).fds_count {
+        let _ = proc_fd_close(proc, fd as isize);
     }
 
+    /* undo semaphore operations done with SEM_UNDO */
+    sem_exit(proc);
+
     let vm_space = &mut (*proc).vm_space;
 
     vm_space.destroy();
@@ -349,21 +504,182 @@ pub unsafe extern "C" fn proc_reap(proc: *mut Process) -> isize {
     return 0;
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn proc_fd_get(proc: *mut Process) -> isize {
-    for i in 0..FDS_COUNT {
-        if (*(*proc).fds.offset(i as isize)).backend.vnode.is_null() {
-            (*(*proc).fds.offset(i as isize)).backend.vnode = (-1isize as usize) as *mut Node;
-            return i as isize;
+/**
+ * move the break of `proc` to `brk`, the heap may neither run into the
+ * next mapping nor reach into kernel space
+ */
+pub unsafe fn proc_heap_set(proc: *mut Process, brk: usize) -> Result<(), Error> {
+    let heap_vm = (*proc).heap_vm;
+    let start = (*proc).heap_start;
+
+    if brk < start || brk > USER_STACK {
+        return Err(Error::ENOMEM);
+    }
+
+    let size = page_round!(brk - start);
+    let end  = start + size;
+
+    if end > USER_STACK {
+        return Err(Error::ENOMEM);
+    }
+
+    for qnode in (*proc).vm_space.vm_entries.iter() {
+        let vm_entry = qnode.value as *mut VmEntry;
+
+        if vm_entry != heap_vm && (*vm_entry).base < end && (*vm_entry).base + (*vm_entry).size > start {
+            return Err(Error::ENOMEM);
+        }
+    }
+
+    (*proc).heap = brk;
+    (*heap_vm).size = size;
+
+    Ok(())
+}
+
+/** make room for at least `count` descriptors in the table of `proc` */
+unsafe fn proc_fd_grow(proc: *mut Process, count: usize) -> Result<(), Error> {
+    if count <= (*proc).fds_count {
+        return Ok(());
+    }
+
+    if count > FD_MAX {
+        return Err(Error::EMFILE);
+    }
+
+    let new_count = core::cmp::min(core::cmp::max(count, 2 * (*proc).fds_count), FD_MAX);
+    let fds = kmalloc(new_count * core::mem::size_of::<FdEntry>(), &M_FDS, M_ZERO) as *mut FdEntry;
+
+    if fds.is_null() {
+        return Err(Error::ENOMEM);
+    }
+
+    core::ptr::copy_nonoverlapping((*proc).fds, fds, (*proc).fds_count);
+    kfree((*proc).fds as *mut u8);
+
+    (*proc).fds = fds;
+    (*proc).fds_count = new_count;
+
+    Ok(())
+}
+
+/** reserve the lowest free descriptor of `proc` not below `min`, -1 if there is none */
+pub unsafe fn proc_fd_get_from(proc: *mut Process, min: usize) -> isize {
+    for fd in min..FD_MAX {
+        if fd >= (*proc).fds_count && proc_fd_grow(proc, fd + 1).is_err() {
+            break;
+        }
+
+        let entry = (*proc).fds.offset(fd as isize);
+
+        if (*entry).file.is_null() {
+            (*entry).file  = FD_RESERVED;
+            (*entry).flags = 0;
+            return fd as isize;
         }
     }
 
     return -1;
 }
 
+/**
+ * reserve descriptor `fd` of `proc` whether free or not, closing the file
+ * it referred to only once the slot is taken
+ */
+pub unsafe fn proc_fd_reserve(proc: *mut Process, fd: isize) -> Result<(), Error> {
+    if fd < 0 || fd as usize >= FD_MAX {
+        return Err(Error::EBADF);
+    }
+
+    proc_fd_grow(proc, fd as usize + 1)?;
+
+    let entry = (*proc).fds.offset(fd);
+    let file = (*entry).file;
+
+    /* being opened by another thread */
+    if file == FD_RESERVED {
+        return Err(Error::EBUSY);
+    }
+
+    (*entry).file  = FD_RESERVED;
+    (*entry).flags = 0;
+
+    if !file.is_null() {
+        lock_close(proc, file);
+        let _ = file_unref(file);
+    }
+
+    Ok(())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proc_fd_get(proc: *mut Process) -> isize {
+    proc_fd_get_from(proc, 0)
+}
+
+/** give back a slot reserved by proc_fd_get which was not opened */
 #[no_mangle]
 pub unsafe extern "C" fn proc_fd_release(proc: *mut Process, fd: isize) {
-    if (fd as usize) < FDS_COUNT {
-        (*(*proc).fds.offset(fd)).backend.vnode = core::ptr::null_mut();
+    if fd >= 0 && (fd as usize) < (*proc).fds_count {
+        (*(*proc).fds.offset(fd)).file = core::ptr::null_mut();
+    }
+}
+
+/** the open file behind descriptor `fd` of `proc` */
+pub unsafe fn proc_fd_file(proc: *mut Process, fd: isize) -> Option<*mut FileDescriptor> {
+    if fd < 0 || (fd as usize) >= (*proc).fds_count || (*proc).fds.is_null() {
+        return None;
+    }
+
+    let file = (*(*proc).fds.offset(fd)).file;
+
+    if file.is_null() || file == FD_RESERVED {
+        return None;
+    }
+
+    Some(file)
+}
+
+/** point the reserved or free descriptor `fd` of `proc` at `file`, taking a reference */
+pub unsafe fn proc_fd_set(proc: *mut Process, fd: isize, file: *mut FileDescriptor, flags: usize) {
+    let entry = (*proc).fds.offset(fd);
+
+    file_ref(file);
+
+    (*entry).file  = file;
+    (*entry).flags = flags;
+}
+
+/**
+ * install `file`, opened with O_CLOEXEC or not, as the reserved descriptor
+ * `fd` of `proc`. the file becomes an open file of its own
+ */
+pub unsafe fn proc_fd_install(proc: *mut Process, fd: isize, file: FileDescriptor) {
+    let flags = if file.flags & O_CLOEXEC != 0 { FD_CLOEXEC } else { 0 };
+    let file = file_alloc(FileDescriptor { flags: file.flags & !O_CLOEXEC, ..file });
+
+    /* the descriptor holds the only reference */
+    (*(*proc).fds.offset(fd)).file  = file;
+    (*(*proc).fds.offset(fd)).flags = flags;
+}
+
+/** close descriptor `fd` of `proc`, the open file goes with its last descriptor */
+pub unsafe fn proc_fd_close(proc: *mut Process, fd: isize) -> Result<(), Error> {
+    let file = proc_fd_file(proc, fd).ok_or(Error::EBADF)?;
+
+    (*(*proc).fds.offset(fd)).file = core::ptr::null_mut();
+
+    /* record locks of the process on the file go with any of its descriptors */
+    lock_close(proc, file);
+
+    file_unref(file)
+}
+
+/** close the descriptors of `proc` marked FD_CLOEXEC, on exec */
+pub unsafe fn proc_fd_cloexec(proc: *mut Process) {
+    for fd in 0..(*proc).fds_count {
+        if (*(*proc).fds.offset(fd as isize)).flags & FD_CLOEXEC != 0 {
+            let _ = proc_fd_close(proc, fd as isize);
+        }
     }
 }
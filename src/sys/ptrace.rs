@@ -0,0 +1,320 @@
+use prelude::*;
+
+use arch::mm::i386::*;
+use arch::sys::sched::arch_sleep;
+use arch::sys::thread::*;
+use bits::ptrace::*;
+use mm::*;
+use sys::process::*;
+use sys::signal::*;
+use sys::thread::*;
+
+use crate::{page_align};
+
+/* ptrace state flags */
+pub const PT_TRACED   : usize = 0x01; /**< traced by `Process::tracer` */
+pub const PT_SYSCALL  : usize = 0x02; /**< stop at syscall entry and exit */
+pub const PT_STOPPED  : usize = 0x04; /**< stopped, waiting for the tracer */
+pub const PT_REPORTED : usize = 0x08; /**< current stop was reported by waitpid */
+
+pub unsafe fn ptrace_traced(proc: *mut Process) -> bool {
+    (*proc).ptrace & PT_TRACED != 0
+}
+
+/** is `proc` traced and should stop at syscall entry and exit? */
+pub unsafe fn ptrace_syscall_traced(proc: *mut Process) -> bool {
+    (*proc).ptrace & (PT_TRACED | PT_SYSCALL) == PT_TRACED | PT_SYSCALL
+}
+
+/**
+ * stop the current thread of a traced process and report `sig` to the
+ * tracer, returns the signal the tracer resumed us with, 0 for none
+ */
+pub unsafe fn ptrace_stop(sig: isize) -> isize {
+    let proc = curproc!();
+    let thread = curthread!();
+
+    if !ptrace_traced(proc) {
+        return sig;
+    }
+
+    (*proc).ptrace_sig = sig;
+    (*proc).ptrace_thread = thread;
+    (*proc).ptrace = ((*proc).ptrace & !PT_REPORTED) | PT_STOPPED;
+
+    let tracer = (*proc).tracer;
+
+    thread_queue_wakeup(&mut (*tracer).wait_queue);
+    signal_proc_send(tracer, SIGCHLD);
+
+    /* only the tracer or SIGKILL gets us out of here */
+    while (*proc).ptrace & PT_STOPPED != 0 {
+        (*thread).sleep_node  = (*proc).ptrace_queue.enqueue(thread);
+        (*thread).sleep_queue = &mut (*proc).ptrace_queue;
+        (*thread).state = ThreadState::USLEEP;
+
+        arch_sleep();
+
+        (*thread).sleep_queue = core::ptr::null_mut();
+    }
+
+    (*thread).state = ThreadState::RUNNABLE;
+    (*proc).ptrace_thread = core::ptr::null_mut();
+
+    (*proc).ptrace_sig
+}
+
+/** resume a stopped tracee, delivering `sig` if not 0 */
+pub unsafe fn ptrace_resume(proc: *mut Process, sig: isize) {
+    (*proc).ptrace_sig = sig;
+    (*proc).ptrace &= !(PT_STOPPED | PT_REPORTED);
+
+    thread_queue_wakeup(&mut (*proc).ptrace_queue);
+}
+
+/** stop tracing `proc`, resuming it with `sig` if it is stopped */
+pub unsafe fn ptrace_detach(proc: *mut Process, sig: isize) {
+    let thread = (*proc).ptrace_thread;
+
+    if !thread.is_null() {
+        arch_thread_singlestep(thread, false);
+    }
+
+    let stopped = (*proc).ptrace & PT_STOPPED != 0;
+
+    (*proc).tracer = core::ptr::null_mut();
+    (*proc).ptrace = 0;
+
+    if stopped {
+        ptrace_resume(proc, sig);
+    }
+}
+
+/**
+ * wait status of a tracee stop not yet reported to `tracer`, marks the
+ * stop as reported
+ */
+pub unsafe fn ptrace_wait_status(proc: *mut Process, tracer: *mut Process) -> Option<isize> {
+    if (*proc).tracer != tracer || (*proc).ptrace & (PT_STOPPED | PT_REPORTED) != PT_STOPPED {
+        return None;
+    }
+
+    (*proc).ptrace |= PT_REPORTED;
+
+    /* WIFSTOPPED */
+    Some(((*proc).ptrace_sig & 0xff) << 8 | 0x7f)
+}
+
+/** a dying process stops being traced and stops tracing others */
+pub unsafe fn ptrace_exit(proc: *mut Process) {
+    if ptrace_traced(proc) {
+        let tracer = (*proc).tracer;
+
+        (*proc).tracer = core::ptr::null_mut();
+        (*proc).ptrace = 0;
+
+        if (*proc).parent != tracer {
+            thread_queue_wakeup(&mut (*tracer).wait_queue);
+        }
+    }
+
+    for qnode in PROCS.iter() {
+        let tracee = qnode.value;
+
+        if (*tracee).tracer == proc {
+            ptrace_detach(tracee, 0);
+        }
+    }
+}
+
+/**
+ * copy `size` bytes between `buf` and `addr` in the address space of
+ * `proc`. like FOLL_FORCE in linux, writes to private read-only mappings
+ * go to a private copy of the page so breakpoints can be set in text
+ */
+pub unsafe fn ptrace_access(proc: *mut Process, addr: usize, buf: *mut u8, size: usize, write: bool) -> Result<(), Error> {
+    let vm_space = &mut (*proc).vm_space as *mut VmSpace;
+    let pmap = (*vm_space).pmap;
+
+    let mut done = 0;
+
+    while done < size {
+        let vaddr = addr + done;
+        let page = page_align!(vaddr);
+        let off = vaddr - page;
+        let len = if size - done > PAGE_SIZE - off { PAGE_SIZE - off } else { size - done };
+
+        let vm_entry = match (*vm_space).find(page) {
+            Some(vm_entry) => vm_entry as *const VmEntry as *mut VmEntry,
+            None => return Err(Error::EIO),
+        };
+
+        let flags = (*vm_entry).flags;
+        let force = write && flags & VM_UW == 0;
+
+        if force && flags & VM_SHARED != 0 {
+            return Err(Error::EIO);
+        }
+
+        let mapped = arch_page_get_mapping(pmap, page) != 0;
+
+        if write || !mapped {
+            let fault = (if write { PF_WRITE } else { PF_READ }) | (if mapped { PF_PRESENT } else { 0 });
+
+            if force {
+                (*vm_entry).flags |= VM_UW;
+            }
+
+            let old_map = pmap_switch(pmap);
+            let err = vm_space_fault(vm_space, page, fault);
+            pmap_switch(old_map);
+
+            if force {
+                (*vm_entry).flags = flags;
+                pmap_protect(pmap, page, page + PAGE_SIZE, (flags & VM_PERM) as u32);
+            }
+
+            if err != 0 {
+                return Err(Error::EIO);
+            }
+        }
+
+        let paddr = arch_page_get_mapping(pmap, page);
+
+        if paddr == 0 {
+            return Err(Error::EIO);
+        }
+
+        if write {
+            pmap_page_write(paddr, off, len, buf.offset(done as isize));
+        } else {
+            pmap_page_read(paddr, off, len, buf.offset(done as isize));
+        }
+
+        done += len;
+    }
+
+    Ok(())
+}
+
+/** find a tracee of `tracer` stopped for it */
+unsafe fn ptrace_tracee(tracer: *mut Process, pid: pid_t) -> Result<*mut Process, Error> {
+    let proc = proc_pid_find(pid);
+
+    if proc.is_null() || (*proc).tracer != tracer || (*proc).ptrace & PT_STOPPED == 0 {
+        return Err(Error::ESRCH);
+    }
+
+    Ok(proc)
+}
+
+pub unsafe fn ptrace(tracer: *mut Process, request: usize, pid: pid_t, addr: usize, data: usize) -> Result<isize, Error> {
+    match request {
+        PTRACE_TRACEME => {
+            let parent = (*tracer).parent;
+
+            if ptrace_traced(tracer) || parent.is_null() {
+                return Err(Error::EPERM);
+            }
+
+            (*tracer).tracer = parent;
+            (*tracer).ptrace = PT_TRACED;
+
+            return Ok(0);
+        },
+        PTRACE_ATTACH => {
+            let proc = proc_pid_find(pid);
+
+            if proc.is_null() || (*proc).running == 0 {
+                return Err(Error::ESRCH);
+            }
+
+            if proc == tracer || ptrace_traced(proc) || (*proc).pid == 1 {
+                return Err(Error::EPERM);
+            }
+
+            if (*tracer).uid != 0 && (*tracer).uid != (*proc).uid {
+                return Err(Error::EPERM);
+            }
+
+            (*proc).tracer = tracer;
+            (*proc).ptrace = PT_TRACED;
+
+            /* the tracee reports the SIGSTOP as its first stop */
+            signal_proc_send(proc, SIGSTOP);
+
+            return Ok(0);
+        },
+        _ => {},
+    }
+
+    let proc = ptrace_tracee(tracer, pid)?;
+    let thread = (*proc).ptrace_thread;
+
+    if data > SIG_MAX && (request == PTRACE_CONT || request == PTRACE_SYSCALL || request == PTRACE_SINGLESTEP || request == PTRACE_DETACH) {
+        return Err(Error::EIO);
+    }
+
+    match request {
+        PTRACE_PEEKTEXT | PTRACE_PEEKDATA => {
+            let mut word: usize = 0;
+
+            ptrace_access(proc, addr, &mut word as *mut usize as *mut u8, core::mem::size_of::<usize>(), false)?;
+
+            /* the word is stored at `data`, the return value is left for errors */
+            copyout(data as *mut usize, &word)?;
+        },
+        PTRACE_POKETEXT | PTRACE_POKEDATA => {
+            let mut word = data;
+
+            ptrace_access(proc, addr, &mut word as *mut usize as *mut u8, core::mem::size_of::<usize>(), true)?;
+        },
+        PTRACE_PEEKUSER => {
+            let idx = addr / core::mem::size_of::<usize>();
+
+            if addr % core::mem::size_of::<usize>() != 0 || idx >= ELF_NGREG {
+                return Err(Error::EIO);
+            }
+
+            copyout(data as *mut usize, &arch_thread_gregs(thread)[idx])?;
+        },
+        PTRACE_POKEUSER => {
+            let idx = addr / core::mem::size_of::<usize>();
+
+            if addr % core::mem::size_of::<usize>() != 0 || idx >= ELF_NGREG {
+                return Err(Error::EIO);
+            }
+
+            let mut gregs = arch_thread_gregs(thread);
+            gregs[idx] = data;
+            arch_thread_set_gregs(thread, &gregs);
+        },
+        PTRACE_GETREGS => {
+            copyout(data as *mut [usize; ELF_NGREG], &arch_thread_gregs(thread))?;
+        },
+        PTRACE_SETREGS => {
+            let gregs = copyin(data as *const [usize; ELF_NGREG])?;
+            arch_thread_set_gregs(thread, &gregs);
+        },
+        PTRACE_CONT | PTRACE_SYSCALL | PTRACE_SINGLESTEP => {
+            if request == PTRACE_SYSCALL {
+                (*proc).ptrace |= PT_SYSCALL;
+            } else {
+                (*proc).ptrace &= !PT_SYSCALL;
+            }
+
+            arch_thread_singlestep(thread, request == PTRACE_SINGLESTEP);
+            ptrace_resume(proc, data as isize);
+        },
+        PTRACE_KILL => {
+            arch_thread_singlestep(thread, false);
+            ptrace_resume(proc, SIGKILL);
+        },
+        PTRACE_DETACH => {
+            ptrace_detach(proc, data as isize);
+        },
+        _ => return Err(Error::EIO),
+    }
+
+    Ok(0)
+}
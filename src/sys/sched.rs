@@ -3,9 +3,13 @@ use prelude::*;
 use sys::session::*;
 use sys::process::*;
 use sys::thread::*;
+use sys::timer::*;
 use arch::sys::*;
 
 pub static mut READY_QUEUE: Queue<*mut Thread> = Queue::empty();
+/** threads sleeping with a deadline, see thread_queue_sleep_timeout */
+pub static mut SLEEP_TIMEOUTS: Queue<*mut Thread> = Queue::empty();
+
 pub static mut _CURTHREAD: *mut Thread = core::ptr::null_mut();
 
 pub macro curthread {
@@ -47,6 +51,7 @@ pub unsafe fn sched_init_spawn(init: *mut Process) {
     /* init defaults */
     (*init).mask = 0775;
     (*init).cwd = "/".to_owned();
+    (*init).root = "/".to_owned();
 
     arch_sched_init();
 
@@ -62,8 +67,35 @@ pub unsafe fn sched_init_spawn(init: *mut Process) {
     sched_thread_spawn(curthread!());
 }
 
+/* wake up threads whose sleep deadline has passed */
+unsafe fn sched_timeouts() {
+    if SLEEP_TIMEOUTS.count() == 0 {
+        return;
+    }
+
+    let now = arch_rtime_ns();
+
+    for qnode in SLEEP_TIMEOUTS.iter() {
+        let thread = qnode.value;
+
+        /* already woken up, but not yet running */
+        if (*thread).sleep_node.is_null() || (*thread).sleep_deadline > now {
+            continue;
+        }
+
+        (*(*thread).sleep_queue).node_remove((*thread).sleep_node);
+        (*thread).sleep_node = core::ptr::null_mut();
+        (*thread).sleep_timedout = true;
+
+        sched_thread_ready(thread);
+    }
+}
+
 /* called from arch-specific timer event handler */
 pub unsafe fn schedule() {
+    sched_timeouts();
+    timer_run();
+
     if kidle == 0 {
         sched_thread_ready(curthread!());
     }
@@ -1,8 +1,10 @@
 use prelude::*;
 
 use arch;
+use fs;
 use sys::pgroup::*;
 use sys::process::*;
+use sys::ptrace::*;
 use sys::sched::*;
 use sys::session::*;
 use sys::thread::*;
@@ -42,6 +44,19 @@ pub const SIG_MAX:  usize = 26;
 
 pub const SIG_DFL:  usize = 0; /* Default action */
 
+/* sigmask(2) operations */
+pub const SIG_SETMASK: isize = 0; /* Set the mask to the given set */
+pub const SIG_BLOCK:   isize = 1; /* Add the given set to the mask */
+pub const SIG_UNBLOCK: isize = 2; /* Remove the given set from the mask */
+
+/** `sig` as a member of a sigset_t */
+pub const fn sig_bit(sig: isize) -> sigset_t {
+    1 << (sig - 1)
+}
+
+/** signals which can be neither blocked nor read from a signalfd */
+pub const SIG_UNBLOCKABLE: sigset_t = sig_bit(SIGKILL) | sig_bit(SIGSTOP);
+
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub enum SignalDefaultAction {
@@ -95,17 +110,74 @@ pub static sig_default_action: [SignalDefaultAction; SIG_MAX+1] = [
     /* SIGUSR2  */ SignalDefaultAction::SIGACT_TERMINATE,
 ];
 
+/** is `sig` held back from delivery to `thread`? */
+pub unsafe fn signal_blocked(thread: *mut Thread, sig: isize) -> bool {
+    (*thread).sig_mask & sig_bit(sig) != 0
+}
+
+/** does `thread` have a signal waiting for delivery which it does not block? */
+pub unsafe fn signal_pending(thread: *mut Thread) -> bool {
+    /* sig_pending has bit `sig` set, the mask bit `sig - 1` */
+    if (*thread).sig_pending & !(((*thread).sig_mask as u32) << 1) != 0 {
+        return true;
+    }
+
+    match (*(*thread).owner).sig_queue.as_ref() {
+        Some(queue) => queue.iter().any(|qnode| !signal_blocked(thread, qnode.value)),
+        None => false,
+    }
+}
+
+/** change the blocked signals of the current thread as `how` says, returns the old mask */
+pub unsafe fn signal_mask(how: isize, set: Option<sigset_t>) -> Result<sigset_t, Error> {
+    let thread = curthread!();
+    let old = (*thread).sig_mask;
+
+    if let Some(set) = set {
+        let mask = match how {
+            SIG_BLOCK   => old | set,
+            SIG_UNBLOCK => old & !set,
+            SIG_SETMASK => set,
+            _ => return Err(Error::EINVAL),
+        };
+
+        /* unblocked signals are delivered on the next switch to the thread */
+        (*thread).sig_mask = mask & !SIG_UNBLOCKABLE;
+    }
+
+    Ok(old)
+}
+
+/** take the oldest signal in `set` off the queue of `proc` */
+pub unsafe fn signal_dequeue(proc: *mut Process, set: sigset_t) -> Option<isize> {
+    let queue = (*proc).sig_queue.as_mut()?;
+    let sig = queue.iter().map(|qnode| qnode.value).find(|&sig| set & sig_bit(sig) != 0)?;
+
+    /* removes the first node holding `sig`, which is the one found */
+    queue.remove(sig);
+
+    Some(sig)
+}
+
 pub unsafe fn signal_proc_send(proc: *mut Process, signal: isize) -> isize {
-    if proc == curproc!() {
+    if proc == curproc!() && !signal_blocked(curthread!(), signal) {
         arch::handle_signal(signal as usize);
+    } else if signal == SIGKILL && (*proc).ptrace & PT_STOPPED != 0 {
+        /* a stopped tracee only wakes up for its tracer, resume it straight into SIGKILL */
+        ptrace_resume(proc, SIGKILL);
     } else {
         (*proc).sig_queue.as_mut().unwrap().enqueue(signal);
 
-        /* wake up main thread if sleeping - XXX */
-        let thread = (*proc).threads.head().unwrap().value;
+        /* signalfds may have something to read now */
+        fs::signalfd_notify(proc);
 
-        if (*thread).state == ThreadState::ISLEEP {
-            thread_queue_wakeup((*thread).sleep_queue);
+        /* any thread taking the signal delivers it, interrupt one if it sleeps */
+        let sleeper = (*proc).threads.iter()
+            .map(|qnode| qnode.value)
+            .find(|&thread| !signal_blocked(thread, signal) && (*thread).state == ThreadState::ISLEEP);
+
+        if let Some(thread) = sleeper {
+            thread_interrupt(thread);
         }
     }
 
@@ -123,8 +195,7 @@ pub unsafe fn signal_pgrp_send(pg: *mut ProcessGroup, signal: isize) -> isize {
 
 pub unsafe fn signal_send(pid: pid_t, signal: isize) -> isize {
     if (*curproc!()).pid == pid {
-        arch::handle_signal(signal as usize);
-        return 0;
+        return signal_proc_send(curproc!(), signal);
     } else {
         let proc = proc_pid_find(pid);
 
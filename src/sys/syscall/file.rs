@@ -3,8 +3,13 @@ use prelude::*;
 use arch;
 use arch::sys::sched::*;
 use bits::dirent::*;
+use bits::epoll::*;
 use bits::fcntl::*;
 use bits::mman::*;
+use bits::mount::*;
+use bits::poll::*;
+use bits::timerfd::*;
+use bits::uio::*;
 use bits::utsname::*;
 use fs::{self, *};
 use kern::time::*;
@@ -17,22 +22,51 @@ use sys::process::*;
 use sys::sched::*;
 use sys::session::*;
 use sys::signal::*;
+use sys::syscall::table::*;
 use sys::thread::*;
 
-/* XXX */
-const FDS_COUNT: usize = 64;
-
 #[derive(Copy, Clone)]
 pub union FileBackend {
     pub vnode: *mut Node,
     pub socket: *mut Socket,
 }
 
+/** an open file, shared by all descriptors dup'ed or inherited from the one opening it */
 #[derive(Copy, Clone)]
 pub struct FileDescriptor {
     pub backend: FileBackend,
     pub offset: off_t,
+
+    /** access mode and status flags */
     pub flags: usize,
+
+    /** descriptors referring to the open file */
+    pub refcnt: usize,
+}
+
+/** move `file` into a new open file referenced once */
+pub unsafe fn file_alloc(file: FileDescriptor) -> *mut FileDescriptor {
+    Box::leak(Box::new(FileDescriptor { refcnt: 1, ..file }))
+}
+
+pub unsafe fn file_ref(file: *mut FileDescriptor) {
+    (*file).refcnt += 1;
+}
+
+/** drop a reference to `file`, closing it with the last one */
+pub unsafe fn file_unref(file: *mut FileDescriptor) -> Result<(), Error> {
+    (*file).refcnt -= 1;
+
+    if (*file).refcnt > 0 {
+        return Ok(());
+    }
+
+    fs::lock_file_close(file);
+
+    let ret = vfs_file_close(file);
+    Box::from_raw(file);
+
+    Error::wrap_isize_to_usize(ret).map(|_| ())
 }
 
 
@@ -78,6 +112,9 @@ impl FileDescriptor {
                                 thread_queue_wakeup((*self.backend.vnode).write_queue.as_mut().unwrap().as_mut());
                             }
 
+                            /* and whoever polls the file */
+                            poll_notify(self.backend.vnode);
+
                             /* return read bytes count */
                             return retval as isize;
                         } else if vfs_file_eof(self) != 0 {
@@ -112,6 +149,8 @@ impl FileDescriptor {
             if self.flags & O_NONBLOCK != 0 {
                 /* non-blocking I/O */
                 if vfs_file_can_write(self, size) != 0 {
+                    self.append_seek();
+
                     /* write up to `size' from `buf' into file */
                     match (*self.backend.vnode).write(self.offset as usize, size, buf) {
                         Ok(retval) => {
@@ -123,6 +162,9 @@ impl FileDescriptor {
                                 thread_queue_wakeup((*self.backend.vnode).read_queue.as_mut().unwrap().as_mut());
                             }
 
+                            /* and whoever polls the file */
+                            poll_notify(self.backend.vnode);
+
                             /* return written bytes count */
                             return retval as isize;
                         },
@@ -141,10 +183,15 @@ impl FileDescriptor {
                 let mut size = size;
                 
                 while size > 0 {
-                    match (*self.backend.vnode).write(self.offset as usize, size, buf) {
+                    self.append_seek();
+
+                    match (*self.backend.vnode).write(self.offset as usize, size, buf.add(retval as usize - size)) {
                         Ok(retval) => {
                             size -= retval;
 
+                            /* update file offset */
+                            self.offset += retval as isize;
+
                             /* no bytes left to be written, or reached end-of-file */
                             if size == 0 || vfs_file_eof(self) != 0 {
                                 /* done writting */
@@ -163,19 +210,26 @@ impl FileDescriptor {
                 /* store written bytes count */
                 retval -= size as isize;
 
-                /* update file offset */
-                self.offset += retval;
-
                 /* wake up all sleeping readers if a `read_queue' is attached */
                 if !(*self.backend.vnode).read_queue.is_none() {
                     thread_queue_wakeup((*self.backend.vnode).read_queue.as_mut().unwrap().as_mut());
                 }
 
+                /* and whoever polls the file */
+                poll_notify(self.backend.vnode);
+
                 return retval;
             }
         }
     }
 
+    /** move to the end of the file before a write if it is open for appending */
+    unsafe fn append_seek(&mut self) {
+        if self.flags & O_APPEND != 0 {
+            self.offset = (*self.backend.vnode).size() as isize;
+        }
+    }
+
     pub fn readdir(&mut self, dirent: *mut DirectoryEntry) -> isize {
         unsafe {
             if self.flags & O_WRONLY != 0 {
@@ -287,125 +341,426 @@ impl FileDescriptor {
 
 
 
-pub unsafe fn open(path: *const u8, oflags: usize, mode: mode_t) {
+pub unsafe fn open(path: *const u8, oflags: usize, mode: mode_t) -> SyscallResult {
     //syscall_log!(LOG_DEBUG, "open(path={}, oflags={:o}, mode={:o})\n", cstr(path), oflags, mode);
 
+    openat(AT_FDCWD, path, oflags, mode)
+}
+
+pub unsafe fn openat(dirfd: isize, path: *const u8, oflags: usize, mode: mode_t) -> SyscallResult {
+    let (dir, kpath) = user_path_at(dirfd, path)?;
+
     /* look up the file */
     let mut uio = proc_uio!(curproc!());
     uio.flags = oflags;
 
-    match fs::lookup(&cstr(path), &uio) {
-        Err(err) => {
-            /* lookup failed */
-            if (err == ENOENT) && (oflags & O_CREAT) != 0 {
-                match fs::creat(&cstr(path), mode, &mut uio) {
-                    Err(err) => arch::syscall_return(curthread!(), err.unwrap() as usize),
-                    Ok(_) => {
-                        open(path, oflags, mode);
-                        return;
-                    }
+    let (node, realpath) = match fs::lookup_at(dir, &kpath, &uio) {
+        Err(err) if (err == ENOENT) && (oflags & O_CREAT) != 0 => {
+            fs::creat_at(dir, &kpath, mode, &mut uio)?;
+            fs::lookup_at(dir, &kpath, &uio)?
+        },
+        /* lookup failed */
+        Err(err) => return Err(err),
+        Ok(found) => found,
+    };
+
+    /* the lookup stops at a final link, which is not opened in its place */
+    if node.is_symlink() && oflags & O_NOFOLLOW != 0 {
+        return Err(Error::ELOOP);
+    }
+
+    /* flags of the mount the file lives on */
+    if oflags & (O_WRONLY | O_RDWR | O_TRUNC) != 0 {
+        fs::mount_writable(&realpath)?;
+    }
+
+    if node.is_device() && fs::mount_flags(&realpath) & MS_NODEV != 0 {
+        return Err(Error::EACCES);
+    }
+
+    let mut file = FileDescriptor {
+        offset: 0,
+        flags:  oflags,
+        refcnt: 0,
+        backend: FileBackend {
+            vnode: node,
+        }
+    };
+
+    let err = vfs_perms_check(&mut file, &mut uio);
+    if err != 0 {
+        return Error::wrap_isize_to_usize(err);
+    }
+
+    vfs_file_open(&mut file)?;
+
+    let fd = proc_fd_get(curproc!());
+    if fd == -1 {
+        /* reached maximum number of open file descriptors */
+        return Err(Error::EMFILE);
+    }
+
+    proc_fd_install(curproc!(), fd, file);
+
+    /* return the file descriptor */
+    return Ok(fd as usize);
+}
+
+
+pub unsafe fn read(fildes: isize, buf: *mut u8, nbytes: size_t) -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "read(fd=%d, buf=%p, count=%d)\n", fildes, buf, nbytes);
+    
+    if !user_access_ok(buf as usize, nbytes, VM_UW) {
+        return Err(Error::EFAULT);
+    }
+
+    /* reads are bounced through the kernel, large ones may come back short */
+    let len = core::cmp::min(nbytes, UACCESS_CHUNK);
+    let mut kbuf = vec![0u8; len];
+
+    let file = proc_fd_file(curproc!(), fildes).ok_or(Error::EBADF)?;
+    let ret = vfs_file_read(file, kbuf.as_mut_ptr(), len);
+
+    if ret > 0 {
+        copy_to_user(buf, kbuf.as_ptr(), ret as usize)?;
+    }
+
+    Error::wrap_isize_to_usize(ret)
+}
+
+pub unsafe fn write(fd: isize, buf: *mut u8, nbytes: size_t) -> SyscallResult {
+    //syscall_log!(LOG_DEBUG, "write(fd={}, buf={:p}, nbytes={})\n", fd, buf, nbytes);
+    
+    if !user_access_ok(buf as usize, nbytes, VM_UR) {
+        return Err(Error::EFAULT);
+    }
+
+    let file = proc_fd_file(curproc!(), fd).ok_or(Error::EBADF)?;
+    let mut kbuf = vec![0u8; core::cmp::min(nbytes, UACCESS_CHUNK)];
+    let mut written = 0;
+
+    /* copy the data in chunks, stop at the first short write */
+    while written < nbytes {
+        let len = core::cmp::min(nbytes - written, kbuf.len());
+
+        if let Err(err) = copy_from_user(kbuf.as_mut_ptr(), buf.offset(written as isize), len) {
+            if written == 0 {
+                return Err(err);
+            }
+
+            break;
+        }
+
+        let ret = vfs_file_write(file, kbuf.as_mut_ptr(), len);
+
+        if ret < 0 {
+            if written == 0 {
+                return Error::wrap_isize_to_usize(ret);
+            }
+
+            break;
+        }
+
+        written += ret as usize;
+
+        if (ret as usize) < len {
+            break;
+        }
+    }
+
+    Ok(written)
+}
+
+/** can `file` be read from again without possibly sleeping? */
+unsafe fn file_read_ready(file: *mut FileDescriptor) -> bool {
+    if (*file).flags & FILE_SOCKET == 0 {
+        match (*(*file).backend.vnode).node_type() {
+            NodeType::Regular | NodeType::BlkDev => return true,
+            _ => {}
+        }
+    }
+
+    vfs_file_can_read(file, 1) > 0
+}
+
+/** copy in the `iovcnt` buffers at `iov` */
+pub unsafe fn user_iovec(iov: *const IoVec, iovcnt: usize) -> Result<Vec<IoVec>, Error> {
+    if iovcnt > IOV_MAX {
+        return Err(Error::EINVAL);
+    }
+
+    let mut vecs = Vec::with_capacity(iovcnt);
+    let mut total: usize = 0;
+
+    for i in 0..iovcnt {
+        let vec: IoVec = copyin(iov.offset(i as isize))?;
+
+        /* the result has to fit the return value */
+        total = total.checked_add(vec.iov_len).ok_or(Error::EINVAL)?;
+        if total > isize::max_value() as usize {
+            return Err(Error::EINVAL);
+        }
+
+        vecs.push(vec);
+    }
+
+    Ok(vecs)
+}
+
+/**
+ * read into or write from the buffers of `iov` in turn through descriptor
+ * `fd`, at `offset` without moving the file offset if given. stops at the
+ * first short transfer, or before a read which could sleep once something
+ * was read
+ */
+pub unsafe fn vectored_io(fd: isize, iov: &[IoVec], offset: Option<off_t>, write: bool) -> SyscallResult {
+    let file = proc_fd_file(curproc!(), fd).ok_or(Error::EBADF)?;
+
+    if (*file).flags & FILE_SOCKET == 0 && (*file).backend.vnode.is_null() {
+        return Err(Error::EBADF);
+    }
+
+    if offset.is_some() && !vfs_file_seekable(file) {
+        return Err(Error::ESPIPE);
+    }
+
+    let size = iov.iter().map(|vec| vec.iov_len).max().unwrap_or(0);
+    let mut kbuf = vec![0u8; core::cmp::min(size, UACCESS_CHUNK)];
+    let mut total = 0;
+
+    for vec in iov {
+        let mut done = 0;
+
+        while done < vec.iov_len {
+            let len = core::cmp::min(vec.iov_len - done, kbuf.len());
+            let ubuf = vec.iov_base.offset(done as isize);
+
+            if !write && total > 0 && offset.is_none() && !file_read_ready(file) {
+                return Ok(total);
+            }
+
+            let ret = if write {
+                if let Err(err) = copy_from_user(kbuf.as_mut_ptr(), ubuf, len) {
+                    return if total == 0 { Err(err) } else { Ok(total) };
+                }
+
+                match offset {
+                    Some(offset) => vfs_file_pwrite(file, kbuf.as_mut_ptr(), len, offset + total as off_t),
+                    None => vfs_file_write(file, kbuf.as_mut_ptr(), len),
                 }
             } else {
-                arch::syscall_return(curthread!(), err.unwrap() as usize);
-                return;
-            }
-        },
-        Ok((node, _)) => {
-            let mut file = FileDescriptor {
-                offset: 0,
-                flags:  oflags,
-                backend: FileBackend {
-                    vnode: node,
+                match offset {
+                    Some(offset) => vfs_file_pread(file, kbuf.as_mut_ptr(), len, offset + total as off_t),
+                    None => vfs_file_read(file, kbuf.as_mut_ptr(), len),
                 }
             };
 
-            let err = vfs_perms_check(&mut file, &mut uio);
-            if err != 0 {
-                arch::syscall_return(curthread!(), err as usize);
-                return;
+            if ret < 0 {
+                return if total == 0 { Error::wrap_isize_to_usize(ret) } else { Ok(total) };
             }
 
-            if let Err(err) = vfs_file_open(&mut file) {
-                arch::syscall_return(curthread!(), err.unwrap() as usize);
-                return;
+            if !write && ret > 0 {
+                if let Err(err) = copy_to_user(ubuf, kbuf.as_ptr(), ret as usize) {
+                    return if total == 0 { Err(err) } else { Ok(total) };
+                }
             }
 
-            let fd = proc_fd_get(curproc!());
-            if fd == -1 {
-                /* reached maximum number of open file descriptors */
-                arch::syscall_return(curthread!(), -EMFILE as usize);
-                return;
+            done  += ret as usize;
+            total += ret as usize;
+
+            if (ret as usize) < len {
+                return Ok(total);
             }
+        }
+    }
+
+    Ok(total)
+}
+
+pub unsafe fn readv(fd: isize, iov: *const IoVec, iovcnt: usize) -> SyscallResult {
+    vectored_io(fd, &user_iovec(iov, iovcnt)?, None, false)
+}
+
+pub unsafe fn writev(fd: isize, iov: *const IoVec, iovcnt: usize) -> SyscallResult {
+    vectored_io(fd, &user_iovec(iov, iovcnt)?, None, true)
+}
+
+pub unsafe fn preadv(fd: isize, iov: *const IoVec, iovcnt: usize, offset: off_t) -> SyscallResult {
+    vectored_io(fd, &user_iovec(iov, iovcnt)?, Some(offset), false)
+}
+
+pub unsafe fn pwritev(fd: isize, iov: *const IoVec, iovcnt: usize, offset: off_t) -> SyscallResult {
+    vectored_io(fd, &user_iovec(iov, iovcnt)?, Some(offset), true)
+}
+
+pub unsafe fn pread(fd: isize, buf: *mut u8, nbytes: size_t, offset: off_t) -> SyscallResult {
+    vectored_io(fd, &[IoVec { iov_base: buf, iov_len: nbytes }], Some(offset), false)
+}
+
+pub unsafe fn pwrite(fd: isize, buf: *mut u8, nbytes: size_t, offset: off_t) -> SyscallResult {
+    vectored_io(fd, &[IoVec { iov_base: buf, iov_len: nbytes }], Some(offset), true)
+}
+
+/**
+ * move up to `count` bytes from the open file `input` to the open file
+ * `output` through a kernel buffer, at the offsets given for either side
+ * instead of the file offsets, which are advanced past what was moved
+ */
+pub unsafe fn file_transfer(input: *mut FileDescriptor, mut in_off: Option<&mut off_t>, output: *mut FileDescriptor, mut out_off: Option<&mut off_t>, count: usize, nonblock: bool) -> SyscallResult {
+    if (in_off.is_some() && !vfs_file_seekable(input)) || (out_off.is_some() && !vfs_file_seekable(output)) {
+        return Err(Error::ESPIPE);
+    }
+
+    if (*input).flags & O_ACCMODE == O_WRONLY || (*output).flags & O_ACCMODE == O_RDONLY {
+        return Err(Error::EBADF);
+    }
 
-            (*curproc!()).fds.offset(fd).write(file);
+    let mut kbuf = vec![0u8; core::cmp::min(count, UACCESS_CHUNK)];
+    let mut done = 0;
 
-            /* return the file descriptor */
-            arch::syscall_return(curthread!(), fd as usize);
-            return;
+    while done < count {
+        let len = core::cmp::min(count - done, kbuf.len());
+
+        /* once something moved, return rather than wait for more */
+        let would_block = (in_off.is_none() && !file_read_ready(input))
+            || (nonblock && vfs_file_can_write(output, len) == 0);
+
+        if (done > 0 || nonblock) && would_block {
+            if done == 0 {
+                return Err(Error::EAGAIN);
+            }
+
+            break;
+        }
+
+        let got = match in_off {
+            Some(ref off) => vfs_file_pread(input, kbuf.as_mut_ptr(), len, **off),
+            None => vfs_file_read(input, kbuf.as_mut_ptr(), len),
+        };
+
+        if got <= 0 {
+            if got < 0 && done == 0 {
+                return Error::wrap_isize_to_usize(got);
+            }
+
+            break;
+        }
+
+        let got = got as usize;
+        let mut put = 0;
+        let mut err = 0;
+
+        /* a seekable input takes the unwritten tail back, a pipe or socket has to see all of it written */
+        let rewind = in_off.is_some() || vfs_file_seekable(input);
+
+        while put < got {
+            let n = match out_off {
+                Some(ref off) => vfs_file_pwrite(output, kbuf.as_mut_ptr().add(put), got - put, **off + put as off_t),
+                None => vfs_file_write(output, kbuf.as_mut_ptr().add(put), got - put),
+            };
+
+            if n <= 0 {
+                err = n;
+                break;
+            }
+
+            put += n as usize;
+
+            if rewind {
+                break;
+            }
+        }
+
+        if put < got && rewind && in_off.is_none() {
+            (*input).offset -= (got - put) as off_t;
+        }
+
+        if let Some(ref mut off) = in_off {
+            **off += put as off_t;
+        }
+
+        if let Some(ref mut off) = out_off {
+            **off += put as off_t;
+        }
+
+        done += put;
+
+        if err < 0 && done == 0 {
+            return Error::wrap_isize_to_usize(err);
+        }
+
+        if put < got || got < len {
+            break;
         }
     }
+
+    Ok(done)
 }
 
+/** copy `count` bytes from descriptor `in_fd` to `out_fd`, reading at and advancing `*offset` if given */
+pub unsafe fn sendfile(out_fd: isize, in_fd: isize, offset: *mut off_t, count: size_t) -> SyscallResult {
+    let input  = proc_fd_file(curproc!(), in_fd).ok_or(Error::EBADF)?;
+    let output = proc_fd_file(curproc!(), out_fd).ok_or(Error::EBADF)?;
 
-pub unsafe fn read(fildes: isize, buf: *mut u8, nbytes: size_t) {
-    //syscall_log(LOG_DEBUG, "read(fd=%d, buf=%p, count=%d)\n", fildes, buf, nbytes);
-    
-    if fildes < 0 || (fildes as usize) >= FDS_COUNT {  /* Out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
+    if offset.is_null() {
+        return file_transfer(input, None, output, None, count, false);
     }
 
-    let file = (*curproc!()).fds.offset(fildes);
-    let err = vfs_file_read(file, buf, nbytes);
-    arch::syscall_return(curthread!(), err as usize);
-    return;
+    let mut off: off_t = copyin(offset)?;
+    let ret = file_transfer(input, Some(&mut off), output, None, count, false);
+
+    copyout(offset, &off)?;
+    ret
 }
 
-pub unsafe fn write(fd: isize, buf: *mut u8, nbytes: size_t) {
-    //syscall_log!(LOG_DEBUG, "write(fd={}, buf={:p}, nbytes={})\n", fd, buf, nbytes);
-    
-    if fd < 0 || (fd as usize) >= FDS_COUNT {
-        /* out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
+/** move `len` bytes between any two descriptors, at `*off_in` and `*off_out` if given */
+pub unsafe fn splice(fd_in: isize, off_in: *mut off_t, fd_out: isize, off_out: *mut off_t, len: size_t, flags: usize) -> SyscallResult {
+    if flags & !(SPLICE_F_MOVE | SPLICE_F_NONBLOCK | SPLICE_F_MORE | SPLICE_F_GIFT) != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let input  = proc_fd_file(curproc!(), fd_in).ok_or(Error::EBADF)?;
+    let output = proc_fd_file(curproc!(), fd_out).ok_or(Error::EBADF)?;
+
+    let mut in_pos:  Option<off_t> = if off_in.is_null()  { None } else { Some(copyin(off_in)?) };
+    let mut out_pos: Option<off_t> = if off_out.is_null() { None } else { Some(copyin(off_out)?) };
+
+    let ret = file_transfer(input, in_pos.as_mut(), output, out_pos.as_mut(), len, flags & SPLICE_F_NONBLOCK != 0);
+
+    if let Some(pos) = in_pos {
+        copyout(off_in, &pos)?;
+    }
+
+    if let Some(pos) = out_pos {
+        copyout(off_out, &pos)?;
     }
 
-    let file = (*curproc!()).fds.offset(fd);
-    let err = vfs_file_write(file, buf, nbytes);
-    arch::syscall_return(curthread!(), err as usize);
-    return;
+    ret
 }
 
 
-pub unsafe fn ioctl(fd: isize, request: isize, argp: *mut u8) {
+pub unsafe fn ioctl(fd: isize, request: isize, argp: *mut u8) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "ioctl(fd=%d, request=0x%x, argp=%p)\n",
     //        fd, request, argp);
 
-    if fd < 0 || (fd as usize) >= FDS_COUNT {
-        /* out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
-    }
-
-    let file = (*curproc!()).fds.offset(fd);
+    let file = proc_fd_file(curproc!(), fd).ok_or(Error::EBADF)?;
     let err = vfs_file_ioctl(file, request as usize, argp);
-    arch::syscall_return(curthread!(), err as usize);
-    return;
+    Error::wrap_isize_to_usize(err)
 }
 
-pub unsafe fn readdir(fd: isize, dirent: *mut DirectoryEntry) {
+pub unsafe fn readdir(fd: isize, dirent: *mut DirectoryEntry) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "readdir(fd=%d, dirent=%p)\n", fd, dirent);
     
-    if fd < 0 || (fd as usize) >= FDS_COUNT {
-        /* out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
+    let file = proc_fd_file(curproc!(), fd).ok_or(Error::EBADF)?;
+    let mut ent: DirectoryEntry = core::mem::zeroed();
+
+    let err = vfs_file_readdir(file, &mut ent);
+
+    if err > 0 {
+        copyout(dirent, &ent)?;
     }
 
-    let file = (*curproc!()).fds.offset(fd);
-    let err = vfs_file_readdir(file, dirent);
-    arch::syscall_return(curthread!(), err as usize);
-    return;
+    Error::wrap_isize_to_usize(err)
 }
 
 #[repr(C)]
@@ -417,36 +772,57 @@ pub struct MountArgs {
 }
 
 
-pub unsafe fn mount(args: *mut MountArgs) {
+pub unsafe fn mount(args: *mut MountArgs) -> SyscallResult {
     if (*curproc!()).uid != 0 {
-        arch::syscall_return(curthread!(), -EACCES as usize);
-        return;
+        return Err(Error::EACCES);
+    }
+
+    let args = copyin(args)?;
+
+    let dir     = user_path(args.dir)?;
+    let flags   = args.flags as usize;
+    let data    = args.data;
+
+    /* bind mounts take the directory to bind as data */
+    if flags & MS_BIND != 0 && flags & MS_REMOUNT == 0 {
+        let source = user_path(data)?;
+        return fs::bind_mount(&source, &dir, flags, &proc_uio!(curproc!())).map(|_| 0);
     }
 
-    let fs_type = (*args).fs_type;
-    let dir     = (*args).dir;
-    let flags   = (*args).flags;
-    let data    = (*args).data;
+    let fs_type = user_string(args.fs_type, PATH_MAX)?;
+
+    fs::mount(&fs_type, &dir, flags, data, &proc_uio!(curproc!())).map(|_| 0)
+}
+
+pub unsafe fn umount(dir: *const u8) -> SyscallResult {
+    umount2(dir, 0)
+}
 
-    match fs::mount(&cstr(fs_type), &cstr(dir), flags, data, &proc_uio!(curproc!())) {
-        Ok(_) => arch::syscall_return(curthread!(), 0),
-        Err(err) => arch::syscall_return(curthread!(), err.unwrap() as usize),
+pub unsafe fn umount2(dir: *const u8, flags: usize) -> SyscallResult {
+    if (*curproc!()).uid != 0 {
+        return Err(Error::EACCES);
     }
+
+    let dir = user_path(dir)?;
+
+    fs::umount(&dir, flags, &proc_uio!(curproc!())).map(|_| 0)
 }
 
 
-pub unsafe fn mkdir(path: *const u8, mode: mode_t) {
+pub unsafe fn mkdir(path: *const u8, mode: mode_t) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "mkdir(path=%s, mode=%x)\n", path, mode);
 
+    mkdirat(AT_FDCWD, path, mode)
+}
+
+pub unsafe fn mkdirat(dirfd: isize, path: *const u8, mode: mode_t) -> SyscallResult {
+    let (dir, path) = user_path_at(dirfd, path)?;
     let mut uio = proc_uio!(curproc!());
 
-    match fs::mkdir(&cstr(path), mode, &mut uio) {
-        Ok(_) => arch::syscall_return(curthread!(), 0),
-        Err(err) => arch::syscall_return(curthread!(), err.unwrap() as usize),
-    }
+    fs::mkdir_at(dir, &path, mode, &mut uio).map(|_| 0)
 }
 
-pub unsafe fn pipe(fd: &mut [isize; 2]) {
+pub unsafe fn pipe(fd: *mut isize) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "pipe(fd=%p)\n", fd);
 
     /*
@@ -459,52 +835,80 @@ pub unsafe fn pipe(fd: &mut [isize; 2]) {
     fd[1] = fd2;
     */
 
-    arch::syscall_return(curthread!(), 0);
+    Ok(0)
 }
 
 
-pub unsafe fn fcntl(fd: isize, cmd: isize, arg: usize) {
+pub unsafe fn fcntl(fd: isize, cmd: isize, arg: usize) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "fcntl(fd=%d, cmd=%d, arg=0x%x)\n",
     //        fd, cmd, arg);
 
-    if fd < 0 || (fd as usize) >= FDS_COUNT {
-        /* out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
-    }
-
-    let file = (*curproc!()).fds.offset(fd);
+    let file = proc_fd_file(curproc!(), fd).ok_or(Error::EBADF)?;
 
-    let mut dupfd = 0;
+    let entry = (*curproc!()).fds.offset(fd);
 
     match (cmd as usize) {
-        F_DUPFD => {
-            if arg == 0 {
-                dupfd = proc_fd_get(curproc!());
-            } else {
-                dupfd = arg as isize;
+        F_DUPFD | F_DUPFD_CLOEXEC => {
+            /* the lowest free descriptor not below `arg` */
+            if arg >= FD_MAX {
+                return Err(Error::EINVAL);
+            }
+
+            let dupfd = proc_fd_get_from(curproc!(), arg);
+            if dupfd == -1 {
+                return Err(Error::EMFILE);
             }
 
-            *((*curproc!()).fds.offset(dupfd)) = *file;
-            arch::syscall_return(curthread!(), dupfd as usize);
-            return;
+            let flags = if cmd as usize == F_DUPFD_CLOEXEC { FD_CLOEXEC } else { 0 };
+            proc_fd_set(curproc!(), dupfd, file, flags);
+
+            return Ok(dupfd as usize);
         }
         F_GETFD => {
-            arch::syscall_return(curthread!(), (*file).flags);
-            return;
+            return Ok((*entry).flags);
         }
         F_SETFD => {
-            (*file).flags = arg as usize; /* XXX */
-            arch::syscall_return(curthread!(), 0);
-            return;
+            (*entry).flags = arg & FD_CLOEXEC;
+            return Ok(0);
+        }
+        F_GETFL => {
+            return Ok((*file).flags);
+        }
+        F_SETFL => {
+            /* only the status flags change, the access mode stays */
+            let settable = O_APPEND | O_NONBLOCK;
+            (*file).flags = ((*file).flags & !settable) | (arg & settable);
+            return Ok(0);
+        }
+        F_GETLK => {
+            let mut flock: Flock = copyin(arg as *const Flock)?;
+            let (start, end) = fs::lock_range(file, flock.l_whence as usize, flock.l_start as i64, flock.l_len as i64)?;
+
+            match fs::vfs_lock_get(file, flock.l_type as usize, start, end)? {
+                None => flock.l_type = F_UNLCK as i16,
+                Some(lock) => {
+                    flock.l_type   = lock.kind as i16;
+                    flock.l_whence = SEEK_SET as i16;
+                    flock.l_start  = lock.start as off_t;
+                    flock.l_len    = lock.len() as off_t;
+                    flock.l_pid    = lock.pid();
+                },
+            }
+
+            copyout(arg as *mut Flock, &flock)?;
+            return Ok(0);
+        }
+        F_SETLK | F_SETLKW => {
+            let flock: Flock = copyin(arg as *const Flock)?;
+            let (start, end) = fs::lock_range(file, flock.l_whence as usize, flock.l_start as i64, flock.l_len as i64)?;
+
+            fs::vfs_lock_set(file, flock.l_type as usize, start, end, cmd as usize == F_SETLKW)?;
+            return Ok(0);
         }
         _ => {
-            arch::syscall_return(curthread!(), -EINVAL as usize);
-            return;
+            return Err(Error::EINVAL);
         }
     }
-
-    arch::syscall_return(curthread!(), -EINVAL as usize);
 }
 
 
@@ -519,41 +923,40 @@ pub struct MmapArgs {
 }
 
 
-pub unsafe fn mmap(args: *mut MmapArgs, ret: *mut *mut u8) {
-    //syscall_log(LOG_DEBUG, "mmap(addr=%p, len=%d, prot=%x, flags=%x, fildes=%d, off=%d, ret=%p)\n",
-    //        args->addr, args->len, args->prot, args->flags, args->fildes, args->off, ret);
-
+/**
+ * map `len` bytes of `file` at offset `off` into the current process, an
+ * anonymous zero filled mapping if `file` is null. returns the address
+ */
+pub unsafe fn mmap_region(addr: usize, len: size_t, prot: usize, flags: usize, file: *mut FileDescriptor, off: off_t) -> Result<usize, Error> {
     let mut err = 0;
 
-    let fildes = (*args).fildes;
-    if fildes < 0 || (fildes as usize) >= FDS_COUNT {
-        /* out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
-    }
-
-    let file = (*curproc!()).fds.offset(fildes);
-
-    if (*file).backend.vnode.is_null() {
-        /* invalid file descriptor */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return;
+    /* files on noexec mounts can not be mapped executable either */
+    if !file.is_null() && prot & PROT_EXEC != 0 {
+        if let Some(path) = (*(*file).backend.vnode).path.as_ref() {
+            if fs::mount_flags(path) & MS_NOEXEC != 0 {
+                return Err(Error::EPERM);
+            }
+        }
     }
 
     let vm_entry = Box::leak(VmEntry::alloc(VmEntry::new()));
 
     /* initialize vm entry */
-    vm_entry.base   = (*args).addr;
-    vm_entry.size   = (*args).len;
-    vm_entry.flags  = if (*args).prot & PROT_READ  != 0 { VM_UR } else { 0 };
-    vm_entry.flags |= if (*args).prot & PROT_WRITE != 0 { VM_UW } else { 0 };
-    vm_entry.flags |= if (*args).prot & PROT_EXEC  != 0 { VM_UX } else { 0 };
-    vm_entry.flags |= if (*args).flags & MAP_SHARED != 0 { VM_SHARED } else { 0 };
-    vm_entry.off    = (*args).off as usize;
-
-    vm_entry.vm_object = vm_object_vnode((*file).backend.vnode);
-
-    if (*args).flags & MAP_FIXED == 0 {
+    vm_entry.base   = addr;
+    vm_entry.size   = len;
+    vm_entry.flags  = if prot & PROT_READ  != 0 { VM_UR } else { 0 };
+    vm_entry.flags |= if prot & PROT_WRITE != 0 { VM_UW } else { 0 };
+    vm_entry.flags |= if prot & PROT_EXEC  != 0 { VM_UX } else { 0 };
+    vm_entry.flags |= if flags & MAP_SHARED != 0 { VM_SHARED } else { 0 };
+    vm_entry.off    = off as usize;
+
+    vm_entry.vm_object = if file.is_null() {
+        core::ptr::null_mut()
+    } else {
+        vm_object_vnode((*file).backend.vnode)
+    };
+
+    if flags & MAP_FIXED == 0 {
         /* allocate memory region */
         (*vm_entry).base = 0;
     }
@@ -568,11 +971,10 @@ pub unsafe fn mmap(args: *mut MmapArgs, ret: *mut *mut u8) {
 
         Box::from_raw(vm_entry);
 
-        arch::syscall_return(curthread!(), err as usize);
-        return;
+        return Error::wrap_isize_to_usize(err);
     }
 
-    if (*args).flags & MAP_PRIVATE == 0 {
+    if !file.is_null() && flags & MAP_PRIVATE == 0 {
         err = vfs_map(vm_space, vm_entry);
 
         if err != 0 {
@@ -582,19 +984,41 @@ pub unsafe fn mmap(args: *mut MmapArgs, ret: *mut *mut u8) {
 
             Box::from_raw(vm_entry);
 
-            arch::syscall_return(curthread!(), err as usize);
-            return;
+            return Error::wrap_isize_to_usize(err);
         }
     }
 
-    *ret = vm_entry.base as *mut u8;
+    if !vm_entry.vm_object.is_null() {
+        (*vm_entry.vm_object).incref();
+    }
+
+    Ok(vm_entry.base)
+}
+
+
+pub unsafe fn mmap(args: *mut MmapArgs, ret: *mut *mut u8) -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "mmap(addr=%p, len=%d, prot=%x, flags=%x, fildes=%d, off=%d, ret=%p)\n",
+    //        args->addr, args->len, args->prot, args->flags, args->fildes, args->off, ret);
+
+    let args = &copyin(args)?;
+
+    let fildes = (*args).fildes;
+    let file = proc_fd_file(curproc!(), fildes).ok_or(Error::EBADF)?;
+
+    if (*file).backend.vnode.is_null() {
+        /* invalid file descriptor */
+        return Err(Error::EBADFD);
+    }
+
+    let base = mmap_region((*args).addr, (*args).len, (*args).prot, (*args).flags, file, (*args).off)?;
+
+    copyout(ret, &(base as *mut u8))?;
 
-    arch::syscall_return(curthread!(), err as usize);
-    return;
+    Ok(0)
 }
 
 
-pub unsafe fn munmap(addr: usize, len: size_t) {
+pub unsafe fn munmap(addr: usize, len: size_t) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "munmap(addr=%p, len=%d)\n", addr, len);
 
     let vm_space = &mut (*curproc!()).vm_space;
@@ -605,137 +1029,169 @@ pub unsafe fn munmap(addr: usize, len: size_t) {
         if ((*vm_entry).base == addr && (*vm_entry).size == len) {
             (*vm_space).vm_entries.node_remove((*vm_entry).qnode);
             vm_unmap_full(vm_space, vm_entry);
+            (*vm_entry).destroy();
             kfree(vm_entry as *mut u8);
-            arch::syscall_return(curthread!(), 0);
-            return;
+            return Ok(0);
         }
     }
 
     /* not found */
-    arch::syscall_return(curthread!(), -EINVAL as usize);
-    return;
+    Err(Error::EINVAL)
 }
 
 
-pub unsafe fn close(fildes: isize) {
+pub unsafe fn close(fildes: isize) -> SyscallResult {
     //syscall_log!(LOG_DEBUG, "close(fildes={})\n", fildes);
 
-    if fildes < 0 || (fildes as usize) >= FDS_COUNT {
-        /* out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
+    proc_fd_close(curproc!(), fildes).map(|_| 0)
+}
+
+/** make `newfd` refer to the open file of `oldfd`, closing whatever `newfd` referred to */
+unsafe fn dup_to(oldfd: isize, newfd: isize, flags: usize) -> SyscallResult {
+    let file = proc_fd_file(curproc!(), oldfd).ok_or(Error::EBADF)?;
+
+    /* closing `newfd` may block, `oldfd` could be closed meanwhile */
+    file_ref(file);
+
+    /* take the slot before closing its file, nobody else gets it in between */
+    let ret = proc_fd_reserve(curproc!(), newfd);
+
+    if ret.is_ok() {
+        proc_fd_set(curproc!(), newfd, file, flags);
     }
 
-    let file = (*curproc!()).fds.offset(fildes);
-    
-    if file == (-1isize as *mut _) || (*file).backend.vnode.is_null() {
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return;
+    let _ = file_unref(file);
+
+    ret.map(|_| newfd as usize)
+}
+
+pub unsafe fn dup(fd: isize) -> SyscallResult {
+    fcntl(fd, F_DUPFD as isize, 0)
+}
+
+pub unsafe fn dup2(oldfd: isize, newfd: isize) -> SyscallResult {
+    if oldfd == newfd {
+        /* nothing to do for a valid descriptor */
+        return proc_fd_file(curproc!(), oldfd).map(|_| newfd as usize).ok_or(Error::EBADF);
     }
 
-    let ret = vfs_file_close(file);
-    (*(*curproc!()).fds.offset(fildes)).backend.vnode = core::ptr::null_mut();
-    arch::syscall_return(curthread!(), ret as usize);
+    dup_to(oldfd, newfd, 0)
 }
 
-pub unsafe fn fstat(fildes: isize, statbuf: *mut Stat) {
-    //syscall_log(LOG_DEBUG, "fstat(fildes=%d, buf=%p)\n", fildes, buf);
+pub unsafe fn dup3(oldfd: isize, newfd: isize, flags: usize) -> SyscallResult {
+    if flags & !O_CLOEXEC != 0 || oldfd == newfd {
+        return Err(Error::EINVAL);
+    }
+
+    dup_to(oldfd, newfd, if flags & O_CLOEXEC != 0 { FD_CLOEXEC } else { 0 })
+}
+
+pub unsafe fn ftruncate(fildes: isize, length: off_t) -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "ftruncate(fildes=%d, length=%d)\n", fildes, length);
+
+    let file = proc_fd_file(curproc!(), fildes).ok_or(Error::EBADF)?;
+
+    if (*file).backend.vnode.is_null() || (*file).flags & (O_WRONLY | O_RDWR) == 0 {
+        return Err(Error::EBADFD);
+    }
 
-    if fildes < 0 || (fildes as usize) >= FDS_COUNT {  /* Out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
+    if length < 0 {
+        return Err(Error::EINVAL);
     }
 
-    let file = (*curproc!()).fds.offset(fildes);
+    let err = vfs_file_trunc(file, length);
+    Error::wrap_isize_to_usize(err)
+}
+
+pub unsafe fn fstat(fildes: isize, statbuf: *mut Stat) -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "fstat(fildes=%d, buf=%p)\n", fildes, buf);
+
+    let file = proc_fd_file(curproc!(), fildes).ok_or(Error::EBADF)?;
     
     if (*file).backend.vnode.is_null() {
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return;
+        return Err(Error::EBADFD);
     }
 
     let vnode = (*file).backend.vnode;
 
-    match fs::stat(&*vnode) {
-        Ok(stat) => {
-            statbuf.write(stat);
-            arch::syscall_return(curthread!(), 0);
-        },
-        Err(err) => arch::syscall_return(curthread!(), err.unwrap() as usize),
-    }
+    let stat = fs::stat(&*vnode)?;
+    copyout(statbuf, &stat)?;
+
+    Ok(0)
 }
 
-pub unsafe fn isatty(fildes: isize) {
+pub unsafe fn isatty(fildes: isize) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "isatty(fildes=%d)\n", fildes);
 
-    if fildes < 0 || (fildes as usize >= FDS_COUNT) {
-        /* out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
-    }
-
-    let node = (*(*curproc!()).fds.offset(fildes)).backend.vnode;
-
-    if node.is_null() {
-        /* invalid file descriptor */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return;
-    }
+    let node = (*proc_fd_file(curproc!(), fildes).ok_or(Error::EBADF)?).backend.vnode;
 
     //arch::syscall_return(curthread, node->rdev & (136 << 8));
-    arch::syscall_return(curthread!(), 1);
+    Ok(1)
 }
 
-pub unsafe fn rmdir(path: *const u8) {
+pub unsafe fn rmdir(path: *const u8) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "rmdir(path=%s)\n", path);
-    arch::syscall_return(curthread!(), -ENOSYS as usize);
+
+    unlinkat(AT_FDCWD, path, AT_REMOVEDIR)
 }
 
-pub unsafe fn chown(path: *const u8, uid: uid_t, gid: gid_t) {
+pub unsafe fn chown(path: *const u8, uid: uid_t, gid: gid_t) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "chown(path=%s, uid=%d, gid=%d)\n", path, uid, gid);
 
-    match fs::lookup(&cstr(path), &proc_uio!(curproc!())) {
-        Err(err) => arch::syscall_return(curthread!(), err.unwrap() as usize),
-        Ok((node, _)) => {
-            match node.chown(uid, gid) {
-                Ok(val) => arch::syscall_return(curthread!(), 0),
-                Err(err) => arch::syscall_return(curthread!(), err.unwrap() as usize),
-            }
-        }
-    }
+    fchownat(AT_FDCWD, path, uid, gid, 0)
 }
 
+pub unsafe fn fchownat(dirfd: isize, path: *const u8, uid: uid_t, gid: gid_t, flags: usize) -> SyscallResult {
+    let (dir, path) = user_path_at(dirfd, path)?;
+
+    let mut uio = proc_uio!(curproc!());
+    uio.flags = if flags & AT_SYMLINK_NOFOLLOW != 0 { O_NOFOLLOW } else { 0 };
+
+    let (node, realpath) = fs::lookup_at(dir, &path, &uio)?;
+
+    fs::mount_writable(&realpath)?;
+    node.chown(uid, gid)?;
 
-pub unsafe fn fchown(fd: isize, owner: uid_t, group: gid_t) {
+    Ok(0)
+}
+
+
+pub unsafe fn fchown(fd: isize, owner: uid_t, group: gid_t) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "fchown(fd=%d, owner=%d, group=%d)\n", fd, owner, group);
-    arch::syscall_return(curthread!(), -ENOSYS as usize);
+    Err(Error::ENOSYS)
 }
 
 
-pub unsafe fn lchown(path: *const u8, owner: uid_t, group: gid_t) {
+pub unsafe fn lchown(path: *const u8, owner: uid_t, group: gid_t) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "lchown(path=%s, owner=%d, group=%d)\n", path, owner, group);
-    arch::syscall_return(curthread!(), -ENOSYS as usize);
+
+    fchownat(AT_FDCWD, path, owner, group, AT_SYMLINK_NOFOLLOW)
 }
 
 
-pub unsafe fn utime(path: *const u8, times: *const utimbuf) {
+pub unsafe fn utime(path: *const u8, times: *const utimbuf) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "utime(path=%s, times=%p)\n", path, times);
-    arch::syscall_return(curthread!(), -ENOSYS as usize);
+    Err(Error::ENOSYS)
 }
 
-pub unsafe fn chmod(path: *const u8, mode: mode_t) {
+pub unsafe fn chmod(path: *const u8, mode: mode_t) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "chmod(path=%s, mode=%d)\n", path, mode);
 
+    fchmodat(AT_FDCWD, path, mode, 0)
+}
+
+pub unsafe fn fchmodat(dirfd: isize, path: *const u8, mode: mode_t, flags: usize) -> SyscallResult {
+    let (dir, path) = user_path_at(dirfd, path)?;
+
     let mut uio = proc_uio!(curproc!());
+    uio.flags = if flags & AT_SYMLINK_NOFOLLOW != 0 { O_NOFOLLOW } else { 0 };
 
-    match fs::lookup(&cstr(path), &uio) {
-        Err(err) => arch::syscall_return(curthread!(), err.unwrap() as usize),
-        Ok((node, _)) => {
-            match node.chmod(mode) {
-                Ok(val)  => arch::syscall_return(curthread!(), 0),
-                Err(err) => arch::syscall_return(curthread!(), err.unwrap() as usize),
-            }
-        }
-    }
+    let (node, realpath) = fs::lookup_at(dir, &path, &uio)?;
+
+    fs::mount_writable(&realpath)?;
+    node.chmod(mode)?;
+
+    Ok(0)
 }
 
 const F_OK: usize = 0;
@@ -743,123 +1199,364 @@ const X_OK: usize = 1;
 const W_OK: usize = 2;
 const R_OK: usize = 4;
 
-pub unsafe fn access(path: *const u8, mode: usize) {
+pub unsafe fn access(path: *const u8, mode: usize) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "access(path=%s, mode=%d)\n", path, mode);
 
+    faccessat(AT_FDCWD, path, mode, 0)
+}
+
+pub unsafe fn faccessat(dirfd: isize, path: *const u8, mode: usize, flags: usize) -> SyscallResult {
+    let (dir, path) = user_path_at(dirfd, path)?;
     let mut err = 0;
 
-    /* look up the file */
+    /* look up the file, real and effective ids are the same */
     let mut uio = proc_uio!(curproc!());
+    uio.flags = if flags & AT_SYMLINK_NOFOLLOW != 0 { O_NOFOLLOW } else { 0 };
 
     uio.flags |= if mode & R_OK != 0 { O_RDONLY } else { 0 };
     uio.flags |= if mode & W_OK != 0 { O_WRONLY } else { 0 };
     uio.flags |= if mode & X_OK != 0 { O_EXEC   } else { 0 };
 
-    match fs::lookup(&cstr(path), &uio) {
-        Err(err) => arch::syscall_return(curthread!(), err.unwrap() as usize),
-        Ok((node, realpath)) => {
-            let mut file = FileDescriptor {
-                backend: FileBackend { vnode: &mut *node },
-                offset: 0,
-                flags: uio.flags,
-            };
+    let (node, _) = fs::lookup_at(dir, &path, &uio)?;
 
-            err = fs::vfs_perms_check(&mut file, &mut uio);
-            arch::syscall_return(curthread!(), err as usize);
-        }
-    }
+    let mut file = FileDescriptor {
+        backend: FileBackend { vnode: &mut *node },
+        offset: 0,
+        flags: uio.flags,
+        refcnt: 0,
+    };
+
+    err = fs::vfs_perms_check(&mut file, &mut uio);
+    Error::wrap_isize_to_usize(err)
 }
 
-pub unsafe fn stat(path: *const u8, statbuf: *mut Stat) {
+pub unsafe fn stat(path: *const u8, statbuf: *mut Stat) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "stat(path=%s, buf=%p)\n", path, buf);
 
-    let mut err = 0;
+    fstatat(AT_FDCWD, path, statbuf, 0)
+}
+
+pub unsafe fn fstatat(dirfd: isize, path: *const u8, statbuf: *mut Stat, flags: usize) -> SyscallResult {
+    let (dir, path) = user_path_at(dirfd, path)?;
+
     let mut uio = proc_uio!(curproc!());
+    uio.flags = if flags & AT_SYMLINK_NOFOLLOW != 0 { O_NOFOLLOW } else { 0 };
 
-    match fs::lookup(&cstr(path), &uio) {
-        Err(err) => arch::syscall_return(curthread!(), err.unwrap() as usize),
-        Ok((node, _)) => {
-            match fs::stat(node) {
-                Ok(buf) => {
-                    statbuf.write(buf);
-                    arch::syscall_return(curthread!(), 0);
-                },
-                Err(err) => arch::syscall_return(curthread!(), err.unwrap() as usize),
-            }
-        }
-    }
+    let (node, _) = fs::lookup_at(dir, &path, &uio)?;
+    let buf = fs::stat(node)?;
+
+    copyout(statbuf, &buf)?;
+
+    Ok(0)
 }
 
-pub unsafe fn lseek(fildes: isize, offset: off_t, whence: isize) {
+pub unsafe fn lseek(fildes: isize, offset: off_t, whence: isize) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "lseek(fildes=%d, offset=%d, whence=%d)\n",
     //        fildes, offset, whence);
 
-    if fildes < 0 || (fildes as usize >= FDS_COUNT) {
-        /* out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
-    }
-
-    let file = (*curproc!()).fds.offset(fildes);
+    let file = proc_fd_file(curproc!(), fildes).ok_or(Error::EBADF)?;
 
     if (*file).backend.vnode.is_null() {
         /* invalid file descriptor */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return;
+        return Err(Error::EBADFD);
     }
 
     let err = fs::vfs_file_lseek(file, offset, whence);
-    arch::syscall_return(curthread!(), err as usize);
+    Error::wrap_isize_to_usize(err)
 }
 
-pub unsafe fn link(oldpath: *const u8, newpath: *const u8) {
+/** copy a path from user memory along with the directory open as `dirfd` relative paths are
+ * taken from, none for the working directory */
+pub unsafe fn user_path_at(dirfd: isize, path: *const u8) -> Result<(Option<&'static Node>, String), Error> {
+    let path = user_path(path)?;
+
+    if path.is_empty() {
+        return Err(Error::ENOENT);
+    }
+
+    if path.starts_with('/') || dirfd == AT_FDCWD {
+        return Ok((None, path));
+    }
+
+    let file = proc_fd_file(curproc!(), dirfd).ok_or(Error::EBADF)?;
+    let vnode = (*file).backend.vnode;
+
+    if vnode.is_null() || (*file).flags & FILE_SOCKET != 0 {
+        return Err(Error::EBADF);
+    }
+
+    if !(*vnode).is_directory() {
+        return Err(Error::ENOTDIR);
+    }
+
+    Ok((Some(&*vnode), path))
+}
+
+pub unsafe fn link(oldpath: *const u8, newpath: *const u8) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "link(oldpath=%s, newpath=%s)\n",
     //        oldpath, newpath);
 
-    /* TODO */
+    let oldpath = user_path(oldpath)?;
+    let newpath = user_path(newpath)?;
+
+    fs::link(&oldpath, &newpath, &proc_uio!(curproc!())).map(|_| 0)
+}
+
+pub unsafe fn rename(oldpath: *const u8, newpath: *const u8) -> SyscallResult {
+    renameat(AT_FDCWD, oldpath, AT_FDCWD, newpath)
+}
+
+pub unsafe fn renameat(olddirfd: isize, oldpath: *const u8, newdirfd: isize, newpath: *const u8) -> SyscallResult {
+    let (olddir, oldpath) = user_path_at(olddirfd, oldpath)?;
+    let (newdir, newpath) = user_path_at(newdirfd, newpath)?;
 
-    arch::syscall_return(curthread!(), -ENOSYS as usize);
+    fs::rename_at(olddir, &oldpath, newdir, &newpath, &proc_uio!(curproc!())).map(|_| 0)
 }
 
-pub unsafe fn unlink(path: *const u8) {
+pub unsafe fn symlink(target: *const u8, path: *const u8) -> SyscallResult {
+    let target = user_path(target)?;
+    let path = user_path(path)?;
+
+    fs::symlink(&target, &path, &proc_uio!(curproc!())).map(|_| 0)
+}
+
+pub unsafe fn readlink(path: *const u8, buf: *mut u8, bufsize: size_t) -> SyscallResult {
+    readlinkat(AT_FDCWD, path, buf, bufsize)
+}
+
+pub unsafe fn readlinkat(dirfd: isize, path: *const u8, buf: *mut u8, bufsize: size_t) -> SyscallResult {
+    let (dir, path) = user_path_at(dirfd, path)?;
+
+    if bufsize == 0 {
+        return Err(Error::EINVAL);
+    }
+
+    let target = fs::readlink_at(dir, &path, &proc_uio!(curproc!()))?;
+
+    /* silently truncated and not null terminated */
+    let len = core::cmp::min(target.len(), core::cmp::min(bufsize, PATH_MAX));
+    copy_to_user(buf, target.as_ptr(), len)?;
+
+    Ok(len)
+}
+
+pub unsafe fn unlink(path: *const u8) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "unlink(path=%p)\n", path);
 
-    match fs::unlink(&cstr(path), &proc_uio!(curproc!())) {
-        Ok(_) => arch::syscall_return(curthread!(), 0),
-        Err(err) => arch::syscall_return(curthread!(), err.unwrap() as usize),
+    unlinkat(AT_FDCWD, path, 0)
+}
+
+pub unsafe fn unlinkat(dirfd: isize, path: *const u8, flags: usize) -> SyscallResult {
+    let (dir, path) = user_path_at(dirfd, path)?;
+
+    if flags & !AT_REMOVEDIR != 0 {
+        return Err(Error::EINVAL);
+    }
+
+    if flags & AT_REMOVEDIR != 0 {
+        fs::rmdir_at(dir, &path, &proc_uio!(curproc!())).map(|_| 0)
+    } else {
+        fs::unlink_at(dir, &path, &proc_uio!(curproc!())).map(|_| 0)
     }
 }
 
-pub unsafe fn mknod(path: *const u8, mode: mode_t, dev: dev_t) {
+pub unsafe fn mknod(path: *const u8, mode: mode_t, dev: dev_t) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "mknod(path=%s, mode=%x, dev=%x)\n", path, mode, dev);
 
-    match fs::mknod(&cstr(path), mode, dev, &proc_uio!(curproc!())) {
-        Ok(_) => arch::syscall_return(curthread!(), 0),
-        Err(err) => arch::syscall_return(curthread!(), err.unwrap() as usize),
-    }
+    let path = user_path(path)?;
+
+    fs::mknod(&path, mode, dev, &proc_uio!(curproc!())).map(|_| 0)
 }
 
 
-pub unsafe fn lstat(path: *const u8, statbuf: *mut Stat) {
-    if path.is_null() || statbuf.is_null() {
-        arch::syscall_return(curthread!(), -Error::EINVAL as usize);
-        return;
+pub unsafe fn lstat(path: *const u8, statbuf: *mut Stat) -> SyscallResult {
+    fstatat(AT_FDCWD, path, statbuf, AT_SYMLINK_NOFOLLOW)
+}
+
+
+/** wait for events on `nfds` entries at `fds`, forever if `timeout` is none */
+pub unsafe fn poll_fds(fds: *mut PollFd, nfds: usize, timeout: Option<TimeSpec>) -> SyscallResult {
+    if nfds > FD_MAX {
+        return Err(Error::EINVAL);
     }
 
-    let mut uio = proc_uio!(curproc!());
-    uio.flags = O_NOFOLLOW;
-
-    match fs::lookup(&cstr(path), &uio) {
-        Err(err) => arch::syscall_return(curthread!(), err as usize),
-        Ok((node, _)) => {
-            match fs::stat(node) {
-                Ok(buf) => {
-                    statbuf.write(buf);
-                    arch::syscall_return(curthread!(), 0);
-                },
-                Err(err) => arch::syscall_return(curthread!(), err.unwrap() as usize),
+    let ns = match timeout {
+        None => None,
+        Some(timeout) => {
+            if timeout.tv_nsec >= 1000000000 {
+                return Err(Error::EINVAL);
             }
-        }
+
+            Some(timeout.tv_sec as u64 * 1000000000 + timeout.tv_nsec as u64)
+        },
+    };
+
+    let mut kfds = Vec::with_capacity(nfds);
+
+    for i in 0..nfds {
+        kfds.push(copyin(fds.offset(i as isize))?);
+    }
+
+    let count = fs::vfs_poll(&mut kfds, ns)?;
+
+    for (i, pfd) in kfds.iter().enumerate() {
+        copyout(fds.offset(i as isize), pfd)?;
     }
+
+    Ok(count)
 }
 
+pub unsafe fn poll(fds: *mut PollFd, nfds: usize, timeout: isize) -> SyscallResult {
+    /* milliseconds, negative waits forever */
+    let timeout = if timeout < 0 {
+        None
+    } else {
+        Some(TimeSpec {
+            tv_sec:  (timeout / 1000) as time_t,
+            tv_nsec: (timeout % 1000) as u32 * 1000000,
+        })
+    };
+
+    poll_fds(fds, nfds, timeout)
+}
+
+pub unsafe fn ppoll(fds: *mut PollFd, nfds: usize, timeout: *const TimeSpec, sigmask: *const sigset_t) -> SyscallResult {
+    let timeout = if timeout.is_null() { None } else { Some(copyin(timeout)?) };
+    let sigmask = if sigmask.is_null() { None } else { Some(copyin(sigmask)?) };
+
+    poll_fds_masked(fds, nfds, timeout, sigmask)
+}
+
+/** `poll_fds` with the blocked signals replaced by `sigmask`, if given, for the duration of the wait */
+pub unsafe fn poll_fds_masked(fds: *mut PollFd, nfds: usize, timeout: Option<TimeSpec>, sigmask: Option<sigset_t>) -> SyscallResult {
+    let old = match sigmask {
+        Some(mask) => Some(signal_mask(SIG_SETMASK, Some(mask))?),
+        None => None,
+    };
+
+    let ret = poll_fds(fds, nfds, timeout);
+
+    if let Some(old) = old {
+        signal_mask(SIG_SETMASK, Some(old))?;
+    }
+
+    ret
+}
+
+pub unsafe fn epoll_create(size: isize) -> SyscallResult {
+    /* the size is only a hint, but has to be positive */
+    if size <= 0 {
+        return Err(Error::EINVAL);
+    }
+
+    epoll_create1(0)
+}
+
+/** install the file returned by `open` as the lowest free descriptor */
+unsafe fn fd_install<F>(open: F) -> SyscallResult
+where F: FnOnce() -> Result<FileDescriptor, Error> {
+    let fd = proc_fd_get(curproc!());
+    if fd == -1 {
+        /* reached maximum number of open file descriptors */
+        return Err(Error::EMFILE);
+    }
+
+    match open() {
+        Ok(file) => {
+            proc_fd_install(curproc!(), fd, file);
+            Ok(fd as usize)
+        },
+        Err(err) => {
+            proc_fd_release(curproc!(), fd);
+            Err(err)
+        },
+    }
+}
+
+pub unsafe fn epoll_create1(flags: usize) -> SyscallResult {
+    fd_install(|| fs::epoll_open(flags))
+}
+
+pub unsafe fn epoll_ctl(epfd: isize, op: usize, fd: isize, event: *const EpollEvent) -> SyscallResult {
+    let epfile = fs::poll_file(curproc!(), epfd).ok_or(Error::EBADF)?;
+
+    /* removal takes no event */
+    let event = if op == EPOLL_CTL_DEL || event.is_null() { None } else { Some(copyin(event)?) };
+
+    fs::epoll_control(epfile, op, fd, event).map(|_| 0)
+}
+
+pub unsafe fn epoll_wait(epfd: isize, events: *mut EpollEvent, maxevents: isize, timeout: isize) -> SyscallResult {
+    let epfile = fs::poll_file(curproc!(), epfd).ok_or(Error::EBADF)?;
+
+    if maxevents <= 0 {
+        return Err(Error::EINVAL);
+    }
+
+    /* milliseconds, negative waits forever */
+    let timeout = if timeout < 0 { None } else { Some(timeout as u64 * 1000000) };
+
+    let ready = fs::epoll_wait_events(epfile, maxevents as usize, timeout)?;
+
+    for (i, event) in ready.iter().enumerate() {
+        copyout(events.offset(i as isize), event)?;
+    }
+
+    Ok(ready.len())
+}
+
+pub unsafe fn eventfd(initval: u32, flags: usize) -> SyscallResult {
+    fd_install(|| fs::eventfd_open(initval, flags))
+}
+
+/** signal numbers are reported as they are */
+fn signalfd_signo(sig: isize) -> isize {
+    sig
+}
+
+/** create a signalfd if `fd` is -1, else change the mask of the signalfd `fd` */
+pub unsafe fn signalfd(fd: isize, mask: *const sigset_t, flags: usize) -> SyscallResult {
+    signalfd_with(fd, copyin(mask)?, flags, signalfd_signo)
+}
+
+/** signalfd with the mask already copied in, `signo` translates the numbers read */
+pub unsafe fn signalfd_with(fd: isize, mask: sigset_t, flags: usize, signo: fn(isize) -> isize) -> SyscallResult {
+    if fd == -1 {
+        return fd_install(|| fs::signalfd_open(mask, flags, signo));
+    }
+
+    let file = fs::poll_file(curproc!(), fd).ok_or(Error::EBADF)?;
+    fs::signalfd_set_mask(file, mask)?;
+
+    Ok(fd as usize)
+}
+
+pub unsafe fn timerfd_create(clock: usize, flags: usize) -> SyscallResult {
+    fd_install(|| fs::timerfd_open(clock, flags))
+}
+
+pub unsafe fn timerfd_settime(fd: isize, flags: usize, new_value: *const ITimerSpec, old_value: *mut ITimerSpec) -> SyscallResult {
+    let file = fs::poll_file(curproc!(), fd).ok_or(Error::EBADF)?;
+    let old = fs::timerfd_set(file, flags, &copyin(new_value)?)?;
+
+    if !old_value.is_null() {
+        copyout(old_value, &old)?;
+    }
+
+    Ok(0)
+}
+
+pub unsafe fn timerfd_gettime(fd: isize, curr_value: *mut ITimerSpec) -> SyscallResult {
+    let file = fs::poll_file(curproc!(), fd).ok_or(Error::EBADF)?;
+    let curr = fs::timerfd_get(file)?;
+
+    copyout(curr_value, &curr)?;
+
+    Ok(0)
+}
+
+pub unsafe fn flock(fd: isize, op: usize) -> SyscallResult {
+    let file = fs::poll_file(curproc!(), fd).ok_or(Error::EBADF)?;
+
+    fs::vfs_flock(file, op).map(|_| 0)
+}
@@ -0,0 +1,146 @@
+use prelude::*;
+
+use bits::ipc::*;
+use bits::msg::*;
+use bits::sem::*;
+use bits::shm::*;
+use fs::{self, *};
+use fs::shmfs;
+use mm::*;
+use sys::ipc::*;
+use sys::process::*;
+use sys::sched::*;
+use sys::syscall::table::*;
+
+use sys::syscall::file::{FileDescriptor, FileBackend};
+
+pub unsafe fn shm_open(name: *const u8, oflags: usize, mode: mode_t) -> SyscallResult {
+    //syscall_log!(LOG_DEBUG, "shm_open(name={}, oflags={:o}, mode={:o})\n", cstr(name), oflags, mode);
+
+    let name = user_path(name)?;
+
+    let mut uio = proc_uio!(curproc!());
+    uio.flags = oflags;
+
+    let node = shmfs::lookup(&name, oflags, mode, &uio)?;
+
+    let mut file = FileDescriptor {
+        refcnt: 0,
+        offset: 0,
+        flags:  oflags,
+        backend: FileBackend {
+            vnode: node,
+        }
+    };
+
+    let err = vfs_perms_check(&mut file, &mut uio);
+    if err != 0 {
+        return Error::wrap_isize_to_usize(err);
+    }
+
+    vfs_file_open(&mut file)?;
+
+    let fd = proc_fd_get(curproc!());
+    if fd == -1 {
+        /* reached maximum number of open file descriptors */
+        return Err(Error::EMFILE);
+    }
+
+    proc_fd_install(curproc!(), fd, file);
+
+    Ok(fd as usize)
+}
+
+pub unsafe fn shm_unlink(name: *const u8) -> SyscallResult {
+    //syscall_log!(LOG_DEBUG, "shm_unlink(name={})\n", cstr(name));
+
+    let name = user_path(name)?;
+
+    shmfs::remove(&name, &proc_uio!(curproc!())).map(|_| 0)
+}
+
+pub unsafe fn shmget(key: key_t, size: size_t, shmflg: usize) -> SyscallResult {
+    //syscall_log!(LOG_DEBUG, "shmget(key={}, size={}, shmflg={:o})\n", key, size, shmflg);
+
+    shm_get(curproc!(), key, size, shmflg).map(|shmid| shmid as usize)
+}
+
+pub unsafe fn shmat(shmid: isize, shmaddr: usize, shmflg: usize) -> SyscallResult {
+    //syscall_log!(LOG_DEBUG, "shmat(shmid={}, shmaddr={:p}, shmflg={:o})\n", shmid, shmaddr as *const u8, shmflg);
+
+    shm_attach(curproc!(), shmid, shmaddr, shmflg)
+}
+
+pub unsafe fn shmdt(shmaddr: usize) -> SyscallResult {
+    //syscall_log!(LOG_DEBUG, "shmdt(shmaddr={:p})\n", shmaddr as *const u8);
+
+    shm_detach(curproc!(), shmaddr).map(|_| 0)
+}
+
+pub unsafe fn shmctl(shmid: isize, cmd: usize, buf: *mut ShmidDs) -> SyscallResult {
+    //syscall_log!(LOG_DEBUG, "shmctl(shmid={}, cmd={}, buf={:p})\n", shmid, cmd, buf);
+
+    shm_ctl(curproc!(), shmid, cmd, buf).map(|_| 0)
+}
+
+pub unsafe fn msgget(key: key_t, msgflg: usize) -> SyscallResult {
+    //syscall_log!(LOG_DEBUG, "msgget(key={}, msgflg={:o})\n", key, msgflg);
+
+    msg_get(curproc!(), key, msgflg).map(|msqid| msqid as usize)
+}
+
+pub unsafe fn msgsnd(msqid: isize, msgp: *const u8, msgsz: size_t, msgflg: usize) -> SyscallResult {
+    //syscall_log!(LOG_DEBUG, "msgsnd(msqid={}, msgp={:p}, msgsz={}, msgflg={:o})\n", msqid, msgp, msgsz, msgflg);
+
+    /* struct msgbuf { long mtype; char mtext[]; } */
+    let mtype = copyin(msgp as *const isize)?;
+    let mtext = msgp.offset(core::mem::size_of::<isize>() as isize);
+
+    msg_send(curproc!(), msqid, mtype, mtext, msgsz, msgflg).map(|_| 0)
+}
+
+pub unsafe fn msgrcv(msqid: isize, msgp: *mut u8, msgsz: size_t, msgtyp: isize, msgflg: usize) -> SyscallResult {
+    //syscall_log!(LOG_DEBUG, "msgrcv(msqid={}, msgp={:p}, msgsz={}, msgtyp={}, msgflg={:o})\n",
+    //        msqid, msgp, msgsz, msgtyp, msgflg);
+
+    let mtext = msgp.offset(core::mem::size_of::<isize>() as isize);
+
+    let (mtype, len) = msg_recv(curproc!(), msqid, mtext, msgsz, msgtyp, msgflg)?;
+    copyout(msgp as *mut isize, &mtype)?;
+
+    Ok(len)
+}
+
+pub unsafe fn msgctl(msqid: isize, cmd: usize, buf: *mut MsqidDs) -> SyscallResult {
+    //syscall_log!(LOG_DEBUG, "msgctl(msqid={}, cmd={}, buf={:p})\n", msqid, cmd, buf);
+
+    msg_ctl(curproc!(), msqid, cmd, buf).map(|_| 0)
+}
+
+pub unsafe fn semget(key: key_t, nsems: usize, semflg: usize) -> SyscallResult {
+    //syscall_log!(LOG_DEBUG, "semget(key={}, nsems={}, semflg={:o})\n", key, nsems, semflg);
+
+    sem_get(curproc!(), key, nsems, semflg).map(|semid| semid as usize)
+}
+
+pub unsafe fn semop(semid: isize, sops: *const SemBuf, nsops: usize) -> SyscallResult {
+    //syscall_log!(LOG_DEBUG, "semop(semid={}, sops={:p}, nsops={})\n", semid, sops, nsops);
+
+    if nsops > SEMOPM {
+        return Err(Error::E2BIG);
+    }
+
+    let mut ops = Vec::with_capacity(nsops);
+
+    for i in 0..nsops {
+        ops.push(copyin(sops.offset(i as isize))?);
+    }
+
+    sem_op(curproc!(), semid, &ops).map(|_| 0)
+}
+
+pub unsafe fn semctl(semid: isize, semnum: usize, cmd: usize, arg: usize) -> SyscallResult {
+    //syscall_log!(LOG_DEBUG, "semctl(semid={}, semnum={}, cmd={}, arg={:x})\n", semid, semnum, cmd, arg);
+
+    sem_ctl(curproc!(), semid, semnum, cmd, arg).map(|val| val as usize)
+}
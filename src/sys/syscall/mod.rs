@@ -1,4 +1,7 @@
 pub mod syscall;
 pub mod file;
+pub mod ipc;
+pub mod table;
+pub mod trace;
 
 pub use self::syscall::*;
\ No newline at end of file
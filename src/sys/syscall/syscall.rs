@@ -2,9 +2,13 @@ use prelude::*;
 
 use arch;
 use arch::sys::sched::*;
+use arch::sys::thread::{arch_set_thread_area, arch_get_thread_area};
+use arch::cpu::gdt::UserDesc;
 use bits::dirent::*;
 use bits::fcntl::*;
+use bits::futex::*;
 use bits::mman::*;
+use bits::resource::*;
 use bits::utsname::*;
 use fs::{self, S_ISDIR, Stat};
 use kern::time::*;
@@ -12,17 +16,20 @@ use mm::*;
 use net::socket::*;
 use sys::execve::*;
 use sys::fork::*;
+use sys::futex::*;
 use sys::pgroup::*;
 use sys::process::*;
+use sys::ptrace;
+use sys::ptrace::*;
 use sys::sched::*;
 use sys::session::*;
 use sys::signal::*;
 use sys::thread::*;
 
 use sys::syscall::file::*;
-
-/* XXX */
-const FDS_COUNT: usize = 64;
+use sys::syscall::ipc::*;
+use sys::syscall::table::*;
+use sys::syscall::trace::TraceArg::*;
 
 macro syscall_log {
     ($_:tt, $($ex:expr),*) => {
@@ -33,7 +40,11 @@ macro syscall_log {
     }
 }
 
-unsafe fn sys_exit(code: isize) {
+unsafe fn sys_nosys() -> SyscallResult {
+    Err(Error::ENOSYS)
+}
+
+pub unsafe fn sys_exit(code: isize) -> SyscallResult {
     syscall_log!(LOG_DEBUG, "exit(code={})\n", code);
 
     let owner = curproc!();
@@ -47,83 +58,144 @@ unsafe fn sys_exit(code: isize) {
     loop {}
 }
 
-// FIXME argp and envp should be slices/arrays
+/* total size of the argument and environment strings accepted by execve */
+const ARG_MAX: usize = 128 * 1024;
 
-unsafe fn sys_execve(path: *const u8, argp: *const *const u8, envp: *const *const u8) {
-    syscall_log!(LOG_DEBUG, "execve(path={}, argp={:p}, envp={:p})\n", cstr(path), argp, envp);
+/* copy a null terminated array of user strings, `total` accounts for ARG_MAX */
+unsafe fn user_string_array(array: *const *const u8, total: &mut usize) -> Result<Vec<Vec<u8>>, Error> {
+    let mut strings = Vec::new();
 
-    if path.is_null() || *path == 0 {
-        arch::syscall_return(curthread!(), -ENOENT as usize);
-        return;
+    if array.is_null() {
+        return Ok(strings);
     }
 
-    let mut err = 0;
-    let path = strdup(path);
+    loop {
+        let ptr = copyin(array.offset(strings.len() as isize))?;
+
+        if ptr.is_null() {
+            return Ok(strings);
+        }
+
+        let string = match user_cstr(ptr, ARG_MAX - *total) {
+            Err(err) if err == Error::ENAMETOOLONG => return Err(Error::E2BIG),
+            ret => ret?,
+        };
 
-    if path.is_null() {
-        arch::syscall_return(curthread!(), -ENOMEM as usize);
-        return;
+        *total += string.len() + core::mem::size_of::<*const u8>();
+
+        if *total > ARG_MAX {
+            return Err(Error::E2BIG);
+        }
+
+        strings.push(string);
     }
+}
 
-    err = proc_execve(curthread!(), path, argp, envp);
+/* null terminated pointer array over `strings` */
+fn string_array_ptrs(strings: &Vec<Vec<u8>>) -> Vec<*const u8> {
+    let mut ptrs: Vec<*const u8> = strings.iter().map(|string| string.as_ptr()).collect();
+    ptrs.push(core::ptr::null());
+    ptrs
+}
+
+pub unsafe fn sys_execve(path: *const u8, argp: *const *const u8, envp: *const *const u8) -> SyscallResult {
+    syscall_log!(LOG_DEBUG, "execve(path={:p}, argp={:p}, envp={:p})\n", path, argp, envp);
 
-    kfree(path);
+    let path = user_cstr(path, PATH_MAX)?;
+
+    if path[0] == 0 {
+        return Err(Error::ENOENT);
+    }
+
+    let mut total = 0;
+    let args = user_string_array(argp, &mut total)?;
+    let envs = user_string_array(envp, &mut total)?;
+
+    let args_ptrs = string_array_ptrs(&args);
+    let envs_ptrs = string_array_ptrs(&envs);
+
+    let err = proc_execve(curthread!(), path.as_ptr(),
+        if argp.is_null() { core::ptr::null() } else { args_ptrs.as_ptr() },
+        if envp.is_null() { core::ptr::null() } else { envs_ptrs.as_ptr() });
+
+    /* sched_thread_spawn never returns, release the copies now */
+    drop(args_ptrs);
+    drop(envs_ptrs);
+    drop(args);
+    drop(envs);
+    drop(path);
 
     if err != 0 {
-        arch::syscall_return(curthread!(), err as usize);
-    } else {
-        sched_thread_spawn(curthread!());
+        return Error::wrap_isize_to_usize(err);
+    }
+
+    /* let the tracer look at the new image before it runs */
+    if ptrace_traced(curproc!()) {
+        signal_thread_send(curthread!(), SIGTRAP);
     }
+
+    sched_thread_spawn(curthread!());
+
+    /* never reached, the new image starts running instead */
+    Ok(0)
 }
 
 
-unsafe fn sys_fork() {
+pub unsafe fn sys_fork() -> SyscallResult {
     syscall_log!(LOG_DEBUG, "fork()\n");
 
     let mut fork = core::ptr::null_mut();
-    proc_fork(curthread!(), &mut fork);
 
-    /* Returns are handled inside proc_fork */
-    if !fork.is_null() {
-        let thread = (*fork).threads.head().unwrap().value;
-        sched_thread_ready(thread);
+    /* the child's return value is set inside proc_fork */
+    let err = proc_fork(curthread!(), &mut fork);
+
+    if err != 0 {
+        return Error::wrap_isize_to_usize(err);
     }
+
+    let thread = (*fork).threads.head().unwrap().value;
+    sched_thread_ready(thread);
+
+    Ok((*fork).pid as usize)
 }
 
-unsafe fn sys_getpid() {
+pub unsafe fn sys_getpid() -> SyscallResult {
     //syscall_log(LOG_DEBUG, "getpid()\n");
-    arch::syscall_return(curthread!(), (*curproc!()).pid as usize);
+    Ok((*curproc!()).pid as usize)
 }
 
-unsafe fn sys_kill(pid: pid_t, sig: isize) {
+unsafe fn sys_kill(pid: pid_t, sig: isize) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "kill(pid=%d, sig=%d)\n", pid, sig);
-    let err = signal_send(pid, sig);
-    arch::syscall_return(curthread!(), err as usize);
+    Error::wrap_isize_to_usize(signal_send(pid, sig))
 }
 
-unsafe fn sys_sbrk(incr: isize) {
+unsafe fn sys_sbrk(incr: isize) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "sbrk(incr=0x%x)\n", incr);
 
-    let heap_start = (*curproc!()).heap_start;
-    let heap       = (*curproc!()).heap;
+    let heap = (*curproc!()).heap;
 
-    (*curproc!()).heap = heap + incr as usize;
-    (*(*curproc!()).heap_vm).size = page_round!(heap + (incr as usize) - heap_start);
+    let brk = if incr >= 0 {
+        heap.checked_add(incr as usize)
+    } else {
+        heap.checked_sub(incr.wrapping_neg() as usize)
+    };
 
-    arch::syscall_return(curthread!(), heap);
-    return;
+    proc_heap_set(curproc!(), brk.ok_or(Error::ENOMEM)?)?;
+
+    Ok(heap)
 }
 
 
-unsafe fn sys_times() {
+unsafe fn sys_times() -> SyscallResult {
     /* TODO */
+    Ok(0)
 }
 
 
 /* FIXME: move this */
 const WNOHANG: usize = 1;
 
-unsafe fn sys_waitpid(pid: pid_t, stat_loc: *mut isize, options: usize) {
+pub unsafe fn sys_waitpid(pid: pid_t, stat_loc: *mut isize, options: usize) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "waitpid(pid=%d, stat_loc=%p, options=0x%x)\n", pid, stat_loc, options);
 
     let nohang = (options & WNOHANG) != 0;
@@ -134,38 +206,45 @@ unsafe fn sys_waitpid(pid: pid_t, stat_loc: *mut isize, options: usize) {
         panic!("unsupported");
     } else if pid == -1 {
 
-        /* wait for any child process */
+        /* wait for any child process, or any process we trace */
         loop {
             let mut found = 0;
 
             for node in PROCS.iter() {
                 let proc = (*node).value;
 
-                if (*proc).parent != curproc!() {
+                if (*proc).parent != curproc!() && (*proc).tracer != curproc!() {
                     continue;
                 }
 
                 found = 1;
 
-                if (*proc).running == 0 {
+                /* tracee stops are reported without reaping */
+                if let Some(status) = ptrace_wait_status(proc, curproc!()) {
+                    if !stat_loc.is_null() {
+                        copyout(stat_loc, &status)?;
+                    }
+
+                    return Ok((*proc).pid as usize);
+                }
+
+                if (*proc).running == 0 && (*proc).parent == curproc!() {
                     if !stat_loc.is_null() {
-                        *stat_loc = (*proc).exit;
+                        copyout(stat_loc, &(*proc).exit)?;
                     }
 
-                    arch::syscall_return(curthread!(), (*proc).pid as usize);
+                    let pid = (*proc).pid;
                     proc_reap(proc);
-                    return;
+                    return Ok(pid as usize);
                 }
             }
 
             if nohang {
-                arch::syscall_return(curthread!(), if found != 0 { 0 } else { -ECHILD as usize });
-                return;
+                return if found != 0 { Ok(0) } else { Err(Error::ECHILD) };
             }
 
             if thread_queue_sleep(&mut (*curproc!()).wait_queue) != 0 {
-                arch::syscall_return(curthread!(), -EINTR as usize);
-                return;
+                return Err(Error::EINTR);
             }
         }
 
@@ -175,64 +254,66 @@ unsafe fn sys_waitpid(pid: pid_t, stat_loc: *mut isize, options: usize) {
         panic!("unsupported");
     } else {
         /* wait for the child whose process ID is equal to the
-              value of pid */
-        let child = proc_pid_find(pid);
+              value of pid, or for a process we trace */
+        loop {
+            /* looked up again every time, a tracee may be reaped by its parent */
+            let child = proc_pid_find(pid);
 
-        /* If pid is invalid or current process is not parent of child */
-        if child.is_null() || (*child).parent != curproc!() {
-            arch::syscall_return(curthread!(), -ECHILD as usize);
-            return;
-        }
+            /* If pid is invalid or current process is neither parent nor tracer of child */
+            if child.is_null() || ((*child).parent != curproc!() && (*child).tracer != curproc!()) {
+                return Err(Error::ECHILD);
+            }
 
-        if (*child).running == 0 {
-            /* child is killed */
-            *stat_loc = (*child).exit;
+            if let Some(status) = ptrace_wait_status(child, curproc!()) {
+                if !stat_loc.is_null() {
+                    copyout(stat_loc, &status)?;
+                }
 
-            arch::syscall_return(curthread!(), (*child).pid as usize);
-            proc_reap(child);
-            return;
-        }
+                return Ok((*child).pid as usize);
+            }
 
-        /*
-        if (options & WNOHANG) {
-            arch::syscall_return(curthread, 0);
-            return;
-        }
-        */
+            if (*child).running == 0 && (*child).parent == curproc!() {
+                /* child is killed */
+                if !stat_loc.is_null() {
+                    copyout(stat_loc, &(*child).exit)?;
+                }
+
+                let pid = (*child).pid;
+                proc_reap(child);
+                return Ok(pid as usize);
+            }
+
+            if nohang {
+                return Ok(0);
+            }
 
-        while (*child).running != 0 {
             if thread_queue_sleep(&mut (*curproc!()).wait_queue) != 0 {
-                arch::syscall_return(curthread!(), -EINTR as usize);
-                return;
+                return Err(Error::EINTR);
             }
         }
-
-        *stat_loc = (*child).exit;
-        arch::syscall_return(curthread!(), (*child).pid as usize);
-        proc_reap(child);
     }
 }
 
-unsafe fn sys_sigaction(sig: isize, act: *const SignalAction, oact: *mut SignalAction) {
+unsafe fn sys_sigaction(sig: isize, act: *const SignalAction, oact: *mut SignalAction) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "sigaction(sig=%d, act=%p, oact=%p)\n",
     //        sig, act, oact);
 
     if sig < 1 || (sig as usize) > SIG_MAX {
-        arch::syscall_return(curthread!(), -EINVAL as usize);
-        return;
+        return Err(Error::EINVAL);
     }
 
+    /* fetch the new action first, a bad `act` must leave everything untouched */
+    let new = if act.is_null() { None } else { Some(copyin(act)?) };
+
     if !oact.is_null() {
-        //memcpy(oact, &curproc->sigaction[sig], sizeof(struct sigaction));
-        *oact = (*curproc!()).sigaction[sig as usize];
+        copyout(oact, &(*curproc!()).sigaction[sig as usize])?;
     }
 
-    if !act.is_null() {
-        //memcpy(&curproc->sigaction[sig], act, sizeof(struct sigaction));
-        (*curproc!()).sigaction[sig as usize] = *act;
+    if let Some(new) = new {
+        (*curproc!()).sigaction[sig as usize] = new;
     }
 
-    arch::syscall_return(curthread!(), 0);
+    Ok(0)
 }
 
 
@@ -244,62 +325,105 @@ const UTSNAME_VERSION:  *const u8 = "(rustc 1.45.0-nightly (7ebd87a7a 2020-05-08
 const UTSNAME_MACHINE:  *const u8 = "i386\0".as_ptr();
 
 
-unsafe fn sys_uname(name: *mut UtsName) {
+unsafe fn sys_uname(name: *mut UtsName) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "uname(name=%p)\n", name);
 
-    /* FIXME: Sanity checking */
+    let mut uts: UtsName = core::mem::zeroed();
 
-    strcpy((*name).sysname.as_mut_ptr(),  UTSNAME_SYSNAME);
-    strcpy((*name).nodename.as_mut_ptr(), UTSNAME_NODENAME);
-    strcpy((*name).release.as_mut_ptr(),  UTSNAME_RELEASE);
-    strcpy((*name).version.as_mut_ptr(),  UTSNAME_VERSION);
-    strcpy((*name).machine.as_mut_ptr(),  UTSNAME_MACHINE);
+    strcpy(uts.sysname.as_mut_ptr(),  UTSNAME_SYSNAME);
+    strcpy(uts.nodename.as_mut_ptr(), UTSNAME_NODENAME);
+    strcpy(uts.release.as_mut_ptr(),  UTSNAME_RELEASE);
+    strcpy(uts.version.as_mut_ptr(),  UTSNAME_VERSION);
+    strcpy(uts.machine.as_mut_ptr(),  UTSNAME_MACHINE);
 
-    arch::syscall_return(curthread!(), 0);
-    return;
+    copyout(name, &uts)?;
+
+    Ok(0)
 }
 
-unsafe fn sys_chdir(path: *const u8) {
+pub unsafe fn sys_chdir(path: *const u8) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "chdir(path=%s)\n", path);
 
-    if path.is_null() || *path == 0 {
-        arch::syscall_return(curthread!(), -ENOENT as usize);
-        return;
+    let path = user_path(path)?;
+
+    if path.is_empty() {
+        return Err(Error::ENOENT);
     }
 
-    match fs::lookup(&cstr(path), &proc_uio!(curproc!())) {
-        Err(err) => arch::syscall_return(curthread!(), err.unwrap() as usize),
-        Ok((node, realpath)) => {
-            if !node.is_directory() {
-                arch::syscall_return(curthread!(), Error::ENOTDIR.unwrap() as usize);
-            }
+    let (node, realpath) = fs::lookup(&path, &proc_uio!(curproc!()))?;
 
-            (*curproc!()).cwd = realpath;
-            arch::syscall_return(curthread!(), 0);
-        }
+    if !node.is_directory() {
+        return Err(Error::ENOTDIR);
+    }
+
+    (*curproc!()).cwd = realpath;
+    Ok(0)
+}
+
+pub unsafe fn sys_fchdir(fd: isize) -> SyscallResult {
+    let file = proc_fd_file(curproc!(), fd).ok_or(Error::EBADF)?;
+    let vnode = (*file).backend.vnode;
+
+    if vnode.is_null() || (*file).flags & FILE_SOCKET != 0 {
+        return Err(Error::EBADF);
+    }
+
+    if !(*vnode).is_directory() {
+        return Err(Error::ENOTDIR);
+    }
+
+    /* the directory is named by the path it was opened by */
+    match (*vnode).path.clone() {
+        Some(path) => {
+            (*curproc!()).cwd = path;
+            Ok(0)
+        },
+        None => Err(Error::ENOENT),
     }
 }
 
+pub unsafe fn sys_chroot(path: *const u8) -> SyscallResult {
+    if (*curproc!()).uid != 0 {
+        return Err(Error::EPERM);
+    }
+
+    let path = user_path(path)?;
+
+    if path.is_empty() {
+        return Err(Error::ENOENT);
+    }
+
+    let (node, realpath) = fs::lookup(&path, &proc_uio!(curproc!()))?;
+
+    if !node.is_directory() {
+        return Err(Error::ENOTDIR);
+    }
+
+    /* the working directory is left alone, outside the root it resolves as the root */
+    (*curproc!()).root = realpath;
+    Ok(0)
+}
 
-unsafe fn sys_getcwd(buf: *mut u8, size: size_t) {
+
+pub unsafe fn sys_getcwd(buf: *mut u8, size: size_t) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "getcwd(buf=%p, size=%d)\n", buf, size);
 
     if size == 0 {
-        arch::syscall_return(curthread!(), -EINVAL as usize);
-        return;
+        return Err(Error::EINVAL);
     }
 
-    let len = (*curproc!()).cwd.len();
+    /* as seen from the root directory */
+    let cwd = fs::root_path(&(*curproc!()).cwd, &proc_uio!(curproc!()));
+    let len = cwd.len();
 
     if size < len + 1 {
-        arch::syscall_return(curthread!(), -ERANGE as usize);
-        return;
+        return Err(Error::ERANGE);
     }
 
-    memcpy(buf, (*curproc!()).cwd.as_ptr(), len + 1);
-    buf.offset(len as isize).write(b'\0');
+    copy_to_user(buf, cwd.as_ptr(), len)?;
+    copy_to_user(buf.offset(len as isize), b"\0".as_ptr(), 1)?;
 
-    arch::syscall_return(curthread!(), 0);
+    Ok(0)
 }
 
 #[repr(C)]
@@ -317,106 +441,336 @@ pub struct ThreadArgs {
 }
 
 
-unsafe fn sys_thread_create(args: *const ThreadArgs) {
+unsafe fn sys_thread_create(args: *const ThreadArgs) -> SyscallResult {
     //syscall_log(LOG_DEBUG,
     //        "thread_create(stack=%p, entry=%p, uentry=%p, arg=%p, attr=%p)\n",
     //        __uthread->stack, __uthread->entry, __uthread->uentry,
     //        __uthread->arg, __uthread->attr);
 
+    let args = copyin(args)?;
     let mut thread = core::ptr::null_mut();
-    thread_create(curthread!(), (*args).stack, (*args).entry, (*args).uentry, (*args).arg, (*args).attr, &mut thread);
+
+    let err = thread_create(curthread!(), args.stack, args.entry, args.uentry, args.arg, args.attr, &mut thread);
+    if err != 0 {
+        return Error::wrap_isize_to_usize(err);
+    }
+
     sched_thread_ready(thread);
-    arch::syscall_return(curthread!(), (*thread).tid as usize);
+    Ok((*thread).tid as usize)
 }
 
 
-unsafe fn sys_thread_exit(value_ptr: *mut u8) {
+unsafe fn sys_thread_exit(value_ptr: *mut u8) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "thread_exit(value_ptr=%p)\n", value_ptr);
 
-    //curthread->value_ptr = value_ptr;
+    thread_exit(value_ptr);
+}
+
+
+unsafe fn sys_thread_join(tid: isize, value_ptr: *mut *mut u8) -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "thread_join(tid=%d, value_ptr=%p)\n", tid, value_ptr);
+
     let owner = curproc!();
 
-    (*curthread!()).kill();
+    let thread = match (*owner).find_thread(tid as tid_t) {
+        Some(thread) => thread,
+        None => {
+            /* no such thread */
+            return Err(Error::ESRCH);
+        }
+    };
 
-    /* wakeup owner if it is waiting for joining */
-    thread_queue_wakeup(&mut (*owner).thread_join);
+    if thread == curthread!() {
+        return Err(Error::EDEADLK);
+    }
 
-    arch_sleep();
+    if (*thread).detached {
+        return Err(Error::EINVAL);
+    }
 
-    loop {}
-}
+    while (*thread).state != ThreadState::ZOMBIE {
+        if thread_queue_sleep(&mut (*owner).thread_join) != 0 {
+            return Err(Error::EINTR);
+        }
 
+        /* another joiner may have reaped it meanwhile */
+        if (*owner).find_thread(tid as tid_t) != Some(thread) {
+            return Err(Error::ESRCH);
+        }
+    }
 
-unsafe fn sys_thread_join(tid: isize, value_ptr: *mut *mut u8) {
-    //syscall_log(LOG_DEBUG, "thread_join(tid=%d, value_ptr=%p)\n", tid, value_ptr);
+    if !value_ptr.is_null() {
+        copyout(value_ptr, &(*thread).value_ptr)?;
+    }
+
+    thread_reap(thread);
+
+    Ok(tid as usize)
+}
 
-    let owner = curproc!();
-    let mut thread = core::ptr::null_mut();
 
-    for node in (*owner).threads.iter() {
-        let _thread = (*node).value as *mut Thread;
+unsafe fn sys_thread_detach(tid: isize) -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "thread_detach(tid=%d)\n", tid);
 
-        if (*_thread).tid == tid {
-            thread = _thread;
+    let thread = match (*curproc!()).find_thread(tid as tid_t) {
+        Some(thread) => thread,
+        None => {
+            return Err(Error::ESRCH);
         }
-    }
+    };
 
-    /* no such thread */
-    if thread.is_null() {
-        arch::syscall_return(curthread!(), -ECHILD as usize);
-        return;
+    if (*thread).detached {
+        return Err(Error::EINVAL);
     }
 
+    (*thread).detached = true;
+
     if (*thread).state == ThreadState::ZOMBIE {
-        /* thread is terminated */
-        //*value_ptr = thread->value_ptr;
-        arch::syscall_return(curthread!(), (*thread).tid as usize);
-        return;
+        /* already exited, nobody is going to join it */
+        thread_reap(thread);
     }
 
-    while (*thread).state != ThreadState::ZOMBIE {
-        if thread_queue_sleep(&mut (*curproc!()).thread_join) != 0 {
-            arch::syscall_return(curthread!(), -EINTR as usize);
-            return;
-        }
+    Ok(0)
+}
+
+
+pub unsafe fn sys_gettid() -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "gettid()\n");
+    Ok((*curthread!()).tid as usize)
+}
+
+
+/* send `sig` to thread `tid` of `proc` */
+unsafe fn thread_signal(proc: *mut Process, tid: isize, sig: isize) -> isize {
+    if sig < 0 || sig as usize > SIG_MAX {
+        return -EINVAL;
+    }
+
+    let thread = match (*proc).find_thread(tid as tid_t) {
+        Some(thread) if (*thread).state != ThreadState::ZOMBIE => thread,
+        _ => return -ESRCH,
+    };
+
+    /* signal 0 only checks that the thread exists */
+    if sig == 0 {
+        return 0;
+    }
+
+    signal_thread_send(thread, sig)
+}
+
+
+pub unsafe fn sys_thread_kill(tid: isize, sig: isize) -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "thread_kill(tid=%d, sig=%d)\n", tid, sig);
+
+    let ret = thread_signal(curproc!(), tid, sig);
+    Error::wrap_isize_to_usize(ret)
+}
+
+
+pub unsafe fn sys_tgkill(tgid: pid_t, tid: isize, sig: isize) -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "tgkill(tgid=%d, tid=%d, sig=%d)\n", tgid, tid, sig);
+
+    let proc = proc_pid_find(tgid);
+
+    if proc.is_null() {
+        return Err(Error::ESRCH);
+    }
+
+    let ret = thread_signal(proc, tid, sig);
+    Error::wrap_isize_to_usize(ret)
+}
+
+
+unsafe fn sys_getrlimit(resource: usize, rlim: *mut RLimit) -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "getrlimit(resource=%d, rlim=%p)\n", resource, rlim);
+
+    if resource >= RLIM_NLIMITS {
+        return Err(Error::EINVAL);
     }
 
-    //*value_ptr = thread->value_ptr;
-    arch::syscall_return(curthread!(), (*thread).tid as usize);
+    /* limits other than RLIMIT_CORE are not enforced */
+    let limit = match resource {
+        RLIMIT_CORE => (*curproc!()).rlimit_core,
+        _ => RLimit::infinity(),
+    };
+
+    copyout(rlim, &limit)?;
+
+    Ok(0)
+}
+
+
+unsafe fn sys_setrlimit(resource: usize, rlim: *const RLimit) -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "setrlimit(resource=%d, rlim=%p)\n", resource, rlim);
+
+    if resource != RLIMIT_CORE {
+        return Err(Error::EINVAL);
+    }
+
+    let new = copyin(rlim)?;
+    let old = (*curproc!()).rlimit_core;
+
+    if new.rlim_cur > new.rlim_max {
+        return Err(Error::EINVAL);
+    }
+
+    /* only root may raise the hard limit */
+    if new.rlim_max > old.rlim_max && (*curproc!()).uid != 0 {
+        return Err(Error::EPERM);
+    }
+
+    (*curproc!()).rlimit_core = new;
+    Ok(0)
+}
+
+
+pub unsafe fn sys_set_thread_area(u_info: *mut UserDesc) -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "set_thread_area(u_info=%p)\n", u_info);
+
+    let mut desc = copyin(u_info)?;
+
+    /* the allocated entry number is handed back to the caller */
+    arch_set_thread_area(curthread!(), &mut desc)?;
+    copyout(u_info, &desc)?;
+
+    Ok(0)
+}
+
+
+pub unsafe fn sys_get_thread_area(u_info: *mut UserDesc) -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "get_thread_area(u_info=%p)\n", u_info);
+
+    let mut desc = copyin(u_info)?;
+
+    arch_get_thread_area(curthread!(), &mut desc)?;
+    copyout(u_info, &desc)?;
+
+    Ok(0)
+}
+
+
+/**
+ * futex operation `op` on `uaddr`, `timeout` is only used by waits and
+ * `val2` only by requeues, both come in the same argument slot
+ */
+pub unsafe fn futex(uaddr: usize, op: usize, val: u32, timeout: Option<TimeSpec>, val2: usize, uaddr2: usize, val3: u32) -> SyscallResult {
+    let private  = op & FUTEX_PRIVATE_FLAG != 0;
+    let realtime = op & FUTEX_CLOCK_REALTIME != 0;
+    let cmd      = op & FUTEX_CMD_MASK;
+
+    /* the clock only means something to waits */
+    if realtime && !futex_op_waits(op) {
+        return Err(Error::ENOSYS);
+    }
+
+    match cmd {
+        FUTEX_WAIT | FUTEX_WAIT_BITSET => {
+            let bitset = if cmd == FUTEX_WAIT { FUTEX_BITSET_MATCH_ANY } else { val3 };
+
+            let ns = match timeout {
+                None => None,
+                Some(timeout) => {
+                    /* a negative tv_nsec reads as one past the limit */
+                    if (timeout.tv_sec as i64) < 0 || timeout.tv_nsec >= 1000000000 {
+                        return Err(Error::EINVAL);
+                    }
+
+                    match futex_timespec_ns(&timeout) {
+                        /* too far out to ever expire, wait without a timeout */
+                        None => None,
+
+                        /* FUTEX_WAIT_BITSET takes an absolute time, on the monotonic clock
+                         * unless FUTEX_CLOCK_REALTIME is given */
+                        Some(ns) if cmd == FUTEX_WAIT_BITSET => {
+                            let now = if realtime {
+                                futex_timespec_ns(&gettime()?).unwrap_or(u64::MAX)
+                            } else {
+                                arch_rtime_ns()
+                            };
+
+                            Some(ns.saturating_sub(now))
+                        },
+                        Some(ns) => Some(ns),
+                    }
+                },
+            };
+
+            futex_wait(curproc!(), uaddr, val, ns, bitset, private).map(|_| 0)
+        },
+        FUTEX_WAKE => futex_wake(curproc!(), uaddr, val as usize, FUTEX_BITSET_MATCH_ANY, private),
+        FUTEX_WAKE_BITSET => futex_wake(curproc!(), uaddr, val as usize, val3, private),
+        FUTEX_REQUEUE => {
+            futex_requeue(curproc!(), uaddr, val as usize, val2, uaddr2, None, private)
+                .map(|(woken, _)| woken)
+        },
+        FUTEX_CMP_REQUEUE => {
+            futex_requeue(curproc!(), uaddr, val as usize, val2, uaddr2, Some(val3), private)
+                .map(|(woken, requeued)| woken + requeued)
+        },
+        _ => Err(Error::ENOSYS),
+    }
+}
+
+/** `ts` in nanoseconds, None if that does not fit */
+fn futex_timespec_ns(ts: &TimeSpec) -> Option<u64> {
+    ts.tv_sec.checked_mul(1000000000)?.checked_add(ts.tv_nsec as u64)
+}
+
+/** does futex operation `op` take a timeout? */
+pub fn futex_op_waits(op: usize) -> bool {
+    match op & FUTEX_CMD_MASK {
+        FUTEX_WAIT | FUTEX_WAIT_BITSET => true,
+        _ => false,
+    }
+}
+
+
+unsafe fn sys_futex(uaddr: *mut u32, op: usize, val: u32, timeout: *const TimeSpec, uaddr2: *mut u32, val3: u32) -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "futex(uaddr=%p, op=%d, val=%d, timeout=%p, uaddr2=%p, val3=%d)\n",
+    //        uaddr, op, val, timeout, uaddr2, val3);
+
+    let ts = if futex_op_waits(op) && !timeout.is_null() {
+        Some(copyin(timeout)?)
+    } else {
+        None
+    };
+
+    futex(uaddr as usize, op, val, ts, timeout as usize, uaddr2 as usize, val3)
 }
 
 
-unsafe fn sys_setpgid(pid: pid_t, pgid: pid_t) {
+pub unsafe fn sys_setpgid(pid: pid_t, pgid: pid_t) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "setpgid(pid=%d, pgid=%d)\n", pid, pgid);
 
     //if (pid == 0 && pgid == 0) {
         let err = pgrp_new(curproc!(), core::ptr::null_mut());
-        arch::syscall_return(curthread!(), err as usize);
+        Error::wrap_isize_to_usize(err)
     //} else {
     //    panic("Unsupported");
     //}
 }
 
-unsafe fn sys_auth(uid: uid_t, pw: *const u8) {
+unsafe fn sys_auth(uid: uid_t, pw: *const u8) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "auth(uid=%d, pw=%s)\n", uid, pw);
 
     (*curproc!()).uid = uid;   /* XXX */
-    arch::syscall_return(curthread!(), 0);
+    Ok(0)
 }
 
 
-unsafe fn sys_getuid() {
+pub unsafe fn sys_getuid() -> SyscallResult {
     //syscall_log(LOG_DEBUG, "getuid()\n");
-    arch::syscall_return(curthread!(), (*curproc!()).uid as usize);
+    Ok((*curproc!()).uid as usize)
 }
 
 
-unsafe fn sys_getgid() {
+pub unsafe fn sys_getgid() -> SyscallResult {
     //syscall_log(LOG_DEBUG, "getgid()\n");
-    arch::syscall_return(curthread!(), (*curproc!()).gid as usize);
+    Ok((*curproc!()).gid as usize)
 }
 
-unsafe fn sys_socket(domain: isize, sock_type: isize, protocol: isize) {
+unsafe fn sys_socket(domain: isize, sock_type: isize, protocol: isize) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "socket(domain=%d, type=%d, protocol=%d)\n",
     //        domain, type, protocol);
 
@@ -425,139 +779,137 @@ unsafe fn sys_socket(domain: isize, sock_type: isize, protocol: isize) {
 
     if fd == -1 {
         /* reached maximum number of open file descriptors */
-        arch::syscall_return(curthread!(), -EMFILE as usize);
-        return;
+        return Err(Error::EMFILE);
     }
     
     let mut err = 0;
-    let file = (*curproc!()).fds.offset(fd);
+    let mut file: FileDescriptor = core::mem::zeroed();
 
-    err = socket_create(file, domain, sock_type, protocol);
+    err = socket_create(&mut file, domain, sock_type, protocol);
     if err != 0 {
         proc_fd_release(curproc!(), fd);
-        arch::syscall_return(curthread!(), err as usize);
-        return;
+        return Error::wrap_isize_to_usize(err);
     }
 
-    arch::syscall_return(curthread!(), fd as usize);
-    return;
+    proc_fd_install(curproc!(), fd, file);
+
+    Ok(fd as usize)
 }
 
 
-unsafe fn sys_accept(fd: isize, addr: *const SocketAddress, len: *mut socklen_t) {
+unsafe fn sys_accept(fd: isize, addr: *mut SocketAddress, len: *mut socklen_t) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "accept(fd=%d, addr=%p, len=%p)\n",
     //        fd, addr, len);
 
-    if fd < 0 || (fd as usize) >= FDS_COUNT {
-        /* out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
+    let socket = proc_fd_file(curproc!(), fd).ok_or(Error::EBADF)?;
+
+    /* the peer address goes to a kernel buffer the size the caller has room for */
+    let size = if addr.is_null() { 0 } else { copyin(len)? };
+
+    if size as usize > UACCESS_CHUNK {
+        return Err(Error::EINVAL);
     }
 
+    let mut kaddr = vec![0u8; size as usize];
+    let mut klen = size;
+
     let conn_fd = proc_fd_get(curproc!());
 
     if conn_fd == -1 {
-        arch::syscall_return(curthread!(), -EMFILE as usize);
-        return; 
+        return Err(Error::EMFILE);
     }
 
-    let socket = (*curproc!()).fds.offset(fd);
-    let conn   = (*curproc!()).fds.offset(conn_fd);
+    let mut conn: FileDescriptor = core::mem::zeroed();
 
     let mut err = 0;
-    err = socket_accept(socket, conn, addr, len as usize as u32);
+    if addr.is_null() {
+        err = socket_accept(socket, &mut conn, core::ptr::null_mut(), core::ptr::null_mut());
+    } else {
+        err = socket_accept(socket, &mut conn, kaddr.as_mut_ptr() as *mut SocketAddress, &mut klen);
+    }
 
     if err != 0 {
         proc_fd_release(curproc!(), conn_fd);
-        arch::syscall_return(curthread!(), err as usize);
-        return; 
+        return Error::wrap_isize_to_usize(err);
     }
 
-    arch::syscall_return(curthread!(), conn_fd as usize);
-    return;
-}
-
+    proc_fd_install(curproc!(), conn_fd, conn);
 
-unsafe fn sys_bind(fd: isize, addr: *const SocketAddress, len: socklen_t) {
-    //syscall_log(LOG_DEBUG, "bind(fd=%d, addr=%p, len=%d)\n",
-    //        fd, addr, len);
+    /* truncated to the buffer, the length tells the full size */
+    if !addr.is_null() {
+        let copied = core::cmp::min(size, klen) as usize;
 
-    if fd < 0 || (fd as usize) >= FDS_COUNT {
-        /* out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
+        if copy_to_user(addr as *mut u8, kaddr.as_ptr(), copied).is_err() || copyout(len, &klen).is_err() {
+            let _ = proc_fd_close(curproc!(), conn_fd);
+            return Err(Error::EFAULT);
+        }
     }
 
-    let file = (*curproc!()).fds.offset(fd);
+    Ok(conn_fd as usize)
+}
+
 
-    if file.is_null() {
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
+/* copy a socket address of `len` bytes from user memory */
+unsafe fn user_sockaddr(addr: *const SocketAddress, len: socklen_t) -> Result<Vec<u8>, Error> {
+    if len as usize > UACCESS_CHUNK {
+        return Err(Error::EINVAL);
     }
 
+    let mut buf = vec![0u8; len as usize];
+    copy_from_user(buf.as_mut_ptr(), addr as *const u8, len as usize)?;
+
+    Ok(buf)
+}
+
+
+unsafe fn sys_bind(fd: isize, addr: *const SocketAddress, len: socklen_t) -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "bind(fd=%d, addr=%p, len=%d)\n",
+    //        fd, addr, len);
+
+    let file = proc_fd_file(curproc!(), fd).ok_or(Error::EBADF)?;
+
+    let addr = user_sockaddr(addr, len)?;
     let mut err = 0;
 
-    err = socket_bind(file, addr, len as usize);
+    err = socket_bind(file, addr.as_ptr() as *const SocketAddress, len as usize);
     if err != 0 {
-        arch::syscall_return(curthread!(), err as usize);
-        return; 
+        return Error::wrap_isize_to_usize(err);
     }
 
-    arch::syscall_return(curthread!(), 0);
-    return;
+    Ok(0)
 }
 
 
-unsafe fn sys_connect(fd: isize, addr: *const SocketAddress, len: socklen_t) {
+unsafe fn sys_connect(fd: isize, addr: *const SocketAddress, len: socklen_t) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "connect(fd=%d, addr=%p, len=%d)\n",
     //        fd, addr, len);
 
-    if fd < 0 || (fd as usize) >= FDS_COUNT {
-        /* out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
-    }
-
-    let socket = (*curproc!()).fds.offset(fd);
+    let addr = user_sockaddr(addr, len)?;
+    let socket = proc_fd_file(curproc!(), fd).ok_or(Error::EBADF)?;
     let mut err = 0;
 
-    err = socket_connect(socket, addr, len as usize);
+    err = socket_connect(socket, addr.as_ptr() as *const SocketAddress, len as usize);
     if err != 0 {
-        arch::syscall_return(curthread!(), err as usize);
-        return; 
+        return Error::wrap_isize_to_usize(err);
     }
 
-    arch::syscall_return(curthread!(), 0);
-    return;
+    Ok(0)
 }
 
 
-unsafe fn sys_listen(fd: isize, backlog: isize) {
+unsafe fn sys_listen(fd: isize, backlog: isize) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "listen(fd=%d, backlog=%d)\n", fd, backlog);
 
-    if fd < 0 || (fd as usize) >= FDS_COUNT {
-        /* out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
-    }
-
-    let file = (*curproc!()).fds.offset(fd);
-
-    if file.is_null() {
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
-    }
+    let file = proc_fd_file(curproc!(), fd).ok_or(Error::EBADF)?;
 
     let mut err = 0;
 
     err = socket_listen(file, backlog);
     if err != 0 {
-        arch::syscall_return(curthread!(), err as usize);
-        return; 
+        return Error::wrap_isize_to_usize(err);
     }
 
-    arch::syscall_return(curthread!(), 0);
-    return;
+    Ok(0)
 }
 
 #[repr(C)]
@@ -569,111 +921,107 @@ pub struct SocketIO {
 }
 
 
-unsafe fn sys_send(s: *const SocketIO) {
-    let fd    = (*s).fd;
-    let buf   = (*s).buf;
-    let len   = (*s).len;
-    let flags = (*s).flags;
+unsafe fn sys_send(s: *const SocketIO) -> SyscallResult {
+    let s = copyin(s)?;
+
+    let fd    = s.fd;
+    let buf   = s.buf;
+    let len   = core::cmp::min(s.len, UACCESS_CHUNK);
+    let flags = s.flags;
 
     //syscall_log(LOG_DEBUG, "send(fd=%d, buf=%p, len=%d, flags=%x)\n",
     //        fd, buf, len, flags);
 
-    if fd < 0 || (fd as usize) >= FDS_COUNT {
-        /* out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
-    }
+    let file = proc_fd_file(curproc!(), fd).ok_or(Error::EBADF)?;
 
-    let file = (*curproc!()).fds.offset(fd);
-
-    if file.is_null() {
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
-    }
+    let mut kbuf = vec![0u8; len];
+    copy_from_user(kbuf.as_mut_ptr(), buf, len)?;
 
     let mut err = 0;
 
-    err = socket_send(file, buf, len, flags);
+    err = socket_send(file, kbuf.as_ptr(), len, flags);
     if err != 0 {
-        arch::syscall_return(curthread!(), err as usize);
-        return; 
+        return Error::wrap_isize_to_usize(err);
     }
 
-    arch::syscall_return(curthread!(), 0);
-    return;
+    Ok(0)
 }
 
 
-unsafe fn sys_recv(s: *const SocketIO) {
-    let fd    = (*s).fd;
-    let buf   = (*s).buf;
-    let len   = (*s).len;
-    let flags = (*s).flags;
+unsafe fn sys_recv(s: *const SocketIO) -> SyscallResult {
+    let s = copyin(s)?;
+
+    let fd    = s.fd;
+    let buf   = s.buf;
+    let len   = core::cmp::min(s.len, UACCESS_CHUNK);
+    let flags = s.flags;
 
     //syscall_log(LOG_DEBUG, "recv(fd=%d, buf=%p, len=%d, flags=%x)\n",
     //        fd, buf, len, flags);
 
-    if fd < 0 || (fd as usize) >= FDS_COUNT {
-        /* out of bounds */
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
-    }
-
-    let file = (*curproc!()).fds.offset(fd);
+    let file = proc_fd_file(curproc!(), fd).ok_or(Error::EBADF)?;
 
-    if file.is_null() {
-        arch::syscall_return(curthread!(), -EBADFD as usize);
-        return; 
+    if !user_access_ok(buf as usize, len, VM_UW) {
+        return Err(Error::EFAULT);
     }
 
+    let mut kbuf = vec![0u8; len];
     let mut err = 0;
 
-    err = socket_recv(file, buf, len, flags);
+    err = socket_recv(file, kbuf.as_mut_ptr(), len, flags);
     if err != 0 {
-        arch::syscall_return(curthread!(), err as usize);
-        return; 
+        return Error::wrap_isize_to_usize(err);
     }
 
-    arch::syscall_return(curthread!(), 0);
-    return;
+    copy_to_user(buf, kbuf.as_ptr(), len)?;
+
+    Ok(0)
 }
 
 
-unsafe fn sys_umask(mask: mode_t) {
+pub unsafe fn sys_umask(mask: mode_t) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "umask(mask=%d)\n", mask);
 
     let cur_mask = (*curproc!()).mask;
     (*curproc!()).mask = mask & 0o0777;
 
-    arch::syscall_return(curthread!(), cur_mask as usize);
-    return;
+    Ok(cur_mask as usize)
 }
 
 
-unsafe fn sys_sysconf(name: isize) {
+unsafe fn sys_sysconf(name: isize) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "sysconf(name=%d)\n", name);
-    arch::syscall_return(curthread!(), -ENOSYS as usize);
+    Err(Error::ENOSYS)
 }
 
 
-unsafe fn sys_gettimeofday(p_tv: *mut TimeVal, p_tz: *mut TimeZone) {
+unsafe fn sys_gettimeofday(p_tv: *mut TimeVal, p_tz: *mut TimeZone) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "gettimeofday(tv=%p, tz=%p)\n", tv, tz);
-    match gettimeofday() {
-        Ok((tv, tz)) => {
-            p_tv.write(tv);
-            p_tz.write(tz);
-            arch::syscall_return(curthread!(), 0);
-        },
-        Err(err) => {
-            arch::syscall_return(curthread!(), -err as usize);
-        }
+    let (tv, tz) = gettimeofday()?;
+
+    if !p_tv.is_null() {
+        copyout(p_tv, &tv)?;
+    }
+
+    if !p_tz.is_null() {
+        copyout(p_tz, &tz)?;
     }
+
+    Ok(0)
 }
 
 
-unsafe fn sys_sigmask(how: isize, set: *mut u8, oldset: *mut u8) {
+unsafe fn sys_sigmask(how: isize, set: *const sigset_t, oldset: *mut sigset_t) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "sigmask(how=%d, set=%p, oldset=%p)\n", how, set, oldset);
-    arch::syscall_return(curthread!(), -Error::ENOTSUP as usize);
+
+    let set = if set.is_null() { None } else { Some(copyin(set)?) };
+    let old = signal_mask(how, set)?;
+
+    if !oldset.is_null() {
+        copyout(oldset, &old)?;
+    }
+
+    Ok(0)
 }
 
 type fd_mask = usize;
@@ -705,118 +1053,194 @@ pub struct SelectArgs {
 }
 
 
-unsafe fn sys_select(args: *mut SelectArgs) {
+unsafe fn sys_select(args: *mut SelectArgs) -> SyscallResult {
     //syscall_log(LOG_DEBUG, "select(args=%p)\n", args);
 
-    let nfds = (*args).nfds as usize;
-    let readfds = (*args).readfds;
-    let writefds = (*args).writefds;
-    let exceptfds = (*args).exceptfds;
-    let timeout = (*args).timeout;
+    let args = copyin(args)?;
+
+    let nfds = core::cmp::min(args.nfds as usize, FD_SETSIZE);
+    let mut readfds = if args.readfds.is_null() { None } else { Some(copyin(args.readfds)?) };
+    let mut writefds = if args.writefds.is_null() { None } else { Some(copyin(args.writefds)?) };
+    let exceptfds = args.exceptfds;
+    let timeout = args.timeout;
 
     let mut count = 0;
 
     for i in 0..nfds {
-        if !readfds.is_null() && ((*readfds).fds_bits[i/NFDBITS] & (1 << (i % NFDBITS)) != 0) {
-            let file = (*curproc!()).fds.offset(i as isize);
-            if fs::vfs_file_can_read(file, 1) > 0 {
-                (*readfds).fds_bits[i/NFDBITS] |= (1 << (i % NFDBITS));
-                count += 1;
-            } else {
-                (*readfds).fds_bits[i/NFDBITS] &= !(1 << (i % NFDBITS));
+        if let Some(readfds) = &mut readfds {
+            if readfds.fds_bits[i/NFDBITS] & (1 << (i % NFDBITS)) != 0 {
+                let file = proc_fd_file(curproc!(), i as isize).ok_or(Error::EBADF)?;
+                if fs::vfs_file_can_read(file, 1) > 0 {
+                    readfds.fds_bits[i/NFDBITS] |= (1 << (i % NFDBITS));
+                    count += 1;
+                } else {
+                    readfds.fds_bits[i/NFDBITS] &= !(1 << (i % NFDBITS));
+                }
             }
         }
 
-        if !writefds.is_null() && ((*writefds).fds_bits[i/NFDBITS] & (1 << (i % NFDBITS)) != 0) {
-            let file = (*curproc!()).fds.offset(i as isize);
-            if fs::vfs_file_can_write(file, 1) > 0 {
-                (*writefds).fds_bits[i/NFDBITS] |= (1 << (i % NFDBITS));
-                count += 1;
-            } else {
-                (*writefds).fds_bits[i/NFDBITS] &= !(1 << (i % NFDBITS));
+        if let Some(writefds) = &mut writefds {
+            if writefds.fds_bits[i/NFDBITS] & (1 << (i % NFDBITS)) != 0 {
+                let file = proc_fd_file(curproc!(), i as isize).ok_or(Error::EBADF)?;
+                if fs::vfs_file_can_write(file, 1) > 0 {
+                    writefds.fds_bits[i/NFDBITS] |= (1 << (i % NFDBITS));
+                    count += 1;
+                } else {
+                    writefds.fds_bits[i/NFDBITS] &= !(1 << (i % NFDBITS));
+                }
             }
         }
     }
 
-    arch::syscall_return(curthread!(), count);
+    if let Some(readfds) = &readfds {
+        copyout(args.readfds, readfds)?;
+    }
+
+    if let Some(writefds) = &writefds {
+        copyout(args.writefds, writefds)?;
+    }
+
+    Ok(count)
 }
 
 
-unsafe fn sys_getpgrp() {
+pub unsafe fn sys_getpgrp() -> SyscallResult {
     //syscall_log(LOG_DEBUG, "getpgrp()\n");
-    arch::syscall_return(curthread!(), (*(*curproc!()).pgrp).pgid as usize);
-}
-
-
-#[repr(transparent)]
-pub struct Syscall(pub *const u8);
-unsafe impl Sync for Syscall {}
-
-// XXX find a way to dynamically count syscalls
-
-pub static SYSCALL_TABLE: [Syscall; 60] = [
-    /* 00 */    Syscall(core::ptr::null()),
-    /* 01 */    Syscall(sys_exit as *const _),
-    /* 02 */    Syscall(close as *const _),
-    /* 03 */    Syscall(sys_execve as *const _),
-    /* 04 */    Syscall(sys_fork as *const _),
-    /* 05 */    Syscall(fstat as *const _),
-    /* 06 */    Syscall(sys_getpid as *const _),
-    /* 07 */    Syscall(isatty as *const _),
-    /* 08 */    Syscall(sys_kill as *const _),
-    /* 09 */    Syscall(link as *const _),
-    /* 10 */    Syscall(lseek as *const _),
-    /* 11 */    Syscall(open as *const _),
-    /* 12 */    Syscall(read as *const _),
-    /* 13 */    Syscall(sys_sbrk as *const _),
-    /* 14 */    Syscall(stat as *const _),
-    /* 15 */    Syscall(sys_times as *const _),
-    /* 16 */    Syscall(unlink as *const _),
-    /* 17 */    Syscall(sys_waitpid as *const _),
-    /* 18 */    Syscall(write as *const _),
-    /* 19 */    Syscall(ioctl as *const _),
-    /* 20 */    Syscall(sys_sigaction as *const _),
-    /* 21 */    Syscall(readdir as *const _),
-    /* 22 */    Syscall(mount as *const _),
-    /* 23 */    Syscall(mkdir as *const _),
-    /* 24 */    Syscall(sys_uname as *const _),
-    /* 25 */    Syscall(pipe as *const _),
-    /* 26 */    Syscall(fcntl as *const _),
-    /* 27 */    Syscall(sys_chdir as *const _),
-    /* 28 */    Syscall(sys_getcwd as *const _),
-    /* 29 */    Syscall(sys_thread_create as *const _),
-    /* 30 */    Syscall(sys_thread_exit as *const _),
-    /* 31 */    Syscall(sys_thread_join as *const _),
-    /* 32 */    Syscall(sys_setpgid as *const _),
-    /* 33 */    Syscall(mknod as *const _),
-    /* 34 */    Syscall(lstat as *const _),
-    /* 35 */    Syscall(sys_auth as *const _), /* deprecated */
-    /* 36 */    Syscall(sys_getuid as *const _),
-    /* 37 */    Syscall(sys_getgid as *const _),
-    /* 38 */    Syscall(mmap as *const _),
-    /* 39 */    Syscall(munmap as *const _),
-    /* 40 */    Syscall(sys_socket as *const _),
-    /* 41 */    Syscall(sys_accept as *const _),
-    /* 42 */    Syscall(sys_bind as *const _),
-    /* 43 */    Syscall(sys_connect as *const _),
-    /* 44 */    Syscall(sys_listen as *const _),
-    /* 45 */    Syscall(sys_send as *const _),
-    /* 46 */    Syscall(sys_recv as *const _),
-    /* 47 */    Syscall(sys_umask as *const _),
-    /* 48 */    Syscall(chmod as *const _),
-    /* 49 */    Syscall(sys_sysconf as *const _),
-    /* 50 */    Syscall(sys_gettimeofday as *const _),
-    /* 51 */    Syscall(access as *const _),
-    /* 52 */    Syscall(sys_sigmask as *const _),
-    /* 53 */    Syscall(sys_select as *const _),
-    /* 54 */    Syscall(sys_getpgrp as *const _),
-    /* 55 */    Syscall(chown as *const _),
-    /* 56 */    Syscall(fchown as *const _),
-    /* 57 */    Syscall(lchown as *const _),
-    /* 58 */    Syscall(utime as *const _),
-    /* 59 */    Syscall(rmdir as *const _),
-];
+    Ok((*(*curproc!()).pgrp).pgid as usize)
+}
 
-//pub static syscall_cnt: size_t = core::mem::size_of_val(&syscall_table)/core::mem::size_of_val(&syscall_table[0]);
 
-pub static SYSCALL_CNT: size_t = 60;
+unsafe fn sys_ptrace(request: usize, pid: pid_t, addr: usize, data: usize) -> SyscallResult {
+    //syscall_log(LOG_DEBUG, "ptrace(request=%d, pid=%d, addr=%p, data=%p)\n",
+    //        request, pid, addr, data);
+
+    ptrace::ptrace(curproc!(), request, pid, addr, data).map(|ret| ret as usize)
+}
+
+
+/** builtin syscalls, indexed by number */
+pub static SYSCALL_TABLE: &[SyscallDef] = &[
+    /* 00 */    syscall_def!("nosys", sys_nosys, []),
+    /* 01 */    syscall_def!(noreturn "exit", sys_exit, [Int]),
+    /* 02 */    syscall_def!("close", close, [Int]),
+    /* 03 */    syscall_def!(noreturn "execve", sys_execve, [Str, Hex, Hex]),
+    /* 04 */    syscall_def!("fork", sys_fork, []),
+    /* 05 */    syscall_def!("fstat", fstat, [Int, Hex]),
+    /* 06 */    syscall_def!("getpid", sys_getpid, []),
+    /* 07 */    syscall_def!("isatty", isatty, [Int]),
+    /* 08 */    syscall_def!("kill", sys_kill, [Int, Int]),
+    /* 09 */    syscall_def!("link", link, [Str, Str]),
+    /* 10 */    syscall_def!("lseek", lseek, [Int, Int, Int]),
+    /* 11 */    syscall_def!("open", open, [Str, Hex, Oct]),
+    /* 12 */    syscall_def!("read", read, [Int, Hex, Int]),
+    /* 13 */    syscall_def!("sbrk", sys_sbrk, [Int]),
+    /* 14 */    syscall_def!("stat", stat, [Str, Hex]),
+    /* 15 */    syscall_def!("times", sys_times, []),
+    /* 16 */    syscall_def!("unlink", unlink, [Str]),
+    /* 17 */    syscall_def!("waitpid", sys_waitpid, [Int, Hex, Hex]),
+    /* 18 */    syscall_def!("write", write, [Int, Hex, Int]),
+    /* 19 */    syscall_def!("ioctl", ioctl, [Int, Hex, Hex]),
+    /* 20 */    syscall_def!("sigaction", sys_sigaction, [Int, Hex, Hex]),
+    /* 21 */    syscall_def!("readdir", readdir, [Int, Hex]),
+    /* 22 */    syscall_def!("mount", mount, [Hex]),
+    /* 23 */    syscall_def!("mkdir", mkdir, [Str, Oct]),
+    /* 24 */    syscall_def!("uname", sys_uname, [Hex]),
+    /* 25 */    syscall_def!("pipe", pipe, [Hex]),
+    /* 26 */    syscall_def!("fcntl", fcntl, [Int, Int, Hex]),
+    /* 27 */    syscall_def!("chdir", sys_chdir, [Str]),
+    /* 28 */    syscall_def!("getcwd", sys_getcwd, [Hex, Int]),
+    /* 29 */    syscall_def!("thread_create", sys_thread_create, [Hex]),
+    /* 30 */    syscall_def!(noreturn "thread_exit", sys_thread_exit, [Hex]),
+    /* 31 */    syscall_def!("thread_join", sys_thread_join, [Int, Hex]),
+    /* 32 */    syscall_def!("setpgid", sys_setpgid, [Int, Int]),
+    /* 33 */    syscall_def!("mknod", mknod, [Str, Oct, Hex]),
+    /* 34 */    syscall_def!("lstat", lstat, [Str, Hex]),
+    /* 35 */    syscall_def!("auth", sys_auth, [Int, Hex]), /* deprecated */
+    /* 36 */    syscall_def!("getuid", sys_getuid, []),
+    /* 37 */    syscall_def!("getgid", sys_getgid, []),
+    /* 38 */    syscall_def!("mmap", mmap, [Hex, Hex]),
+    /* 39 */    syscall_def!("munmap", munmap, [Hex, Int]),
+    /* 40 */    syscall_def!("socket", sys_socket, [Int, Int, Int]),
+    /* 41 */    syscall_def!("accept", sys_accept, [Int, Hex, Hex]),
+    /* 42 */    syscall_def!("bind", sys_bind, [Int, Hex, Int]),
+    /* 43 */    syscall_def!("connect", sys_connect, [Int, Hex, Int]),
+    /* 44 */    syscall_def!("listen", sys_listen, [Int, Int]),
+    /* 45 */    syscall_def!("send", sys_send, [Hex]),
+    /* 46 */    syscall_def!("recv", sys_recv, [Hex]),
+    /* 47 */    syscall_def!("umask", sys_umask, [Oct]),
+    /* 48 */    syscall_def!("chmod", chmod, [Str, Oct]),
+    /* 49 */    syscall_def!("sysconf", sys_sysconf, [Int]),
+    /* 50 */    syscall_def!("gettimeofday", sys_gettimeofday, [Hex, Hex]),
+    /* 51 */    syscall_def!("access", access, [Str, Oct]),
+    /* 52 */    syscall_def!("sigmask", sys_sigmask, [Int, Hex, Hex]),
+    /* 53 */    syscall_def!("select", sys_select, [Hex]),
+    /* 54 */    syscall_def!("getpgrp", sys_getpgrp, []),
+    /* 55 */    syscall_def!("chown", chown, [Str, Int, Int]),
+    /* 56 */    syscall_def!("fchown", fchown, [Int, Int, Int]),
+    /* 57 */    syscall_def!("lchown", lchown, [Str, Int, Int]),
+    /* 58 */    syscall_def!("utime", utime, [Str, Hex]),
+    /* 59 */    syscall_def!("rmdir", rmdir, [Str]),
+    /* 60 */    syscall_def!("shm_open", shm_open, [Str, Hex, Oct]),
+    /* 61 */    syscall_def!("shm_unlink", shm_unlink, [Str]),
+    /* 62 */    syscall_def!("shmget", shmget, [Int, Int, Hex]),
+    /* 63 */    syscall_def!("shmat", shmat, [Int, Hex, Hex]),
+    /* 64 */    syscall_def!("shmdt", shmdt, [Hex]),
+    /* 65 */    syscall_def!("shmctl", shmctl, [Int, Int, Hex]),
+    /* 66 */    syscall_def!("ftruncate", ftruncate, [Int, Int]),
+    /* 67 */    syscall_def!("msgget", msgget, [Int, Hex]),
+    /* 68 */    syscall_def!("msgsnd", msgsnd, [Int, Hex, Int, Hex]),
+    /* 69 */    syscall_def!("msgrcv", msgrcv, [Int, Hex, Int, Int, Hex]),
+    /* 70 */    syscall_def!("msgctl", msgctl, [Int, Int, Hex]),
+    /* 71 */    syscall_def!("semget", semget, [Int, Int, Hex]),
+    /* 72 */    syscall_def!("semop", semop, [Int, Hex, Int]),
+    /* 73 */    syscall_def!("semctl", semctl, [Int, Int, Int, Hex]),
+    /* 74 */    syscall_def!("futex", sys_futex, [Hex, Int, Int, Hex, Hex, Int]),
+    /* 75 */    syscall_def!("set_thread_area", sys_set_thread_area, [Hex]),
+    /* 76 */    syscall_def!("get_thread_area", sys_get_thread_area, [Hex]),
+    /* 77 */    syscall_def!("gettid", sys_gettid, []),
+    /* 78 */    syscall_def!("thread_detach", sys_thread_detach, [Int]),
+    /* 79 */    syscall_def!("thread_kill", sys_thread_kill, [Int, Int]),
+    /* 80 */    syscall_def!("tgkill", sys_tgkill, [Int, Int, Int]),
+    /* 81 */    syscall_def!("getrlimit", sys_getrlimit, [Int, Hex]),
+    /* 82 */    syscall_def!("setrlimit", sys_setrlimit, [Int, Hex]),
+    /* 83 */    syscall_def!("ptrace", sys_ptrace, [Int, Int, Hex, Hex]),
+    /* 84 */    syscall_def!("rename", rename, [Str, Str]),
+    /* 85 */    syscall_def!("renameat", renameat, [Int, Str, Int, Str]),
+    /* 86 */    syscall_def!("symlink", symlink, [Str, Str]),
+    /* 87 */    syscall_def!("readlink", readlink, [Str, Hex, Int]),
+    /* 88 */    syscall_def!("openat", openat, [Int, Str, Hex, Oct]),
+    /* 89 */    syscall_def!("mkdirat", mkdirat, [Int, Str, Oct]),
+    /* 90 */    syscall_def!("unlinkat", unlinkat, [Int, Str, Hex]),
+    /* 91 */    syscall_def!("fstatat", fstatat, [Int, Str, Hex, Hex]),
+    /* 92 */    syscall_def!("readlinkat", readlinkat, [Int, Str, Hex, Int]),
+    /* 93 */    syscall_def!("fchmodat", fchmodat, [Int, Str, Oct, Hex]),
+    /* 94 */    syscall_def!("fchownat", fchownat, [Int, Str, Int, Int, Hex]),
+    /* 95 */    syscall_def!("faccessat", faccessat, [Int, Str, Oct, Hex]),
+    /* 96 */    syscall_def!("umount", umount, [Str]),
+    /* 97 */    syscall_def!("umount2", umount2, [Str, Hex]),
+    /* 98 */    syscall_def!("chroot", sys_chroot, [Str]),
+    /* 99 */    syscall_def!("fchdir", sys_fchdir, [Int]),
+    /* 100 */   syscall_def!("poll", poll, [Hex, Int, Int]),
+    /* 101 */   syscall_def!("ppoll", ppoll, [Hex, Int, Hex, Hex]),
+    /* 102 */   syscall_def!("epoll_create", epoll_create, [Int]),
+    /* 103 */   syscall_def!("epoll_create1", epoll_create1, [Hex]),
+    /* 104 */   syscall_def!("epoll_ctl", epoll_ctl, [Int, Int, Int, Hex]),
+    /* 105 */   syscall_def!("epoll_wait", epoll_wait, [Int, Hex, Int, Int]),
+    /* 106 */   syscall_def!("eventfd", eventfd, [Int, Hex]),
+    /* 107 */   syscall_def!("signalfd", signalfd, [Int, Hex, Hex]),
+    /* 108 */   syscall_def!("timerfd_create", timerfd_create, [Int, Hex]),
+    /* 109 */   syscall_def!("timerfd_settime", timerfd_settime, [Int, Hex, Hex, Hex]),
+    /* 110 */   syscall_def!("timerfd_gettime", timerfd_gettime, [Int, Hex]),
+    /* 111 */   syscall_def!("flock", flock, [Int, Hex]),
+    /* 112 */   syscall_def!("dup", dup, [Int]),
+    /* 113 */   syscall_def!("dup2", dup2, [Int, Int]),
+    /* 114 */   syscall_def!("dup3", dup3, [Int, Int, Hex]),
+    /* 115 */   syscall_def!("readv", readv, [Int, Hex, Int]),
+    /* 116 */   syscall_def!("writev", writev, [Int, Hex, Int]),
+    /* 117 */   syscall_def!("pread", pread, [Int, Hex, Int, Int]),
+    /* 118 */   syscall_def!("pwrite", pwrite, [Int, Hex, Int, Int]),
+    /* 119 */   syscall_def!("preadv", preadv, [Int, Hex, Int, Int]),
+    /* 120 */   syscall_def!("pwritev", pwritev, [Int, Hex, Int, Int]),
+    /* 121 */   syscall_def!("sendfile", sendfile, [Int, Int, Hex, Int]),
+    /* 122 */   syscall_def!("splice", splice, [Int, Hex, Int, Hex, Int, Hex]),
+];
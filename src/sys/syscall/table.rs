@@ -0,0 +1,195 @@
+use prelude::*;
+
+use sys::linux::syscall::linux_syscall_lookup;
+use sys::process::*;
+use sys::syscall::SYSCALL_TABLE;
+use sys::syscall::trace::TraceArg;
+
+/*
+ * syscalls are described by a `SyscallDef` built with `syscall_def!`, e.g.
+ *
+ *     syscall_def!("read", read, [Int, Hex, Int])
+ *
+ * the argument list gives both the number of arguments and how the tracer
+ * prints them. every argument is decoded from its register into the type
+ * the handler declares through `SyscallArg`, and the handler returns a
+ * `SyscallResult` which is mapped to the value or -errno seen by userspace
+ * in one place, the arch syscall entry.
+ *
+ * builtin syscalls live in `SYSCALL_TABLE`, modules may add more at runtime
+ * with `syscall_register`, above the builtin ones. processes running with
+ * another personality use that personality's table instead.
+ */
+
+/** most arguments a syscall takes */
+pub const SYSCALL_ARGS_MAX: usize = 6;
+
+/** raw argument registers of a syscall */
+pub type SyscallArgs = [usize; SYSCALL_ARGS_MAX];
+
+pub type SyscallResult = Result<usize, Error>;
+
+pub type SyscallHandler = unsafe fn(args: &SyscallArgs) -> SyscallResult;
+
+pub struct SyscallDef {
+    pub name: &'static str,
+
+    /** decoding of each argument, also gives the argument count */
+    pub args: &'static [TraceArg],
+
+    /** does not return on success */
+    pub noreturn: bool,
+
+    pub handler: SyscallHandler,
+}
+
+/** a type a syscall argument register can be decoded into */
+pub trait SyscallArg {
+    fn from_arg(arg: usize) -> Self;
+}
+
+macro syscall_arg_int {
+    ($($t:ty),*) => {
+        $(
+            impl SyscallArg for $t {
+                #[inline]
+                fn from_arg(arg: usize) -> Self {
+                    arg as $t
+                }
+            }
+        )*
+    }
+}
+
+syscall_arg_int!(usize, isize, u32, i32, u16, i16, u8, i8);
+
+impl<T> SyscallArg for *const T {
+    #[inline]
+    fn from_arg(arg: usize) -> Self {
+        arg as *const T
+    }
+}
+
+impl<T> SyscallArg for *mut T {
+    #[inline]
+    fn from_arg(arg: usize) -> Self {
+        arg as *mut T
+    }
+}
+
+/** build a `SyscallDef` for `handler`, see the top of this file */
+pub macro syscall_def {
+    (@def $name:expr, $handler:path, [$($arg:ident),*], $noreturn:expr) => {
+        SyscallDef {
+            name: $name,
+            args: &[$(TraceArg::$arg),*],
+            noreturn: $noreturn,
+            handler: {
+                unsafe fn handler(args: &SyscallArgs) -> SyscallResult {
+                    let mut _regs = args.iter();
+
+                    /* arguments are evaluated left to right, one register each */
+                    $handler($({ let _ = TraceArg::$arg; SyscallArg::from_arg(*_regs.next().unwrap()) }),*)
+                }
+
+                handler
+            },
+        }
+    },
+
+    (noreturn $name:expr, $handler:path, [$($arg:ident),*]) => {
+        syscall_def!(@def $name, $handler, [$($arg),*], true)
+    },
+
+    ($name:expr, $handler:path, [$($arg:ident),*]) => {
+        syscall_def!(@def $name, $handler, [$($arg),*], false)
+    },
+}
+
+/** syscalls registered at runtime, indexed by number - SYSCALL_TABLE.len() */
+static mut SYSCALL_DYNAMIC: Vec<Option<&'static SyscallDef>> = Vec::new();
+
+/** number of syscall slots, including empty ones */
+pub unsafe fn syscall_count() -> usize {
+    SYSCALL_TABLE.len() + SYSCALL_DYNAMIC.len()
+}
+
+pub unsafe fn syscall_lookup(nr: usize) -> Option<&'static SyscallDef> {
+    if nr < SYSCALL_TABLE.len() {
+        return Some(&SYSCALL_TABLE[nr]);
+    }
+
+    match SYSCALL_DYNAMIC.get(nr - SYSCALL_TABLE.len()) {
+        Some(def) => *def,
+        None => None,
+    }
+}
+
+/** syscall `nr` as numbered by the personality of `proc` */
+pub unsafe fn proc_syscall_lookup(proc: *mut Process, nr: usize) -> Option<&'static SyscallDef> {
+    match (*proc).personality {
+        Personality::Aquila => syscall_lookup(nr),
+        Personality::Linux => linux_syscall_lookup(nr),
+    }
+}
+
+/** number of the syscall called `name` */
+pub unsafe fn syscall_find(name: &str) -> Option<usize> {
+    (0..syscall_count()).find(|&nr| match syscall_lookup(nr) {
+        Some(def) => def.name == name,
+        None => false,
+    })
+}
+
+/** register `def` as syscall `nr`, builtin numbers can not be replaced */
+pub unsafe fn syscall_register(nr: usize, def: &'static SyscallDef) -> Result<(), Error> {
+    if def.args.len() > SYSCALL_ARGS_MAX {
+        return Err(Error::EINVAL);
+    }
+
+    if nr < SYSCALL_TABLE.len() {
+        return Err(Error::EEXIST);
+    }
+
+    let idx = nr - SYSCALL_TABLE.len();
+
+    if idx >= SYSCALL_DYNAMIC.len() {
+        SYSCALL_DYNAMIC.resize(idx + 1, None);
+    }
+
+    if SYSCALL_DYNAMIC[idx].is_some() {
+        return Err(Error::EEXIST);
+    }
+
+    SYSCALL_DYNAMIC[idx] = Some(def);
+
+    Ok(())
+}
+
+pub unsafe fn syscall_unregister(nr: usize) -> Result<(), Error> {
+    if nr < SYSCALL_TABLE.len() {
+        return Err(Error::EPERM);
+    }
+
+    match SYSCALL_DYNAMIC.get_mut(nr - SYSCALL_TABLE.len()) {
+        Some(slot) if slot.is_some() => {
+            *slot = None;
+        },
+        _ => return Err(Error::ENOENT),
+    }
+
+    /* drop empty slots at the end */
+    while let Some(None) = SYSCALL_DYNAMIC.last() {
+        SYSCALL_DYNAMIC.pop();
+    }
+
+    Ok(())
+}
+
+/** entry point of the arch syscall handler, returns the value for userspace */
+pub unsafe fn syscall_dispatch(def: &SyscallDef, args: &SyscallArgs) -> usize {
+    match (def.handler)(args) {
+        Ok(val) => val,
+        Err(err) => err.unwrap() as usize,
+    }
+}
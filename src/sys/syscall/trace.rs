@@ -0,0 +1,412 @@
+use prelude::*;
+
+use arch::sys::sched::arch_rtime_ns;
+use core::fmt::Write;
+use dev::dev::*;
+use dev::kdev::*;
+use fs::*;
+use kern::kargs::kargs_get;
+use mm::*;
+use sys::process::*;
+use sys::sched::*;
+use sys::syscall::file::FileDescriptor;
+use sys::syscall::table::*;
+
+/*
+ * kernel side syscall tracer, every traced syscall is logged as
+ *
+ *     [pid:tid] name(args...) = ret <duration>
+ *
+ * into a ring buffer read through the log device (major 10, minor 0). the
+ * tracer is configured with the `strace`, `strace.pid` and `strace.syscalls`
+ * kernel arguments and at runtime by writing commands to the control device
+ * (major 10, minor 1):
+ *
+ *     on | off             enable or disable tracing
+ *     pid <pid>            only trace <pid>, 0 traces all processes
+ *     syscalls all        trace every syscall
+ *     syscalls <a,b,...>  only trace the named syscalls
+ *     clear                drop everything in the ring buffer
+ */
+
+/** how an argument is decoded in the log */
+#[derive(Copy, Clone, PartialEq)]
+pub enum TraceArg {
+    /** signed decimal */
+    Int,
+    /** hexadecimal, pointers and flags */
+    Hex,
+    /** octal, file modes */
+    Oct,
+    /** null terminated user string */
+    Str,
+}
+
+use self::TraceArg::*;
+
+/** major number of the trace devices */
+const TRACE_MAJOR: devid_t = 10;
+
+const TRACE_MINOR_LOG: devid_t = 0;
+const TRACE_MINOR_CTL: devid_t = 1;
+
+/** size of the log ring buffer, the oldest entries are dropped when full */
+const TRACE_BUFLEN: usize = 16384;
+
+/** longest string argument logged, longer ones are cut with "..." */
+const TRACE_STRLEN: usize = 32;
+
+/** longest control command accepted */
+const TRACE_CTL_MAX: usize = 512;
+
+static mut TRACE_ON: bool = false;
+
+/** only trace this process, 0 for all */
+static mut TRACE_PID: pid_t = 0;
+
+/** bitmap of traced syscall numbers */
+static mut TRACE_SET: [u32; 8] = [!0; 8];
+
+/** thread whose syscall in progress read the log, its trace is dropped so reading doesn't feed the log */
+static mut TRACE_READER: tid_t = 0;
+
+static mut TRACE_RING: *mut RingBuffer = core::ptr::null_mut();
+
+/** a syscall being traced, from entry to exit */
+pub struct SyscallTrace {
+    def: &'static SyscallDef,
+    line: String,
+    start: u64,
+}
+
+fn trace_set_has(set: &[u32; 8], nr: usize) -> bool {
+    nr < set.len() * 32 && set[nr / 32] & (1 << (nr % 32)) != 0
+}
+
+unsafe fn syscall_traced(nr: usize, def: &SyscallDef) -> bool {
+    let pid = (*curproc!()).pid;
+
+    TRACE_ON && !TRACE_RING.is_null()
+        && (TRACE_PID == 0 || TRACE_PID == pid)
+        && (TRACE_SET == [!0; 8] || trace_set_has(&TRACE_SET, trace_native_nr(nr, def)))
+}
+
+/** the trace set holds native numbers, other personalities are matched by name */
+unsafe fn trace_native_nr(nr: usize, def: &SyscallDef) -> usize {
+    match (*curproc!()).personality {
+        Personality::Aquila => nr,
+        _ => syscall_find(def.name).unwrap_or(!0),
+    }
+}
+
+/** is `addr` inside a readable mapping of the current process? */
+unsafe fn trace_user_readable(addr: usize) -> bool {
+    match (*curproc!()).vm_space.find(addr) {
+        Some(vm_entry) => vm_entry.flags & VM_UR != 0,
+        None => false,
+    }
+}
+
+/** append user string `s` to `line`, quoted and escaped */
+unsafe fn trace_str(line: &mut String, s: *const u8) {
+    if s.is_null() {
+        line.push_str("NULL");
+        return;
+    }
+
+    if !trace_user_readable(s as usize) {
+        let _ = write!(line, "{:p}", s);
+        return;
+    }
+
+    line.push('"');
+
+    /* strings running into an unmapped page are cut there */
+    let readable = |i: usize| {
+        let addr = s as usize + i;
+        addr & (PAGE_SIZE - 1) != 0 || trace_user_readable(addr)
+    };
+
+    let mut i = 0;
+
+    while i < TRACE_STRLEN && readable(i) {
+        let c = *s.offset(i as isize);
+
+        match c {
+            0 => break,
+            b'"' | b'\\' => { line.push('\\'); line.push(c as char); },
+            b'\n' => line.push_str("\\n"),
+            b'\t' => line.push_str("\\t"),
+            0x20..=0x7e => line.push(c as char),
+            _ => { let _ = write!(line, "\\x{:02x}", c); },
+        }
+
+        i += 1;
+    }
+
+    line.push('"');
+
+    if i == TRACE_STRLEN && readable(i) && *s.offset(i as isize) != 0 {
+        line.push_str("...");
+    }
+}
+
+/** errno name of `err`, if it has one */
+fn trace_errno(err: isize) -> Option<Error> {
+    Error::from_errno(err)
+}
+
+unsafe fn trace_log(line: &String) {
+    (*TRACE_RING).write_overwrite(line.len(), line.as_ptr() as *mut u8);
+}
+
+/** called on syscall entry, returns the trace to finish at exit if `nr` is traced */
+pub unsafe fn syscall_trace_enter(nr: usize, def: &'static SyscallDef, args: &SyscallArgs) -> Option<SyscallTrace> {
+    /* left over from an untraced read of the log */
+    if TRACE_READER == (*curthread!()).tid {
+        TRACE_READER = 0;
+    }
+
+    if !syscall_traced(nr, def) {
+        return None;
+    }
+
+    let mut line = String::new();
+
+    let _ = write!(line, "[{}:{}] {}(", (*curproc!()).pid, (*curthread!()).tid, def.name);
+
+    for (i, arg) in def.args.iter().enumerate() {
+        if i != 0 {
+            line.push_str(", ");
+        }
+
+        let val = args[i];
+
+        let _ = match arg {
+            Int => write!(line, "{}", val as isize),
+            Hex => write!(line, "{:#x}", val),
+            Oct => write!(line, "{:#o}", val),
+            Str => { trace_str(&mut line, val as *const u8); Ok(()) },
+        };
+    }
+
+    line.push(')');
+
+    /* the syscall won't come back if it succeeds, log it now */
+    if def.noreturn {
+        let mut entry = line.clone();
+        entry.push_str(" ...\n");
+        trace_log(&entry);
+    }
+
+    Some(SyscallTrace { def, line, start: arch_rtime_ns() })
+}
+
+/** called on syscall exit with the value returned to userspace */
+pub unsafe fn syscall_trace_exit(trace: SyscallTrace, ret: isize) {
+    if TRACE_READER == (*curthread!()).tid {
+        TRACE_READER = 0;
+        return;
+    }
+
+    let def = trace.def;
+    let elapsed = arch_rtime_ns() - trace.start;
+
+    let mut line = trace.line;
+
+    if def.noreturn {
+        line.clear();
+        let _ = write!(line, "[{}:{}] <... {} resumed>", (*curproc!()).pid, (*curthread!()).tid, def.name);
+    }
+
+    let _ = match trace_errno(-ret) {
+        Some(err) => write!(line, " = -1 {:?}", err),
+        None => write!(line, " = {}", ret),
+    };
+
+    let _ = write!(line, " <{}.{:06}>\n", elapsed / 1000000000, elapsed % 1000000000 / 1000);
+
+    trace_log(&line);
+}
+
+/** parse a `syscalls` argument, a comma separated list of names or "all" */
+unsafe fn trace_parse_set(list: &str) -> Result<[u32; 8], Error> {
+    let mut set = [0u32; 8];
+
+    if list == "all" {
+        return Ok([!0; 8]);
+    }
+
+    for name in list.split(',').filter(|name| !name.is_empty()) {
+        match syscall_find(name) {
+            Some(nr) if nr < set.len() * 32 => set[nr / 32] |= 1 << (nr % 32),
+            Some(_) => return Err(Error::ERANGE),
+            None => return Err(Error::EINVAL),
+        }
+    }
+
+    Ok(set)
+}
+
+unsafe fn trace_command(cmd: &str) -> Result<(), Error> {
+    let mut words = cmd.split_whitespace();
+
+    match (words.next(), words.next()) {
+        (None, _) => {},
+        (Some("on"), None) => {
+            TRACE_ON = true;
+        },
+        (Some("off"), None) => {
+            TRACE_ON = false;
+        },
+        (Some("clear"), None) => {
+            let mut c = 0u8;
+            while (*TRACE_RING).read(1, &mut c) == 1 {}
+        },
+        (Some("pid"), Some(pid)) => {
+            TRACE_PID = pid.parse::<pid_t>().map_err(|_| Error::EINVAL)?;
+        },
+        (Some("syscalls"), Some(list)) => {
+            TRACE_SET = trace_parse_set(list)?;
+        },
+        _ => return Err(Error::EINVAL),
+    }
+
+    if words.next().is_some() {
+        return Err(Error::EINVAL);
+    }
+
+    Ok(())
+}
+
+/** current settings, as read from the control device */
+unsafe fn trace_settings() -> String {
+    let mut s = String::new();
+
+    let _ = write!(s, "{}\npid {}\nsyscalls ", if TRACE_ON { "on" } else { "off" }, TRACE_PID);
+
+    if TRACE_SET == [!0; 8] {
+        s.push_str("all");
+    } else {
+        let names = (0..syscall_count())
+            .filter(|&nr| trace_set_has(&TRACE_SET, nr))
+            .filter_map(|nr| syscall_lookup(nr))
+            .map(|def| def.name)
+            .collect::<Vec<&str>>();
+
+        s.push_str(&names.join(","));
+    }
+
+    s.push('\n');
+    s
+}
+
+unsafe fn trace_read(dd: *mut DeviceDescriptor, offset: off_t, size: usize, buf: *mut u8) -> isize {
+    match (*dd).minor {
+        TRACE_MINOR_LOG => {
+            TRACE_READER = (*curthread!()).tid;
+            (*TRACE_RING).read(size, buf) as isize
+        },
+        TRACE_MINOR_CTL => {
+            let settings = trace_settings();
+            let offset = offset as usize;
+
+            if offset >= settings.len() {
+                return 0;
+            }
+
+            let size = if size < settings.len() - offset { size } else { settings.len() - offset };
+            memcpy(buf, settings.as_ptr().offset(offset as isize), size);
+
+            size as isize
+        },
+        _ => -ENXIO,
+    }
+}
+
+unsafe fn trace_write(dd: *mut DeviceDescriptor, _offset: off_t, size: usize, buf: *mut u8) -> isize {
+    if (*dd).minor != TRACE_MINOR_CTL {
+        return -EPERM;
+    }
+
+    if (*curproc!()).uid != 0 {
+        return -EPERM;
+    }
+
+    if size > TRACE_CTL_MAX {
+        return -EINVAL;
+    }
+
+    let cmds = match core::str::from_utf8(core::slice::from_raw_parts(buf, size)) {
+        Ok(cmds) => cmds,
+        Err(_) => return -EINVAL,
+    };
+
+    for cmd in cmds.lines() {
+        if let Err(err) = trace_command(cmd) {
+            return err.unwrap();
+        }
+    }
+
+    size as isize
+}
+
+unsafe fn trace_file_open(_file: *mut FileDescriptor) -> isize {
+    0
+}
+
+static mut TRACEDEV: Device = Device {
+    name:  "strace",
+    read:  Some(trace_read),
+    write: Some(trace_write),
+
+    fops: FileOps {
+        _open:      Some(trace_file_open),
+        _can_write: Some(__vfs_can_always),
+        _eof:       Some(__vfs_eof_always),
+
+        ..FileOps::none()
+    },
+
+    ..Device::none()
+};
+
+/** kernel argument `key` as a string */
+unsafe fn trace_karg(key: &str) -> Option<&'static str> {
+    let mut value: *const u8 = core::ptr::null();
+
+    if kargs_get(key.as_ptr(), &mut value) != 0 || value.is_null() {
+        return None;
+    }
+
+    core::str::from_utf8(core::slice::from_raw_parts(value, strlen(value))).ok()
+}
+
+fn init() -> Result<(), Error> {
+    unsafe {
+        TRACE_RING = Box::leak(RingBuffer::alloc(RingBuffer::new(TRACE_BUFLEN)));
+
+        if let Some(pid) = trace_karg("strace.pid\0") {
+            TRACE_PID = pid.parse::<pid_t>().map_err(|_| Error::EINVAL)?;
+        }
+
+        if let Some(list) = trace_karg("strace.syscalls\0") {
+            TRACE_SET = trace_parse_set(list)?;
+        }
+
+        if let Some(on) = trace_karg("strace\0") {
+            TRACE_ON = on == "1";
+        }
+
+        kdev_chrdev_register(TRACE_MAJOR, &mut TRACEDEV);
+
+        Ok(())
+    }
+}
+
+module_define!{
+    "strace",
+    None,
+    Some(init),
+    None
+}
@@ -1,9 +1,13 @@
 use prelude::*;
 
+use arch;
 use arch::sys::*;
 use core::fmt;
+use fs;
+use sys::futex::*;
 use sys::process::*;
 use sys::sched::*;
+use sys::signal::*;
 
 malloc_define!(M_THREAD, "thread\0", "thread structure\0");
 
@@ -40,6 +44,12 @@ pub struct Thread {
     pub sleep_queue: *mut Queue<*mut Thread>,
     pub sleep_node: *mut QueueNode<*mut Thread>,
 
+    /** sleep deadline in nanoseconds since boot, 0 if none */
+    pub sleep_deadline: u64,
+
+    /** the last sleep ended because the deadline passed */
+    pub sleep_timedout: bool,
+
     /** scheduler queue */
     pub sched_queue: *mut Queue<*mut Thread>,
     pub sched_node: *mut QueueNode<*mut Thread>,
@@ -49,6 +59,18 @@ pub struct Thread {
 
     /** thread flags */
     pub spawned: isize,
+
+    /** thread is reaped on exit instead of waiting to be joined */
+    pub detached: bool,
+
+    /** value passed to thread_exit */
+    pub value_ptr: *mut u8,
+
+    /** signals sent to this thread only, one bit per signal */
+    pub sig_pending: u32,
+
+    /** signals held back from delivery, in sigset_t layout */
+    pub sig_mask: sigset_t,
 }
 
 unsafe impl Sync for Thread {}
@@ -58,16 +80,144 @@ impl Thread {
         unsafe { Box::new_zeroed_tagged(&M_THREAD).assume_init() }
     }
 
+    /** detach the thread from all queues and free its kernel resources */
     pub fn kill(&mut self) -> isize {
         unsafe {
-            /* free resources */
-            arch_thread_kill(self);
+            /* thread may be blocked on a futex */
+            futex_thread_kill(self as *mut Thread);
+
+            if !self.sleep_node.is_null() {
+                /* thread is sleeping on some queue */
+                (*self.sleep_queue).node_remove(self.sleep_node);
+                self.sleep_node = core::ptr::null_mut();
+            }
+
+            if self.sleep_deadline != 0 {
+                /* thread is sleeping with a timeout */
+                SLEEP_TIMEOUTS.remove(self as *mut Thread);
+                self.sleep_deadline = 0;
+            }
+
+            if !self.sched_node.is_null() {
+                /* thread is in the scheduler queue */
+                (*self.sched_queue).node_remove(self.sched_node);
+                self.sched_node = core::ptr::null_mut();
+            }
+
+            /* free resources, a zombie has none left */
+            if !self.arch.is_null() {
+                arch_thread_kill(self);
+                self.arch = core::ptr::null_mut();
+            }
+
             self.state = ThreadState::ZOMBIE;
             return 0;
         }
     }
 }
 
+static mut TID_BITMAP: BitMap = bitmap_new!(4096);
+static mut FF_TID: isize = 1;
+
+pub unsafe fn thread_tid_alloc() -> isize {
+    for i in (FF_TID as usize)..TID_BITMAP.max_idx {
+        if bitmap_check(&mut TID_BITMAP, i) == 0 {
+            bitmap_set(&mut TID_BITMAP, i);
+            FF_TID = i as isize;
+            return i as isize;
+        }
+    }
+
+    return -1;
+}
+
+pub unsafe fn thread_tid_free(tid: isize) {
+    bitmap_clear(&mut TID_BITMAP, tid as usize);
+
+    if tid < FF_TID {
+        FF_TID = tid;
+    }
+}
+
+/** release a dead thread that is no longer referenced */
+pub unsafe fn thread_reap(thread: *mut Thread) {
+    (*(*thread).owner).threads.remove(thread);
+    thread_tid_free((*thread).tid as isize);
+    kfree(thread as *mut u8);
+}
+
+/**
+ * terminate the calling thread, detached threads are reaped right away,
+ * others stay around as zombies until joined
+ */
+pub unsafe fn thread_exit(value_ptr: *mut u8) -> ! {
+    let thread = curthread!();
+    let owner  = (*thread).owner;
+
+    (*thread).value_ptr = value_ptr;
+
+    let alive = (*owner).threads.iter()
+        .filter(|qnode| qnode.value != thread && (*qnode.value).state != ThreadState::ZOMBIE)
+        .count();
+
+    if alive == 0 {
+        /* last thread out, the whole process exits */
+        (*owner).exit = proc_exit!(0, 0);
+        proc_kill(owner);
+    }
+
+    if (*thread).detached {
+        (*owner).threads.remove(thread);
+        thread_tid_free((*thread).tid as isize);
+
+        /* frees the thread structure as well */
+        arch_cur_thread_kill();
+    } else {
+        /* wakeup owner if it is waiting for joining */
+        thread_queue_wakeup(&mut (*owner).thread_join);
+        arch_cur_thread_exit();
+    }
+
+    panic!("how did we get here?");
+}
+
+/** send `signal` to a specific thread */
+pub unsafe fn signal_thread_send(thread: *mut Thread, signal: isize) -> isize {
+    if thread == curthread!() && !signal_blocked(thread, signal) {
+        arch::handle_signal(signal as usize);
+    } else {
+        (*thread).sig_pending |= 1 << signal;
+
+        /* signalfds may have something to read now */
+        fs::signalfd_notify((*thread).owner);
+
+        /* interrupt the thread if sleeping, blocked signals wait */
+        if !signal_blocked(thread, signal) {
+            thread_interrupt(thread);
+        }
+    }
+
+    return 0;
+}
+
+/**
+ * take `thread` out of an interruptible sleep, leaving whoever else sleeps
+ * on the same queue alone, its thread_queue_sleep returns -EINTR
+ */
+pub unsafe fn thread_interrupt(thread: *mut Thread) {
+    if (*thread).state != ThreadState::ISLEEP || (*thread).sleep_node.is_null() {
+        return;
+    }
+
+    (*(*thread).sleep_queue).node_remove((*thread).sleep_node);
+    (*thread).sleep_node = core::ptr::null_mut();
+
+    /* a sleeper woken up in any other state was interrupted */
+    (*thread).state = ThreadState::RUNNABLE;
+
+    sched_thread_ready(thread);
+}
+
 pub unsafe fn thread_queue_sleep(queue: *mut Queue<*mut Thread>) -> isize {
     if queue.is_null() {
         panic!("sleeping in a blackhole?");
@@ -82,16 +232,40 @@ pub unsafe fn thread_queue_sleep(queue: *mut Queue<*mut Thread>) -> isize {
     arch_sleep();
 
     /* Woke up */
+    (*curthread!()).sleep_queue = core::ptr::null_mut();
+
     if ((*curthread!()).state != ThreadState::ISLEEP) {
         /* a signal interrupted the sleep */
-        //return -EINTR;
-        return -1; /* FIXME */
+        return -EINTR;
     } else {
         (*curthread!()).state = ThreadState::RUNNABLE;
         return 0;
     }
 }
 
+/**
+ * like thread_queue_sleep, but give up after `timeout` nanoseconds,
+ * returns 1 if the deadline passed before the thread was woken up
+ */
+pub unsafe fn thread_queue_sleep_timeout(queue: *mut Queue<*mut Thread>, timeout: u64) -> isize {
+    let thread = curthread!();
+
+    (*thread).sleep_deadline = arch_rtime_ns() + timeout;
+    (*thread).sleep_timedout = false;
+
+    let timeout_node = SLEEP_TIMEOUTS.enqueue(thread);
+    let ret = thread_queue_sleep(queue);
+    SLEEP_TIMEOUTS.node_remove(timeout_node);
+
+    (*thread).sleep_deadline = 0;
+
+    if ret == 0 && (*thread).sleep_timedout {
+        return 1;
+    }
+
+    return ret;
+}
+
 pub unsafe fn thread_queue_wakeup(queue: *mut Queue<*mut Thread>) -> isize {
     if queue.is_null() {
         //return -EINVAL;
@@ -109,7 +283,14 @@ pub unsafe fn thread_queue_wakeup(queue: *mut Queue<*mut Thread>) -> isize {
 
 pub unsafe fn thread_create(thread: *mut Thread, stack: usize, entry: usize, uentry: usize, arg: usize, _attr: usize, new_thread: *mut *mut Thread) -> isize {
     let mut t: *mut Thread = core::ptr::null_mut();
-    (*(*thread).owner).new_thread(&mut t);
+
+    let err = (*(*thread).owner).new_thread(&mut t);
+    if err != 0 {
+        return err;
+    }
+
+    /* new threads start with the signal mask of their creator */
+    (*t).sig_mask = (*thread).sig_mask;
 
     arch_thread_create(t, stack, entry, uentry, arg);
 
@@ -0,0 +1,76 @@
+use prelude::*;
+
+use arch::sys::sched::arch_rtime_ns;
+
+/*
+ * kernel timers, callbacks run from the scheduler once their deadline on
+ * the monotonic clock passed. the resolution is that of the scheduler tick.
+ */
+
+pub struct Timer {
+    /** expiry in nanoseconds since boot, 0 while disarmed */
+    pub deadline: u64,
+
+    /** called on expiry with the timer disarmed, may arm it again */
+    pub func: Option<unsafe fn(timer: *mut Timer)>,
+
+    /** owner private data */
+    pub data: *mut u8,
+
+    /** node in the armed timers queue */
+    qnode: *mut QueueNode<*mut Timer>,
+}
+
+impl Timer {
+    pub const fn new(func: unsafe fn(timer: *mut Timer), data: *mut u8) -> Self {
+        Timer {
+            deadline: 0,
+            func:     Some(func),
+            data:     data,
+            qnode:    core::ptr::null_mut(),
+        }
+    }
+}
+
+/** armed timers */
+static mut TIMERS: Queue<*mut Timer> = Queue::empty();
+
+/** fire `timer` at `deadline` nanoseconds since boot, `timer` must not move while armed */
+pub unsafe fn timer_arm(timer: *mut Timer, deadline: u64) {
+    timer_disarm(timer);
+
+    (*timer).deadline = core::cmp::max(deadline, 1);
+    (*timer).qnode = TIMERS.enqueue(timer);
+}
+
+pub unsafe fn timer_disarm(timer: *mut Timer) {
+    if !(*timer).qnode.is_null() {
+        TIMERS.node_remove((*timer).qnode);
+        (*timer).qnode = core::ptr::null_mut();
+    }
+
+    (*timer).deadline = 0;
+}
+
+/** run the callbacks of expired timers, called from the scheduler */
+pub unsafe fn timer_run() {
+    if TIMERS.count() == 0 {
+        return;
+    }
+
+    let now = arch_rtime_ns();
+
+    /* callbacks may arm timers again, collect the expired ones first */
+    let expired: Vec<*mut Timer> = TIMERS.iter()
+        .map(|qnode| qnode.value)
+        .filter(|&timer| (*timer).deadline <= now)
+        .collect();
+
+    for timer in expired {
+        timer_disarm(timer);
+
+        if let Some(func) = (*timer).func {
+            func(timer);
+        }
+    }
+}